@@ -23,9 +23,10 @@ async def main():
 
     print(f"Proxy chain: {chain}")
 
-    # In production, the chain would be passed to the client for activation.
-    # For now, this demonstrates the API.
-    client = await Client.from_registry("claude-acp")
+    # Resolve the agent, then route it through the chain. The client builds
+    # the chain on connect and tears it down (in reverse order) on exit.
+    agent = await Client.from_registry("claude-acp")
+    client = Client(agent._config.command, env=dict(agent._config.env), proxy_chain=chain)
 
     async with client:
         response = await client.prompt_sync("What is ACP?")
@@ -4,9 +4,9 @@
 Demonstrates how to detect and react to rate-limit extension
 notifications that some ACP agents (e.g. Claude) emit during a session.
 
-The ``UpdateKind.RateLimit`` event carries a JSON payload with
-utilization data that you can parse into a ``RateLimitInfo`` object
-for structured access.
+The ``UpdateKind.RateLimit`` event carries the parsed payload as a
+``RateLimitInfo`` in ``update.rate_limit``; fields the agent didn't send
+are ``None``.
 
 Usage:
     uv run examples/28_rate_limit_awareness.py
@@ -47,14 +47,14 @@ async def main() -> None:
                 print(update.text or "", end="", flush=True)
 
             elif update.kind == UpdateKind.RateLimit:
-                # Parse the structured rate-limit info.
-                info = RateLimitInfo.from_json(update.rate_limit_json or "{}")
+                info = update.rate_limit or RateLimitInfo.from_json(update.rate_limit_json or "{}")
                 print(f"\n⚠️  Rate limit event:")
                 print(f"   Status:      {info.status}")
-                print(f"   Utilization: {info.utilization:.0%}")
+                if info.utilization is not None:
+                    print(f"   Utilization: {info.utilization:.0%}")
                 print(f"   Type:        {info.rate_limit_type}")
-                print(f"   Resets at:   {info.resets_at}")
-                if info.utilization >= 0.9:
+                print(f"   Resets at:   {info.reset_at}")
+                if (info.utilization or 0.0) >= 0.9:
                     print("   🚨 WARNING: Very close to rate limit!")
 
             elif update.kind == UpdateKind.Done:
@@ -32,14 +32,15 @@ Manual command (no registry)::
 
 from __future__ import annotations
 
-# Version from the Rust native module.
-from conduit_sdk._conduit_sdk import __version__
+# Version and tracing from the Rust native module.
+from conduit_sdk._conduit_sdk import __version__, enable_tracing
 
 # Public API — high-level classes.
 from conduit_sdk.activate import query as _query_func
 from conduit_sdk.client import Client
 from conduit_sdk.exceptions import (
     AgentNotFoundError,
+    AuthRequiredError,
     CancelledError,
     ConduitError,
     ConnectionError,
@@ -54,10 +55,12 @@ from conduit_sdk.exceptions import (
     TimeoutError,
     ToolError,
     TransportError,
+    UnsupportedError,
 )
 from conduit_sdk.hooks import HookRunner, HookType, hook
 from conduit_sdk.options import AgentOptions
 from conduit_sdk.permissions import (
+    PermissionOption,
     PermissionResult,
     PermissionResultAllow,
     PermissionResultDeny,
@@ -81,38 +84,53 @@ from conduit_sdk.tools import (
     tool,
 )
 from conduit_sdk.types import (
+    AgentInfo,
     AudioBlock,
     Capabilities,
     ClientConfig,
+    ConnectionState,
     ContentBlock,
     ContentType,
     ControlMessage,
     ControlResponse,
+    Cost,
     EmbeddedResourceBlock,
+    FileEdit,
+    Framing,
     HookContext,
     ImageBlock,
+    McpServerConfig,
     Message,
     MessageRole,
     PermissionRequest,
     PermissionResponse,
+    PlanEntry,
     PromptContent,
+    PromptResult,
     RateLimitInfo,
     ResourceLinkBlock,
     ResultMessage,
+    ResumeResult,
     SessionUpdate,
     StreamEvent,
     TextBlock,
     ThinkingBlock,
+    ToolContent,
     ToolDefinition,
+    ToolKind,
+    ToolLocation,
     ToolResultBlock,
     ToolSchema,
+    ToolStatus,
     ToolUseBlock,
     UpdateKind,
+    UsageInfo,
 )
 
 __all__ = [
     # Core
     "__version__",
+    "enable_tracing",
     "Client",
     "Session",
     "Query",
@@ -122,6 +140,7 @@ __all__ = [
     "AgentInfo",
     # Options & Permissions
     "AgentOptions",
+    "PermissionOption",
     "PermissionResult",
     "PermissionResultAllow",
     "PermissionResultDeny",
@@ -144,24 +163,37 @@ __all__ = [
     "ContextInjector",
     "ResponseFilter",
     # Types — original
+    "AgentInfo",
     "Capabilities",
     "ClientConfig",
+    "ConnectionState",
     "ContentBlock",
     "ContentType",
+    "FileEdit",
     "HookContext",
+    "McpServerConfig",
     "Message",
     "MessageRole",
+    "PlanEntry",
     "SessionUpdate",
+    "ToolContent",
+    "ToolLocation",
     "ToolDefinition",
+    "ToolKind",
+    "ToolStatus",
     "ToolSchema",
     "UpdateKind",
     # Types — control protocol
     "ControlMessage",
     "ControlResponse",
+    "Framing",
     "PermissionRequest",
     "PermissionResponse",
     "ResultMessage",
     "StreamEvent",
+    # Types — session results
+    "PromptResult",
+    "ResumeResult",
     # Types — content block helpers
     "TextBlock",
     "ThinkingBlock",
@@ -175,18 +207,23 @@ __all__ = [
     "PromptContent",
     # Types — rate limit
     "RateLimitInfo",
+    # Types — usage
+    "Cost",
+    "UsageInfo",
     # Exceptions
     "ConduitError",
     "ConnectionError",
     "SessionError",
     "TransportError",
     "ProtocolError",
+    "AuthRequiredError",
     "ToolError",
     "HookError",
     "ProxyError",
     "TimeoutError",
     "CancelledError",
     "PermissionError",
+    "UnsupportedError",
     # Registry exceptions
     "RegistryError",
     "AgentNotFoundError",
@@ -2,6 +2,7 @@
 
 from __future__ import annotations
 
+from decimal import Decimal
 from enum import IntEnum
 from typing import Any
 
@@ -23,13 +24,46 @@ class ContentType(IntEnum):
     ToolResult = ...
     Image = ...
     Error = ...
+    ResourceLink = ...
+    Diff = ...
+    Resource = ...
 
 class UpdateKind(IntEnum):
     TextDelta = ...
+    ThoughtDelta = ...
     ToolUseStart = ...
     ToolUseEnd = ...
+    ToolUseUpdate = ...
+    ModeChange = ...
+    Plan = ...
+    ConfigUpdate = ...
+    CommandsUpdate = ...
+    Usage = ...
+    SessionInfo = ...
     Done = ...
     Error = ...
+    RateLimit = ...
+    Confirmation = ...
+    Permission = ...
+    Extension = ...
+    Content = ...
+
+class ToolKind(IntEnum):
+    Read = ...
+    Edit = ...
+    Delete = ...
+    Move = ...
+    Search = ...
+    Execute = ...
+    Think = ...
+    Fetch = ...
+    Other = ...
+
+class ToolStatus(IntEnum):
+    Pending = ...
+    InProgress = ...
+    Completed = ...
+    Failed = ...
 
 class HookType(IntEnum):
     PreToolUse = ...
@@ -41,21 +75,47 @@ class HookType(IntEnum):
     Connected = ...
     Disconnected = ...
 
+class ConnectionState(IntEnum):
+    Disconnected = ...
+    Connecting = ...
+    Connected = ...
+    Failed = ...
+
 class Capabilities:
     sessions: bool
     tools: bool
     proxy: bool
     modes: list[str]
     models: list[str]
+    mcp_hot_add: bool
+    protocol_version: str
 
     def __init__(
         self,
         sessions: bool = False,
-        tools: bool = False,
+        tools: bool = True,
         proxy: bool = False,
         modes: list[str] | None = None,
         models: list[str] | None = None,
+        mcp_hot_add: bool = False,
+        protocol_version: str = "",
     ) -> None: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(raw_json: str) -> Capabilities: ...
+    def __repr__(self) -> str: ...
+
+class AgentInfo:
+    name: str
+    version: str
+    title: str | None
+
+    def __init__(self, name: str, version: str = "", title: str | None = None) -> None: ...
+    def to_dict(self) -> dict[str, str | None]: ...
+    def keys(self) -> list[str]: ...
+    def get(self, key: str, default: Any = None) -> Any: ...
+    def __getitem__(self, key: str) -> str | None: ...
+    def __contains__(self, key: str) -> bool: ...
     def __repr__(self) -> str: ...
 
 class ContentBlock:
@@ -64,6 +124,12 @@ class ContentBlock:
     tool_name: str | None
     tool_input: str | None
     tool_use_id: str | None
+    mime_type: str | None
+    data: str | None
+    uri: str | None
+    path: str | None
+    old_text: str | None
+    new_text: str | None
 
     def __init__(
         self,
@@ -72,19 +138,31 @@ class ContentBlock:
         tool_name: str | None = None,
         tool_input: str | None = None,
         tool_use_id: str | None = None,
+        mime_type: str | None = None,
+        data: str | None = None,
+        uri: str | None = None,
+        path: str | None = None,
+        old_text: str | None = None,
+        new_text: str | None = None,
     ) -> None: ...
+    @staticmethod
+    def from_updates(updates: list[SessionUpdate]) -> list[ContentBlock]: ...
     def __repr__(self) -> str: ...
 
 class Message:
     role: MessageRole
     content: list[ContentBlock]
     session_id: str | None
+    stop_reason: str | None
+    turn_id: int | None
 
     def __init__(
         self,
         role: MessageRole,
         content: list[ContentBlock],
         session_id: str | None = None,
+        stop_reason: str | None = None,
+        turn_id: int | None = None,
     ) -> None: ...
     def text(self) -> str: ...
     def __repr__(self) -> str: ...
@@ -96,6 +174,29 @@ class SessionUpdate:
     tool_input: str | None
     tool_use_id: str | None
     error: str | None
+    stop_reason: str | None
+    tool_kind: ToolKind | None
+    tool_status: ToolStatus | None
+    tool_content: str | None
+    tool_locations: str | None
+    mode_id: str | None
+    plan_json: str | None
+    config_json: str | None
+    commands_json: str | None
+    usage_json: str | None
+    session_info_json: str | None
+    rate_limit_json: str | None
+    confirmation_json: str | None
+    permission_options_json: str | None
+    request_id: str | None
+    turn_id: int | None
+    locations: list[ToolLocation]
+    plan_entries: list[PlanEntry]
+    rate_limit: RateLimitInfo | None
+    method: str | None
+    params_json: str | None
+    cost: Cost | None
+    content: ContentBlock | None
 
     def __init__(
         self,
@@ -105,7 +206,129 @@ class SessionUpdate:
         tool_input: str | None = None,
         tool_use_id: str | None = None,
         error: str | None = None,
+        stop_reason: str | None = None,
+        tool_kind: ToolKind | None = None,
+        tool_status: ToolStatus | None = None,
+        tool_content: str | None = None,
+        tool_locations: str | None = None,
+        mode_id: str | None = None,
+        plan_json: str | None = None,
+        config_json: str | None = None,
+        commands_json: str | None = None,
+        usage_json: str | None = None,
+        session_info_json: str | None = None,
+        rate_limit_json: str | None = None,
+        confirmation_json: str | None = None,
+        permission_options_json: str | None = None,
+        request_id: str | None = None,
+        locations: list[ToolLocation] = ...,
+        plan_entries: list[PlanEntry] = ...,
+        rate_limit: RateLimitInfo | None = None,
+        method: str | None = None,
+        params_json: str | None = None,
+        cost: Cost | None = None,
+        content: ContentBlock | None = None,
+        turn_id: int | None = None,
+    ) -> None: ...
+    @property
+    def tool_contents(self) -> list[ToolContent]: ...
+    @property
+    def tool_content_blocks(self) -> list[ContentBlock]: ...
+    @property
+    def file_edits(self) -> list[FileEdit]: ...
+    def __repr__(self) -> str: ...
+
+class ToolContent:
+    kind: str
+    text: str | None
+    mime_type: str | None
+    data: str | None
+    uri: str | None
+    raw_json: str
+
+    def __init__(
+        self,
+        kind: str,
+        text: str | None = None,
+        mime_type: str | None = None,
+        data: str | None = None,
+        uri: str | None = None,
+        raw_json: str = "{}",
     ) -> None: ...
+    @staticmethod
+    def parse_list(content_json: str) -> list[ToolContent]: ...
+    def __repr__(self) -> str: ...
+
+class ToolLocation:
+    path: str
+    line: int | None
+
+    def __init__(self, path: str, line: int | None = None) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __repr__(self) -> str: ...
+
+class FileEdit:
+    path: str
+    old_text: str | None
+    new_text: str | None
+
+    def __init__(
+        self, path: str, old_text: str | None = None, new_text: str | None = None
+    ) -> None: ...
+    @staticmethod
+    def parse_list(content_json: str) -> list[FileEdit]: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __repr__(self) -> str: ...
+
+class PlanEntry:
+    content: str
+    priority: str
+    status: str
+
+    def __init__(
+        self, content: str, priority: str = "medium", status: str = "pending"
+    ) -> None: ...
+    @staticmethod
+    def parse_list(entries_json: str) -> list[PlanEntry]: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __repr__(self) -> str: ...
+
+class RateLimitInfo:
+    method: str
+    retry_after_secs: float | None
+    limit: int | None
+    remaining: int | None
+    reset_at: str | None
+    status: str | None
+    rate_limit_type: str | None
+    utilization: float | None
+    is_using_overage: bool | None
+    surpassed_threshold: float | None
+    raw_json: str
+
+    def __init__(
+        self,
+        method: str = "",
+        retry_after_secs: float | None = None,
+        limit: int | None = None,
+        remaining: int | None = None,
+        reset_at: str | None = None,
+        status: str | None = None,
+        rate_limit_type: str | None = None,
+        utilization: float | None = None,
+        is_using_overage: bool | None = None,
+        surpassed_threshold: float | None = None,
+        raw_json: str = "",
+    ) -> None: ...
+    @staticmethod
+    def from_json(json_str: str) -> RateLimitInfo: ...
+    def __repr__(self) -> str: ...
+
+class Cost:
+    amount: str
+    currency: str
+
+    def __init__(self, amount: str | int | float | Decimal, currency: str) -> None: ...
     def __repr__(self) -> str: ...
 
 class ClientConfig:
@@ -113,6 +336,18 @@ class ClientConfig:
     cwd: str | None
     env: dict[str, str]
     timeout_secs: int
+    protocol_version: int | None
+    use_shell: bool
+    retry_on_rate_limit: bool
+    max_rate_limit_wait_secs: float
+    endpoint: str | None
+    socket_path: str | None
+    on_busy: str
+    heartbeat_secs: float
+    client_name: str
+    client_version: str
+    client_title: str | None
+    record_path: str | None
 
     def __init__(
         self,
@@ -120,7 +355,40 @@ class ClientConfig:
         cwd: str | None = None,
         env: dict[str, str] | None = None,
         timeout_secs: int = 30,
+        protocol_version: int | None = None,
+        use_shell: bool = False,
+        retry_on_rate_limit: bool = False,
+        endpoint: str | None = None,
+        socket_path: str | None = None,
+        on_busy: str = "queue",
+        heartbeat_secs: float = 0.0,
+        client_name: str | None = None,
+        client_version: str | None = None,
+        client_title: str | None = None,
+        record_path: str | None = None,
+        max_rate_limit_wait_secs: float = 60.0,
     ) -> None: ...
+    def validate(self, check_path: bool = False) -> None: ...
+    def __repr__(self) -> str: ...
+
+class McpServerConfig:
+    name: str
+    command: str | None
+    args: list[str]
+    env: dict[str, str]
+    url: str | None
+    headers: dict[str, str]
+
+    def __init__(
+        self,
+        name: str,
+        command: str | None = None,
+        args: list[str] | None = None,
+        env: dict[str, str] | None = None,
+        url: str | None = None,
+        headers: dict[str, str] | None = None,
+    ) -> None: ...
+    def to_json(self) -> str: ...
     def __repr__(self) -> str: ...
 
 class ToolDefinition:
@@ -134,8 +402,16 @@ class ToolDefinition:
 class ProxyConfig:
     name: str
     command: list[str]
+    restart: bool
+    env: dict[str, str]
 
-    def __init__(self, name: str, command: list[str]) -> None: ...
+    def __init__(
+        self,
+        name: str,
+        command: list[str],
+        restart: bool = False,
+        env: dict[str, str] | None = None,
+    ) -> None: ...
     def __repr__(self) -> str: ...
 
 # ---------------------------------------------------------------------------
@@ -157,6 +433,28 @@ class PermissionRequest:
     ) -> None: ...
     def __repr__(self) -> str: ...
 
+class PermissionResult:
+    decision: str
+    option_id: str | None
+    reason: str | None
+
+    def __init__(
+        self,
+        decision: str,
+        option_id: str | None = None,
+        reason: str | None = None,
+    ) -> None: ...
+    def __repr__(self) -> str: ...
+
+class PermissionOption:
+    option_id: str
+    name: str
+    kind: str
+
+    def __init__(self, option_id: str, name: str, kind: str) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __repr__(self) -> str: ...
+
 class PermissionResponse:
     decision: str
     reason: str | None
@@ -180,6 +478,8 @@ class ResultMessage:
     session_id: str
     total_cost_usd: float | None
     result: str | None
+    currency: str | None
+    turn_id: int | None
 
     def __init__(
         self,
@@ -190,7 +490,50 @@ class ResultMessage:
         session_id: str,
         total_cost_usd: float | None = None,
         result: str | None = None,
+        currency: str | None = None,
+        turn_id: int | None = None,
+    ) -> None: ...
+    def __repr__(self) -> str: ...
+
+class PromptResult:
+    messages: list[Message]
+    session_id: str
+    tool_calls: list[ContentBlock]
+    usage_json: str | None
+    stop_reason: str | None
+    duration_secs: float
+    result_message: ResultMessage | None
+
+    def __init__(
+        self,
+        session_id: str,
+        messages: list[Message] = ...,
+        tool_calls: list[ContentBlock] = ...,
+        usage_json: str | None = None,
+        stop_reason: str | None = None,
+        duration_secs: float = 0.0,
+        result_message: ResultMessage | None = None,
+    ) -> None: ...
+    def text(self) -> str: ...
+    def __repr__(self) -> str: ...
+
+class ResumeResult:
+    session_id: str
+    mode: str | None
+    model: str | None
+    updated_at: str | None
+    raw_json: str
+
+    def __init__(
+        self,
+        session_id: str,
+        mode: str | None = None,
+        model: str | None = None,
+        updated_at: str | None = None,
+        raw_json: str = "{}",
     ) -> None: ...
+    @staticmethod
+    def from_json(session_id: str, raw_json: str) -> ResumeResult: ...
     def __repr__(self) -> str: ...
 
 class StreamEvent:
@@ -211,6 +554,8 @@ class ControlMessage:
     data: str
 
     def __init__(self, request_id: str, subtype: str, data: str) -> None: ...
+    @staticmethod
+    def try_new(request_id: str, subtype: str, data: str) -> ControlMessage: ...
     def __repr__(self) -> str: ...
 
 class ControlResponse:
@@ -221,18 +566,35 @@ class ControlResponse:
     def __init__(self, request_id: str, subtype: str, data: str) -> None: ...
     def __repr__(self) -> str: ...
 
+class Framing(IntEnum):
+    LineDelimited = ...
+    ContentLength = ...
+
 class RustControlProtocol:
     def __init__(self) -> None: ...
-    async def start(self, stdin_fd: int, stdout_fd: int) -> None: ...
-    async def send_control_request(self, subtype: str, data: str) -> str: ...
+    async def start(
+        self,
+        stdin_fd: int,
+        stdout_fd: int,
+        keepalive_secs: float | None = None,
+        framing: Framing = ...,
+        max_line_bytes: int = ...,
+    ) -> None: ...
+    async def restart(self, stdin_fd: int, stdout_fd: int) -> None: ...
+    async def send_control_request(
+        self, subtype: str, data: str, timeout_secs: float = 30.0
+    ) -> str: ...
     async def send_control_response(
         self, request_id: str, subtype: str, data: str
     ) -> None: ...
-    async def recv_message(self) -> str | None: ...
+    async def recv_message(self) -> ControlMessage | str | None: ...
     def set_permission_callback(self, callback: Any) -> None: ...
     def set_hook_callback(self, callback: Any) -> None: ...
     def set_mcp_callback(self, callback: Any) -> None: ...
+    def set_log_callback(self, callback: Any | None = None) -> None: ...
+    async def pending_count(self) -> int: ...
     async def is_running(self) -> bool: ...
+    async def flush(self, timeout_secs: float = 5.0) -> bool: ...
     async def stop(self) -> None: ...
 
 # ---------------------------------------------------------------------------
@@ -242,21 +604,116 @@ class RustControlProtocol:
 class RustClient:
     def __init__(self, config: ClientConfig) -> None: ...
     def set_permission_callback(self, callback: Any) -> None: ...
-    async def connect(self) -> Capabilities: ...
-    async def new_session(self, cwd: str | None = None) -> str: ...
+    def set_permission_policy(self, policy: str) -> None: ...
+    def set_tool_rules(self, allow: list[str] = ..., deny: list[str] = ...) -> None: ...
+    def set_input_middleware(self, callback: Any | None = None) -> None: ...
+    def set_raw_notification_callback(self, callback: Any | None = None) -> None: ...
+    def set_stale_update_callback(self, callback: Any | None = None) -> None: ...
+    def set_update_callback(self, callback: Any | None = None) -> None: ...
+    def set_log_callback(self, callback: Any | None = None) -> None: ...
+    def set_confirmation_callback(
+        self,
+        callback: Any | None = None,
+        timeout_secs: float | None = None,
+        default: bool = False,
+    ) -> None: ...
+    async def connect(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities: ...
+    def connect_blocking(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities: ...
+    async def reconnect(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities: ...
+    async def connect_via(
+        self,
+        chain: RustProxyChainHandle,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities: ...
+    async def connect_replay(
+        self,
+        recording_path: str,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities: ...
+    async def new_session(
+        self,
+        cwd: str | None = None,
+        meta_json: str | None = None,
+        mcp_servers_json: str | None = None,
+    ) -> str: ...
+    async def new_session_with_servers(
+        self,
+        servers: list[McpServerConfig],
+        cwd: str | None = None,
+        meta_json: str | None = None,
+    ) -> str: ...
     async def load_session(
         self, session_id: str, cwd: str | None = None
     ) -> str: ...
     async def set_session_mode(self, session_id: str, mode_id: str) -> None: ...
+    async def resume_session(
+        self, session_id: str, cwd: str | None = None
+    ) -> ResumeResult: ...
+    async def add_mcp_server(self, session_id: str, config_json: str) -> None: ...
+    async def session_cwd(self, session_id: str) -> str | None: ...
+    async def forget_session(self, session_id: str) -> None: ...
+    async def cancel_all(self, grace_secs: float = 1.0) -> list[str]: ...
+    async def available_commands(self, session_id: str) -> str: ...
+    async def session_info(self, session_id: str) -> str: ...
+    async def active_tool_calls(self, session_id: str) -> str: ...
+    async def rate_limit_status(
+        self, session_id: str | None = None
+    ) -> RateLimitInfo | None: ...
+    async def run_command(
+        self, session_id: str, command_name: str, args: str | None = None
+    ) -> None: ...
+    async def raw_request(self, method: str, params_json: str | None = None) -> str: ...
+    async def raw_notification(self, method: str, params_json: str | None = None) -> None: ...
     async def prompt(
-        self, text: str, session_id: str | None = None
+        self,
+        text: str,
+        session_id: str | None = None,
+        content_json: str | None = None,
+        meta_json: str | None = None,
+    ) -> list[Message]: ...
+    def prompt_blocking(
+        self,
+        text: str,
+        session_id: str | None = None,
+        content_json: str | None = None,
+        meta_json: str | None = None,
     ) -> list[Message]: ...
+    async def prompt_full(
+        self,
+        text: str,
+        session_id: str | None = None,
+        content_json: str | None = None,
+        meta_json: str | None = None,
+    ) -> PromptResult: ...
     async def send_prompt(
-        self, text: str, session_id: str | None = None
+        self,
+        text: str,
+        session_id: str | None = None,
+        content_json: str | None = None,
+        meta_json: str | None = None,
     ) -> None: ...
     async def recv_update(self) -> SessionUpdate | None: ...
+    async def default_session_id(self) -> str | None: ...
     async def capabilities(self) -> Capabilities | None: ...
+    async def agent_info(self) -> AgentInfo | None: ...
+    async def agent_info_json(self) -> str | None: ...
     async def disconnect(self) -> None: ...
+    def state(self) -> ConnectionState: ...
+    def heartbeat_latency(self) -> float | None: ...
 
 # ---------------------------------------------------------------------------
 # Tools
@@ -264,18 +721,31 @@ class RustClient:
 
 class RustToolRegistry:
     def __init__(self) -> None: ...
-    async def register(self, definition: ToolDefinition, callback: Any) -> None: ...
+    async def register(
+        self,
+        definition: ToolDefinition,
+        callback: Any,
+        strict_schema: bool = False,
+        max_concurrency: int | None = None,
+        timeout_secs: float | None = None,
+    ) -> None: ...
+    def set_input_middleware(self, callback: Any | None = None) -> None: ...
     async def unregister(self, name: str) -> None: ...
     async def list_tools(self) -> list[str]: ...
     async def invoke(self, name: str, input_json: str) -> str: ...
+    async def invoke_structured(self, name: str, input_json: str) -> str: ...
+    async def stats(self) -> str: ...
+    async def manifest(self) -> str: ...
 
 # ---------------------------------------------------------------------------
 # Hooks
 # ---------------------------------------------------------------------------
 
 class RustHookDispatcher:
-    def __init__(self) -> None: ...
-    async def register(self, hook_type: HookType, callback: Any, priority: int = 0) -> None: ...
+    def __init__(self, fail_fast: bool = False, error_sink: Any | None = None) -> None: ...
+    def set_log_callback(self, callback: Any | None = None) -> None: ...
+    async def register(self, hook_type: HookType, callback: Any, priority: int = 0) -> int: ...
+    async def unregister(self, handle: int) -> bool: ...
     async def dispatch(self, hook_type: HookType, context_json: str) -> str: ...
     async def clear(self, hook_type: HookType) -> None: ...
 
@@ -285,8 +755,30 @@ class RustHookDispatcher:
 
 class RustProxyChain:
     def __init__(self) -> None: ...
+    def set_log_callback(self, callback: Any | None = None) -> None: ...
     async def add(self, proxy: ProxyConfig) -> None: ...
     async def insert(self, index: int, proxy: ProxyConfig) -> None: ...
+    async def remove(self, name: str) -> bool: ...
+    async def move_to(self, name: str, index: int) -> None: ...
+    async def index_of(self, name: str) -> int | None: ...
     async def list(self) -> list[ProxyConfig]: ...
     async def clear(self) -> None: ...
-    async def build(self) -> None: ...
+    async def validate(self) -> list[str]: ...
+    async def build(
+        self,
+        agent_command: list[str],
+        cwd: str | None = None,
+        env: dict[str, str] | None = None,
+    ) -> RustProxyChainHandle: ...
+    async def status(self) -> str: ...
+    async def ensure_healthy(self) -> None: ...
+
+class RustProxyChainHandle:
+    async def names(self) -> list[str]: ...
+    async def teardown(self) -> None: ...
+
+# ---------------------------------------------------------------------------
+# Tracing
+# ---------------------------------------------------------------------------
+
+def enable_tracing(level: str = "info") -> None: ...
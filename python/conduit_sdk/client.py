@@ -19,23 +19,49 @@ With options::
 
 from __future__ import annotations
 
-from collections.abc import AsyncIterator
+import logging
+import os
+from collections.abc import AsyncIterator, Callable
 from typing import Any
 
 from conduit_sdk._conduit_sdk import (
     ClientConfig,
     RustClient,
     RustControlProtocol,
+    RustHookDispatcher,
+    RustToolRegistry,
     SessionUpdate,
     UpdateKind,
 )
-from conduit_sdk.exceptions import ConnectionError
+from conduit_sdk.exceptions import ConduitError, ConnectionError
 from conduit_sdk.hooks import HookRunner
 from conduit_sdk.options import AgentOptions
+from conduit_sdk.proxy import ProxyChain
 from conduit_sdk.query import Query
 from conduit_sdk.registry import Registry
 from conduit_sdk.session import Session
-from conduit_sdk.types import Capabilities, Message
+from conduit_sdk.types import (
+    AgentInfo,
+    Capabilities,
+    ConnectionState,
+    Message,
+    PromptResult,
+    RateLimitInfo,
+)
+
+logger = logging.getLogger(__name__)
+
+
+def _meta_json(meta: dict | None) -> str | None:
+    """Serialize a prompt's ``meta`` for the Rust client."""
+    import json
+    return None if meta is None else json.dumps(meta)
+
+
+def _log_from_rust(level: str, message: str) -> None:
+    """Log callback handed to the Rust client: its internal events (agent
+    spawn, handshake, background task errors) go to this module's logger."""
+    logger.log(logging.getLevelName(level.upper()), message)
 
 
 class Client:
@@ -45,16 +71,70 @@ class Client:
     ----------
     command:
         Shell command to spawn the agent process.
-        Example: ``["claude", "--agent"]`` or ``["goose"]``.
+        Example: ``["claude", "--agent"]`` or ``["goose"]``. ``$VAR`` and
+        ``${VAR}`` are expanded from ``env``, then the process environment;
+        ``$$`` is a literal ``$``.
     cwd:
         Working directory for the agent process.
     env:
         Additional environment variables for the agent.
     timeout:
-        Connection timeout in seconds.
+        Connection timeout in seconds: how long ``connect()`` waits for the
+        agent to answer ``initialize`` before killing it and raising
+        :class:`TimeoutError`. ``0`` waits forever.
+    protocol_version:
+        ACP protocol version to pin. The agent must negotiate exactly this
+        version or :meth:`connect` raises :class:`UnsupportedError`.
+        ``None`` (the default) requests the latest version.
+    use_shell:
+        Run ``command`` through the shell (``sh -c``, or ``cmd /C`` on
+        Windows) instead of executing it directly, so pipes, redirects and
+        shell variables work. The elements are joined with spaces and
+        passed on verbatim, *not* quoted: quote anything that may contain
+        spaces or shell metacharacters (e.g. with :func:`shlex.quote`), and
+        never let untrusted input into the command. On Windows the joined
+        line reaches ``cmd`` exactly as written, so quote for ``cmd``
+        there, not for :func:`shlex.quote`.
+    retry_on_rate_limit:
+        When a prompt fails after the agent reported a rate limit with a
+        retry hint (see :meth:`rate_limit_status`), wait that long and send
+        the prompt once more.
+    max_rate_limit_wait:
+        Longest that retry waits, in seconds, however long the agent's hint
+        asks for. Defaults to 60.
+    endpoint:
+        ``host:port`` of an agent served over TCP. When set, :meth:`connect`
+        connects there instead of spawning ``command`` (which may be
+        empty); ``cwd``, ``env`` and ``use_shell`` then have no effect.
+    socket_path:
+        Path of a Unix domain socket an agent listens on, for agents on the
+        same host (Unix only). Used like ``endpoint``; set at most one.
+    on_busy:
+        What :meth:`prompt` and :meth:`prompt_full` do when another prompt
+        is still running in the same session: ``"queue"`` (the default)
+        waits for it, so overlapping prompts run one after another in call
+        order; ``"error"`` raises :class:`SessionError` instead.
+    heartbeat:
+        Seconds between liveness pings to the agent once connected; 0 (the
+        default) sends none. If three pings in a row go unanswered within
+        that interval, :attr:`state` becomes ``ConnectionState.Failed`` and
+        pending and later calls raise :class:`ConnectionError`. See
+        :attr:`heartbeat_latency`.
+    client_name, client_version, client_title:
+        Identity the client reports to the agent in ``initialize``. Name and
+        version default to this SDK's; the title is omitted by default.
+    record_path:
+        File to append every ACP message to, in both directions, as
+        newline-delimited JSON objects with ``direction`` (``"in"`` or
+        ``"out"``), ``timestamp`` and ``message``. Useful for bug reports;
+        :meth:`connect_replay` can play the file back.
     options:
         Comprehensive agent configuration. Overrides ``cwd`` and ``env``
         if provided in both places.
+    proxy_chain:
+        Optional :class:`ProxyChain` to route all ACP traffic through.
+        The chain is built in front of the agent on connect and torn
+        down on disconnect.
     """
 
     def __init__(
@@ -64,9 +144,23 @@ class Client:
         cwd: str | None = None,
         env: dict[str, str] | None = None,
         timeout: int = 30,
+        protocol_version: int | None = None,
+        use_shell: bool = False,
+        retry_on_rate_limit: bool = False,
+        max_rate_limit_wait: float = 60.0,
+        endpoint: str | None = None,
+        socket_path: str | None = None,
+        on_busy: str = "queue",
+        heartbeat: float = 0.0,
+        client_name: str | None = None,
+        client_version: str | None = None,
+        client_title: str | None = None,
+        record_path: str | os.PathLike[str] | None = None,
         options: AgentOptions | None = None,
+        proxy_chain: ProxyChain | None = None,
     ) -> None:
         self._options = options
+        self._proxy_chain = proxy_chain
 
         # Options override individual params when provided.
         effective_cwd = cwd
@@ -82,13 +176,28 @@ class Client:
             cwd=effective_cwd,
             env=effective_env,
             timeout_secs=timeout,
+            protocol_version=protocol_version,
+            use_shell=use_shell,
+            retry_on_rate_limit=retry_on_rate_limit,
+            endpoint=endpoint,
+            socket_path=socket_path,
+            on_busy=on_busy,
+            heartbeat_secs=heartbeat,
+            client_name=client_name,
+            client_version=client_version,
+            client_title=client_title,
+            record_path=None if record_path is None else os.fspath(record_path),
+            max_rate_limit_wait_secs=max_rate_limit_wait,
         )
         self._rust_client = RustClient(self._config)
+        self._rust_client.set_log_callback(_log_from_rust)
         self._capabilities: Capabilities | None = None
         self._connected = False
         self._hooks = HookRunner()
         self._query: Query | None = None
         self._protocol: RustControlProtocol | None = None
+        # Open sessions by ID.
+        self._sessions: dict[str, Session] = {}
 
     # -- Factory methods -----------------------------------------------------
 
@@ -142,28 +251,149 @@ class Client:
 
     # -- Connection lifecycle ------------------------------------------------
 
-    async def connect(self) -> Capabilities:
+    async def connect(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities:
         """Spawn the agent and perform the ACP initialize handshake.
 
+        Parameters
+        ----------
+        registry:
+            Tool registry (e.g. :func:`~conduit_sdk.tools.get_registry`)
+            that answers the agent's MCP ``tools/call`` requests. Without
+            one, such requests are rejected as unsupported.
+        hooks:
+            Hook dispatcher whose ``PreToolUse`` hooks run on each
+            permission request before ``can_use_tool``. A hook may rewrite
+            ``tool_input`` in the context it returns, or deny the tool with
+            ``{"__action__": "deny", "reason": ...}``.
+
         Returns the agent's advertised capabilities.
         """
-        # Wire the permission callback into Rust before connecting.
+        self._wire_options()
+        self._wire_hooks(hooks)
+        if self._proxy_chain is not None:
+            handle = await self._proxy_chain.build(
+                list(self._config.command),
+                cwd=self._config.cwd,
+                env=dict(self._config.env),
+            )
+            try:
+                self._capabilities = await self._rust_client.connect_via(handle, registry, hooks)
+            except BaseException:
+                await self._proxy_chain.teardown()
+                raise
+        else:
+            self._capabilities = await self._rust_client.connect(registry, hooks)
+        self._on_connected()
+        return self._capabilities
+
+    async def connect_replay(
+        self,
+        recording_path: str | os.PathLike[str],
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities:
+        """Connect to a recorded agent instead of spawning ``command``.
+
+        ``recording_path`` is a newline-delimited JSON file of the messages
+        an agent sent, in order. Each request the client makes is answered
+        by playing the recording up to and including its next response, so
+        code under test sees the same updates every run. ``initialize`` is
+        answered by a stub unless the recording opens with its response.
+        ``registry`` and ``hooks`` work as for :meth:`connect`.
+        """
+        self._wire_options()
+        self._wire_hooks(hooks)
+        self._capabilities = await self._rust_client.connect_replay(
+            os.fspath(recording_path), registry, hooks
+        )
+        self._on_connected()
+        return self._capabilities
+
+    def connect_blocking(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities:
+        """Blocking :meth:`connect` for scripts and notebooks without asyncio.
+
+        Must not be called from inside a running event loop. Proxy chains
+        and async option callbacks or hooks (which need a loop to run on)
+        are not supported; use :meth:`connect` for those. An async
+        ``can_use_tool``, ``confirm_callback``, ``raw_notification_callback``,
+        ``stale_update_callback``, ``tool_input_middleware``, update
+        callback or hook raises ``ValueError`` before anything is spawned.
+        """
+        if self._proxy_chain is not None:
+            raise ConduitError("connect_blocking() does not support proxy chains; use connect()")
+        self._wire_options()
+        self._wire_hooks(hooks)
+        self._capabilities = self._rust_client.connect_blocking(registry, hooks)
+        self._on_connected()
+        return self._capabilities
+
+    async def reconnect(
+        self,
+        registry: RustToolRegistry | None = None,
+        hooks: RustHookDispatcher | None = None,
+    ) -> Capabilities:
+        """Respawn a crashed agent and restore the default session.
+
+        The stale process is killed, the agent is started again from the
+        same command, and the previous default session is reloaded with
+        ``session/load``. Calls still in flight on the old connection fail
+        with :class:`ConnectionError`. Proxy chains are not supported.
+        """
+        if self._proxy_chain is not None:
+            raise ConduitError("reconnect() does not support proxy chains")
+        self._connected = False
+        self._wire_hooks(hooks)
+        self._capabilities = await self._rust_client.reconnect(registry, hooks)
+        self._connected = True
+        return self._capabilities
+
+    @staticmethod
+    def _wire_hooks(hooks: RustHookDispatcher | None) -> None:
+        """Route the hook dispatcher's internal log to this module's logger."""
+        if hooks is not None:
+            hooks.set_log_callback(_log_from_rust)
+
+    def _wire_options(self) -> None:
+        """Hand the option callbacks to the Rust client before connecting."""
         if self._options is not None and self._options.can_use_tool is not None:
             self._rust_client.set_permission_callback(self._options.can_use_tool)
+        if self._options is not None and self._options.permission_policy is not None:
+            self._rust_client.set_permission_policy(self._options.permission_policy)
+        if self._options is not None and self._options.tool_input_middleware is not None:
+            self._rust_client.set_input_middleware(self._options.tool_input_middleware)
+        if self._options is not None and self._options.raw_notification_callback is not None:
+            self._rust_client.set_raw_notification_callback(
+                self._options.raw_notification_callback
+            )
+        if self._options is not None and self._options.stale_update_callback is not None:
+            self._rust_client.set_stale_update_callback(self._options.stale_update_callback)
+        if self._options is not None:
+            self._rust_client.set_confirmation_callback(
+                self._options.confirm_callback,
+                self._options.confirm_timeout_secs,
+                self._options.confirm_default,
+            )
 
-        self._capabilities = await self._rust_client.connect()
+    def _on_connected(self) -> None:
         self._connected = True
 
         # Set up control protocol with Query if options have callbacks.
         if self._options is not None:
             self._protocol = RustControlProtocol()
+            self._protocol.set_log_callback(_log_from_rust)
             self._query = Query(
                 self._protocol,
                 can_use_tool=self._options.can_use_tool,
             )
 
-        return self._capabilities
-
     async def disconnect(self) -> None:
         """Terminate the agent subprocess and clean up."""
         if self._query is not None:
@@ -172,11 +402,42 @@ class Client:
         if self._connected:
             await self._rust_client.disconnect()
             self._connected = False
+        if self._proxy_chain is not None:
+            await self._proxy_chain.teardown()
+
+    async def aclose(self, grace_secs: float = 1.0) -> None:
+        """Tear down everything this client owns, in order.
+
+        Cancels every session with :meth:`cancel_all`, waiting up to
+        ``grace_secs`` for running turns to end, detaches open sessions,
+        then disconnects, which writes out any session recording before
+        the agent is stopped and tears down any proxy chain. Safe to call
+        repeatedly.
+        """
+        if self._connected:
+            await self._rust_client.cancel_all(grace_secs)
+        for session in list(self._sessions.values()):
+            await session._detach()
+        await self.disconnect()
 
     @property
     def connected(self) -> bool:
         return self._connected
 
+    @property
+    def state(self) -> ConnectionState:
+        """Current connection state of the underlying client."""
+        return self._rust_client.state()
+
+    @property
+    def heartbeat_latency(self) -> float | None:
+        """Round trip in seconds of the last heartbeat the agent answered.
+
+        ``None`` until one has been answered on the current connection, or
+        if heartbeats are off.
+        """
+        return self._rust_client.heartbeat_latency()
+
     @property
     def capabilities(self) -> Capabilities | None:
         return self._capabilities
@@ -189,6 +450,15 @@ class Client:
     def options(self) -> AgentOptions | None:
         return self._options
 
+    @property
+    def proxy_chain(self) -> ProxyChain | None:
+        return self._proxy_chain
+
+    @property
+    def sessions(self) -> list[Session]:
+        """Sessions opened on this client that have not been closed."""
+        return list(self._sessions.values())
+
     @property
     def query(self) -> Query | None:
         return self._query
@@ -225,6 +495,7 @@ class Client:
         text: str | list,
         *,
         session_id: str | None = None,
+        meta: dict | None = None,
     ) -> AsyncIterator[Message]:
         """Send a prompt to the agent and stream back response messages.
         message contains the text received so far (not deltas).
@@ -236,20 +507,77 @@ class Client:
         session_id:
             Optional session ID. If ``None``, uses the client's default
             session (auto-created on first prompt).
+        meta:
+            JSON object sent as the prompt request's ``_meta``, e.g. to
+            carry trace or correlation IDs. The SDK sets ``requestId`` in
+            it to the turn's own ID.
         """
         if not self._connected:
             raise ConnectionError("client is not connected \u2014 call connect() first")
 
         text_str, content_json = self._prepare_prompt(text)
-        messages = await self._rust_client.prompt(text_str, session_id, content_json)
+        meta_json = _meta_json(meta)
+        messages = await self._rust_client.prompt(text_str, session_id, content_json, meta_json)
         for msg in messages:
             yield msg
 
+    def prompt_blocking(
+        self,
+        text: str | list,
+        *,
+        session_id: str | None = None,
+        meta: dict | None = None,
+    ) -> list[Message]:
+        """Blocking :meth:`prompt`: send a prompt and return its messages.
+
+        Must not be called from inside a running event loop. ``meta`` is
+        as for :meth:`prompt`.
+        """
+        if not self._connected:
+            raise ConnectionError("client is not connected \u2014 call connect() first")
+
+        text_str, content_json = self._prepare_prompt(text)
+        meta_json = _meta_json(meta)
+        return self._rust_client.prompt_blocking(text_str, session_id, content_json, meta_json)
+
+    async def prompt_full(
+        self,
+        text: str | list,
+        *,
+        session_id: str | None = None,
+        meta: dict | None = None,
+    ) -> PromptResult:
+        """Send a prompt and return the whole turn as one :class:`PromptResult`.
+
+        Unlike :meth:`prompt`, the result also carries the tool calls the
+        agent made, its last usage report, the stop reason and the turn's
+        duration.
+
+        Parameters
+        ----------
+        text:
+            The prompt text (string) or a list of content blocks.
+        session_id:
+            Optional session ID. If ``None``, uses the client's default
+            session (auto-created on first prompt).
+        meta:
+            JSON object sent as the prompt request's ``_meta``, e.g. to
+            carry trace or correlation IDs. The SDK sets ``requestId`` in
+            it to the turn's own ID.
+        """
+        if not self._connected:
+            raise ConnectionError("client is not connected \u2014 call connect() first")
+
+        text_str, content_json = self._prepare_prompt(text)
+        meta_json = _meta_json(meta)
+        return await self._rust_client.prompt_full(text_str, session_id, content_json, meta_json)
+
     async def prompt_stream(
         self,
         text: str | list,
         *,
         session_id: str | None = None,
+        meta: dict | None = None,
     ) -> AsyncIterator[SessionUpdate]:
         """Send a prompt and yield real-time :class:`SessionUpdate` objects.
         (text deltas, thought deltas, tool use start/end) as it arrives.
@@ -259,12 +587,68 @@ class Client:
         session_id:
             Optional session ID. If ``None``, uses the client's default
             session (auto-created on first prompt).
+        meta:
+            JSON object sent as the prompt request's ``_meta``, e.g. to
+            carry trace or correlation IDs. The SDK sets ``requestId`` in
+            it to the turn's own ID.
         """
         if not self._connected:
             raise ConnectionError("client is not connected \u2014 call connect() first")
 
         text_str, content_json = self._prepare_prompt(text)
-        await self._rust_client.send_prompt(text_str, session_id, content_json)
+        meta_json = _meta_json(meta)
+        await self._rust_client.send_prompt(text_str, session_id, content_json, meta_json)
+        while True:
+            update = await self._rust_client.recv_update()
+            if update is None:
+                break
+            yield update
+
+    async def session_info(self, session_id: str) -> dict:
+        """Return the title and update time the agent last reported for a session.
+
+        A dict with ``title`` and ``updated_at`` keys, tracked from
+        ``session_info_update`` notifications; each is ``None`` until the
+        agent reports it.
+        """
+        import json
+        return json.loads(await self._rust_client.session_info(session_id))
+
+    async def active_tool_calls(self, session_id: str) -> list[dict]:
+        """Return the tool calls still running in a session, oldest first.
+
+        Each is a dict with ``tool_use_id``, ``name``, ``status`` (its ACP
+        wire name, e.g. ``"in_progress"``) and ``started_at`` (seconds since
+        the epoch), tracked from the agent's tool call notifications. Calls
+        leave once they complete or fail, or their turn ends cancelled.
+        """
+        import json
+        return json.loads(await self._rust_client.active_tool_calls(session_id))
+
+    async def rate_limit_status(self, session_id: str | None = None) -> RateLimitInfo | None:
+        """Return the latest rate-limit status the agent reported.
+
+        Parsed from the agent's rate-limit notifications, for ``session_id``
+        or, when ``None``, for any session. ``None`` if there hasn't been one.
+        """
+        return await self._rust_client.rate_limit_status(session_id)
+
+    async def run_command(
+        self,
+        session_id: str,
+        name: str,
+        args: str | None = None,
+    ) -> AsyncIterator[SessionUpdate]:
+        """Invoke one of the agent's slash commands and stream its result.
+
+        Sends ``/name args`` as a prompt and yields updates as
+        :meth:`prompt_stream` does. Raises :class:`ProtocolError` unless
+        the command is among :meth:`available_commands` for the session.
+        """
+        if not self._connected:
+            raise ConnectionError("client is not connected \u2014 call connect() first")
+
+        await self._rust_client.run_command(session_id, name, args)
         while True:
             update = await self._rust_client.recv_update()
             if update is None:
@@ -307,12 +691,68 @@ class Client:
         """Cancel a running prompt in the given session (ACP CancelNotification)."""
         await self._rust_client.cancel_session(session_id)
 
+    async def cancel_all(self, grace_secs: float = 1.0) -> list[str]:
+        """Cancel every session of this client and wait briefly for running turns.
+
+        Call before :meth:`disconnect` on shutdown so the agent can clean
+        up. Closed sessions are skipped; turns started with
+        :meth:`prompt_stream` are waited for like any other. Returns the
+        cancelled session IDs. Raises ``ValueError`` if ``grace_secs`` is
+        negative or not finite.
+        """
+        return await self._rust_client.cancel_all(grace_secs)
+
+    async def cancel_tool(self, session_id: str, tool_use_id: str) -> None:
+        """Cancel one running tool call without cancelling the rest of the turn.
+
+        Raises ``ProtocolError`` if the agent doesn't support it.
+        """
+        await self._rust_client.cancel_tool(session_id, tool_use_id)
+
     async def set_config(self, session_id: str, config_id: str, value: str) -> dict:
         """Set a config option on a session. Returns the response as a dict."""
         import json
         result_json = await self._rust_client.set_config_option(session_id, config_id, value)
         return json.loads(result_json)
 
+    async def add_mcp_server(self, session_id: str, name: str, config: Any) -> None:
+        """Attach an MCP server to a running session.
+
+        Parameters
+        ----------
+        session_id:
+            Session to attach the server to.
+        name:
+            Server name, unique within the session.
+        config:
+            An ``McpSdkServerConfig`` or a raw dict in the same shape as
+            :attr:`AgentOptions.mcp_servers` values.
+
+        Raises :class:`ProtocolError` if the agent does not advertise
+        ``mcp_hot_add``, and :class:`SessionError` if ``name`` is taken.
+        """
+        import json
+        server = config.to_dict() if hasattr(config, "to_dict") else dict(config)
+        server["name"] = name
+        await self._rust_client.add_mcp_server(session_id, json.dumps(server))
+
+    async def session_cwd(self, session_id: str) -> str | None:
+        """Return the working directory a session was created or loaded with.
+
+        ``None`` if this client never created, loaded, forked or resumed
+        ``session_id``.
+        """
+        return await self._rust_client.session_cwd(session_id)
+
+    async def available_commands(self, session_id: str) -> list[dict]:
+        """Return the commands the agent last advertised for a session.
+
+        Tracked from ``available_commands_update`` notifications, so no
+        prompt is needed; empty until the agent sends one.
+        """
+        import json
+        return json.loads(await self._rust_client.available_commands(session_id))
+
     async def fork_session(self, session_id: str, cwd: str | None = None) -> Session:
         """Fork a session, creating a new session with shared history.
 
@@ -321,6 +761,7 @@ class Client:
         new_sid = await self._rust_client.fork_session(session_id, cwd)
         session = Session(self)
         session._session_id = new_sid
+        self._sessions[new_sid] = session
         return session
 
     async def list_sessions(self, cwd: str | None = None) -> list[dict]:
@@ -329,24 +770,59 @@ class Client:
         result_json = await self._rust_client.list_sessions(cwd)
         return json.loads(result_json)
 
+    async def raw_request(self, method: str, params: Any = None) -> Any:
+        """Send an ACP request the SDK doesn't model and return its result.
+
+        ``params`` is JSON-encoded as-is (an empty object when ``None``).
+        Agent errors raise :class:`ProtocolError` carrying the JSON-RPC code.
+        """
+        import json
+        params_json = None if params is None else json.dumps(params)
+        result_json = await self._rust_client.raw_request(method, params_json)
+        return json.loads(result_json)
+
+    async def raw_notification(self, method: str, params: Any = None) -> None:
+        """Send an ACP notification the SDK doesn't model.
+
+        Returns as soon as the notification is queued; there is no reply.
+        """
+        import json
+        params_json = None if params is None else json.dumps(params)
+        await self._rust_client.raw_notification(method, params_json)
+
+    def set_update_callback(self, callback: Callable[[str], Any] | None) -> None:
+        """Observe every session update as the agent sent it, or stop with ``None``.
+
+        ``callback`` receives each ``session/update`` notification's params
+        as a JSON string, byte for byte as the agent sent them, and may be
+        sync or async. It runs on its own task in arrival order, so a slow callback
+        doesn't hold up the connection.
+        """
+        self._rust_client.set_update_callback(callback)
+
     async def resume_session(self, session_id: str, cwd: str | None = None) -> Session:
         """Resume an existing agent-side session.
 
-        Returns a :class:`Session` bound to the resumed session ID.
+        Returns a :class:`Session` bound to the resumed session ID, with
+        its mode and model restored from the agent's response. The parsed
+        response is available as :attr:`Session.resume_result`.
         """
-        resumed_sid = await self._rust_client.resume_session(session_id, cwd)
+        result = await self._rust_client.resume_session(session_id, cwd)
         session = Session(self)
-        session._session_id = resumed_sid
+        session._apply_resume(result)
+        self._sessions[result.session_id] = session
         return session
 
     @property
-    async def agent_info(self) -> dict | None:
-        """Return agent server info (name, version, title) or None."""
-        import json
-        info_json = await self._rust_client.agent_info()
-        if info_json is None:
-            return None
-        return json.loads(info_json)
+    async def agent_info(self) -> AgentInfo | None:
+        """Return the agent's reported name, version and title, or None.
+
+        The result is an :class:`AgentInfo`. It still supports the read
+        access of the dict this used to return (``info["name"]``,
+        ``info.get("title")``, ``"version" in info``, ``info.keys()``);
+        use ``info.to_dict()`` where a real dict is needed.
+        """
+        return await self._rust_client.agent_info()
 
     # -- Session shortcuts ---------------------------------------------------
 
@@ -372,7 +848,7 @@ class Client:
         return self
 
     async def __aexit__(self, *exc: Any) -> None:
-        await self.disconnect()
+        await self.aclose()
 
     def __repr__(self) -> str:
         status = "connected" if self._connected else "disconnected"
@@ -24,7 +24,45 @@ class TransportError(ConduitError):
 
 
 class ProtocolError(ConduitError):
-    """ACP protocol violation or unexpected message format."""
+    """ACP protocol violation or unexpected message format.
+
+    When the agent answered with a JSON-RPC error, ``code`` holds its
+    numeric code and ``data`` its JSON-encoded ``data`` member (if any);
+    both are ``None`` for errors raised on the client side. ``session_id``
+    is the session the failed request was about, when it was about one.
+    """
+
+    def __init__(
+        self,
+        message: str = "",
+        *,
+        code: int | None = None,
+        data: str | None = None,
+        session_id: str | None = None,
+    ) -> None:
+        super().__init__(message)
+        self.code = code
+        self.data = data
+        self.session_id = session_id
+
+
+class AuthRequiredError(ProtocolError):
+    """The agent requires authentication before serving the request.
+
+    ``auth_methods`` lists the auth method ids the agent will accept, as
+    sent in the error's ``data.authMethods``; it is empty if none were sent.
+    """
+
+    def __init__(
+        self,
+        message: str = "",
+        *,
+        code: int | None = None,
+        auth_methods: list[str] | None = None,
+        session_id: str | None = None,
+    ) -> None:
+        super().__init__(message, code=code, session_id=session_id)
+        self.auth_methods = list(auth_methods or [])
 
 
 class ToolError(ConduitError):
@@ -51,6 +89,11 @@ class CancelledError(ConduitError):
     """Operation was cancelled."""
 
 
+class UnsupportedError(ConduitError):
+    """The agent does not support what the client requires (e.g. a pinned
+    protocol version)."""
+
+
 # -- Registry errors ---------------------------------------------------------
 
 
@@ -25,9 +25,40 @@ class AgentOptions:
         Permission enforcement mode. One of:
         ``"default"``, ``"acceptEdits"``, ``"plan"``, ``"bypassPermissions"``.
     can_use_tool:
-        Async callback invoked for each tool use. Receives
+        Callback invoked for each tool use. Receives
         ``(tool_name, tool_input, context)`` and must return a
-        ``PermissionResult``.
+        ``PermissionResult``. May be sync or async; ``connect_blocking()``
+        only accepts sync callbacks.
+    permission_policy:
+        How permission requests are answered: ``"prompt"`` (ask
+        ``can_use_tool``, denying if it is unset), ``"allow_all"`` or
+        ``"deny_all"``. ``None`` keeps the client's default, ``"prompt"``.
+    tool_input_middleware:
+        Callback that may rewrite tool input before it is used, e.g. to
+        redact secrets or normalize paths. Receives ``(tool_name,
+        tool_input)`` with the input as a dict and returns the rewritten
+        dict, or ``None`` to leave it unchanged. May be sync or async.
+        Applied to SDK-hosted tool calls and to the input passed to
+        ``can_use_tool``.
+    raw_notification_callback:
+        Opt-in tap for protocol tooling. Receives the params of every ACP
+        ``session/update`` notification as a JSON string, byte for byte as
+        the agent sent them (even ones that fail to parse), before they
+        are mapped to :class:`SessionUpdate`. May be sync or async.
+    stale_update_callback:
+        Receives each :class:`SessionUpdate` left over from an earlier
+        turn (e.g. late tool updates from a cancelled prompt), which is
+        discarded when the next prompt starts. May be sync or async.
+    confirm_callback:
+        Callback answering the agent's yes/no confirmation requests
+        (e.g. "proceed with 12 edits?"). Receives ``(message, params)``
+        and returns a bool. May be sync or async.
+    confirm_timeout_secs:
+        How long ``confirm_callback`` may take before it is cancelled
+        and ``confirm_default`` is answered instead.
+    confirm_default:
+        Answer used when there is no ``confirm_callback``, it raises, or
+        it times out.
     tools:
         List of built-in tool names available to the agent.
     allowed_tools:
@@ -53,6 +84,13 @@ class AgentOptions:
     model: str | None = None
     permission_mode: str | None = None
     can_use_tool: Callable | None = None
+    permission_policy: str | None = None
+    tool_input_middleware: Callable | None = None
+    raw_notification_callback: Callable | None = None
+    stale_update_callback: Callable | None = None
+    confirm_callback: Callable | None = None
+    confirm_timeout_secs: float | None = None
+    confirm_default: bool = False
     tools: list[str] | None = None
     allowed_tools: list[str] = field(default_factory=list)
     disallowed_tools: list[str] = field(default_factory=list)
@@ -7,24 +7,41 @@ functions that can be passed as the ``can_use_tool`` callback in
 
 from __future__ import annotations
 
-from dataclasses import dataclass
+from dataclasses import dataclass, field
 from typing import Any
 
+from conduit_sdk._conduit_sdk import PermissionOption, PermissionResult
 
 # ---------------------------------------------------------------------------
 # Permission result types
 # ---------------------------------------------------------------------------
 
-
-class PermissionResult:
-    """Base class for permission decisions."""
+# ``PermissionResult`` is the Rust base class: ``decision`` ("allow" or
+# "deny"), ``option_id`` and ``reason``. Callbacks may also return a plain
+# dict with those keys.
 
 
 class PermissionResultAllow(PermissionResult):
-    """Approve a tool use request."""
+    """Approve a tool use request.
+
+    Parameters
+    ----------
+    reason:
+        Optional explanation for the approval (e.g. for audit logs).
+    option_id:
+        Permission option to select; defaults to the agent's first
+        allow option.
+    """
+
+    def __new__(
+        cls, reason: str | None = None, option_id: str | None = None
+    ) -> PermissionResultAllow:
+        return super().__new__(cls, "allow", option_id=option_id, reason=reason)
 
     def __repr__(self) -> str:
-        return "PermissionResultAllow()"
+        if self.reason is None:
+            return "PermissionResultAllow()"
+        return f"PermissionResultAllow(reason={self.reason!r})"
 
 
 class PermissionResultDeny(PermissionResult):
@@ -36,8 +53,8 @@ class PermissionResultDeny(PermissionResult):
         Human-readable explanation for the denial.
     """
 
-    def __init__(self, reason: str = "") -> None:
-        self.reason = reason
+    def __new__(cls, reason: str = "") -> PermissionResultDeny:
+        return super().__new__(cls, "deny", reason=reason)
 
     def __repr__(self) -> str:
         return f"PermissionResultDeny(reason={self.reason!r})"
@@ -62,12 +79,16 @@ class ToolPermissionContext:
         Unique identifier for this tool invocation.
     session_id:
         Session in which the tool use occurs.
+    options:
+        The choices the agent offered, as :class:`PermissionOption`. Return
+        one's ``option_id`` in the result to select it.
     """
 
     tool_name: str
     tool_input: str
     tool_use_id: str | None = None
     session_id: str | None = None
+    options: list[PermissionOption] = field(default_factory=list)
 
 
 # ---------------------------------------------------------------------------
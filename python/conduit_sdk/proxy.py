@@ -8,17 +8,29 @@ Example::
     chain = ProxyChain()
     chain.add(ContextInjector(context="You are helpful."))
     chain.add(ResponseFilter(max_tokens=1000))
-    await chain.build()
+
+    async with Client(["claude", "--agent"], proxy_chain=chain) as client:
+        ...
 """
 
 from __future__ import annotations
 
+import json
+import logging
 from abc import ABC, abstractmethod
 from typing import Any
 
-from conduit_sdk._conduit_sdk import ProxyConfig, RustProxyChain
+from conduit_sdk._conduit_sdk import ProxyConfig, RustProxyChain, RustProxyChainHandle
 from conduit_sdk.exceptions import ProxyError
 
+logger = logging.getLogger(__name__)
+
+
+def _log_from_rust(level: str, message: str) -> None:
+    """Log callback handed to the Rust chain: failed or restarted hops and
+    conductor errors go to this module's logger."""
+    logger.log(logging.getLevelName(level.upper()), message)
+
 
 class Proxy(ABC):
     """Base class for custom ACP proxies.
@@ -38,9 +50,21 @@ class Proxy(ABC):
     def command(self) -> list[str]:
         """Shell command to spawn this proxy's subprocess."""
 
+    @property
+    def restart(self) -> bool:
+        """Whether to respawn this proxy if it exits mid-run. Default ``False``."""
+        return False
+
+    @property
+    def env(self) -> dict[str, str]:
+        """Additional environment variables for the subprocess. Default empty."""
+        return {}
+
     def to_config(self) -> ProxyConfig:
         """Convert to the Rust-side proxy configuration."""
-        return ProxyConfig(name=self.name, command=self.command)
+        return ProxyConfig(
+            name=self.name, command=self.command, restart=self.restart, env=self.env
+        )
 
 
 class ProxyChain:
@@ -48,11 +72,15 @@ class ProxyChain:
 
     The chain is built and activated via :meth:`build`, which spawns
     each proxy subprocess and connects them using the sacp-conductor.
+    Passing the chain to :class:`Client` as ``proxy_chain`` builds it on
+    connect and tears it down on disconnect.
     """
 
     def __init__(self) -> None:
         self._rust_chain = RustProxyChain()
+        self._rust_chain.set_log_callback(_log_from_rust)
         self._proxies: list[Proxy] = []
+        self._handle: RustProxyChainHandle | None = None
 
     def add(self, proxy: Proxy) -> ProxyChain:
         """Append a proxy to the end of the chain. Returns self for chaining."""
@@ -64,17 +92,106 @@ class ProxyChain:
         self._proxies.insert(index, proxy)
         return self
 
-    async def build(self) -> None:
-        """Build and activate the proxy chain.
+    def remove(self, name: str) -> bool:
+        """Remove the proxy with the given name. Returns whether one was removed."""
+        index = self.index_of(name)
+        if index is None:
+            return False
+        del self._proxies[index]
+        return True
+
+    def move_to(self, name: str, index: int) -> ProxyChain:
+        """Move the named proxy to ``index``. Returns self for chaining.
 
-        Spawns each proxy subprocess and connects them via the conductor.
+        The relative order of the other proxies is preserved. Raises
+        :class:`ProxyError` if no proxy has that name or ``index`` is out
+        of range.
         """
-        if not self._proxies:
-            raise ProxyError("cannot build an empty proxy chain")
+        current = self.index_of(name)
+        if current is None:
+            raise ProxyError(f"no proxy named {name!r}")
+        if not 0 <= index < len(self._proxies):
+            raise ProxyError(
+                f"index {index} out of range (chain length: {len(self._proxies)})"
+            )
+        self._proxies.insert(index, self._proxies.pop(current))
+        return self
 
+    def index_of(self, name: str) -> int | None:
+        """Position of the named proxy in the chain, or ``None`` if absent."""
+        for index, proxy in enumerate(self._proxies):
+            if proxy.name == name:
+                return index
+        return None
+
+    async def _sync(self) -> None:
+        """Mirror the Python-side proxy list into the Rust chain."""
+        await self._rust_chain.clear()
         for proxy in self._proxies:
             await self._rust_chain.add(proxy.to_config())
-        await self._rust_chain.build()
+
+    async def validate(self) -> list[str]:
+        """Check each proxy's command without spawning anything.
+
+        Returns a list of problems, such as executables that cannot be
+        found on ``PATH``. An empty list means the chain looks runnable.
+        """
+        await self._sync()
+        return await self._rust_chain.validate()
+
+    async def build(
+        self,
+        agent_command: list[str],
+        *,
+        cwd: str | None = None,
+        env: dict[str, str] | None = None,
+    ) -> RustProxyChainHandle:
+        """Build and activate the proxy chain in front of an agent.
+
+        Spawns each proxy subprocess, then the agent, and connects them
+        via the conductor. Returns the running chain handle, which is also
+        kept on this instance until :meth:`teardown`.
+
+        Parameters
+        ----------
+        agent_command:
+            Shell command to spawn the agent at the end of the chain.
+        cwd:
+            Working directory for the agent process.
+        env:
+            Additional environment variables for the agent.
+        """
+        if not self._proxies:
+            raise ProxyError("cannot build an empty proxy chain")
+        if self._handle is not None:
+            raise ProxyError("proxy chain is already built")
+
+        await self._sync()
+        self._handle = await self._rust_chain.build(agent_command, cwd, env or {})
+        return self._handle
+
+    async def teardown(self) -> None:
+        """Kill the chain's subprocesses in reverse order. No-op if not built."""
+        if self._handle is not None:
+            await self._handle.teardown()
+            self._handle = None
+
+    @property
+    def built(self) -> bool:
+        """Whether the chain is currently built and running."""
+        return self._handle is not None
+
+    async def status(self) -> list[dict[str, Any]]:
+        """Per-hop health of the built chain.
+
+        Returns one ``{"name", "pid", "alive", "restarts"}`` dict per hop
+        in chain order, with the agent last. Empty before :meth:`build`.
+        """
+        return json.loads(await self._rust_chain.status())
+
+    async def ensure_healthy(self) -> None:
+        """Raise :class:`ProxyError` if a hop died without a restart policy."""
+        await self._rust_chain.ensure_healthy()
 
     @property
     def proxies(self) -> list[Proxy]:
@@ -94,9 +211,18 @@ class ProxyChain:
 class ContextInjector(Proxy):
     """Proxy that injects system context into prompts sent to the agent."""
 
-    def __init__(self, context: str, *, cmd: list[str] | None = None) -> None:
+    def __init__(
+        self,
+        context: str,
+        *,
+        cmd: list[str] | None = None,
+        restart: bool = False,
+        env: dict[str, str] | None = None,
+    ) -> None:
         self._context = context
         self._cmd = cmd or ["conduit-proxy-context"]
+        self._restart = restart
+        self._env = env or {}
 
     @property
     def name(self) -> str:
@@ -106,6 +232,14 @@ class ContextInjector(Proxy):
     def command(self) -> list[str]:
         return self._cmd
 
+    @property
+    def restart(self) -> bool:
+        return self._restart
+
+    @property
+    def env(self) -> dict[str, str]:
+        return self._env
+
     @property
     def context(self) -> str:
         return self._context
@@ -114,9 +248,18 @@ class ContextInjector(Proxy):
 class ResponseFilter(Proxy):
     """Proxy that filters or truncates agent responses."""
 
-    def __init__(self, *, max_tokens: int = 0, cmd: list[str] | None = None) -> None:
+    def __init__(
+        self,
+        *,
+        max_tokens: int = 0,
+        cmd: list[str] | None = None,
+        restart: bool = False,
+        env: dict[str, str] | None = None,
+    ) -> None:
         self._max_tokens = max_tokens
         self._cmd = cmd or ["conduit-proxy-filter"]
+        self._restart = restart
+        self._env = env or {}
 
     @property
     def name(self) -> str:
@@ -126,6 +269,14 @@ class ResponseFilter(Proxy):
     def command(self) -> list[str]:
         return self._cmd
 
+    @property
+    def restart(self) -> bool:
+        return self._restart
+
+    @property
+    def env(self) -> dict[str, str]:
+        return self._env
+
     @property
     def max_tokens(self) -> int:
         return self._max_tokens
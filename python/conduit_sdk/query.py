@@ -11,10 +11,13 @@ from __future__ import annotations
 import json
 from typing import Any, Callable
 
-from conduit_sdk._conduit_sdk import RustControlProtocol
+from conduit_sdk._conduit_sdk import (
+    ControlMessage,
+    PermissionOption,
+    RustControlProtocol,
+)
 from conduit_sdk.permissions import (
     PermissionResult,
-    PermissionResultAllow,
     PermissionResultDeny,
     ToolPermissionContext,
 )
@@ -28,7 +31,8 @@ class Query:
     protocol:
         The Rust control protocol instance.
     can_use_tool:
-        Optional permission callback.
+        Optional permission callback. Without one, every permission
+        request is denied.
     hook_callback:
         Optional hook dispatch callback.
     mcp_callback:
@@ -78,18 +82,27 @@ class Query:
         except (json.JSONDecodeError, TypeError):
             return {}
 
-    async def handle_control_request(self, raw_message: str) -> None:
+    async def handle_control_request(self, raw_message: ControlMessage | str) -> None:
         """Route an incoming control request by subtype.
 
         Parameters
         ----------
         raw_message:
-            Raw JSON string of the control message from agent stdout.
+            A ``ControlMessage`` as returned by ``recv_message()``, or the
+            raw JSON string of the control message from agent stdout.
         """
-        try:
-            msg = json.loads(raw_message)
-        except json.JSONDecodeError:
-            return
+        if isinstance(raw_message, ControlMessage):
+            msg = {
+                "type": "control",
+                "request_id": raw_message.request_id,
+                "subtype": raw_message.subtype,
+                "data": json.loads(raw_message.data),
+            }
+        else:
+            try:
+                msg = json.loads(raw_message)
+            except json.JSONDecodeError:
+                return
 
         if msg.get("type") != "control":
             return
@@ -117,20 +130,28 @@ class Query:
         tool_input = json.dumps(data.get("tool_input", {}))
         tool_use_id = data.get("tool_use_id")
         session_id = data.get("session_id")
+        options = [
+            PermissionOption(option["optionId"], option["name"], option["kind"])
+            for option in data.get("options") or []
+            if isinstance(option, dict)
+            and {"optionId", "name", "kind"} <= option.keys()
+        ]
 
         context = ToolPermissionContext(
             tool_name=tool_name,
             tool_input=tool_input,
             tool_use_id=tool_use_id,
             session_id=session_id,
+            options=options,
         )
 
         if self._can_use_tool is not None:
             result = await self._can_use_tool(tool_name, tool_input, context)
         else:
-            result = PermissionResultAllow()
+            # Refused without a callback, as the client's "prompt" policy does.
+            result = PermissionResultDeny("no permission callback set")
 
-        if isinstance(result, PermissionResultDeny):
+        if isinstance(result, PermissionResult) and result.decision == "deny":
             response_data = json.dumps(
                 {"decision": "deny", "reason": result.reason}
             )
@@ -6,13 +6,15 @@ Each session has its own message history, mode, and model settings.
 
 from __future__ import annotations
 
-from typing import TYPE_CHECKING
+from typing import TYPE_CHECKING, Any
 
 from conduit_sdk.exceptions import SessionError
 
 if TYPE_CHECKING:
+    from collections.abc import AsyncIterator
+
     from conduit_sdk.client import Client
-    from conduit_sdk.types import Message
+    from conduit_sdk.types import Message, RateLimitInfo, ResumeResult, SessionUpdate
 
 
 class Session:
@@ -31,6 +33,9 @@ class Session:
         self._client = client
         self._session_id: str | None = None
         self._mode: str | None = None
+        self._model: str | None = None
+        self._resume_result: ResumeResult | None = None
+        self._closed = False
 
     # -- Lifecycle -----------------------------------------------------------
 
@@ -45,6 +50,7 @@ class Session:
         self._session_id = await self._client._rust_client.new_session(
             cwd, meta_json, mcp_servers_json
         )
+        self._client._sessions[self._session_id] = self
         return self._session_id
 
     async def load(self, session_id: str, cwd: str | None = None) -> str:
@@ -52,8 +58,42 @@ class Session:
         self._session_id = await self._client._rust_client.load_session(
             session_id, cwd
         )
+        self._client._sessions[self._session_id] = self
         return self._session_id
 
+    def _apply_resume(self, result: ResumeResult) -> None:
+        """Adopt the session state restored by a ``session/resume`` response."""
+        self._session_id = result.session_id
+        if result.mode is not None:
+            self._mode = result.mode
+        if result.model is not None:
+            self._model = result.model
+        self._resume_result = result
+
+    async def close(self) -> None:
+        """Close this session: cancel any in-flight turn and detach it.
+
+        ACP has no session-close request, so the agent keeps the session
+        around; this only stops local use of it. The cancel is a
+        notification an idle agent ignores. Safe to call repeatedly.
+        """
+        if self._closed:
+            return
+        if self._session_id is not None and self._client.connected:
+            await self._client.cancel(self._session_id)
+        await self._detach()
+
+    async def _detach(self) -> None:
+        """Stop local use of this session without cancelling anything."""
+        if self._closed:
+            return
+        self._closed = True
+        if self._session_id is None:
+            return
+        if self._client.connected:
+            await self._client._rust_client.forget_session(self._session_id)
+        self._client._sessions.pop(self._session_id, None)
+
     # -- Configuration -------------------------------------------------------
 
     async def set_mode(self, mode: str) -> None:
@@ -75,6 +115,57 @@ class Session:
             raise SessionError("session not created")
         await self._client.cancel(self._session_id)
 
+    async def cancel_tool(self, tool_use_id: str) -> None:
+        """Cancel one running tool call in this session. See :meth:`Client.cancel_tool`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        await self._client.cancel_tool(self._session_id, tool_use_id)
+
+    async def add_mcp_server(self, name: str, config: Any) -> None:
+        """Attach an MCP server to this session. See :meth:`Client.add_mcp_server`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        await self._client.add_mcp_server(self._session_id, name, config)
+
+    async def cwd(self) -> str | None:
+        """Working directory of this session. See :meth:`Client.session_cwd`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        return await self._client.session_cwd(self._session_id)
+
+    async def available_commands(self) -> list[dict]:
+        """Commands the agent last advertised here. See :meth:`Client.available_commands`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        return await self._client.available_commands(self._session_id)
+
+    async def session_info(self) -> dict:
+        """Title and update time of this session. See :meth:`Client.session_info`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        return await self._client.session_info(self._session_id)
+
+    async def active_tool_calls(self) -> list[dict]:
+        """Tool calls still running here. See :meth:`Client.active_tool_calls`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        return await self._client.active_tool_calls(self._session_id)
+
+    async def rate_limit_status(self) -> RateLimitInfo | None:
+        """Latest rate-limit status here. See :meth:`Client.rate_limit_status`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        return await self._client.rate_limit_status(self._session_id)
+
+    async def run_command(
+        self, name: str, args: str | None = None
+    ) -> AsyncIterator[SessionUpdate]:
+        """Invoke a slash command in this session. See :meth:`Client.run_command`."""
+        if self._session_id is None:
+            raise SessionError("session not created")
+        async for update in self._client.run_command(self._session_id, name, args):
+            yield update
+
     async def fork(self, cwd: str | None = None) -> Session:
         """Fork this session into a new one with shared history."""
         if self._session_id is None:
@@ -87,6 +178,8 @@ class Session:
         """Send a prompt within this session."""
         if self._session_id is None:
             raise SessionError("session not created — call create() first")
+        if self._closed:
+            raise SessionError("session is closed")
         return await self._client.prompt_sync(text, session_id=self._session_id)
 
     # -- Properties ----------------------------------------------------------
@@ -99,5 +192,18 @@ class Session:
     def mode(self) -> str | None:
         return self._mode
 
+    @property
+    def model(self) -> str | None:
+        return self._model
+
+    @property
+    def resume_result(self) -> ResumeResult | None:
+        """The parsed resume response, if this session was resumed."""
+        return self._resume_result
+
+    @property
+    def closed(self) -> bool:
+        return self._closed
+
     def __repr__(self) -> str:
         return f"Session(id={self._session_id!r}, mode={self._mode!r})"
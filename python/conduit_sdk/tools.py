@@ -31,6 +31,9 @@ def tool(
     *,
     description: str = "",
     input_schema: dict[str, Any] | None = None,
+    strict_schema: bool = False,
+    max_concurrency: int | None = None,
+    timeout_secs: float | None = None,
 ) -> Callable:
     """Decorator to register an async function as an ACP tool.
 
@@ -44,6 +47,16 @@ def tool(
         JSON Schema dict describing the tool's input parameters.
         If omitted, a minimal schema is generated from the function
         signature.
+    strict_schema:
+        Validate the agent's input against ``input_schema`` before
+        calling the function. Off by default, which passes input
+        through unchecked.
+    max_concurrency:
+        Maximum number of concurrent invocations; further calls wait for
+        a slot. Unlimited by default.
+    timeout_secs:
+        Per-invocation time limit. A call that exceeds it is cancelled
+        and fails with a tool error. Unlimited by default.
 
     Example::
 
@@ -70,7 +83,12 @@ def tool(
         # stores the callback for later async invocation.
         # NOTE: In production this needs to be awaited. For now the
         # decorator is sync and registration is deferred to connect().
-        _pending_registrations.append((definition, fn))
+        options = {
+            "strict_schema": strict_schema,
+            "max_concurrency": max_concurrency,
+            "timeout_secs": timeout_secs,
+        }
+        _pending_registrations.append((definition, fn, options))
 
         @functools.wraps(fn)
         async def wrapper(*args: Any, **kwargs: Any) -> Any:
@@ -84,13 +102,13 @@ def tool(
 
 # Tools registered via the decorator are collected here and bulk-registered
 # when the client connects.
-_pending_registrations: list[tuple[ToolDefinition, Callable]] = []
+_pending_registrations: list[tuple[ToolDefinition, Callable, dict[str, Any]]] = []
 
 
 async def register_pending_tools() -> None:
     """Register all ``@tool``-decorated functions with the Rust registry."""
-    for definition, callback in _pending_registrations:
-        await _registry.register(definition, callback)
+    for definition, callback, options in _pending_registrations:
+        await _registry.register(definition, callback, **options)
     _pending_registrations.clear()
 
 
@@ -145,7 +163,7 @@ async def create_mcp_server(
     dict:
         MCP server configuration suitable for passing to the client.
     """
-    tool_list = tools or [fn for _, fn in _pending_registrations]
+    tool_list = tools or [fn for _, fn, _ in _pending_registrations]
     definitions = []
     for fn in tool_list:
         defn = getattr(fn, "_tool_definition", None)
@@ -267,7 +285,7 @@ def create_sdk_mcp_server(
         server = create_sdk_mcp_server("my-tools", tools=[query_db])
     """
     if tools is None:
-        tools = [fn for _, fn in _pending_registrations]
+        tools = [fn for _, fn, _ in _pending_registrations]
 
     # Validate all functions are @tool-decorated.
     for fn in tools:
@@ -7,48 +7,76 @@ pure-Python convenience types where needed.
 from __future__ import annotations
 
 from dataclasses import dataclass, field
+from decimal import Decimal
 from typing import Any
 
 # Re-export Rust-defined types so the rest of the Python layer
 # (and end-users) can import from ``conduit_sdk.types``.
 from conduit_sdk._conduit_sdk import (
+    AgentInfo,
     Capabilities,
     ClientConfig,
+    ConnectionState,
     ContentBlock,
     ContentType,
     ControlMessage,
     ControlResponse,
+    Cost,
+    FileEdit,
+    Framing,
+    McpServerConfig,
     Message,
     MessageRole,
     PermissionRequest,
     PermissionResponse,
+    PlanEntry,
+    PromptResult,
+    RateLimitInfo,
     ResultMessage,
+    ResumeResult,
     SessionUpdate,
     StreamEvent,
+    ToolContent,
     ToolDefinition,
+    ToolKind,
+    ToolLocation,
+    ToolStatus,
     UpdateKind,
 )
 
 __all__ = [
     # Original types
+    "AgentInfo",
     "Capabilities",
     "ClientConfig",
+    "ConnectionState",
     "ContentBlock",
     "ContentType",
+    "FileEdit",
+    "McpServerConfig",
     "Message",
     "MessageRole",
+    "PlanEntry",
     "SessionUpdate",
+    "ToolContent",
     "ToolDefinition",
+    "ToolKind",
+    "ToolLocation",
+    "ToolStatus",
     "UpdateKind",
     "ToolSchema",
     "HookContext",
     # New control protocol types
     "ControlMessage",
     "ControlResponse",
+    "Framing",
     "PermissionRequest",
     "PermissionResponse",
     "ResultMessage",
     "StreamEvent",
+    # Session results
+    "PromptResult",
+    "ResumeResult",
     # Content block helpers
     "TextBlock",
     "ThinkingBlock",
@@ -56,6 +84,9 @@ __all__ = [
     "ToolResultBlock",
     # Rate limit
     "RateLimitInfo",
+    # Usage
+    "Cost",
+    "UsageInfo",
 ]
 
 
@@ -268,53 +299,47 @@ class EmbeddedResourceBlock:
 
 
 @dataclass
-class RateLimitInfo:
-    """Rate limit event data from the agent.
+class UsageInfo:
+    """Context-window usage and cost reported by the agent.
 
-    This is surfaced when the agent sends a ``rate_limit_event``
-    extension notification (e.g. from Claude).
+    Parsed from ``SessionUpdate.usage_json``. The cost amount is kept as a
+    :class:`~decimal.Decimal` so billing code never sees float rounding.
 
     Parameters
     ----------
-    status:
-        Rate limit status (e.g. ``"allowed_warning"``).
-    resets_at:
-        Unix timestamp when the rate limit resets.
-    rate_limit_type:
-        Type of rate limit (e.g. ``"seven_day"``).
-    utilization:
-        Current utilization as a float (0.0–1.0).
-    is_using_overage:
-        Whether overage is being consumed.
-    surpassed_threshold:
-        The threshold that was surpassed (e.g. 0.75).
+    used:
+        Tokens currently in the context window.
+    size:
+        Total context window size in tokens.
+    cost_amount:
+        Cumulative session cost, or ``None`` if the agent did not report one.
+    cost_currency:
+        ISO 4217 currency code for ``cost_amount`` (e.g. ``"USD"``).
     raw_json:
         The full raw JSON string for any extra fields.
     """
 
-    status: str = ""
-    resets_at: int = 0
-    rate_limit_type: str = ""
-    utilization: float = 0.0
-    is_using_overage: bool = False
-    surpassed_threshold: float = 0.0
+    used: int = 0
+    size: int = 0
+    cost_amount: Decimal | None = None
+    cost_currency: str | None = None
     raw_json: str = ""
 
     @classmethod
-    def from_json(cls, json_str: str) -> "RateLimitInfo":
-        """Parse from the JSON string in ``SessionUpdate.rate_limit_json``."""
+    def from_json(cls, json_str: str) -> "UsageInfo":
+        """Parse from the JSON string in ``SessionUpdate.usage_json``."""
         import json
 
-        data = json.loads(json_str)
-        params = data.get("params", {})
-        info = params.get("rate_limit_info", params)
+        # parse_float keeps numeric amounts exact too, should an agent or
+        # older SDK emit a JSON number rather than a decimal string.
+        data = json.loads(json_str, parse_float=Decimal)
+        cost = data.get("cost") or {}
+        amount = cost.get("amount")
         return cls(
-            status=info.get("status", ""),
-            resets_at=info.get("resetsAt", 0),
-            rate_limit_type=info.get("rateLimitType", ""),
-            utilization=info.get("utilization", 0.0),
-            is_using_overage=info.get("isUsingOverage", False),
-            surpassed_threshold=info.get("surpassedThreshold", 0.0),
+            used=int(data.get("used", 0)),
+            size=int(data.get("size", 0)),
+            cost_amount=Decimal(str(amount)) if amount is not None else None,
+            cost_currency=cost.get("currency"),
             raw_json=json_str,
         )
 
@@ -11,11 +11,26 @@
 //! explicitly requires `F: Future + Send + 'static`.
 
 use crate::error::ConduitError;
-use crate::transport::AgentProcess;
+use crate::hooks::{
+    deny_reason, is_coroutine_function, HookDispatcherHandle, HookType, RustHookDispatcher,
+};
+use crate::log::LogSink;
+use crate::proxy::RustProxyChainHandle;
+use crate::tools::{
+    apply_input_middleware, InputMiddleware, PyTask, RustToolRegistry, ToolRegistryHandle,
+};
+#[cfg(unix)]
+use crate::transport::UnixSocketTransport;
+use crate::transport::{
+    AgentProcess, Direction, Recorded, ReplayTransport, TcpTransport, WatchBrokenPipe, WireRecorder,
+};
 use crate::types::{
-    Capabilities, ClientConfig, ContentBlock, ContentType, Message, MessageRole, SessionUpdate,
-    UpdateKind,
+    AgentInfo, Capabilities, ClientConfig, ConnectionState, ContentBlock, ContentType, Cost,
+    McpServerConfig, Message, MessageRole, PermissionOption, PermissionResult, PlanEntry,
+    PromptResult, RateLimitInfo, ReplyBlocks, ResultMessage, ResumeResult, SessionUpdate, ToolKind,
+    ToolLocation, ToolStatus, UpdateKind,
 };
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use sacp::schema::{
     AgentNotification, CancelNotification, ContentBlock as AcpContentBlock,
@@ -23,13 +38,18 @@ use sacp::schema::{
     PermissionOptionKind, PromptRequest, RequestPermissionOutcome, RequestPermissionRequest,
     RequestPermissionResponse, SelectedPermissionOutcome,
     SessionNotification, SetSessionModeRequest,
-    SessionUpdate as AcpSessionUpdate, ToolCallStatus,
+    SessionUpdate as AcpSessionUpdate, StopReason,
 };
 use sacp::UntypedMessage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+use tokio_util::sync::CancellationToken;
 
 // ---------------------------------------------------------------------------
 // Internal types for communicating with the background ACP task
@@ -40,7 +60,7 @@ enum AcpCommand {
     NewSession {
         cwd: String,
         meta_json: Option<String>,
-        mcp_servers_json: Option<String>,
+        mcp_servers: Vec<sacp::schema::McpServer>,
         reply: oneshot::Sender<Result<String, ConduitError>>,
     },
     LoadSession {
@@ -59,9 +79,6 @@ enum AcpCommand {
         value: String,
         reply: oneshot::Sender<Result<String, ConduitError>>,
     },
-    Cancel {
-        session_id: String,
-    },
     ForkSession {
         session_id: String,
         cwd: String,
@@ -74,17 +91,169 @@ enum AcpCommand {
     ResumeSession {
         session_id: String,
         cwd: String,
-        reply: oneshot::Sender<Result<String, ConduitError>>,
+        reply: oneshot::Sender<Result<ResumeResult, ConduitError>>,
+    },
+    AddMcpServer {
+        session_id: String,
+        server: serde_json::Value,
+        reply: oneshot::Sender<Result<(), ConduitError>>,
     },
     Prompt {
         session_id: String,
         text: String,
         content_json: Option<String>,
+        /// Caller's `_meta` for the request; `requestId` is set over it.
+        meta: serde_json::Map<String, serde_json::Value>,
+        request_id: String,
+        turn_id: u64,
+        /// Counts the turn as running until the command is answered.
+        running: RunningTurn,
         reply: oneshot::Sender<Result<(), ConduitError>>,
     },
+    /// Any request the SDK doesn't model, sent as-is.
+    Raw {
+        method: String,
+        params: serde_json::Value,
+        reply: oneshot::Sender<Result<String, ConduitError>>,
+    },
+    /// Any notification the SDK doesn't model; fire-and-forget.
+    RawNotify {
+        method: String,
+        params: serde_json::Value,
+    },
     Shutdown,
 }
 
+impl AcpCommand {
+    /// The ACP method the command sends, for tracing.
+    fn method(&self) -> &str {
+        match self {
+            AcpCommand::NewSession { .. } => "session/new",
+            AcpCommand::LoadSession { .. } => "session/load",
+            AcpCommand::SetSessionMode { .. } => "session/set_mode",
+            AcpCommand::SetConfigOption { .. } => "session/set_config_option",
+            AcpCommand::ForkSession { .. } => "session/fork",
+            AcpCommand::ListSessions { .. } => "session/list",
+            AcpCommand::ResumeSession { .. } => "session/resume",
+            AcpCommand::AddMcpServer { .. } => ADD_MCP_SERVER_METHOD,
+            AcpCommand::Prompt { .. } => "session/prompt",
+            AcpCommand::Raw { method, .. } | AcpCommand::RawNotify { method, .. } => method,
+            AcpCommand::Shutdown => "shutdown",
+        }
+    }
+
+    /// Answer the command with a connection error instead of running it.
+    fn fail(self, reason: &str) {
+        let error = || ConduitError::Connection(reason.to_string());
+        match self {
+            AcpCommand::NewSession { reply, .. }
+            | AcpCommand::LoadSession { reply, .. }
+            | AcpCommand::SetConfigOption { reply, .. }
+            | AcpCommand::ForkSession { reply, .. }
+            | AcpCommand::ListSessions { reply, .. }
+            | AcpCommand::Raw { reply, .. } => {
+                let _ = reply.send(Err(error()));
+            }
+            AcpCommand::SetSessionMode { reply, .. }
+            | AcpCommand::AddMcpServer { reply, .. }
+            | AcpCommand::Prompt { reply, .. } => {
+                let _ = reply.send(Err(error()));
+            }
+            AcpCommand::ResumeSession { reply, .. } => {
+                let _ = reply.send(Err(error()));
+            }
+            AcpCommand::RawNotify { .. } | AcpCommand::Shutdown => {}
+        }
+    }
+}
+
+/// Why the connection ended when the agent stopped reading its input.
+const AGENT_INPUT_CLOSED: &str = "agent closed its input";
+
+/// Why the connection ended when the agent closed its output, e.g. by
+/// exiting.
+const AGENT_DISCONNECTED: &str = "agent closed the connection";
+
+/// Why the connection ended when the agent stopped answering heartbeats.
+const AGENT_UNRESPONSIVE: &str = "agent stopped answering heartbeats";
+
+/// Extension method sent as the heartbeat ping. Any answer, even a
+/// method-not-found error, shows the agent is still responsive.
+const HEARTBEAT_METHOD: &str = "_conduit/ping";
+
+/// Extension method asking the agent to stop one running tool call while
+/// the rest of the turn carries on. ACP has no standard request for this.
+const CANCEL_TOOL_METHOD: &str = "_conduit/cancel_tool";
+
+/// Extension method attaching an MCP server to a running session, for
+/// agents advertising `mcp_hot_add`. ACP has no standard request for this.
+const ADD_MCP_SERVER_METHOD: &str = "_conduit/add_mcp_server";
+
+/// JSON-RPC code for a method the receiver doesn't implement.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Unanswered heartbeats in a row after which the agent is given up on.
+const HEARTBEAT_MAX_MISSES: u32 = 3;
+
+/// [`Heartbeat::latency`] before any ping has been answered.
+const NO_LATENCY: u64 = u64::MAX;
+
+/// Liveness pings for one connection, enabled by `heartbeat_secs`.
+#[derive(Clone)]
+struct Heartbeat {
+    /// Time between pings, and how long each may go unanswered.
+    interval: Duration,
+    /// Round trip of the last answered ping in microseconds, or
+    /// [`NO_LATENCY`].
+    latency: Arc<AtomicU64>,
+}
+
+/// The turn an event belongs to, stamped on it when it is queued.
+#[derive(Clone, Debug, Default)]
+struct TurnStamp {
+    request_id: Option<String>,
+    turn_id: Option<u64>,
+}
+
+/// A [`StreamEvent`] as queued for `recv_update()` and the prompt collector.
+#[derive(Debug)]
+struct QueuedEvent {
+    event: StreamEvent,
+    turn: TurnStamp,
+}
+
+/// The turn most recently started in each session, by session ID.
+type SessionTurns = Arc<std::sync::Mutex<HashMap<String, TurnStamp>>>;
+
+/// Queues [`StreamEvent`]s stamped with one turn, so an event read after
+/// the next turn has started still carries the turn it came from.
+#[derive(Clone)]
+struct EventSender {
+    tx: mpsc::Sender<QueuedEvent>,
+    turn: TurnStamp,
+}
+
+impl EventSender {
+    /// A sender stamping with the turn now running in `session_id`.
+    fn in_session(&self, turns: &SessionTurns, session_id: &str) -> Self {
+        let turn = turns.lock().unwrap().get(session_id).cloned();
+        Self {
+            tx: self.tx.clone(),
+            turn: turn.unwrap_or_default(),
+        }
+    }
+
+    async fn send(&self, event: StreamEvent) -> Result<(), mpsc::error::SendError<QueuedEvent>> {
+        let turn = self.turn.clone();
+        self.tx.send(QueuedEvent { event, turn }).await
+    }
+
+    fn try_send(&self, event: StreamEvent) -> Result<(), mpsc::error::TrySendError<QueuedEvent>> {
+        let turn = self.turn.clone();
+        self.tx.try_send(QueuedEvent { event, turn })
+    }
+}
+
 /// Streaming events pushed from the notification handler to the prompt collector.
 #[derive(Debug)]
 enum StreamEvent {
@@ -94,14 +263,15 @@ enum StreamEvent {
         tool_name: String,
         tool_input: String,
         tool_use_id: String,
-        tool_kind: Option<String>,
-        tool_status: Option<String>,
+        tool_kind: Option<ToolKind>,
+        tool_status: Option<ToolStatus>,
     },
     ToolUseUpdate {
         tool_use_id: String,
-        tool_status: Option<String>,
+        tool_status: Option<ToolStatus>,
         tool_content: Option<String>,
         tool_locations: Option<String>,
+        locations: Vec<ToolLocation>,
     },
     ToolUseEnd {
         tool_use_id: String,
@@ -131,6 +301,33 @@ enum StreamEvent {
         method: String,
         params_json: String,
     },
+    Extension {
+        method: String,
+        params_json: String,
+    },
+    Confirmation {
+        message: String,
+        params_json: String,
+    },
+    PermissionRequest {
+        tool_name: String,
+        tool_input: String,
+        tool_use_id: String,
+        options_json: String,
+    },
+    /// An image, resource or resource link in the agent's message.
+    Content {
+        block: ContentBlock,
+    },
+    /// Agent message content that could not be converted; the turn
+    /// carries on.
+    ContentError {
+        message: String,
+    },
+    /// The connection to the agent is gone; nothing follows.
+    Closed {
+        reason: String,
+    },
 }
 
 // ---------------------------------------------------------------------------
@@ -139,13 +336,38 @@ enum StreamEvent {
 
 /// Internal state shared across the client's async operations.
 struct ClientInner {
-    process: AgentProcess,
+    /// The agent subprocess, or `None` when connected through a proxy chain
+    /// (the chain owns the agent process in that case).
+    process: Option<AgentProcess>,
     capabilities: Option<Capabilities>,
     initialized: bool,
-    session_id: Option<String>,
+    /// The default session, used by prompts that name none. Shared with
+    /// [`RustClient`], which queues such prompts under it.
+    session_id: DefaultSession,
     cmd_tx: mpsc::Sender<AcpCommand>,
-    /// JSON-serialized agent info from initialize response.
-    agent_info_json: Option<String>,
+    /// The connection itself, for cancels and `cancel_tool()`: the command
+    /// loop awaits each prompt before reading on, so they would only reach
+    /// the agent once the turn they are meant to stop had ended.
+    cx: sacp::JrConnectionCx,
+    /// Agent info from the initialize response.
+    agent_info: Option<AgentInfo>,
+    /// Names of the MCP servers attached to each session, by session ID.
+    mcp_servers: HashMap<String, HashSet<String>>,
+    /// Working directory each session was created or loaded with.
+    session_cwds: SessionCwds,
+    /// Commands the agent last advertised for each session.
+    available_commands: SessionCommands,
+    /// Latest title and update time the agent reported for each session.
+    session_info: SessionInfos,
+    /// Latest rate-limit status the agent reported for each session.
+    rate_limits: RateLimits,
+    /// Tool calls still running in each session.
+    active_tool_calls: ActiveToolCalls,
+    /// Prompt turns sent and not yet answered in each session, whether
+    /// from `prompt()` or `send_prompt()`.
+    running_turns: RunningTurns,
+    /// Writer of `record_path`, closed before the agent is killed.
+    recorder: Option<WireRecorder>,
 }
 
 // ---------------------------------------------------------------------------
@@ -164,450 +386,802 @@ pub struct RustClient {
     /// Streaming events from the background task's notification handler.
     /// Separated from `inner` so prompt() can drain it without holding the
     /// inner lock across await points.
-    update_rx: Arc<Mutex<Option<mpsc::Receiver<StreamEvent>>>>,
+    update_rx: Arc<Mutex<Option<mpsc::Receiver<QueuedEvent>>>>,
     /// Reply receiver from the most recent `send_prompt()` call.
     prompt_reply_rx: Arc<Mutex<Option<oneshot::Receiver<Result<(), ConduitError>>>>>,
     /// Python permission callback, set before connect().
     permission_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    /// Tool allow/deny rules and the policy for everything else.
+    permission_rules: Arc<std::sync::Mutex<PermissionRules>>,
+    /// Rewrites tool input before it reaches the permission callback.
+    input_middleware: InputMiddleware,
+    /// Receives every session notification as wire JSON, set before connect().
+    raw_notification_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    /// Receives every session notification as wire JSON, off the read loop.
+    update_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    /// Answers the agent's yes/no confirmation requests.
+    confirmation: Arc<std::sync::Mutex<ConfirmationPolicy>>,
+    /// Current [`ConnectionState`], stored as its `u8` discriminant.
+    state: Arc<AtomicU8>,
+    /// Prompt turns sent so far; numbers the turn request IDs. Kept across
+    /// reconnects so IDs stay unique for the client's lifetime.
+    prompt_count: Arc<AtomicU64>,
+    /// Prompt turns started in each session so far; numbers the turn IDs.
+    /// Kept across reconnects, like `prompt_count`.
+    turn_counts: TurnCounts,
+    /// `prompt()`-style turns currently collecting updates.
+    active_turns: Arc<AtomicUsize>,
+    /// Receives updates left over from earlier turns as they are drained.
+    stale_update_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    /// Where internal events (spawn, handshake, task errors) are logged.
+    log: LogSink,
+    /// Runs each session's `prompt()`-style turns one at a time, in call
+    /// order.
+    prompt_queues: PromptQueues,
+    /// The connected client's default session ID, readable without
+    /// awaiting `inner`.
+    default_session: DefaultSession,
+    /// Round trip of the last answered heartbeat, in microseconds.
+    heartbeat_latency: Arc<AtomicU64>,
 }
 
-#[pymethods]
 impl RustClient {
-    #[new]
-    fn new(config: ClientConfig) -> Self {
-        Self {
-            inner: Arc::new(Mutex::new(None)),
-            config,
-            update_rx: Arc::new(Mutex::new(None)),
-            prompt_reply_rx: Arc::new(Mutex::new(None)),
-            permission_callback: Arc::new(std::sync::Mutex::new(None)),
+    /// The identity sent in `initialize`, from the config's `client_*`
+    /// fields.
+    fn client_info(&self) -> Implementation {
+        let mut info = Implementation::new(
+            self.config.client_name.clone(),
+            self.config.client_version.clone(),
+        );
+        info.title = self.config.client_title.clone();
+        info
+    }
+
+    /// What [`serve_acp`] needs from this client for a new connection,
+    /// with no recorder yet.
+    fn serve_config(
+        &self,
+        tool_registry: Option<ToolRegistryHandle>,
+        hooks: Option<HookDispatcherHandle>,
+    ) -> ServeConfig {
+        ServeConfig {
+            perm_callback: self.permission_callback.clone(),
+            perm_rules: self.permission_rules.clone(),
+            input_middleware: self.input_middleware.clone(),
+            raw_callback: self.raw_notification_callback.clone(),
+            update_callback: self.update_callback.clone(),
+            confirmation: self.confirmation.clone(),
+            tool_registry,
+            hooks,
+            protocol_version: self.config.protocol_version,
+            client_info: self.client_info(),
+            timeout: handshake_timeout(self.config.timeout_secs),
+            heartbeat: self.heartbeat(),
+            state: self.state.clone(),
+            recorder: None,
+            log: self.log.clone(),
         }
     }
 
-    /// Store a Python permission callback to be invoked for tool use requests.
-    ///
-    /// Must be called before `connect()`. The callback signature should be:
-    /// `async def callback(tool_name: str, tool_input: str, context) -> PermissionResult`
-    fn set_permission_callback(&self, callback: PyObject) {
-        *self.permission_callback.lock().unwrap() = Some(callback);
+    /// Heartbeat settings for a new connection, if `heartbeat_secs` is set.
+    /// The field is settable after validation, so an interval too long for
+    /// a `Duration` is clamped rather than trusted.
+    fn heartbeat(&self) -> Option<Heartbeat> {
+        (self.config.heartbeat_secs > 0.0).then(|| Heartbeat {
+            interval: Duration::try_from_secs_f64(self.config.heartbeat_secs)
+                .unwrap_or(Duration::MAX),
+            latency: self.heartbeat_latency.clone(),
+        })
     }
 
-    /// Spawn the agent subprocess and perform the ACP initialize handshake.
+    /// The work behind `connect()`/`connect_blocking()`: spawn the agent (or
+    /// connect to `config.endpoint` over TCP or `config.socket_path` over a
+    /// Unix socket), run the handshake and store the connection.
     ///
-    /// Returns the agent's advertised [`Capabilities`].
-    fn connect<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    /// The config is validated first, so a bad one raises `ValueError`
+    /// before anything is spawned.
+    fn connect_future(
+        &self,
+        tool_registry: Option<ToolRegistryHandle>,
+        hooks: Option<HookDispatcherHandle>,
+    ) -> PyResult<impl Future<Output = Result<Capabilities, ConduitError>> + Send + 'static> {
+        self.config.validate(false)?;
         let inner = self.inner.clone();
         let config = self.config.clone();
         let update_rx_slot = self.update_rx.clone();
-        let perm_callback_for_connect = self.permission_callback.clone();
+        let mut serve = self.serve_config(tool_registry, hooks);
+        let state = self.state.clone();
+        let log = self.log.clone();
+        let default_session = self.default_session.clone();
 
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let mut process = AgentProcess::spawn(
-                &config.command,
-                config.cwd.as_deref(),
-                &config.env,
-            )
-            .await?;
-
-            // Take ownership of subprocess stdio for the ACP byte-stream transport.
-            let child_stdin = process.take_stdin()?;
-            let child_stdout = process.take_stdout()?;
-            let transport =
-                sacp::ByteStreams::new(child_stdin.compat_write(), child_stdout.compat());
-
-            // Channels: commands → background task, streaming events ← notification handler
-            let (cmd_tx, cmd_rx) = mpsc::channel::<AcpCommand>(32);
-            let (update_tx, update_rx) = mpsc::channel::<StreamEvent>(512);
-            let (caps_tx, caps_rx) =
-                oneshot::channel::<Result<(Capabilities, Option<String>), ConduitError>>();
-
-            // Clone update_tx for the notification handler (the other copy
-            // goes into the spawned task to send Done events).
-            let notif_tx = update_tx.clone();
-            let ext_notif_tx = update_tx.clone();
-
-            // Clone the permission callback for the request handler.
-            let perm_callback = perm_callback_for_connect;
-
-            // Build the handler chain with a spawned client task.
-            let chain = sacp::JrHandlerChain::new()
-                .name("conduit-sdk")
-                // --- Session update notifications (streaming chunks) ---
-                .on_receive_notification(
-                    async move |notification: SessionNotification, _cx| {
-                        match &notification.update {
-                            AcpSessionUpdate::AgentMessageChunk(chunk) => {
-                                if let AcpContentBlock::Text(tc) = &chunk.content {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::TextDelta(tc.text.clone()))
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::AgentThoughtChunk(chunk) => {
-                                if let AcpContentBlock::Text(tc) = &chunk.content {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::ThoughtDelta(tc.text.clone()))
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::ToolCall(tc) => {
-                                let tool_name = tc.title.clone();
-                                let tool_input = tc
-                                    .raw_input
-                                    .as_ref()
-                                    .map(|v| v.to_string())
-                                    .unwrap_or_default();
-                                let tool_use_id = tc.tool_call_id.0.to_string();
-                                let tool_kind = Some(format!("{:?}", tc.kind));
-                                let tool_status = Some(format!("{:?}", tc.status));
-                                let _ = notif_tx
-                                    .send(StreamEvent::ToolUseStart {
-                                        tool_name,
-                                        tool_input,
-                                        tool_use_id,
-                                        tool_kind,
-                                        tool_status,
-                                    })
-                                    .await;
-                            }
-                            AcpSessionUpdate::ToolCallUpdate(tcu) => {
-                                let tool_use_id = tcu.tool_call_id.0.to_string();
-                                let tool_status = tcu.fields.status.as_ref().map(|s| format!("{:?}", s));
-                                let tool_content = tcu.fields.content.as_ref()
-                                    .and_then(|c| serde_json::to_string(c).ok());
-                                let tool_locations = tcu.fields.locations.as_ref()
-                                    .and_then(|l| serde_json::to_string(l).ok());
-
-                                // Send rich update event
-                                let _ = notif_tx
-                                    .send(StreamEvent::ToolUseUpdate {
-                                        tool_use_id: tool_use_id.clone(),
-                                        tool_status: tool_status.clone(),
-                                        tool_content,
-                                        tool_locations,
-                                    })
-                                    .await;
-
-                                // Also send legacy ToolUseEnd if terminal status
-                                let is_terminal = tcu.fields.status.as_ref().map_or(false, |s| {
-                                    matches!(s, ToolCallStatus::Completed | ToolCallStatus::Failed)
-                                });
-                                if is_terminal {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::ToolUseEnd { tool_use_id })
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::Plan(plan) => {
-                                if let Ok(json) = serde_json::to_string(&plan.entries) {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::Plan { entries_json: json })
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::AvailableCommandsUpdate(cmd_update) => {
-                                if let Ok(json) = serde_json::to_string(&cmd_update.available_commands) {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::CommandsUpdate { commands_json: json })
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::CurrentModeUpdate(mode_update) => {
-                                let _ = notif_tx
-                                    .send(StreamEvent::ModeChange {
-                                        mode_id: mode_update.current_mode_id.0.to_string(),
-                                    })
-                                    .await;
-                            }
-                            AcpSessionUpdate::ConfigOptionUpdate(config_update) => {
-                                if let Ok(json) = serde_json::to_string(&config_update.config_options) {
-                                    let _ = notif_tx
-                                        .send(StreamEvent::ConfigUpdate { config_json: json })
-                                        .await;
-                                }
-                            }
-                            AcpSessionUpdate::UsageUpdate(usage) => {
-                                let usage_data = serde_json::json!({
-                                    "used": usage.used,
-                                    "size": usage.size,
-                                    "cost": usage.cost.as_ref().map(|c| serde_json::json!({
-                                        "amount": c.amount,
-                                        "currency": &c.currency,
-                                    })),
-                                });
-                                let _ = notif_tx
-                                    .send(StreamEvent::Usage {
-                                        usage_json: usage_data.to_string(),
-                                    })
-                                    .await;
-                            }
-                            AcpSessionUpdate::SessionInfoUpdate(info) => {
-                                let info_data = serde_json::json!({
-                                    "title": serde_json::to_value(&info.title).unwrap_or_default(),
-                                    "updated_at": serde_json::to_value(&info.updated_at).unwrap_or_default(),
-                                });
-                                let _ = notif_tx
-                                    .send(StreamEvent::SessionInfo {
-                                        info_json: info_data.to_string(),
-                                    })
-                                    .await;
-                            }
-                            AcpSessionUpdate::UserMessageChunk(_) => {
-                                // Echo of user message — ignore.
-                            }
-                            _ => {
-                                // Future variants — ignore gracefully.
-                            }
-                        }
-                        Ok(())
-                    },
-                )
-                // --- Extension notifications (rate_limit_event, etc.) ---
-                .on_receive_notification(
-                    async move |notification: AgentNotification, _cx| {
-                        if let AgentNotification::ExtNotification(ext) = notification {
-                            let method = ext.method.to_string();
-                            let params_json = ext.params.to_string();
-                            let _ = ext_notif_tx
-                                .send(StreamEvent::RateLimit {
-                                    method,
-                                    params_json,
-                                })
-                                .await;
-                        }
-                        Ok(())
-                    },
-                )
-                // --- Permission requests ---
-                .on_receive_request(
-                    async move |request: RequestPermissionRequest, request_cx| {
-                        // Try to call the Python permission callback.
-                        let decision = call_permission_callback(
-                            &perm_callback,
-                            &request,
-                        )
-                        .await;
-
-                        match decision {
-                            PermissionDecision::Allow => {
-                                // Select the first "allow" option, or just the first option.
-                                let allow_option = request
-                                    .options
-                                    .iter()
-                                    .find(|o| {
-                                        o.kind == PermissionOptionKind::AllowOnce
-                                            || o.kind == PermissionOptionKind::AllowAlways
-                                    })
-                                    .or_else(|| request.options.first());
-
-                                if let Some(opt) = allow_option {
-                                    request_cx.respond(RequestPermissionResponse::new(
-                                        RequestPermissionOutcome::Selected(
-                                            SelectedPermissionOutcome::new(
-                                                opt.option_id.clone(),
-                                            ),
-                                        ),
-                                    ))
-                                } else {
-                                    request_cx.respond(RequestPermissionResponse::new(
-                                        RequestPermissionOutcome::Cancelled,
-                                    ))
-                                }
-                            }
-                            PermissionDecision::Deny => {
-                                request_cx.respond(RequestPermissionResponse::new(
-                                    RequestPermissionOutcome::Cancelled,
-                                ))
-                            }
+        async move {
+            let guard = ConnectGuard::acquire(&state, &inner).await?;
+            let recorder = match config.record_path.as_deref() {
+                Some(path) => Some(WireRecorder::create(path, log.clone()).await?),
+                None => None,
+            };
+            let (outgoing, incoming, mut process): (
+                Box<dyn tokio::io::AsyncWrite + Unpin + Send>,
+                Box<dyn tokio::io::AsyncRead + Unpin + Send>,
+                Option<AgentProcess>,
+            ) = match (config.endpoint.as_deref(), config.socket_path.as_deref()) {
+                (Some(_), Some(_)) => {
+                    return Err(ConduitError::Connection(
+                        "set either endpoint or socket_path, not both".into(),
+                    ));
+                }
+                (Some(endpoint), None) => {
+                    let (tcp_read, tcp_write) =
+                        TcpTransport::connect(endpoint, &log).await?.into_split();
+                    (Box::new(tcp_write), Box::new(tcp_read), None)
+                }
+                #[cfg(unix)]
+                (None, Some(path)) => {
+                    let (uds_read, uds_write) =
+                        UnixSocketTransport::connect(path).await?.into_split();
+                    (Box::new(uds_write), Box::new(uds_read), None)
+                }
+                #[cfg(not(unix))]
+                (None, Some(_)) => {
+                    return Err(ConduitError::Unsupported(
+                        "socket_path needs Unix domain sockets, which this platform lacks".into(),
+                    ));
+                }
+                (None, None) => {
+                    let (command, cwd, env) = (&config.command, config.cwd.as_deref(), &config.env);
+                    let spawned = if config.use_shell {
+                        AgentProcess::spawn_shell(command, cwd, env, &log).await
+                    } else {
+                        AgentProcess::spawn(command, cwd, env, &log).await
+                    };
+                    let mut process = spawned.inspect_err(|e| log.error(e.to_string()))?;
+                    // Take ownership of subprocess stdio for the ACP
+                    // byte-stream transport.
+                    let child_stdin = process.take_stdin()?;
+                    let child_stdout = process.take_stdout()?;
+                    (Box::new(child_stdin), Box::new(child_stdout), Some(process))
+                }
+            };
+            serve.recorder = recorder;
+            let handshake = serve_acp(outgoing, incoming, serve).await;
+            let conn = match handshake {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log.error(format!("initialize handshake failed: {e}"));
+                    // Don't leave a half-started agent behind.
+                    if let Some(process) = process.as_mut() {
+                        if let Err(kill_err) = process.kill().await {
+                            log.error(kill_err.to_string());
                         }
-                    },
-                )
-                // --- Client logic (init handshake + command loop) ---
-                .with_spawned(move |cx| {
-                    acp_task(cx, caps_tx, cmd_rx, update_tx)
-                });
-
-            // Spawn the long-lived background task that owns the ACP connection.
-            tokio::spawn(async move {
-                if let Err(e) = chain.serve(transport).await {
-                    eprintln!("conduit-sdk: ACP background task error: {e}");
+                    }
+                    return Err(e);
                 }
-            });
-
-            // Wait for the background task to complete the initialize handshake.
-            let (capabilities, agent_info_json) = caps_rx
-                .await
-                .map_err(|_| {
-                    ConduitError::Connection(
-                        "ACP background task dropped before sending capabilities".into(),
-                    )
-                })?
-                ?;
+            };
 
             // Store the streaming receiver for prompt() to drain.
-            *update_rx_slot.lock().await = Some(update_rx);
+            *update_rx_slot.lock().await = Some(conn.update_rx);
 
+            *default_session.lock().unwrap() = None;
             let client_inner = ClientInner {
                 process,
-                capabilities: Some(capabilities.clone()),
+                capabilities: Some(conn.capabilities.clone()),
                 initialized: true,
-                session_id: None,
-                cmd_tx,
-                agent_info_json,
+                session_id: default_session,
+                cmd_tx: conn.cmd_tx,
+                cx: conn.cx,
+                agent_info: conn.agent_info,
+                mcp_servers: HashMap::new(),
+                session_cwds: conn.session_cwds,
+                available_commands: conn.available_commands,
+                session_info: conn.session_info,
+                rate_limits: conn.rate_limits,
+                active_tool_calls: conn.active_tool_calls,
+                running_turns: RunningTurns::default(),
+                recorder: conn.recorder,
             };
 
             *inner.lock().await = Some(client_inner);
-            Ok(capabilities)
-        })
+            guard.connected();
+            Ok(conn.capabilities)
+        }
     }
 
-    /// Create a new ACP session and return its ID.
-    #[pyo3(signature = (cwd=None, meta_json=None, mcp_servers_json=None))]
-    fn new_session<'py>(
+    /// The work behind `connect_via()` and `connect_replay()`: run the
+    /// handshake over the `(outgoing, incoming)` halves `open` yields once
+    /// the connect guard is held, and store the connection. No process is
+    /// owned; whatever is on the other end lives and dies elsewhere.
+    fn connect_stream_future<W, R>(
         &self,
-        py: Python<'py>,
-        cwd: Option<String>,
-        meta_json: Option<String>,
-        mcp_servers_json: Option<String>,
-    ) -> PyResult<Bound<'py, PyAny>> {
+        open: impl Future<Output = Result<(W, R), ConduitError>> + Send + 'static,
+        tool_registry: Option<ToolRegistryHandle>,
+        hooks: Option<HookDispatcherHandle>,
+    ) -> impl Future<Output = Result<Capabilities, ConduitError>> + Send + 'static
+    where
+        W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+        R: tokio::io::AsyncRead + Unpin + Send + 'static,
+    {
         let inner = self.inner.clone();
+        let update_rx_slot = self.update_rx.clone();
+        let mut serve = self.serve_config(tool_registry, hooks);
+        let state = self.state.clone();
+        let log = self.log.clone();
+        let record_path = self.config.record_path.clone();
+        let default_session = self.default_session.clone();
 
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let cmd_tx = {
-                let guard = inner.lock().await;
-                let client = guard
-                    .as_ref()
-                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                client.cmd_tx.clone()
+        async move {
+            let guard = ConnectGuard::acquire(&state, &inner).await?;
+            let recorder = match record_path.as_deref() {
+                Some(path) => Some(WireRecorder::create(path, log.clone()).await?),
+                None => None,
             };
-
-            let cwd = cwd.unwrap_or_else(|| {
-                std::env::current_dir()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string()
-            });
-            let (reply_tx, reply_rx) = oneshot::channel();
-            cmd_tx
-                .send(AcpCommand::NewSession {
-                    cwd,
-                    meta_json,
-                    mcp_servers_json,
-                    reply: reply_tx,
-                })
+            let (outgoing, incoming) = open.await?;
+            serve.recorder = recorder;
+            let conn = serve_acp(outgoing, incoming, serve)
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .inspect_err(|e| log.error(format!("initialize handshake failed: {e}")))?;
 
-            let session_id = reply_rx
-                .await
-                .map_err(|_| ConduitError::Connection("session reply dropped".into()))??;
+            *update_rx_slot.lock().await = Some(conn.update_rx);
 
-            // Store as the default session for prompt() auto-use.
-            {
-                let mut guard = inner.lock().await;
-                if let Some(client) = guard.as_mut() {
-                    client.session_id = Some(session_id.clone());
-                }
+            *default_session.lock().unwrap() = None;
+            let client_inner = ClientInner {
+                process: None,
+                capabilities: Some(conn.capabilities.clone()),
+                initialized: true,
+                session_id: default_session,
+                cmd_tx: conn.cmd_tx,
+                cx: conn.cx,
+                agent_info: conn.agent_info,
+                mcp_servers: HashMap::new(),
+                session_cwds: conn.session_cwds,
+                available_commands: conn.available_commands,
+                session_info: conn.session_info,
+                rate_limits: conn.rate_limits,
+                active_tool_calls: conn.active_tool_calls,
+                running_turns: RunningTurns::default(),
+                recorder: conn.recorder,
+            };
+
+            *inner.lock().await = Some(client_inner);
+            guard.connected();
+            Ok(conn.capabilities)
+        }
+    }
+
+    /// The prompt queue for a turn in `session_id`. A turn that names no
+    /// session queues under the default session's ID, so it waits behind
+    /// turns that name that session explicitly and they behind it.
+    fn queue_key(&self, session_id: Option<&str>) -> String {
+        match session_id {
+            Some(id) => id.to_string(),
+            None => self
+                .default_session
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The work behind `prompt()` and friends: one full prompt turn.
+    ///
+    /// With `retry_on_rate_limit` set, a turn that fails after the agent
+    /// reported a rate limit with a retry hint is sent once more after
+    /// waiting that long, or `max_rate_limit_wait_secs` if that is less.
+    ///
+    /// Events still buffered from earlier turns are drained first, unless
+    /// another turn is running.
+    ///
+    /// The turn takes its place in the session's prompt queue when called,
+    /// and waits there until earlier turns of the session are done; with
+    /// `on_busy="error"` it fails with `SessionError` instead of waiting.
+    /// Its request ID is minted only once it is through the queue, so a
+    /// turn rejected there, or dropped while waiting, doesn't use one up.
+    fn turn_future(
+        &self,
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta: serde_json::Map<String, serde_json::Value>,
+    ) -> impl Future<Output = Result<Turn, ConduitError>> + Send + 'static {
+        let inner = self.inner.clone();
+        let update_rx_slot = self.update_rx.clone();
+        let state = ConnectionState::from_u8(self.state.load(Ordering::Acquire));
+        let prompt_count = self.prompt_count.clone();
+        let turn_counts = self.turn_counts.clone();
+        let prompt_reply_rx = self.prompt_reply_rx.clone();
+        let active_turns = self.active_turns.clone();
+        let stale_callback = self.stale_update_callback.clone();
+        let log = self.log.clone();
+        let max_wait = Duration::try_from_secs_f64(self.config.max_rate_limit_wait_secs)
+            .unwrap_or(Duration::MAX);
+        let retry = self.config.retry_on_rate_limit.then(|| {
+            (
+                text.clone(),
+                session_id.clone(),
+                content_json.clone(),
+                meta.clone(),
+            )
+        });
+        let ticket = PromptTicket::take(
+            &self.prompt_queues,
+            &self.queue_key(session_id.as_deref()),
+            self.config.on_busy == "error",
+        );
+
+        async move {
+            if state != ConnectionState::Connected {
+                return Err(ConduitError::Connection(format!(
+                    "cannot prompt: client is {state:?}, not Connected"
+                )));
             }
-            Ok(session_id)
-        })
+            let ticket = ticket?;
+            ticket.wait().await;
+            let request_id = mint_request_id(&prompt_count);
+            let active = ActiveTurn::enter(&active_turns);
+            if active.alone {
+                drain_pending(&log, &update_rx_slot, &prompt_reply_rx, &stale_callback).await;
+            }
+            let started = Instant::now();
+            let result = run_turn(
+                &inner,
+                &update_rx_slot,
+                &turn_counts,
+                request_id,
+                text,
+                session_id,
+                content_json,
+                meta,
+            )
+            .await;
+
+            let Some((text, session_id, content_json, meta)) = retry else {
+                return result;
+            };
+            let Err(e) = &result else {
+                return result;
+            };
+            let Some(wait) =
+                rate_limit_wait(&inner, session_id.as_deref(), started, max_wait).await
+            else {
+                return result;
+            };
+            log.warning(format!(
+                "prompt failed while rate limited ({e}); retrying in {:.1}s",
+                wait.as_secs_f64()
+            ));
+            tokio::time::sleep(wait).await;
+            run_turn(
+                &inner,
+                &update_rx_slot,
+                &turn_counts,
+                mint_request_id(&prompt_count),
+                text,
+                session_id,
+                content_json,
+                meta,
+            )
+            .await
+        }
     }
 
-    /// Resume an existing session by ID.
-    fn load_session<'py>(
+    /// The work behind `send_prompt()`: start a turn whose updates are
+    /// read with `recv_update()`, after draining events still buffered from
+    /// earlier turns unless another turn is running. The turn's request ID
+    /// is minted when the future first runs, so a caller that validates
+    /// before awaiting it doesn't use one up on a call it rejects.
+    fn send_prompt_future(
         &self,
-        py: Python<'py>,
-        session_id: String,
-        cwd: Option<String>,
-    ) -> PyResult<Bound<'py, PyAny>> {
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta: serde_json::Map<String, serde_json::Value>,
+    ) -> impl Future<Output = Result<(), ConduitError>> + Send + 'static {
         let inner = self.inner.clone();
+        let update_rx_slot = self.update_rx.clone();
+        let prompt_reply_rx = self.prompt_reply_rx.clone();
+        let prompt_count = self.prompt_count.clone();
+        let turn_counts = self.turn_counts.clone();
+        let active_turns = self.active_turns.clone();
+        let stale_callback = self.stale_update_callback.clone();
+        let log = self.log.clone();
+        let state = ConnectionState::from_u8(self.state.load(Ordering::Acquire));
 
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let cmd_tx = {
+        async move {
+            if state != ConnectionState::Connected {
+                return Err(ConduitError::Connection(format!(
+                    "cannot prompt: client is {state:?}, not Connected"
+                )));
+            }
+            let request_id = mint_request_id(&prompt_count);
+            let (cmd_tx, default_session_id, running_turns) = {
                 let guard = inner.lock().await;
                 let client = guard
                     .as_ref()
                     .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                client.cmd_tx.clone()
-            };
-
-            let cwd = cwd.unwrap_or_else(|| {
-                std::env::current_dir()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string()
-            });
-            let (reply_tx, reply_rx) = oneshot::channel();
-            cmd_tx
-                .send(AcpCommand::LoadSession {
-                    session_id,
-                    cwd,
-                    reply: reply_tx,
-                })
-                .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
-
-            let session_id = reply_rx
-                .await
-                .map_err(|_| ConduitError::Connection("load session reply dropped".into()))??;
-
-            // Store as the default session.
-            {
-                let mut guard = inner.lock().await;
-                if let Some(client) = guard.as_mut() {
-                    client.session_id = Some(session_id.clone());
+                if !client.initialized {
+                    return Err(ConduitError::Connection("client not initialized".into()));
                 }
-            }
-            Ok(session_id)
-        })
-    }
+                (
+                    client.cmd_tx.clone(),
+                    client.session_id.lock().unwrap().clone(),
+                    client.running_turns.clone(),
+                )
+            };
 
-    /// Set the agent mode for a session (e.g. "ask", "code", "architect").
-    fn set_session_mode<'py>(
-        &self,
-        py: Python<'py>,
-        session_id: String,
-        mode_id: String,
-    ) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let cmd_tx = {
-                let guard = inner.lock().await;
-                let client = guard
-                    .as_ref()
-                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                client.cmd_tx.clone()
+            // Auto-create session if needed.
+            let session_id = match session_id.or(default_session_id) {
+                Some(id) => id,
+                None => {
+                    let cwd = std::env::current_dir()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string();
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    cmd_tx
+                        .send(AcpCommand::NewSession {
+                            cwd,
+                            meta_json: None,
+                            mcp_servers: Vec::new(),
+                            reply: reply_tx,
+                        })
+                        .await
+                        .map_err(|_| task_closed("send_prompt/new_session", None))?;
+                    let id = reply_rx
+                        .await
+                        .map_err(|_| reply_dropped("send_prompt/new_session", None))??;
+                    {
+                        let mut guard = inner.lock().await;
+                        if let Some(client) = guard.as_mut() {
+                            *client.session_id.lock().unwrap() = Some(id.clone());
+                        }
+                    }
+                    id
+                }
             };
 
+            if active_turns.load(Ordering::Acquire) == 0 {
+                drain_pending(&log, &update_rx_slot, &prompt_reply_rx, &stale_callback).await;
+            }
+
+            // Send prompt and store the reply receiver for later.
+            let turn_id = mint_turn_id(&turn_counts, &session_id);
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::SetSessionMode {
-                    session_id,
-                    mode_id,
+                .send(AcpCommand::Prompt {
+                    session_id: session_id.clone(),
+                    text,
+                    content_json,
+                    meta,
+                    request_id,
+                    turn_id,
+                    running: RunningTurn::start(&running_turns, &session_id),
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("send_prompt", Some(&session_id)))?;
 
-            reply_rx
-                .await
-                .map_err(|_| ConduitError::Connection("set mode reply dropped".into()))??;
+            *prompt_reply_rx.lock().await = Some(reply_rx);
             Ok(())
-        })
+        }
     }
+}
 
-    /// Set a config option on a session (replaces set_mode/set_model).
-    fn set_config_option<'py>(
+#[pymethods]
+impl RustClient {
+    #[new]
+    fn new(config: ClientConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(None)),
+            config,
+            update_rx: Arc::new(Mutex::new(None)),
+            prompt_reply_rx: Arc::new(Mutex::new(None)),
+            permission_callback: Arc::new(std::sync::Mutex::new(None)),
+            permission_rules: Arc::new(std::sync::Mutex::new(PermissionRules::default())),
+            input_middleware: Arc::new(std::sync::Mutex::new(None)),
+            raw_notification_callback: Arc::new(std::sync::Mutex::new(None)),
+            update_callback: Arc::new(std::sync::Mutex::new(None)),
+            confirmation: Arc::new(std::sync::Mutex::new(ConfirmationPolicy::default())),
+            state: Arc::new(AtomicU8::new(ConnectionState::Disconnected as u8)),
+            prompt_count: Arc::new(AtomicU64::new(0)),
+            turn_counts: TurnCounts::default(),
+            active_turns: Arc::new(AtomicUsize::new(0)),
+            stale_update_callback: Arc::new(std::sync::Mutex::new(None)),
+            log: LogSink::default(),
+            prompt_queues: PromptQueues::default(),
+            default_session: DefaultSession::default(),
+            heartbeat_latency: Arc::new(AtomicU64::new(NO_LATENCY)),
+        }
+    }
+
+    /// Store a Python permission callback to be invoked for tool use requests.
+    ///
+    /// Must be called before `connect()`. The callback signature should be:
+    /// `async def callback(tool_name: str, tool_input: str, context) -> PermissionResult`
+    fn set_permission_callback(&self, callback: PyObject) {
+        *self.permission_callback.lock().unwrap() = Some(callback);
+    }
+
+    /// Choose how permission requests are answered.
+    ///
+    /// `"prompt"` (the default) asks the permission callback; a request
+    /// that arrives while none is set is refused, with a logged warning.
+    /// `"allow_all"` approves every request and `"deny_all"` refuses every
+    /// one, selecting the agent's reject option, without calling the
+    /// callback. `PreToolUse` hooks still run first under every policy.
+    /// Raises `ValueError` for any other policy.
+    fn set_permission_policy(&self, policy: &str) -> PyResult<()> {
+        let policy = match policy {
+            "prompt" => PermissionPolicy::Prompt,
+            "allow_all" => PermissionPolicy::AllowAll,
+            "deny_all" => PermissionPolicy::DenyAll,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "permission policy must be \"prompt\", \"allow_all\" or \"deny_all\", \
+                     got {other:?}"
+                )))
+            }
+        };
+        self.permission_rules.lock().unwrap().policy = policy;
+        Ok(())
+    }
+
+    /// Pre-approve or block tools by name, ahead of the callback and policy.
+    ///
+    /// Patterns match the whole tool name, with `*` standing for any run of
+    /// characters (e.g. `"Read*"`, `"*_delete"`). A tool matching `deny` is
+    /// refused, one matching `allow` is approved, and any other falls
+    /// through to the permission policy. `deny` wins when both match.
+    /// Replaces the rules from any earlier call.
+    #[pyo3(signature = (allow=Vec::new(), deny=Vec::new()))]
+    fn set_tool_rules(&self, allow: Vec<String>, deny: Vec<String>) {
+        let mut rules = self.permission_rules.lock().unwrap();
+        rules.allow = allow;
+        rules.deny = deny;
+    }
+
+    /// Store a tool input middleware applied before the permission callback.
+    ///
+    /// Must be called before `connect()`. The callable receives
+    /// `(tool_name, tool_input: dict)` and returns the rewritten dict or
+    /// `None`; the permission callback then sees the rewritten input.
+    #[pyo3(signature = (callback=None))]
+    fn set_input_middleware(&self, callback: Option<PyObject>) {
+        *self.input_middleware.lock().unwrap() = callback.map(Arc::new);
+    }
+
+    /// Store a raw notification tap, or clear it with `None`.
+    ///
+    /// Must be called before `connect()`. The callable receives each
+    /// `session/update` notification's params as a JSON string, taken from
+    /// the wire as sent (even if the typed mapping behind `recv_update()`
+    /// then fails to parse them), before that mapping, and may be sync or
+    /// async. Errors it raises are logged and otherwise ignored.
+    #[pyo3(signature = (callback=None))]
+    fn set_raw_notification_callback(&self, callback: Option<PyObject>) {
+        *self.raw_notification_callback.lock().unwrap() = callback;
+    }
+
+    /// Store an observer for every incoming session update, or clear it
+    /// with `None`.
+    ///
+    /// The callable receives each `session/update` notification's params
+    /// as a JSON string, from the same wire tap as the raw notification
+    /// callback, and may be sync or async. Unlike that callback it runs on
+    /// its own task, in arrival order, so a slow observer never delays the
+    /// connection. It may be set or cleared at any time; errors it raises
+    /// are logged and otherwise ignored.
+    #[pyo3(signature = (callback=None))]
+    fn set_update_callback(&self, callback: Option<PyObject>) {
+        *self.update_callback.lock().unwrap() = callback;
+    }
+
+    /// Store a callback for updates left over from earlier turns, or clear
+    /// it with `None`.
+    ///
+    /// When a prompt starts, events still buffered from a previous turn
+    /// (e.g. late tool updates from a cancelled prompt) are discarded
+    /// rather than mixed into the new one. The callable receives each as a
+    /// [`SessionUpdate`] and may be sync or async.
+    #[pyo3(signature = (callback=None))]
+    fn set_stale_update_callback(&self, callback: Option<PyObject>) {
+        *self.stale_update_callback.lock().unwrap() = callback;
+    }
+
+    /// Route the client's internal log to `callback(level, message)`, or
+    /// buffer it again with `None`.
+    ///
+    /// `level` is `"warning"` or `"error"`. Lines logged before a callback
+    /// is set (up to the last 256) are delivered to it when it is. The
+    /// callable must be synchronous.
+    #[pyo3(signature = (callback=None))]
+    fn set_log_callback(&self, py: Python<'_>, callback: Option<PyObject>) {
+        self.log.set_callback(py, callback);
+    }
+
+    /// Store the callback answering the agent's confirmation requests
+    /// (`_conduit/request_confirmation`, e.g. "proceed with 12 edits?").
+    ///
+    /// Must be called before `connect()`. The callable receives
+    /// `(message, params: dict)`, may be sync or async, and its truthiness
+    /// is the answer. `default` is answered when there is no callback, the
+    /// callback raises, or it runs past `timeout_secs`, which must be
+    /// positive and finite (else `ValueError`).
+    #[pyo3(signature = (callback=None, timeout_secs=None, default=false))]
+    fn set_confirmation_callback(
+        &self,
+        callback: Option<PyObject>,
+        timeout_secs: Option<f64>,
+        default: bool,
+    ) -> PyResult<()> {
+        let timeout = match timeout_secs {
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(secs) => {
+                return Err(PyValueError::new_err(format!(
+                    "timeout_secs must be positive, got {secs}"
+                )))
+            }
+            None => None,
+        };
+        *self.confirmation.lock().unwrap() = ConfirmationPolicy {
+            callback: callback.map(Arc::new),
+            timeout,
+            default,
+        };
+        Ok(())
+    }
+
+    /// Spawn the agent subprocess and perform the ACP initialize handshake.
+    ///
+    /// With a `registry`, MCP `tools/call` requests from the agent are
+    /// answered by invoking the registry's tools. With `hooks`, the
+    /// dispatcher's `PreToolUse` hooks run on each permission request
+    /// before the permission callback, and may rewrite the tool input it
+    /// sees or deny the tool outright.
+    ///
+    /// Returns the agent's advertised [`Capabilities`].
+    #[pyo3(signature = (registry=None, hooks=None))]
+    fn connect<'py>(
         &self,
         py: Python<'py>,
-        session_id: String,
-        config_id: String,
-        value: String,
+        registry: Option<PyRef<'py, RustToolRegistry>>,
+        hooks: Option<PyRef<'py, RustHookDispatcher>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let connect =
+            self.connect_future(registry.map(|r| r.handle()), hooks.map(|h| h.handle()))?;
+        pyo3_async_runtimes::tokio::future_into_py(py, async move { Ok(connect.await?) })
+    }
+
+    /// Blocking `connect()` for callers without an event loop.
+    ///
+    /// Drives the SDK's tokio runtime to completion on the calling thread;
+    /// raises if called from inside a running asyncio loop. Raises
+    /// `ValueError` if the permission, confirmation, raw notification,
+    /// update or stale update callback, the input middleware or a hook is
+    /// an `async def` function, since there is no loop to run it on.
+    #[pyo3(signature = (registry=None, hooks=None))]
+    fn connect_blocking(
+        &self,
+        py: Python<'_>,
+        registry: Option<PyRef<'_, RustToolRegistry>>,
+        hooks: Option<PyRef<'_, RustHookDispatcher>>,
+    ) -> PyResult<Capabilities> {
+        let current = |slot: &Arc<std::sync::Mutex<Option<PyObject>>>| {
+            slot.lock().unwrap().as_ref().map(|cb| cb.clone_ref(py))
+        };
+        let permission = current(&self.permission_callback);
+        let raw_notification = current(&self.raw_notification_callback);
+        let update = current(&self.update_callback);
+        let stale_update = current(&self.stale_update_callback);
+        let confirmation = self.confirmation.lock().unwrap().callback.clone();
+        let middleware = self.input_middleware.lock().unwrap().clone();
+        for (name, callback) in [
+            ("permission callback", permission.as_ref()),
+            ("confirmation callback", confirmation.as_deref()),
+            ("raw notification callback", raw_notification.as_ref()),
+            ("update callback", update.as_ref()),
+            ("stale update callback", stale_update.as_ref()),
+            ("tool input middleware", middleware.as_deref()),
+        ] {
+            if let Some(callback) = callback {
+                if is_coroutine_function(callback.bind(py))? {
+                    return Err(PyValueError::new_err(format!(
+                        "connect_blocking() cannot run an async {name}; use connect()"
+                    )));
+                }
+            }
+        }
+        if let Some(hooks) = hooks.as_deref() {
+            if hooks.has_async_hook(py)? {
+                return Err(PyValueError::new_err(
+                    "connect_blocking() cannot run async hooks; use connect()",
+                ));
+            }
+        }
+
+        let connect =
+            self.connect_future(registry.map(|r| r.handle()), hooks.map(|h| h.handle()))?;
+        block_on(py, "connect_blocking", connect)
+    }
+
+    /// Connect through a built proxy chain instead of spawning the agent.
+    ///
+    /// The chain's conductor owns the proxy and agent subprocesses; this
+    /// client only speaks ACP to the head of the chain. Tear the chain down
+    /// separately with `RustProxyChainHandle.teardown()`. `registry` and
+    /// `hooks` work as for `connect()`.
+    #[pyo3(signature = (chain, registry=None, hooks=None))]
+    fn connect_via<'py>(
+        &self,
+        py: Python<'py>,
+        chain: PyRef<'py, RustProxyChainHandle>,
+        registry: Option<PyRef<'py, RustToolRegistry>>,
+        hooks: Option<PyRef<'py, RustHookDispatcher>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client_stream = chain.client_stream();
+        let open = async move {
+            let stream = client_stream.lock().await.take().ok_or_else(|| {
+                ConduitError::Proxy("proxy chain already has a connected client".into())
+            })?;
+            let (chain_read, chain_write) = tokio::io::split(stream);
+            Ok((chain_write, chain_read))
+        };
+        let (registry, hooks) = (registry.map(|r| r.handle()), hooks.map(|h| h.handle()));
+        let connect = self.connect_stream_future(open, registry, hooks);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move { Ok(connect.await?) })
+    }
+
+    /// Connect to a recorded agent instead of spawning one, for
+    /// deterministic tests.
+    ///
+    /// `recording_path` is a newline-delimited file of the JSON-RPC
+    /// messages an agent sent. Each request the client makes is answered by
+    /// playing the recording up to and including its next response (see
+    /// `ReplayTransport`), so the same notifications reach the handler chain
+    /// and come out as the same [`SessionUpdate`]s. `initialize` gets a stub
+    /// answer unless the recording opens with one. `registry` and `hooks`
+    /// work as for `connect()`.
+    #[pyo3(signature = (recording_path, registry=None, hooks=None))]
+    fn connect_replay<'py>(
+        &self,
+        py: Python<'py>,
+        recording_path: String,
+        registry: Option<PyRef<'py, RustToolRegistry>>,
+        hooks: Option<PyRef<'py, RustHookDispatcher>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let open = async move {
+            let (replay_read, replay_write) =
+                ReplayTransport::open(&recording_path).await?.into_split();
+            Ok((replay_write, replay_read))
+        };
+        let (registry, hooks) = (registry.map(|r| r.handle()), hooks.map(|h| h.handle()));
+        let connect = self.connect_stream_future(open, registry, hooks);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move { Ok(connect.await?) })
+    }
+
+    /// Create a new ACP session and return its ID.
+    ///
+    /// `meta_json` must be a JSON object and `mcp_servers_json` a JSON array
+    /// of ACP `McpServer` objects; either raises `ProtocolError` if it
+    /// doesn't parse. `None` leaves the field out of the request.
+    #[pyo3(signature = (cwd=None, meta_json=None, mcp_servers_json=None))]
+    fn new_session<'py>(
+        &self,
+        py: Python<'py>,
+        cwd: Option<String>,
+        meta_json: Option<String>,
+        mcp_servers_json: Option<String>,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
+        let mcp_names_json = mcp_servers_json.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let invalid = |e: serde_json::Error| {
+                ConduitError::Protocol(format!("invalid mcp_servers_json: {e}"))
+            };
+            let mcp_servers: Vec<sacp::schema::McpServer> = match mcp_servers_json.as_deref() {
+                Some(json) => serde_json::from_str(json).map_err(invalid)?,
+                None => Vec::new(),
+            };
             let cmd_tx = {
                 let guard = inner.lock().await;
                 let client = guard
@@ -616,52 +1190,63 @@ impl RustClient {
                 client.cmd_tx.clone()
             };
 
+            let cwd = cwd.unwrap_or_else(|| {
+                std::env::current_dir()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::SetConfigOption {
-                    session_id,
-                    config_id,
-                    value,
+                .send(AcpCommand::NewSession {
+                    cwd,
+                    meta_json,
+                    mcp_servers,
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("new_session", None))?;
 
-            reply_rx
+            let session_id = reply_rx
                 .await
-                .map_err(|_| ConduitError::Connection("set config reply dropped".into()))?
-                .map_err(Into::into)
+                .map_err(|_| reply_dropped("new_session", None))??;
+
+            // Store as the default session for prompt() auto-use.
+            {
+                let mut guard = inner.lock().await;
+                if let Some(client) = guard.as_mut() {
+                    *client.session_id.lock().unwrap() = Some(session_id.clone());
+                    client
+                        .mcp_servers
+                        .insert(session_id.clone(), mcp_server_names(mcp_names_json.as_deref()));
+                }
+            }
+            Ok(session_id)
         })
     }
 
-    /// Cancel (interrupt) a running prompt in a session.
-    fn cancel_session<'py>(
+    /// Create a new ACP session with the given MCP servers attached.
+    ///
+    /// Like `new_session`, but typed: raises `SessionError` straight away
+    /// if a server config is invalid.
+    #[pyo3(signature = (servers, cwd=None, meta_json=None))]
+    fn new_session_with_servers<'py>(
         &self,
         py: Python<'py>,
-        session_id: String,
+        servers: Vec<McpServerConfig>,
+        cwd: Option<String>,
+        meta_json: Option<String>,
     ) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let cmd_tx = {
-                let guard = inner.lock().await;
-                let client = guard
-                    .as_ref()
-                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                client.cmd_tx.clone()
-            };
-
-            cmd_tx
-                .send(AcpCommand::Cancel { session_id })
-                .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
-
-            Ok(())
-        })
+        let servers = servers
+            .iter()
+            .map(McpServerConfig::to_acp)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mcp_servers_json = serde_json::Value::Array(servers).to_string();
+        self.new_session(py, cwd, meta_json, Some(mcp_servers_json))
     }
 
-    /// Fork a session, creating a new session with shared history.
-    fn fork_session<'py>(
+    /// Resume an existing session by ID.
+    fn load_session<'py>(
         &self,
         py: Python<'py>,
         session_id: String,
@@ -686,26 +1271,35 @@ impl RustClient {
             });
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::ForkSession {
-                    session_id,
+                .send(AcpCommand::LoadSession {
+                    session_id: session_id.clone(),
                     cwd,
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("load_session", Some(&session_id)))?;
 
-            reply_rx
+            let session_id = reply_rx
                 .await
-                .map_err(|_| ConduitError::Connection("fork session reply dropped".into()))?
-                .map_err(Into::into)
+                .map_err(|_| reply_dropped("load_session", Some(&session_id)))??;
+
+            // Store as the default session.
+            {
+                let mut guard = inner.lock().await;
+                if let Some(client) = guard.as_mut() {
+                    *client.session_id.lock().unwrap() = Some(session_id.clone());
+                }
+            }
+            Ok(session_id)
         })
     }
 
-    /// List available sessions. Returns JSON array.
-    fn list_sessions<'py>(
+    /// Set the agent mode for a session (e.g. "ask", "code", "architect").
+    fn set_session_mode<'py>(
         &self,
         py: Python<'py>,
-        cwd: Option<String>,
+        session_id: String,
+        mode_id: String,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
 
@@ -720,26 +1314,28 @@ impl RustClient {
 
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::ListSessions {
-                    cwd,
+                .send(AcpCommand::SetSessionMode {
+                    session_id: session_id.clone(),
+                    mode_id,
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("set_session_mode", Some(&session_id)))?;
 
             reply_rx
                 .await
-                .map_err(|_| ConduitError::Connection("list sessions reply dropped".into()))?
-                .map_err(Into::into)
+                .map_err(|_| reply_dropped("set_session_mode", Some(&session_id)))??;
+            Ok(())
         })
     }
 
-    /// Resume an existing agent-side session.
-    fn resume_session<'py>(
+    /// Set a config option on a session (replaces set_mode/set_model).
+    fn set_config_option<'py>(
         &self,
         py: Python<'py>,
         session_id: String,
-        cwd: Option<String>,
+        config_id: String,
+        value: String,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
 
@@ -752,460 +1348,2659 @@ impl RustClient {
                 client.cmd_tx.clone()
             };
 
-            let cwd = cwd.unwrap_or_else(|| {
-                std::env::current_dir()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string()
-            });
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::ResumeSession {
-                    session_id,
-                    cwd,
+                .send(AcpCommand::SetConfigOption {
+                    session_id: session_id.clone(),
+                    config_id,
+                    value,
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("set_config_option", Some(&session_id)))?;
 
-            let session_id = reply_rx
+            reply_rx
                 .await
-                .map_err(|_| ConduitError::Connection("resume session reply dropped".into()))??;
-
-            // Store as the default session.
-            {
-                let mut guard = inner.lock().await;
-                if let Some(client) = guard.as_mut() {
-                    client.session_id = Some(session_id.clone());
-                }
-            }
-            Ok(session_id)
+                .map_err(|_| reply_dropped("set_config_option", Some(&session_id)))?
+                .map_err(Into::into)
         })
     }
 
-    /// Send a prompt to the agent within the given (or default) session.
+    /// Cancel (interrupt) a running prompt in a session.
+    fn cancel_session<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let cx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cx.clone()
+            };
+
+            // Sent straight away, even while a prompt is running.
+            cx.send_notification(CancelNotification::new(session_id.clone()))
+                .map_err(|e| ConduitError::from(&e).in_session(&session_id))?;
+            Ok(())
+        })
+    }
+
+    /// Cancel every session this client created, loaded, forked or
+    /// resumed and has not forgotten, plus any with a prompt turn still
+    /// running, then wait up to `grace_secs` for the running turns (from
+    /// `prompt()` and `send_prompt()` alike) to be answered.
     ///
-    /// Returns a list of [`Message`] objects. Streaming is handled at the
-    /// Python layer by wrapping this in an async iterator.
-    #[pyo3(signature = (text, session_id=None, content_json=None))]
-    fn prompt<'py>(
+    /// Meant for shutdown: call it before `disconnect()` so the agent gets
+    /// a chance to clean up. Returns the cancelled session IDs, sorted.
+    /// Raises `ValueError` if `grace_secs` is negative or not finite.
+    #[pyo3(signature = (grace_secs=1.0))]
+    fn cancel_all<'py>(&self, py: Python<'py>, grace_secs: f64) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        let grace = Duration::try_from_secs_f64(grace_secs).map_err(|_| {
+            PyValueError::new_err(format!(
+                "grace_secs must be 0 or a positive number of seconds, got {grace_secs}"
+            ))
+        })?;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let (cx, running_turns, session_ids) = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                let cwds = client.session_cwds.lock().unwrap();
+                let mut ids: BTreeSet<String> = cwds.keys().cloned().collect();
+                ids.extend(client.running_turns.lock().unwrap().keys().cloned());
+                (client.cx.clone(), client.running_turns.clone(), ids)
+            };
+            let session_ids: Vec<String> = session_ids.into_iter().collect();
+
+            // Sent straight away, even while prompts are running.
+            for session_id in &session_ids {
+                cx.send_notification(CancelNotification::new(session_id.clone()))
+                    .map_err(|e| ConduitError::from(&e).in_session(session_id))?;
+            }
+
+            // Each cancelled turn ends once the agent answers its prompt.
+            let deadline = Instant::now() + grace;
+            while !running_turns.lock().unwrap().is_empty() && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(session_ids)
+        })
+    }
+
+    /// Cancel one running tool call, leaving the rest of the turn running.
+    ///
+    /// Sent as the `_conduit/cancel_tool` extension request with the
+    /// session and tool call ids, straight to the agent rather than behind
+    /// the running prompt. Raises `ProtocolError` if the agent doesn't
+    /// implement it.
+    fn cancel_tool<'py>(
         &self,
         py: Python<'py>,
-        text: String,
-        session_id: Option<String>,
-        content_json: Option<String>,
+        session_id: String,
+        tool_use_id: String,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
-        let update_rx_slot = self.update_rx.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            // Snapshot cmd_tx and session_id without holding the lock across awaits.
-            let (cmd_tx, default_session_id) = {
+            let cx = {
                 let guard = inner.lock().await;
                 let client = guard
                     .as_ref()
                     .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                if !client.initialized {
-                    return Err(
-                        ConduitError::Connection("client not initialized".into()).into()
-                    );
-                }
-                (client.cmd_tx.clone(), client.session_id.clone())
+                client.cx.clone()
             };
 
-            // Use explicit session_id, or fall back to default, or auto-create.
-            let session_id = match session_id.or(default_session_id) {
-                Some(id) => id,
-                None => {
-                    let cwd = std::env::current_dir()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string();
-                    let (reply_tx, reply_rx) = oneshot::channel();
-                    cmd_tx
-                        .send(AcpCommand::NewSession {
-                            cwd,
-                            meta_json: None,
-                            mcp_servers_json: None,
-                            reply: reply_tx,
-                        })
-                        .await
-                        .map_err(|_| {
-                            ConduitError::Connection("background task closed".into())
-                        })?;
-                    let id = reply_rx.await.map_err(|_| {
-                        ConduitError::Connection("session reply dropped".into())
-                    })??;
-
-                    // Persist session_id for subsequent prompts.
-                    {
-                        let mut guard = inner.lock().await;
-                        if let Some(client) = guard.as_mut() {
-                            client.session_id = Some(id.clone());
-                        }
-                    }
-                    id
-                }
+            // Sent straight away, so it reaches the agent mid-turn.
+            let params = serde_json::json!({
+                "sessionId": session_id,
+                "toolCallId": tool_use_id,
+            });
+            let request = UntypedMessage::new(CANCEL_TOOL_METHOD, &params)
+                .map_err(|e| ConduitError::Protocol(e.to_string()))?;
+            let result = cx
+                .send_request(request)
+                .block_task()
+                .await
+                .map_err(|e| ConduitError::from(&e).in_session(&session_id));
+            match result {
+                Ok(_) => Ok(()),
+                Err(ConduitError::ProtocolCoded {
+                    code: METHOD_NOT_FOUND,
+                    ..
+                }) => Err(ConduitError::Protocol(
+                    "agent does not support cancelling a single tool call".into(),
+                )
+                .into()),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    /// Fork a session, creating a new session with shared history.
+    fn fork_session<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+        cwd: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let cmd_tx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cmd_tx.clone()
             };
 
-            // Send the prompt command to the background task.
+            let cwd = cwd.unwrap_or_else(|| {
+                std::env::current_dir()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::Prompt {
+                .send(AcpCommand::ForkSession {
                     session_id: session_id.clone(),
-                    text,
-                    content_json: content_json.clone(),
+                    cwd,
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("fork_session", Some(&session_id)))?;
 
-            // Collect streaming updates until the Done sentinel arrives.
-            let mut collected_text = String::new();
-            let mut got_message = false;
-            let mut stop_reason: Option<String> = None;
-            {
-                let mut rx_guard = update_rx_slot.lock().await;
-                let update_rx = rx_guard.as_mut().ok_or_else(|| {
-                    ConduitError::Connection("update channel not initialized".into())
-                })?;
-                loop {
-                    match update_rx.recv().await {
-                        Some(StreamEvent::TextDelta(t)) => {
-                            got_message = true;
-                            collected_text.push_str(&t);
-                        }
-                        Some(StreamEvent::ThoughtDelta(t)) => {
-                            if !got_message {
-                                collected_text.push_str(&t);
-                            }
-                        }
-                        Some(StreamEvent::ToolUseStart { .. })
-                        | Some(StreamEvent::ToolUseEnd { .. })
-                        | Some(StreamEvent::ToolUseUpdate { .. })
-                        | Some(StreamEvent::ModeChange { .. })
-                        | Some(StreamEvent::Plan { .. })
-                        | Some(StreamEvent::ConfigUpdate { .. })
-                        | Some(StreamEvent::CommandsUpdate { .. })
-                        | Some(StreamEvent::Usage { .. })
-                        | Some(StreamEvent::SessionInfo { .. })
-                        | Some(StreamEvent::RateLimit { .. }) => {
-                            // Non-text events consumed in batch mode.
-                        }
-                        Some(StreamEvent::Done { stop_reason: sr }) => {
-                            stop_reason = sr;
-                            break;
-                        }
-                        None => break,
+            reply_rx
+                .await
+                .map_err(|_| reply_dropped("fork_session", Some(&session_id)))?
+                .map_err(Into::into)
+        })
+    }
+
+    /// Attach an MCP server to an existing session.
+    ///
+    /// `config_json` is a single MCP server config in the same shape as
+    /// the entries of `new_session`'s `mcp_servers_json`, and must carry a
+    /// `name` unique within the session. Requires the agent to advertise
+    /// `mcp_hot_add`; the server is sent via the `_conduit/add_mcp_server`
+    /// extension request.
+    fn add_mcp_server<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+        config_json: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let server: serde_json::Value =
+                serde_json::from_str(&config_json).map_err(ConduitError::from)?;
+            serde_json::from_value::<sacp::schema::McpServer>(server.clone()).map_err(|e| {
+                ConduitError::Session(format!("invalid MCP server config: {e}"))
+            })?;
+            let name = server
+                .get("name")
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| ConduitError::Session("MCP server config has no name".into()))?
+                .to_string();
+
+            let cmd_tx = {
+                let mut guard = inner.lock().await;
+                let client = guard
+                    .as_mut()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                let hot_add = client.capabilities.as_ref().is_some_and(|c| c.mcp_hot_add);
+                if !hot_add {
+                    return Err(ConduitError::Protocol(
+                        "agent does not support adding MCP servers to a running session".into(),
+                    )
+                    .into());
+                }
+                // Claim the name under the same lock as the check, so two
+                // concurrent adds can't both get through.
+                let attached = client.mcp_servers.entry(session_id.clone()).or_default();
+                if !attached.insert(name.clone()) {
+                    return Err(ConduitError::Session(format!(
+                        "MCP server {name:?} is already attached to session {session_id}"
+                    ))
+                    .into());
+                }
+                client.cmd_tx.clone()
+            };
+
+            let added = async {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                cmd_tx
+                    .send(AcpCommand::AddMcpServer {
+                        session_id: session_id.clone(),
+                        server,
+                        reply: reply_tx,
+                    })
+                    .await
+                    .map_err(|_| task_closed("add_mcp_server", Some(&session_id)))?;
+                reply_rx
+                    .await
+                    .map_err(|_| reply_dropped("add_mcp_server", Some(&session_id)))?
+            }
+            .await;
+
+            if added.is_err() {
+                if let Some(client) = inner.lock().await.as_mut() {
+                    if let Some(attached) = client.mcp_servers.get_mut(&session_id) {
+                        attached.remove(&name);
                     }
                 }
             }
+            added.map_err(Into::into)
+        })
+    }
+
+    /// List available sessions. Returns JSON array.
+    fn list_sessions<'py>(
+        &self,
+        py: Python<'py>,
+        cwd: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let cmd_tx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cmd_tx.clone()
+            };
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            cmd_tx
+                .send(AcpCommand::ListSessions {
+                    cwd,
+                    reply: reply_tx,
+                })
+                .await
+                .map_err(|_| task_closed("list_sessions", None))?;
 
-            // Wait for the background task's confirmation that the prompt completed.
             reply_rx
                 .await
-                .map_err(|_| ConduitError::Connection("prompt reply dropped".into()))??;
-
-            // Assemble a Message from the collected text.
-            let messages: Vec<Message> = if collected_text.is_empty() {
-                vec![]
-            } else {
-                vec![Message {
-                    role: MessageRole::Assistant,
-                    content: vec![ContentBlock {
-                        content_type: ContentType::Text,
-                        text: Some(collected_text),
-                        tool_name: None,
-                        tool_input: None,
-                        tool_use_id: None,
-                    }],
-                    session_id: Some(session_id),
-                    stop_reason,
-                }]
+                .map_err(|_| reply_dropped("list_sessions", None))?
+                .map_err(Into::into)
+        })
+    }
+
+    /// Send an ACP request the SDK doesn't model and return the agent's
+    /// result as a JSON string.
+    ///
+    /// `params_json` must be a JSON value (an empty object when `None`).
+    /// Errors from the agent raise `ProtocolError` with their code.
+    #[pyo3(signature = (method, params_json=None))]
+    fn raw_request<'py>(
+        &self,
+        py: Python<'py>,
+        method: String,
+        params_json: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let params = match params_json.as_deref() {
+                Some(json) => serde_json::from_str(json)
+                    .map_err(|e| ConduitError::Protocol(format!("invalid params_json: {e}")))?,
+                None => serde_json::json!({}),
             };
+            let cmd_tx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cmd_tx.clone()
+            };
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            cmd_tx
+                .send(AcpCommand::Raw {
+                    method,
+                    params,
+                    reply: reply_tx,
+                })
+                .await
+                .map_err(|_| task_closed("raw_request", None))?;
 
-            Ok(messages)
+            reply_rx
+                .await
+                .map_err(|_| reply_dropped("raw_request", None))?
+                .map_err(Into::into)
         })
     }
 
-    /// Send a prompt without waiting for completion.
+    /// Send an ACP notification the SDK doesn't model.
     ///
-    /// Use with [`recv_update`] for real-time streaming. The prompt is sent
-    /// to the background ACP task and streaming events can be polled via
-    /// `recv_update()` until `None` is returned.
-    #[pyo3(signature = (text, session_id=None, content_json=None))]
-    fn send_prompt<'py>(
+    /// `params_json` must be a JSON value (an empty object when `None`).
+    /// Returns once the notification is queued; the agent sends no reply.
+    #[pyo3(signature = (method, params_json=None))]
+    fn raw_notification<'py>(
         &self,
         py: Python<'py>,
-        text: String,
-        session_id: Option<String>,
-        content_json: Option<String>,
+        method: String,
+        params_json: Option<String>,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
-        let prompt_reply_rx = self.prompt_reply_rx.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let (cmd_tx, default_session_id) = {
+            let params = match params_json.as_deref() {
+                Some(json) => serde_json::from_str(json)
+                    .map_err(|e| ConduitError::Protocol(format!("invalid params_json: {e}")))?,
+                None => serde_json::json!({}),
+            };
+            let cmd_tx = {
                 let guard = inner.lock().await;
                 let client = guard
                     .as_ref()
                     .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-                if !client.initialized {
-                    return Err(
-                        ConduitError::Connection("client not initialized".into()).into(),
-                    );
-                }
-                (client.cmd_tx.clone(), client.session_id.clone())
+                client.cmd_tx.clone()
             };
 
-            // Auto-create session if needed.
-            let session_id = match session_id.or(default_session_id) {
-                Some(id) => id,
-                None => {
-                    let cwd = std::env::current_dir()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string();
-                    let (reply_tx, reply_rx) = oneshot::channel();
-                    cmd_tx
-                        .send(AcpCommand::NewSession {
-                            cwd,
-                            meta_json: None,
-                            mcp_servers_json: None,
-                            reply: reply_tx,
-                        })
-                        .await
-                        .map_err(|_| {
-                            ConduitError::Connection("background task closed".into())
-                        })?;
-                    let id = reply_rx.await.map_err(|_| {
-                        ConduitError::Connection("session reply dropped".into())
-                    })??;
-                    {
-                        let mut guard = inner.lock().await;
-                        if let Some(client) = guard.as_mut() {
-                            client.session_id = Some(id.clone());
-                        }
-                    }
-                    id
-                }
+            cmd_tx
+                .send(AcpCommand::RawNotify { method, params })
+                .await
+                .map_err(|_| task_closed("raw_notification", None))?;
+
+            Ok(())
+        })
+    }
+
+    /// Resume an existing agent-side session.
+    ///
+    /// Returns a [`ResumeResult`] with the restored mode/model.
+    fn resume_session<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+        cwd: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let cmd_tx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cmd_tx.clone()
             };
 
-            // Send prompt and store the reply receiver for later.
+            let cwd = cwd.unwrap_or_else(|| {
+                std::env::current_dir()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
+            let (reply_tx, reply_rx) = oneshot::channel();
+            cmd_tx
+                .send(AcpCommand::ResumeSession {
+                    session_id: session_id.clone(),
+                    cwd,
+                    reply: reply_tx,
+                })
+                .await
+                .map_err(|_| task_closed("resume_session", Some(&session_id)))?;
+
+            let result = reply_rx
+                .await
+                .map_err(|_| reply_dropped("resume_session", Some(&session_id)))??;
+
+            // Store as the default session.
+            {
+                let mut guard = inner.lock().await;
+                if let Some(client) = guard.as_mut() {
+                    *client.session_id.lock().unwrap() = Some(result.session_id.clone());
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    /// Send a prompt to the agent within the given (or default) session.
+    ///
+    /// Returns a list of [`Message`] objects. Streaming is handled at the
+    /// Python layer by wrapping this in an async iterator.
+    #[pyo3(signature = (text, session_id=None, content_json=None, meta_json=None))]
+    fn prompt<'py>(
+        &self,
+        py: Python<'py>,
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta_json: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let meta = parse_prompt_meta(meta_json.as_deref())?;
+        let turn = self.turn_future(text, session_id, content_json, meta);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move { Ok(turn.await?.messages()) })
+    }
+
+    /// Blocking `prompt()` for callers without an event loop; see
+    /// [`connect_blocking`].
+    #[pyo3(signature = (text, session_id=None, content_json=None, meta_json=None))]
+    fn prompt_blocking(
+        &self,
+        py: Python<'_>,
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta_json: Option<String>,
+    ) -> PyResult<Vec<Message>> {
+        let meta = parse_prompt_meta(meta_json.as_deref())?;
+        let turn = self.turn_future(text, session_id, content_json, meta);
+        Ok(block_on(py, "prompt_blocking", turn)?.messages())
+    }
+
+    /// Like [`prompt`], but returns one [`PromptResult`] for the turn: the
+    /// messages plus the tool calls made, the last usage report, the stop
+    /// reason and how long the turn took, also summarized as a
+    /// [`ResultMessage`].
+    #[pyo3(signature = (text, session_id=None, content_json=None, meta_json=None))]
+    fn prompt_full<'py>(
+        &self,
+        py: Python<'py>,
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta_json: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let meta = parse_prompt_meta(meta_json.as_deref())?;
+        let turn = self.turn_future(text, session_id, content_json, meta);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let started = Instant::now();
+            let turn = turn.await?;
+            let duration = started.elapsed();
+            Ok(PromptResult {
+                messages: turn.messages(),
+                result_message: Some(turn.result_message(duration)),
+                session_id: turn.session_id,
+                tool_calls: turn.tool_calls,
+                usage_json: turn.usage_json,
+                stop_reason: turn.stop_reason,
+                duration_secs: duration.as_secs_f64(),
+            })
+        })
+    }
+
+    /// Send a prompt without waiting for completion.
+    ///
+    /// Use with [`recv_update`] for real-time streaming. The prompt is sent
+    /// to the background ACP task and streaming events can be polled via
+    /// `recv_update()` until `None` is returned. Raises `ConnectionError`
+    /// unless the client is `Connected`, e.g. after the agent exited.
+    #[pyo3(signature = (text, session_id=None, content_json=None, meta_json=None))]
+    fn send_prompt<'py>(
+        &self,
+        py: Python<'py>,
+        text: String,
+        session_id: Option<String>,
+        content_json: Option<String>,
+        meta_json: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let meta = parse_prompt_meta(meta_json.as_deref())?;
+        let send = self.send_prompt_future(text, session_id, content_json, meta);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move { Ok(send.await?) })
+    }
+
+    /// Invoke one of the agent's slash commands without waiting for it.
+    ///
+    /// Sends `/<command_name> <args>` as a prompt in `session_id`; read the
+    /// result with `recv_update()` as for `send_prompt()`. Raises
+    /// `ProtocolError` unless the agent has advertised the command for
+    /// that session (see `available_commands()`).
+    #[pyo3(signature = (session_id, command_name, args=None))]
+    fn run_command<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+        command_name: String,
+        args: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        let name = command_name.trim_start_matches('/').to_string();
+        let text = match args.as_deref().map(str::trim) {
+            Some(args) if !args.is_empty() => format!("/{name} {args}"),
+            _ => format!("/{name}"),
+        };
+        let meta = serde_json::Map::new();
+        let send = self.send_prompt_future(text, Some(session_id.clone()), None, meta);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let commands_json = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                let commands = client.available_commands.lock().unwrap();
+                commands.get(&session_id).cloned()
+            };
+            let commands: Vec<serde_json::Value> = commands_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default();
+            let advertised = commands
+                .iter()
+                .any(|c| c.get("name").and_then(|n| n.as_str()) == Some(name.as_str()));
+            if !advertised {
+                return Err(ConduitError::Protocol(format!(
+                    "agent has not advertised command {name:?} for session {session_id}"
+                ))
+                .into());
+            }
+            Ok(send.await?)
+        })
+    }
+
+    /// Receive the next streaming update from the agent.
+    ///
+    /// Returns a [`SessionUpdate`] for each chunk (text, thought, tool use,
+    /// mode change, plan, config, commands, usage, session info),
+    /// or `None` when the prompt is complete.
+    fn recv_update<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let update_rx_slot = self.update_rx.clone();
+        let prompt_reply_rx = self.prompt_reply_rx.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut rx_guard = update_rx_slot.lock().await;
+            let update_rx = rx_guard.as_mut().ok_or_else(|| {
+                ConduitError::Connection("update channel not initialized".into())
+            })?;
+            let Some(QueuedEvent { event, turn }) = update_rx.recv().await else {
+                return Ok(None);
+            };
+
+            match event {
+                StreamEvent::Done { stop_reason } => {
+                    // Check prompt completion status.
+                    if let Some(reply_rx) = prompt_reply_rx.lock().await.take() {
+                        if let Ok(result) = reply_rx.await {
+                            result?;
+                        }
+                    }
+                    // Return a Done update with stop_reason if caller wants it.
+                    if stop_reason.is_some() {
+                        let done = StreamEvent::Done { stop_reason };
+                        Ok(Some(session_update(done, turn)))
+                    } else {
+                        Ok(None)
+                    }
+                }
+                StreamEvent::Closed { reason } => Err(ConduitError::Connection(reason).into()),
+                event => Ok(Some(session_update(event, turn))),
+            }
+        })
+    }
+
+    /// Return the default session ID used by `prompt()` when none is given.
+    ///
+    /// `None` until a session has been created, loaded, or auto-created.
+    fn default_session_id<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            Ok(guard
+                .as_ref()
+                .and_then(|client| client.session_id.lock().unwrap().clone()))
+        })
+    }
+
+    /// Return the capabilities received during the initialize handshake.
+    fn capabilities<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            Ok(client.capabilities.clone())
+        })
+    }
+
+    /// Return the [`AgentInfo`] the agent reported in `initialize`, or
+    /// `None` if it sent none.
+    fn agent_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            Ok(client.agent_info.clone())
+        })
+    }
+
+    /// Return agent info as a JSON string (name, version, title); kept for
+    /// callers that predate [`agent_info`].
+    fn agent_info_json<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let info = client.agent_info.as_ref();
+            Ok(info.and_then(|info| serde_json::to_string(info).ok()))
+        })
+    }
+
+    /// Return the working directory `session_id` was created or loaded
+    /// with, or `None` for a session this client hasn't created, loaded,
+    /// forked or resumed.
+    ///
+    /// Handlers that touch the filesystem or run commands on the agent's
+    /// behalf should resolve relative paths against it.
+    fn session_cwd<'py>(&self, py: Python<'py>, session_id: String) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let cwds = client.session_cwds.lock().unwrap();
+            Ok(cwds
+                .get(&session_id)
+                .map(|cwd| cwd.to_string_lossy().into_owned()))
+        })
+    }
+
+    /// Stop tracking a session closed on the Python side: `cancel_all()`
+    /// and `session_cwd()` no longer see it, and its commands, info, tool
+    /// calls and rate-limit status are dropped. ACP has no close request;
+    /// the agent is not told.
+    fn forget_session<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut guard = inner.lock().await;
+            if let Some(client) = guard.as_mut() {
+                client.session_cwds.lock().unwrap().remove(&session_id);
+                client.mcp_servers.remove(&session_id);
+                client
+                    .available_commands
+                    .lock()
+                    .unwrap()
+                    .remove(&session_id);
+                client.session_info.lock().unwrap().remove(&session_id);
+                client.active_tool_calls.lock().unwrap().remove(&session_id);
+                client.rate_limits.lock().unwrap().remove(&session_id);
+            }
+            Ok(())
+        })
+    }
+
+    /// Return the commands the agent last advertised for `session_id`, as
+    /// a JSON array (`"[]"` if it hasn't advertised any yet).
+    ///
+    /// Updated from `available_commands_update` notifications whether or
+    /// not a prompt stream is reading them.
+    fn available_commands<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let commands = client.available_commands.lock().unwrap();
+            Ok(commands
+                .get(&session_id)
+                .cloned()
+                .unwrap_or_else(|| "[]".to_string()))
+        })
+    }
+
+    /// Return the title and update time the agent last reported for
+    /// `session_id`, as a JSON object `{"title": ..., "updated_at": ...}`.
+    ///
+    /// Each field keeps its last value across `session_info_update`
+    /// notifications that leave it out; one that sends it as `null`
+    /// clears it. Both are `null` until the agent has sent one.
+    fn session_info<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let infos = client.session_info.lock().unwrap();
+            let info = infos
+                .get(&session_id)
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({ "title": null, "updated_at": null }));
+            Ok(info.to_string())
+        })
+    }
+
+    /// Return the tool calls still running in `session_id` as a JSON array
+    /// of `{"tool_use_id", "name", "status", "started_at"}`, oldest first.
+    ///
+    /// Tracked from `tool_call` and `tool_call_update` notifications; a
+    /// call is dropped once it reports `completed` or `failed`, or once its
+    /// turn ends `cancelled`. `status` is the ACP wire name (e.g.
+    /// `"in_progress"`); `started_at` is in seconds since the Unix epoch.
+    fn active_tool_calls<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let calls = client.active_tool_calls.lock().unwrap();
+            let mut active: Vec<&ActiveToolCall> = calls
+                .get(&session_id)
+                .into_iter()
+                .flat_map(HashMap::values)
+                .collect();
+            active.sort_by(|a, b| a.started_at.total_cmp(&b.started_at));
+            Ok(serde_json::to_string(&active).unwrap_or_else(|_| "[]".to_string()))
+        })
+    }
+
+    /// Return the latest rate-limit status the agent reported for
+    /// `session_id` (or for any session when `None`), or `None` if it
+    /// hasn't reported one.
+    ///
+    /// Notifications that name no session apply to every session.
+    #[pyo3(signature = (session_id=None))]
+    fn rate_limit_status<'py>(
+        &self,
+        py: Python<'py>,
+        session_id: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.lock().await;
+            let client = guard
+                .as_ref()
+                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+            let status = latest_rate_limit(&client.rate_limits, session_id.as_deref(), None);
+            Ok(status)
+        })
+    }
+
+    /// The client's current [`ConnectionState`]; answers without awaiting.
+    fn state(&self) -> ConnectionState {
+        ConnectionState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    /// Round trip in seconds of the last heartbeat the agent answered on
+    /// the current connection, or `None` if none has been answered yet.
+    fn heartbeat_latency(&self) -> Option<f64> {
+        match self.heartbeat_latency.load(Ordering::Acquire) {
+            NO_LATENCY => None,
+            micros => Some(micros as f64 / 1e6),
+        }
+    }
+
+    /// Disconnect from the agent and terminate the subprocess.
+    ///
+    /// The client may be connected again afterwards.
+    fn disconnect<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        let state = self.state.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let taken = inner.lock().await.take();
+            if let Some(mut client) = taken {
+                state.store(ConnectionState::Disconnected as u8, Ordering::Release);
+                // Ask the background task to exit its command loop.
+                let _ = client.cmd_tx.send(AcpCommand::Shutdown).await;
+                // Get the recording onto disk while the agent is still up.
+                if let Some(recorder) = &client.recorder {
+                    recorder.close().await;
+                }
+                if let Some(process) = client.process.as_mut() {
+                    process.kill().await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Respawn the agent from the stored config, e.g. after it crashed.
+    ///
+    /// Drops the stale connection and kills its process first, so calls
+    /// still in flight fail on the closed channels rather than reaching the
+    /// new agent. After the handshake, the previous default session is
+    /// reloaded with `session/load` and made the default again. `registry`
+    /// and `hooks` work as for `connect()`. Returns the agent's new
+    /// [`Capabilities`].
+    #[pyo3(signature = (registry=None, hooks=None))]
+    fn reconnect<'py>(
+        &self,
+        py: Python<'py>,
+        registry: Option<PyRef<'py, RustToolRegistry>>,
+        hooks: Option<PyRef<'py, RustHookDispatcher>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        let update_rx_slot = self.update_rx.clone();
+        let cwd = self.config.cwd.clone();
+        let connect =
+            self.connect_future(registry.map(|r| r.handle()), hooks.map(|h| h.handle()))?;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let stale = inner.lock().await.take();
+            let session_id = stale
+                .as_ref()
+                .and_then(|c| c.session_id.lock().unwrap().clone());
+            if let Some(mut client) = stale {
+                let _ = client.cmd_tx.send(AcpCommand::Shutdown).await;
+                if let Some(recorder) = &client.recorder {
+                    recorder.close().await;
+                }
+                if let Some(process) = client.process.as_mut() {
+                    // The process may well be gone already; that is the point.
+                    let _ = process.kill().await;
+                }
+            }
+            *update_rx_slot.lock().await = None;
+
+            let capabilities = connect.await?;
+            let Some(session_id) = session_id else {
+                return Ok(capabilities);
+            };
+
+            let cmd_tx = {
+                let guard = inner.lock().await;
+                let client = guard
+                    .as_ref()
+                    .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+                client.cmd_tx.clone()
+            };
+            let cwd = cwd.unwrap_or_else(|| {
+                std::env::current_dir()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
+            let (reply_tx, reply_rx) = oneshot::channel();
+            cmd_tx
+                .send(AcpCommand::LoadSession {
+                    session_id: session_id.clone(),
+                    cwd,
+                    reply: reply_tx,
+                })
+                .await
+                .map_err(|_| task_closed("reconnect/load_session", Some(&session_id)))?;
+            let session_id = reply_rx
+                .await
+                .map_err(|_| reply_dropped("reconnect/load_session", Some(&session_id)))??;
+
+            if let Some(client) = inner.lock().await.as_mut() {
+                *client.session_id.lock().unwrap() = Some(session_id);
+            }
+            Ok(capabilities)
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Command channel errors
+// ---------------------------------------------------------------------------
+
+/// Error for a command the background task is no longer accepting.
+fn task_closed(op: &str, session_id: Option<&str>) -> ConduitError {
+    ConduitError::Connection(format!(
+        "{op}{}: background task closed",
+        session_suffix(session_id)
+    ))
+}
+
+/// Error for a command whose reply the background task dropped without
+/// answering, typically because the agent went away mid-request.
+fn reply_dropped(op: &str, session_id: Option<&str>) -> ConduitError {
+    ConduitError::Connection(format!("{op}{}: reply dropped", session_suffix(session_id)))
+}
+
+fn session_suffix(session_id: Option<&str>) -> String {
+    session_id
+        .map(|sid| format!(" (session {sid})"))
+        .unwrap_or_default()
+}
+
+// ---------------------------------------------------------------------------
+// Connection setup
+// ---------------------------------------------------------------------------
+
+/// Names of the servers in an `mcp_servers_json` array (empty if absent).
+fn mcp_server_names(mcp_servers_json: Option<&str>) -> HashSet<String> {
+    mcp_servers_json
+        .and_then(|json| serde_json::from_str::<Vec<serde_json::Value>>(json).ok())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|server| server.get("name")?.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Mint a turn request ID from the client's prompt counter.
+fn mint_request_id(prompt_count: &AtomicU64) -> String {
+    let n = prompt_count.fetch_add(1, Ordering::Relaxed) + 1;
+    format!("prompt-{n}")
+}
+
+/// Prompt turns started so far in each session, by session ID.
+type TurnCounts = Arc<std::sync::Mutex<HashMap<String, u64>>>;
+
+/// Mint the turn ID of a new turn in `session_id`: 1 for the session's
+/// first turn, counting up from there.
+fn mint_turn_id(turn_counts: &TurnCounts, session_id: &str) -> u64 {
+    let mut counts = turn_counts.lock().unwrap();
+    let count = counts.entry(session_id.to_string()).or_default();
+    *count += 1;
+    *count
+}
+
+/// How long to wait before retrying a turn that failed, if the agent
+/// reported a rate limit with a retry hint since `since`: the hint, capped
+/// at `max_wait`. A hint that isn't a valid duration is ignored.
+async fn rate_limit_wait(
+    inner: &Arc<Mutex<Option<ClientInner>>>,
+    session_id: Option<&str>,
+    since: Instant,
+    max_wait: Duration,
+) -> Option<Duration> {
+    let guard = inner.lock().await;
+    let status = latest_rate_limit(&guard.as_ref()?.rate_limits, session_id, Some(since))?;
+    let wait = Duration::try_from_secs_f64(status.retry_after_secs?).ok()?;
+    Some(wait.min(max_wait))
+}
+
+/// Marks a connect attempt in flight by holding the state at
+/// `Connecting`. Dropped without [`ConnectGuard::connected`], it leaves the
+/// client `Failed` and free to retry.
+struct ConnectGuard {
+    state: Arc<AtomicU8>,
+    outcome: ConnectionState,
+}
+
+impl ConnectGuard {
+    /// Claim the connect slot, failing if another connect is running or
+    /// the client is already connected.
+    async fn acquire(
+        state: &Arc<AtomicU8>,
+        inner: &Mutex<Option<ClientInner>>,
+    ) -> Result<Self, ConduitError> {
+        let previous = state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |s| {
+                (s != ConnectionState::Connecting as u8)
+                    .then_some(ConnectionState::Connecting as u8)
+            })
+            .map_err(|_| ConduitError::Connection("already connected/connecting".into()))?;
+        let mut guard = Self {
+            state: state.clone(),
+            outcome: ConnectionState::Failed,
+        };
+        if inner.lock().await.is_some() {
+            guard.outcome = ConnectionState::from_u8(previous);
+            return Err(ConduitError::Connection(
+                "already connected/connecting".into(),
+            ));
+        }
+        Ok(guard)
+    }
+
+    /// The handshake succeeded: leave the client `Connected`.
+    fn connected(mut self) {
+        self.outcome = ConnectionState::Connected;
+    }
+}
+
+impl Drop for ConnectGuard {
+    fn drop(&mut self) {
+        self.state.store(self.outcome as u8, Ordering::Release);
+    }
+}
+
+/// Counts a `prompt()`-style turn as running while held, so a new turn
+/// only drains stale events when no other turn could still own them.
+struct ActiveTurn {
+    active_turns: Arc<AtomicUsize>,
+    /// No other turn was running when this one started.
+    alone: bool,
+}
+
+impl ActiveTurn {
+    fn enter(active_turns: &Arc<AtomicUsize>) -> Self {
+        let previous = active_turns.fetch_add(1, Ordering::AcqRel);
+        Self {
+            active_turns: active_turns.clone(),
+            alone: previous == 0,
+        }
+    }
+}
+
+impl Drop for ActiveTurn {
+    fn drop(&mut self) {
+        self.active_turns.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Prompt turns sent and not yet answered, by session ID; sessions with
+/// none are left out.
+type RunningTurns = Arc<std::sync::Mutex<HashMap<String, usize>>>;
+
+/// Counts a prompt as running in its session while held. Carried in the
+/// [`AcpCommand::Prompt`] it belongs to, so it is dropped once the command
+/// loop has answered the prompt, or failed it.
+struct RunningTurn {
+    running_turns: RunningTurns,
+    session_id: String,
+}
+
+impl RunningTurn {
+    fn start(running_turns: &RunningTurns, session_id: &str) -> Self {
+        *running_turns
+            .lock()
+            .unwrap()
+            .entry(session_id.to_owned())
+            .or_default() += 1;
+        Self {
+            running_turns: running_turns.clone(),
+            session_id: session_id.to_owned(),
+        }
+    }
+}
+
+impl Drop for RunningTurn {
+    fn drop(&mut self) {
+        let mut running = self.running_turns.lock().unwrap();
+        if let Some(count) = running.get_mut(&self.session_id) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(&self.session_id);
+            }
+        }
+    }
+}
+
+/// Prompt queue of each session, by session ID; `""` holds prompts that
+/// name no session while there is no default session yet.
+type PromptQueues = Arc<std::sync::Mutex<HashMap<String, PromptQueue>>>;
+
+/// The client's default session ID, `None` until a session is created,
+/// loaded or auto-created.
+type DefaultSession = Arc<std::sync::Mutex<Option<String>>>;
+
+/// Tickets for one session's prompt turns, served in the order taken.
+struct PromptQueue {
+    /// Ticket the next prompt gets.
+    next: u64,
+    /// Tickets done ahead of the one being served (turns dropped while
+    /// still waiting).
+    done: BTreeSet<u64>,
+    /// Ticket allowed to run now.
+    serving: watch::Sender<u64>,
+}
+
+impl Default for PromptQueue {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            done: BTreeSet::new(),
+            serving: watch::channel(0).0,
+        }
+    }
+}
+
+/// A prompt turn's place in its session's queue. Dropping it, whether the
+/// turn ran or gave up waiting, lets the next one go.
+struct PromptTicket {
+    queues: PromptQueues,
+    session_id: String,
+    ticket: u64,
+    serving: watch::Receiver<u64>,
+}
+
+impl PromptTicket {
+    /// Queue up behind the session's earlier prompts, or, with
+    /// `reject_if_busy`, fail if there are any.
+    fn take(
+        queues: &PromptQueues,
+        session_id: &str,
+        reject_if_busy: bool,
+    ) -> Result<Self, ConduitError> {
+        let mut guard = queues.lock().unwrap();
+        let queue = guard.entry(session_id.to_string()).or_default();
+        if reject_if_busy && queue.next != *queue.serving.borrow() {
+            let session = match session_id {
+                "" => "the default session".to_string(),
+                id => format!("session {id}"),
+            };
+            return Err(ConduitError::Session(format!(
+                "{session} is busy with another prompt"
+            )));
+        }
+        let ticket = queue.next;
+        queue.next += 1;
+        Ok(Self {
+            queues: queues.clone(),
+            session_id: session_id.to_string(),
+            ticket,
+            serving: queue.serving.subscribe(),
+        })
+    }
+
+    /// Wait until every earlier prompt of the session is done.
+    async fn wait(&self) {
+        let mut serving = self.serving.clone();
+        let _ = serving.wait_for(|serving| *serving == self.ticket).await;
+    }
+}
+
+impl Drop for PromptTicket {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        let Some(queue) = queues.get_mut(&self.session_id) else {
+            return;
+        };
+        queue.done.insert(self.ticket);
+        let mut serving = *queue.serving.borrow();
+        while queue.done.remove(&serving) {
+            serving += 1;
+        }
+        queue.serving.send_replace(serving);
+        if serving == queue.next {
+            queues.remove(&self.session_id);
+        }
+    }
+}
+
+/// Discard the events already buffered from earlier turns (e.g. late tool
+/// updates from a cancelled prompt) so the next turn doesn't collect them,
+/// handing each to `callback` if one is set.
+///
+/// Leaves the channel alone while a streamed turn is still waiting for its
+/// reply or another reader holds it.
+async fn drain_pending(
+    log: &LogSink,
+    update_rx_slot: &Mutex<Option<mpsc::Receiver<QueuedEvent>>>,
+    prompt_reply_rx: &Mutex<Option<oneshot::Receiver<Result<(), ConduitError>>>>,
+    callback: &Arc<std::sync::Mutex<Option<PyObject>>>,
+) {
+    {
+        let mut reply_slot = prompt_reply_rx.lock().await;
+        if let Some(reply_rx) = reply_slot.as_mut() {
+            if let Err(oneshot::error::TryRecvError::Empty) = reply_rx.try_recv() {
+                return;
+            }
+            // That streamed turn is over and nobody read it to the end.
+            *reply_slot = None;
+        }
+    }
+    let stale: Vec<QueuedEvent> = {
+        let Ok(mut rx_guard) = update_rx_slot.try_lock() else {
+            return;
+        };
+        let Some(update_rx) = rx_guard.as_mut() else {
+            return;
+        };
+        std::iter::from_fn(|| update_rx.try_recv().ok()).collect()
+    };
+    if stale.is_empty() {
+        return;
+    }
+
+    let callback = Python::with_gil(|py| {
+        let guard = callback.lock().unwrap();
+        guard.as_ref().map(|cb| cb.clone_ref(py))
+    });
+    let Some(callback) = callback else {
+        return;
+    };
+    for QueuedEvent { event, turn } in stale {
+        let update = session_update(event, turn);
+        let pending = Python::with_gil(|py| -> PyResult<_> {
+            let result = callback.bind(py).call1((update,))?;
+            // An async callback is awaited outside the GIL.
+            if result.hasattr("__await__")? {
+                return Ok(Some(pyo3_async_runtimes::tokio::into_future(result)?));
+            }
+            Ok(None)
+        });
+        let outcome = match pending {
+            Ok(Some(future)) => future.await.map(drop),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = outcome {
+            log.error(format!("stale update callback failed: {e}"));
+        }
+    }
+}
+
+/// What the command task hands back once the initialize handshake
+/// succeeds: the agent's capabilities and info, and the connection.
+type Handshake = (Capabilities, Option<AgentInfo>, sacp::JrConnectionCx);
+
+/// Handles produced by [`serve_acp`] once the initialize handshake succeeds.
+struct AcpConnection {
+    cmd_tx: mpsc::Sender<AcpCommand>,
+    cx: sacp::JrConnectionCx,
+    update_rx: mpsc::Receiver<QueuedEvent>,
+    capabilities: Capabilities,
+    agent_info: Option<AgentInfo>,
+    session_cwds: SessionCwds,
+    available_commands: SessionCommands,
+    session_info: SessionInfos,
+    rate_limits: RateLimits,
+    active_tool_calls: ActiveToolCalls,
+    recorder: Option<WireRecorder>,
+}
+
+/// Bound on the `initialize` handshake for a `timeout_secs` setting; 0
+/// means wait forever.
+fn handshake_timeout(timeout_secs: u64) -> Option<Duration> {
+    (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs))
+}
+
+/// Everything [`serve_acp`] needs besides the byte stream, built by
+/// [`RustClient::serve_config`].
+struct ServeConfig {
+    perm_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    perm_rules: Arc<std::sync::Mutex<PermissionRules>>,
+    input_middleware: InputMiddleware,
+    raw_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    update_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    confirmation: Arc<std::sync::Mutex<ConfirmationPolicy>>,
+    /// Answers the agent's MCP `tools/call` requests.
+    tool_registry: Option<ToolRegistryHandle>,
+    hooks: Option<HookDispatcherHandle>,
+    /// Protocol version to insist on in `initialize`, if pinned.
+    protocol_version: Option<u16>,
+    client_info: Implementation,
+    /// Bound on the `initialize` handshake.
+    timeout: Option<Duration>,
+    heartbeat: Option<Heartbeat>,
+    state: Arc<AtomicU8>,
+    recorder: Option<WireRecorder>,
+    log: LogSink,
+}
+
+/// Serve the ACP handler chain over a byte stream and wait for `initialize`.
+///
+/// `outgoing`/`incoming` are the raw halves of whatever carries ACP to the
+/// agent: a child's stdin/stdout, or the client end of a proxy chain. If
+/// the agent hasn't answered `initialize` within `config.timeout`, the
+/// background task is stopped and `ConduitError::Timeout` returned. With a
+/// heartbeat, the agent is pinged once connected, and if it stops answering
+/// the connection is torn down and `state` set to `Failed`; so is a
+/// connected `state` when the agent exits or the transport errors. With a
+/// recorder, every message either way is also written to its file.
+async fn serve_acp<W, R>(
+    outgoing: W,
+    incoming: R,
+    config: ServeConfig,
+) -> Result<AcpConnection, ConduitError>
+where
+    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    let ServeConfig {
+        perm_callback,
+        perm_rules,
+        input_middleware,
+        raw_callback,
+        update_callback,
+        confirmation,
+        tool_registry,
+        hooks,
+        protocol_version,
+        client_info,
+        timeout,
+        heartbeat,
+        state,
+        recorder,
+        log,
+    } = config;
+    // Raised when a write fails because the agent stopped reading.
+    let input_closed = Arc::new(AtomicBool::new(false));
+    let outgoing = Recorded::new(outgoing, recorder.clone(), Direction::Out);
+    let outgoing = WatchBrokenPipe::new(outgoing, input_closed.clone());
+    // Every line from the agent, for the raw tap and the update callback.
+    let (wire_tx, wire_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let incoming = Recorded::new(incoming, recorder.clone(), Direction::In).tapped(wire_tx);
+    let transport = sacp::ByteStreams::new(outgoing.compat_write(), incoming.compat());
+    // The client's middleware covers the agent's tool calls too, without
+    // touching a registry other clients may share.
+    let tool_registry = tool_registry.map(|r| r.with_input_middleware(&input_middleware));
+
+    // Channels: commands → background task, streaming events ← notification handler
+    let (cmd_tx, cmd_rx) = mpsc::channel::<AcpCommand>(32);
+    // Shared so commands can still be answered once the task is gone.
+    let commands = CommandInbox {
+        rx: Arc::new(Mutex::new(cmd_rx)),
+        closed: CancellationToken::new(),
+    };
+    let pending_cmds = commands.clone();
+    let (queue_tx, update_rx) = mpsc::channel::<QueuedEvent>(512);
+    let update_tx = EventSender {
+        tx: queue_tx,
+        turn: TurnStamp::default(),
+    };
+    let closed_tx = update_tx.clone();
+    // The turn running in each session, stamped on the events it produces.
+    let session_turns: SessionTurns = Arc::default();
+    let notif_turns = session_turns.clone();
+    let ext_turns = session_turns.clone();
+    let perm_turns = session_turns.clone();
+    let confirm_turns = session_turns.clone();
+    let (caps_tx, caps_rx) = oneshot::channel::<Result<Handshake, ConduitError>>();
+    // Fired by the heartbeat when the agent stops answering.
+    let (dead_tx, dead_rx) = oneshot::channel::<()>();
+    let heartbeat = heartbeat.map(|heartbeat| {
+        heartbeat.latency.store(NO_LATENCY, Ordering::Release);
+        (heartbeat, dead_tx)
+    });
+
+    // Clone update_tx for the notification handler (the other copy
+    // goes into the spawned task to send Done events).
+    let notif_tx = update_tx.clone();
+    let ext_notif_tx = update_tx.clone();
+    let confirm_tx = update_tx.clone();
+    // Working directory of each session, by session ID.
+    let session_cwds: SessionCwds = Arc::default();
+    let notif_cwds = session_cwds.clone();
+    // Latest advertised commands of each session, kept for available_commands().
+    let available_commands: SessionCommands = Arc::default();
+    let notif_commands = available_commands.clone();
+    // Latest title/updated_at of each session, kept for session_info().
+    let session_info: SessionInfos = Arc::default();
+    let notif_info = session_info.clone();
+    // Tool calls still running in each session, kept for active_tool_calls().
+    let active_tool_calls: ActiveToolCalls = Arc::default();
+    let notif_tool_calls = active_tool_calls.clone();
+    let task_sessions = TaskSessions {
+        turns: session_turns.clone(),
+        cwds: session_cwds.clone(),
+        tool_calls: active_tool_calls.clone(),
+    };
+    // Latest rate-limit status of each session, kept for rate_limit_status().
+    let rate_limits: RateLimits = Arc::default();
+    let ext_rate_limits = rate_limits.clone();
+    let perm_tx = update_tx.clone();
+    // Notifications for the update callback, delivered by their own task.
+    let (observed_tx, observed_rx) = mpsc::unbounded_channel::<String>();
+    let observer = run_update_callback(log.clone(), update_callback.clone(), observed_rx);
+    tokio::spawn(observer);
+    // How many `session/update` lines the raw tap has handled, and how many
+    // the typed handler has seen, so the latter can wait for the former.
+    let (tapped_tx, tapped_rx) = watch::channel(0u64);
+    let notif_seen = AtomicU64::new(0);
+    let notif_raw_callback = raw_callback.clone();
+    tokio::spawn(run_notification_tap(
+        log.clone(),
+        raw_callback,
+        update_callback,
+        observed_tx,
+        wire_rx,
+        tapped_tx,
+    ));
+    let notif_log = log.clone();
+    let perm_log = log.clone();
+    let confirm_log = log.clone();
+    let task_log = log.clone();
+    // Set once the command loop returns, e.g. on `AcpCommand::Shutdown`.
+    let task_ended = Arc::new(AtomicBool::new(false));
+    let client_ended = task_ended.clone();
+
+    // Build the handler chain with a spawned client task.
+    let chain = sacp::JrHandlerChain::new()
+        .name("conduit-sdk")
+        // --- Session update notifications (streaming chunks) ---
+        .on_receive_notification(
+            async move |notification: SessionNotification, _cx| {
+                tracing::trace!(session_id = %notification.session_id.0, "session/update");
+                let notif_tx = notif_tx.in_session(&notif_turns, &notification.session_id.0);
+                // The raw tap sees each notification before it is mapped.
+                let seen = notif_seen.fetch_add(1, Ordering::Relaxed) + 1;
+                if notif_raw_callback.lock().unwrap().is_some() {
+                    let _ = tapped_rx.clone().wait_for(|&tapped| tapped >= seen).await;
+                }
+                match &notification.update {
+                    AcpSessionUpdate::AgentMessageChunk(chunk) => {
+                        let event = match &chunk.content {
+                            AcpContentBlock::Text(tc) => {
+                                Some(StreamEvent::TextDelta(tc.text.clone()))
+                            }
+                            AcpContentBlock::Audio(audio) => {
+                                notif_log.warning(format!(
+                                    "skipping {} audio in the agent's message: the stream \
+                                     cannot carry audio",
+                                    audio.mime_type
+                                ));
+                                None
+                            }
+                            other => Some(match message_content(other) {
+                                Some(block) => StreamEvent::Content { block },
+                                None => StreamEvent::ContentError {
+                                    message: content_error(other),
+                                },
+                            }),
+                        };
+                        if let Some(event) = event {
+                            let _ = notif_tx.send(event).await;
+                        }
+                    }
+                    AcpSessionUpdate::AgentThoughtChunk(chunk) => {
+                        if let AcpContentBlock::Text(tc) = &chunk.content {
+                            let _ = notif_tx
+                                .send(StreamEvent::ThoughtDelta(tc.text.clone()))
+                                .await;
+                        }
+                    }
+                    AcpSessionUpdate::ToolCall(tc) => {
+                        let tool_name = tc.title.clone();
+                        let tool_input = tc
+                            .raw_input
+                            .as_ref()
+                            .map(|v| v.to_string())
+                            .unwrap_or_default();
+                        let tool_use_id = tc.tool_call_id.0.to_string();
+                        let tool_kind = Some(ToolKind::from_acp(&tc.kind));
+                        let tool_status = ToolStatus::from_acp(&tc.status);
+                        track_tool_call(
+                            &notif_tool_calls,
+                            &notification.session_id.0,
+                            &tool_use_id,
+                            Some(tc.title.as_str()),
+                            tool_status,
+                        );
+                        let _ = notif_tx
+                            .send(StreamEvent::ToolUseStart {
+                                tool_name,
+                                tool_input,
+                                tool_use_id,
+                                tool_kind,
+                                tool_status,
+                            })
+                            .await;
+                    }
+                    AcpSessionUpdate::ToolCallUpdate(tcu) => {
+                        let tool_use_id = tcu.tool_call_id.0.to_string();
+                        let tool_status =
+                            tcu.fields.status.as_ref().and_then(ToolStatus::from_acp);
+                        track_tool_call(
+                            &notif_tool_calls,
+                            &notification.session_id.0,
+                            &tool_use_id,
+                            tcu.fields.title.as_deref(),
+                            tool_status,
+                        );
+                        let tool_content = tcu.fields.content.as_ref()
+                            .and_then(|c| serde_json::to_string(c).ok());
+                        let tool_locations = tcu.fields.locations.as_ref()
+                            .and_then(|l| serde_json::to_string(l).ok());
+                        // Relative paths are taken against the session's cwd.
+                        let cwd = notif_cwds
+                            .lock()
+                            .unwrap()
+                            .get(&*notification.session_id.0)
+                            .cloned();
+                        let locations = tcu
+                            .fields
+                            .locations
+                            .iter()
+                            .flatten()
+                            .map(|l| ToolLocation::resolve(&l.path, l.line, cwd.as_deref()))
+                            .collect();
+
+                        // Send rich update event
+                        let _ = notif_tx
+                            .send(StreamEvent::ToolUseUpdate {
+                                tool_use_id: tool_use_id.clone(),
+                                tool_status,
+                                tool_content,
+                                tool_locations,
+                                locations,
+                            })
+                            .await;
+
+                        // Also send legacy ToolUseEnd if terminal status
+                        if tool_status.is_some_and(ToolStatus::is_terminal) {
+                            let _ = notif_tx
+                                .send(StreamEvent::ToolUseEnd { tool_use_id })
+                                .await;
+                        }
+                    }
+                    AcpSessionUpdate::Plan(plan) => {
+                        if let Ok(json) = serde_json::to_string(&plan.entries) {
+                            let _ = notif_tx
+                                .send(StreamEvent::Plan { entries_json: json })
+                                .await;
+                        }
+                    }
+                    AcpSessionUpdate::AvailableCommandsUpdate(cmd_update) => {
+                        if let Ok(json) = serde_json::to_string(&cmd_update.available_commands) {
+                            notif_commands
+                                .lock()
+                                .unwrap()
+                                .insert(notification.session_id.0.to_string(), json.clone());
+                            let _ = notif_tx
+                                .send(StreamEvent::CommandsUpdate { commands_json: json })
+                                .await;
+                        }
+                    }
+                    AcpSessionUpdate::CurrentModeUpdate(mode_update) => {
+                        let _ = notif_tx
+                            .send(StreamEvent::ModeChange {
+                                mode_id: mode_update.current_mode_id.0.to_string(),
+                            })
+                            .await;
+                    }
+                    AcpSessionUpdate::ConfigOptionUpdate(config_update) => {
+                        if let Ok(json) = serde_json::to_string(&config_update.config_options) {
+                            let _ = notif_tx
+                                .send(StreamEvent::ConfigUpdate { config_json: json })
+                                .await;
+                        }
+                    }
+                    AcpSessionUpdate::UsageUpdate(usage) => {
+                        // `amount` goes out as a decimal string: Rust prints the
+                        // shortest representation that round-trips, so the
+                        // agent's value is kept without float noise.
+                        let usage_data = serde_json::json!({
+                            "used": usage.used,
+                            "size": usage.size,
+                            "cost": usage.cost.as_ref().map(|c| serde_json::json!({
+                                "amount": c.amount.to_string(),
+                                "currency": &c.currency,
+                            })),
+                        });
+                        let _ = notif_tx
+                            .send(StreamEvent::Usage {
+                                usage_json: usage_data.to_string(),
+                            })
+                            .await;
+                    }
+                    AcpSessionUpdate::SessionInfoUpdate(info) => {
+                        let sent = sent_session_info(&info);
+                        record_session_info(&notif_info, &notification.session_id.0, &sent);
+                        let info_data = serde_json::json!({
+                            "title": sent.get("title"),
+                            "updated_at": sent.get("updated_at"),
+                        });
+                        let _ = notif_tx
+                            .send(StreamEvent::SessionInfo {
+                                info_json: info_data.to_string(),
+                            })
+                            .await;
+                    }
+                    AcpSessionUpdate::UserMessageChunk(_) => {
+                        // Echo of user message — ignore.
+                    }
+                    _ => {
+                        // Future variants — ignore gracefully.
+                    }
+                }
+                Ok(())
+            },
+        )
+        // --- Extension notifications (rate_limit_event, etc.) ---
+        .on_receive_notification(
+            async move |notification: AgentNotification, _cx| {
+                if let AgentNotification::ExtNotification(ext) = notification {
+                    let method = wire_ext_method(&ext.method);
+                    let params_json = ext.params.to_string();
+                    let session_id = serde_json::from_str::<serde_json::Value>(&params_json)
+                        .ok()
+                        .and_then(|p| p.get("sessionId")?.as_str().map(str::to_string))
+                        .unwrap_or_default();
+                    let ext_notif_tx = ext_notif_tx.in_session(&ext_turns, &session_id);
+                    let event = if is_rate_limit_method(&method) {
+                        record_rate_limit(&ext_rate_limits, &method, &params_json);
+                        StreamEvent::RateLimit {
+                            method,
+                            params_json,
+                        }
+                    } else {
+                        StreamEvent::Extension {
+                            method,
+                            params_json,
+                        }
+                    };
+                    let _ = ext_notif_tx.send(event).await;
+                }
+                Ok(())
+            },
+        )
+        // --- Permission requests ---
+        .on_receive_request(
+            async move |request: RequestPermissionRequest, request_cx| {
+                // Let stream observers see the request before it is decided.
+                let fields = &request.tool_call.fields;
+                let perm_tx = perm_tx.in_session(&perm_turns, &request.session_id.0);
+                let _ = perm_tx
+                    .send(StreamEvent::PermissionRequest {
+                        tool_name: fields.title.clone().unwrap_or_default(),
+                        tool_input: fields
+                            .raw_input
+                            .as_ref()
+                            .map(|v| v.to_string())
+                            .unwrap_or_else(|| "{}".into()),
+                        tool_use_id: request.tool_call.tool_call_id.0.to_string(),
+                        options_json: serde_json::to_string(&request.options)
+                            .unwrap_or_else(|_| "[]".into()),
+                    })
+                    .await;
+
+                // Try to call the Python permission callback.
+                let rules = perm_rules.lock().unwrap().clone();
+                let decision = call_permission_callback(
+                    &perm_log,
+                    &perm_callback,
+                    &rules,
+                    &input_middleware,
+                    hooks.as_ref(),
+                    &request,
+                )
+                .await;
+
+                match decision {
+                    PermissionDecision::Allow { option_id } => {
+                        // Select the requested option if offered, else the
+                        // first "allow" option, or just the first option.
+                        let allow_option = request
+                            .options
+                            .iter()
+                            .find(|o| option_id.as_deref() == Some(&*o.option_id.0))
+                            .or_else(|| {
+                                request.options.iter().find(|o| {
+                                    o.kind == PermissionOptionKind::AllowOnce
+                                        || o.kind == PermissionOptionKind::AllowAlways
+                                })
+                            })
+                            .or_else(|| request.options.first());
+
+                        if let Some(opt) = allow_option {
+                            request_cx.respond(RequestPermissionResponse::new(
+                                RequestPermissionOutcome::Selected(
+                                    SelectedPermissionOutcome::new(
+                                        opt.option_id.clone(),
+                                    ),
+                                ),
+                            ))
+                        } else {
+                            request_cx.respond(RequestPermissionResponse::new(
+                                RequestPermissionOutcome::Cancelled,
+                            ))
+                        }
+                    }
+                    PermissionDecision::Deny { reason } => {
+                        // Select a "reject" option so the agent sees a
+                        // deliberate refusal; cancel only if none is offered.
+                        let reject_option = request.options.iter().find(|o| {
+                            o.kind == PermissionOptionKind::RejectOnce
+                                || o.kind == PermissionOptionKind::RejectAlways
+                        });
+                        let outcome = match reject_option {
+                            Some(opt) => RequestPermissionOutcome::Selected(
+                                SelectedPermissionOutcome::new(opt.option_id.clone()),
+                            ),
+                            None => RequestPermissionOutcome::Cancelled,
+                        };
+                        let mut response = RequestPermissionResponse::new(outcome);
+                        if let Some(reason) = reason {
+                            let mut meta = serde_json::Map::new();
+                            meta.insert("reason".into(), reason.into());
+                            response = response.meta(meta);
+                        }
+                        request_cx.respond(response)
+                    }
+                }
+            },
+        )
+        // --- Confirmation requests, answered by the Python callback ---
+        .on_receive_request(
+            async move |request: ConfirmationRequest, request_cx| {
+                let params_json = serde_json::to_string(&request).unwrap_or_default();
+                let session_id = request.session_id.as_deref().unwrap_or_default();
+                let confirm_tx = confirm_tx.in_session(&confirm_turns, session_id);
+                let _ = confirm_tx
+                    .send(StreamEvent::Confirmation {
+                        message: request.message.clone(),
+                        params_json: params_json.clone(),
+                    })
+                    .await;
+                let confirmed = ask_confirmation(
+                    &confirm_log,
+                    &confirmation,
+                    &request.message,
+                    &params_json,
+                )
+                .await;
+                request_cx.respond(serde_json::json!({ "confirmed": confirmed }))
+            },
+        )
+        // --- MCP tool calls, dispatched to the tool registry ---
+        .on_receive_request(
+            async move |request: McpToolCallRequest, request_cx| {
+                match call_registry_tool(tool_registry.as_ref(), request).await {
+                    Ok(result) => request_cx.respond(result),
+                    Err(e) => request_cx.respond_with_error(e),
+                }
+            },
+        )
+        // --- Client logic (init handshake + command loop) ---
+        .with_spawned(move |cx| async move {
+            let result = acp_task(
+                cx,
+                caps_tx,
+                commands,
+                update_tx,
+                task_sessions,
+                protocol_version,
+                client_info,
+                heartbeat,
+                task_log,
+            )
+            .await;
+            // Shut down by the client, or never got past the handshake.
+            task_ended.store(true, Ordering::Release);
+            result
+        });
+
+    // Spawn the long-lived background task that owns the ACP connection.
+    let task = tokio::spawn(async move {
+        let (result, closed) = tokio::select! {
+            result = chain.serve(transport) => {
+                // The agent hung up (or the transport broke) under a live
+                // connection, rather than the client shutting it down.
+                if !client_ended.load(Ordering::Acquire) {
+                    let _ = state.compare_exchange(
+                        ConnectionState::Connected as u8,
+                        ConnectionState::Failed as u8,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    );
+                }
+                let closed = match &result {
+                    _ if client_ended.load(Ordering::Acquire) => None,
+                    _ if input_closed.load(Ordering::Acquire) => Some(AGENT_INPUT_CLOSED.into()),
+                    Ok(()) => Some(AGENT_DISCONNECTED.into()),
+                    Err(e) => Some(format!("connection to agent failed: {e}")),
+                };
+                (result, closed)
+            }
+            // Dropping the chain stops the command loop and fails the
+            // requests it has in flight.
+            Ok(()) = dead_rx => {
+                state.store(ConnectionState::Failed as u8, Ordering::Release);
+                (Ok(()), Some(AGENT_UNRESPONSIVE.into()))
+            }
+        };
+        if let Some(reason) = closed {
+            // End the stream in flight, then answer every command still
+            // queued or sent later.
+            log.warning(&reason);
+            let _ = closed_tx.try_send(StreamEvent::Closed {
+                reason: reason.clone(),
+            });
+            drop(closed_tx);
+            pending_cmds.fail_all(&reason).await;
+        } else if let Err(e) = result {
+            log.error(format!("ACP background task error: {e}"));
+        }
+    });
+
+    // Wait for the background task to complete the initialize handshake.
+    let caps = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, caps_rx).await {
+            Ok(caps) => caps,
+            Err(_) => {
+                task.abort();
+                let msg = "initialize handshake timed out".to_string();
+                return Err(ConduitError::Timeout(msg));
+            }
+        },
+        None => caps_rx.await,
+    };
+    let (capabilities, agent_info, cx) = caps
+        .map_err(|_| {
+            ConduitError::Connection(
+                "ACP background task dropped before sending capabilities".into(),
+            )
+        })?
+        ?;
+
+    Ok(AcpConnection {
+        cmd_tx,
+        cx,
+        update_rx,
+        capabilities,
+        agent_info,
+        session_cwds,
+        available_commands,
+        session_info,
+        rate_limits,
+        active_tool_calls,
+        recorder,
+    })
+}
+
+/// Map a [`StreamEvent`] to the [`SessionUpdate`] handed to Python,
+/// stamped with the `request_id` and `turn_id` of its turn.
+fn session_update(event: StreamEvent, turn: TurnStamp) -> SessionUpdate {
+    let TurnStamp {
+        request_id,
+        turn_id,
+    } = turn;
+    let defaults = SessionUpdate {
+        kind: UpdateKind::TextDelta,
+        text: None,
+        tool_name: None,
+        tool_input: None,
+        tool_use_id: None,
+        error: None,
+        stop_reason: None,
+        tool_kind: None,
+        tool_status: None,
+        tool_content: None,
+        tool_locations: None,
+        mode_id: None,
+        plan_json: None,
+        config_json: None,
+        commands_json: None,
+        usage_json: None,
+        session_info_json: None,
+        rate_limit_json: None,
+        confirmation_json: None,
+        permission_options_json: None,
+        request_id,
+        turn_id,
+        locations: Vec::new(),
+        plan_entries: Vec::new(),
+        rate_limit: None,
+        method: None,
+        params_json: None,
+        cost: None,
+        content: None,
+    };
+
+    match event {
+        StreamEvent::TextDelta(t) => SessionUpdate {
+            kind: UpdateKind::TextDelta,
+            text: Some(t),
+            ..defaults
+        },
+        StreamEvent::ThoughtDelta(t) => SessionUpdate {
+            kind: UpdateKind::ThoughtDelta,
+            text: Some(t),
+            ..defaults
+        },
+        StreamEvent::ToolUseStart {
+            tool_name,
+            tool_input,
+            tool_use_id,
+            tool_kind,
+            tool_status,
+        } => SessionUpdate {
+            kind: UpdateKind::ToolUseStart,
+            tool_name: Some(tool_name),
+            tool_input: Some(tool_input),
+            tool_use_id: Some(tool_use_id),
+            tool_kind,
+            tool_status,
+            ..defaults
+        },
+        StreamEvent::ToolUseUpdate {
+            tool_use_id,
+            tool_status,
+            tool_content,
+            tool_locations,
+            locations,
+        } => SessionUpdate {
+            kind: UpdateKind::ToolUseUpdate,
+            tool_use_id: Some(tool_use_id),
+            tool_status,
+            tool_content,
+            tool_locations,
+            locations,
+            ..defaults
+        },
+        StreamEvent::ToolUseEnd { tool_use_id } => SessionUpdate {
+            kind: UpdateKind::ToolUseEnd,
+            tool_use_id: Some(tool_use_id),
+            ..defaults
+        },
+        StreamEvent::ModeChange { mode_id } => SessionUpdate {
+            kind: UpdateKind::ModeChange,
+            mode_id: Some(mode_id),
+            ..defaults
+        },
+        StreamEvent::Plan { entries_json } => SessionUpdate {
+            kind: UpdateKind::Plan,
+            plan_entries: PlanEntry::parse_list(&entries_json),
+            plan_json: Some(entries_json),
+            ..defaults
+        },
+        StreamEvent::ConfigUpdate { config_json } => SessionUpdate {
+            kind: UpdateKind::ConfigUpdate,
+            config_json: Some(config_json),
+            ..defaults
+        },
+        StreamEvent::CommandsUpdate { commands_json } => SessionUpdate {
+            kind: UpdateKind::CommandsUpdate,
+            commands_json: Some(commands_json),
+            ..defaults
+        },
+        StreamEvent::Usage { usage_json } => SessionUpdate {
+            kind: UpdateKind::Usage,
+            cost: serde_json::from_str::<serde_json::Value>(&usage_json)
+                .ok()
+                .and_then(|usage| Cost::parse(&usage["cost"])),
+            usage_json: Some(usage_json),
+            ..defaults
+        },
+        StreamEvent::SessionInfo { info_json } => SessionUpdate {
+            kind: UpdateKind::SessionInfo,
+            session_info_json: Some(info_json),
+            ..defaults
+        },
+        StreamEvent::RateLimit {
+            method,
+            params_json,
+        } => {
+            let params =
+                serde_json::from_str::<serde_json::Value>(&params_json).unwrap_or_default();
+            SessionUpdate {
+                kind: UpdateKind::RateLimit,
+                rate_limit: RateLimitInfo::parse(&method, &params),
+                rate_limit_json: Some(
+                    serde_json::json!({ "method": method, "params": params }).to_string(),
+                ),
+                ..defaults
+            }
+        }
+        StreamEvent::Extension {
+            method,
+            params_json,
+        } => SessionUpdate {
+            kind: UpdateKind::Extension,
+            method: Some(method),
+            params_json: Some(params_json),
+            ..defaults
+        },
+        StreamEvent::Confirmation {
+            message,
+            params_json,
+        } => SessionUpdate {
+            kind: UpdateKind::Confirmation,
+            text: Some(message),
+            confirmation_json: Some(params_json),
+            ..defaults
+        },
+        StreamEvent::PermissionRequest {
+            tool_name,
+            tool_input,
+            tool_use_id,
+            options_json,
+        } => SessionUpdate {
+            kind: UpdateKind::Permission,
+            tool_name: Some(tool_name),
+            tool_input: Some(tool_input),
+            tool_use_id: Some(tool_use_id),
+            permission_options_json: Some(options_json),
+            ..defaults
+        },
+        StreamEvent::Content { block } => SessionUpdate {
+            kind: UpdateKind::Content,
+            content: Some(block),
+            ..defaults
+        },
+        StreamEvent::ContentError { message } => SessionUpdate {
+            kind: UpdateKind::Error,
+            error: Some(message),
+            ..defaults
+        },
+        StreamEvent::Closed { reason } => SessionUpdate {
+            kind: UpdateKind::Error,
+            error: Some(reason),
+            ..defaults
+        },
+        StreamEvent::Done { stop_reason } => SessionUpdate {
+            kind: UpdateKind::Done,
+            stop_reason,
+            ..defaults
+        },
+    }
+}
+
+/// An agent message block surfaced as a `Content` update: images,
+/// resources and resource links.
+fn message_content(block: &AcpContentBlock) -> Option<ContentBlock> {
+    let value = serde_json::to_value(block).ok()?;
+    ContentBlock::from_tool_content(&value).filter(|block| {
+        matches!(
+            block.content_type,
+            ContentType::Image | ContentType::Resource | ContentType::ResourceLink
+        )
+    })
+}
+
+/// The error text for an agent message block with no update of its own:
+/// the block's own text, else a note naming its type.
+fn content_error(block: &AcpContentBlock) -> String {
+    let value = serde_json::to_value(block).unwrap_or_default();
+    match value["text"].as_str() {
+        Some(text) => text.to_string(),
+        None => {
+            let kind = value["type"].as_str().unwrap_or("unknown");
+            format!("agent sent {kind} content the stream cannot carry")
+        }
+    }
+}
+
+/// Feed the raw tap and the update callback from the lines the agent sent,
+/// so both get each `session/update`'s params exactly as sent, including
+/// ones the typed mapping can't parse. `tapped` counts the notifications
+/// handled, for the typed handler to wait on.
+async fn run_notification_tap(
+    log: LogSink,
+    raw_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    update_callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    observed_tx: mpsc::UnboundedSender<String>,
+    mut lines: mpsc::UnboundedReceiver<Vec<u8>>,
+    tapped: watch::Sender<u64>,
+) {
+    while let Some(line) = lines.recv().await {
+        let Some(params) = session_update_params(&line) else {
+            continue;
+        };
+        if update_callback.lock().unwrap().is_some() {
+            let _ = observed_tx.send(params.clone());
+        }
+        let callback = Python::with_gil(|py| {
+            let guard = raw_callback.lock().unwrap();
+            guard.as_ref().map(|cb| cb.clone_ref(py))
+        });
+        if let Some(callback) = callback {
+            call_json_callback(&log, callback, params, "raw notification callback").await;
+        }
+        tapped.send_modify(|count| *count += 1);
+    }
+}
+
+/// The params of `line`, as sent, if it is a `session/update` notification.
+fn session_update_params(line: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Envelope<'a> {
+        method: Option<String>,
+        id: Option<serde::de::IgnoredAny>,
+        #[serde(borrow)]
+        params: Option<&'a serde_json::value::RawValue>,
+    }
+    let envelope: Envelope = serde_json::from_slice(line).ok()?;
+    if envelope.method.as_deref() != Some("session/update") || envelope.id.is_some() {
+        return None;
+    }
+    envelope.params.map(|params| params.get().to_string())
+}
+
+/// Hand each queued notification to the update callback, in order, until
+/// the connection's notification handler is gone.
+async fn run_update_callback(
+    log: LogSink,
+    callback: Arc<std::sync::Mutex<Option<PyObject>>>,
+    mut observed_rx: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(raw) = observed_rx.recv().await {
+        let callback = Python::with_gil(|py| {
+            let guard = callback.lock().unwrap();
+            guard.as_ref().map(|cb| cb.clone_ref(py))
+        });
+        if let Some(callback) = callback {
+            call_json_callback(&log, callback, raw, "update callback").await;
+        }
+    }
+}
+
+/// Call `callback` with a JSON string, awaiting it if it is async. `what`
+/// names the callback when logging what it raised.
+async fn call_json_callback(log: &LogSink, callback: PyObject, raw: String, what: &str) {
+    let pending = Python::with_gil(|py| -> PyResult<_> {
+        let result = callback.bind(py).call1((raw,))?;
+        // An async callback is awaited outside the GIL.
+        if result.hasattr("__await__")? {
+            return Ok(Some(pyo3_async_runtimes::tokio::into_future(result)?));
+        }
+        Ok(None)
+    });
+    let outcome = match pending {
+        Ok(Some(future)) => future.await.map(drop),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = outcome {
+        log.error(format!("{what} failed: {e}"));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Incoming requests outside the ACP schema
+// ---------------------------------------------------------------------------
+
+/// Make a serde struct receivable as the params of the JSON-RPC request
+/// `$method`, answered with a raw JSON value.
+macro_rules! untyped_request {
+    ($ty:ty, $method:literal) => {
+        impl sacp::JrMessage for $ty {
+            fn to_untyped_message(&self) -> Result<UntypedMessage, sacp::Error> {
+                UntypedMessage::new($method, self)
+            }
+
+            fn method(&self) -> &str {
+                $method
+            }
+
+            fn parse_request(
+                method: &str,
+                params: &impl serde::Serialize,
+            ) -> Option<Result<Self, sacp::Error>> {
+                if method != $method {
+                    return None;
+                }
+                Some(sacp::util::json_cast(params))
+            }
+
+            fn parse_notification(
+                _method: &str,
+                _params: &impl serde::Serialize,
+            ) -> Option<Result<Self, sacp::Error>> {
+                None
+            }
+        }
+
+        impl sacp::JrRequest for $ty {
+            type Response = serde_json::Value;
+        }
+    };
+}
+
+// ---------------------------------------------------------------------------
+// Confirmation requests
+// ---------------------------------------------------------------------------
+
+/// How the agent's confirmation requests are answered.
+#[derive(Clone, Default)]
+struct ConfirmationPolicy {
+    /// `callback(message, params) -> bool`, sync or async.
+    callback: Option<Arc<PyObject>>,
+    /// How long the callback may take before `default` is answered.
+    timeout: Option<Duration>,
+    /// Answer used without a callback, on timeout, or if the callback fails.
+    default: bool,
+}
+
+/// A yes/no question from the agent, e.g. "proceed with 12 edits?". ACP
+/// has no standard request for this, so it is a `_conduit/` extension.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfirmationRequest {
+    #[serde(default)]
+    session_id: Option<String>,
+    message: String,
+    /// Any other fields the agent sent, kept for the callback.
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+untyped_request!(ConfirmationRequest, "_conduit/request_confirmation");
+
+/// Ask the confirmation callback, falling back to the policy's default.
+async fn ask_confirmation(
+    log: &LogSink,
+    policy: &Arc<std::sync::Mutex<ConfirmationPolicy>>,
+    message: &str,
+    params_json: &str,
+) -> bool {
+    let policy = policy.lock().unwrap().clone();
+    let Some(callback) = policy.callback else {
+        return policy.default;
+    };
+
+    let mut answer = None;
+    let pending = Python::with_gil(|py| -> PyResult<_> {
+        let params = py.import("json")?.call_method1("loads", (params_json,))?;
+        let result = callback.bind(py).call1((message, params))?;
+        // An async callback runs as a task, so a timeout can cancel it.
+        if result.hasattr("__await__")? {
+            return Ok(Some(PyTask::spawn(&result)?));
+        }
+        answer = Some(result.is_truthy()?);
+        Ok(None)
+    });
+
+    let outcome = match pending {
+        Ok(Some(task)) => {
+            let result = match policy.timeout {
+                Some(timeout) => match task.wait_for(timeout).await {
+                    Some(result) => result,
+                    None => {
+                        log.warning(format!(
+                            "confirmation callback timed out after {}s, answering {}",
+                            timeout.as_secs_f64(),
+                            policy.default
+                        ));
+                        return policy.default;
+                    }
+                },
+                None => task.wait().await,
+            };
+            result.and_then(|result| Python::with_gil(|py| result.bind(py).is_truthy()))
+        }
+        Ok(None) => Ok(answer.unwrap_or(policy.default)),
+        Err(e) => Err(e),
+    };
+    outcome.unwrap_or_else(|e| {
+        log.error(format!("confirmation callback failed: {e}"));
+        policy.default
+    })
+}
+
+// ---------------------------------------------------------------------------
+// MCP tool bridge
+// ---------------------------------------------------------------------------
+
+/// JSON-RPC code MCP uses for an unknown tool name.
+const MCP_INVALID_PARAMS: i32 = -32602;
+
+/// An MCP `tools/call` request sent by the agent.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct McpToolCallRequest {
+    name: String,
+    #[serde(default)]
+    arguments: Option<serde_json::Value>,
+}
+
+untyped_request!(McpToolCallRequest, "tools/call");
+
+/// Answer an MCP `tools/call` from the registry given to `connect()`.
+///
+/// Tool failures are reported in-band (`isError: true`) as MCP expects;
+/// only a missing registry or an unknown tool name is a JSON-RPC error.
+#[tracing::instrument(level = "debug", skip_all, fields(tool = %request.name))]
+async fn call_registry_tool(
+    registry: Option<&ToolRegistryHandle>,
+    request: McpToolCallRequest,
+) -> Result<serde_json::Value, sacp::Error> {
+    let Some(registry) = registry else {
+        return Err(sacp::Error::method_not_found());
+    };
+    if !registry.contains(&request.name).await {
+        return Err(sacp::Error::new(
+            MCP_INVALID_PARAMS,
+            format!("Unknown tool: {}", request.name),
+        ));
+    }
+
+    let arguments = request.arguments.unwrap_or_else(|| serde_json::json!({}));
+    let result = registry
+        .invoke_structured(&request.name, arguments.to_string())
+        .await
+        .map_err(|e| {
+            let message = Python::with_gil(|py| e.value(py).to_string());
+            tracing::debug!(error = %message, "tool call failed");
+            sacp::Error::internal_error().data(message)
+        })?;
+    tracing::debug!(is_error = %result["is_error"], "tool call finished");
+
+    Ok(serde_json::json!({
+        "content": result["content"],
+        "isError": result["is_error"],
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// Prompt turns
+// ---------------------------------------------------------------------------
+
+/// Run `future` on the SDK's tokio runtime, blocking the calling thread
+/// with the GIL released.
+///
+/// Refuses to run inside a running asyncio loop: blocking there would
+/// stall the loop, and with it any Python callbacks the future waits on.
+fn block_on<T: Send>(
+    py: Python<'_>,
+    op: &str,
+    future: impl Future<Output = Result<T, ConduitError>> + Send,
+) -> PyResult<T> {
+    let asyncio = py.import("asyncio")?;
+    if asyncio.call_method0("get_running_loop").is_ok() {
+        return Err(ConduitError::Other(format!(
+            "{op}() cannot be called from a running event loop; await the async method instead"
+        ))
+        .into());
+    }
+    py.allow_threads(|| pyo3_async_runtimes::tokio::get_runtime().block_on(future))
+        .map_err(Into::into)
+}
+
+/// Everything collected from one `prompt()`/`prompt_full()` turn.
+struct Turn {
+    session_id: String,
+    /// Number of the turn within its session.
+    turn_id: u64,
+    text: String,
+    /// The reply as it happened: text runs, tool uses and tool results.
+    blocks: Vec<ContentBlock>,
+    tool_calls: Vec<ContentBlock>,
+    usage_json: Option<String>,
+    /// The session's cost as of the turn's last usage report that carried
+    /// one. Costs are cumulative, so it is not summed across reports.
+    cost: Option<Cost>,
+    stop_reason: Option<String>,
+}
+
+impl Turn {
+    /// The assistant reply as messages (empty if the agent sent no text and
+    /// made no tool calls).
+    fn messages(&self) -> Vec<Message> {
+        if self.blocks.is_empty() {
+            return vec![];
+        }
+        vec![Message {
+            role: MessageRole::Assistant,
+            content: self.blocks.clone(),
+            session_id: Some(self.session_id.clone()),
+            stop_reason: self.stop_reason.clone(),
+            turn_id: Some(self.turn_id),
+        }]
+    }
+
+    /// The turn as a [`ResultMessage`] taking `duration`. Cost is the
+    /// session cost the turn last reported, given as `total_cost_usd` only
+    /// when the agent reported it in USD; the reply and each tool call
+    /// count as a turn.
+    fn result_message(&self, duration: Duration) -> ResultMessage {
+        let cost = self.cost.as_ref();
+        ResultMessage {
+            subtype: "result".into(),
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            is_error: false,
+            num_turns: u32::try_from(self.tool_calls.len() + 1).unwrap_or(u32::MAX),
+            session_id: self.session_id.clone(),
+            total_cost_usd: cost
+                .filter(|cost| cost.currency == "USD")
+                .map(Cost::amount_f64),
+            result: (!self.text.is_empty()).then(|| self.text.clone()),
+            currency: cost.map(|cost| cost.currency.clone()),
+            turn_id: Some(self.turn_id),
+        }
+    }
+}
+
+/// Parse a prompt's `meta_json`, which must be a JSON object; `None` is
+/// an empty one.
+fn parse_prompt_meta(
+    meta_json: Option<&str>,
+) -> Result<serde_json::Map<String, serde_json::Value>, ConduitError> {
+    let Some(meta_json) = meta_json else {
+        return Ok(serde_json::Map::new());
+    };
+    serde_json::from_str(meta_json)
+        .map_err(|e| ConduitError::Protocol(format!("invalid meta_json: {e}")))
+}
+
+/// Send a prompt and collect its streaming updates until the turn is done.
+#[tracing::instrument(level = "debug", skip_all, fields(request_id = %request_id))]
+async fn run_turn(
+    inner: &Arc<Mutex<Option<ClientInner>>>,
+    update_rx_slot: &Arc<Mutex<Option<mpsc::Receiver<QueuedEvent>>>>,
+    turn_counts: &TurnCounts,
+    request_id: String,
+    text: String,
+    session_id: Option<String>,
+    content_json: Option<String>,
+    meta: serde_json::Map<String, serde_json::Value>,
+) -> Result<Turn, ConduitError> {
+    // Snapshot cmd_tx and session_id without holding the lock across awaits.
+    let (cmd_tx, default_session_id, running_turns) = {
+        let guard = inner.lock().await;
+        let client = guard
+            .as_ref()
+            .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
+        if !client.initialized {
+            return Err(ConduitError::Connection("client not initialized".into()));
+        }
+        (
+            client.cmd_tx.clone(),
+            client.session_id.lock().unwrap().clone(),
+            client.running_turns.clone(),
+        )
+    };
+
+    // Use explicit session_id, or fall back to default, or auto-create.
+    let session_id = match session_id.or(default_session_id) {
+        Some(id) => id,
+        None => {
+            let cwd = std::env::current_dir()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
             let (reply_tx, reply_rx) = oneshot::channel();
             cmd_tx
-                .send(AcpCommand::Prompt {
-                    session_id,
-                    text,
-                    content_json,
+                .send(AcpCommand::NewSession {
+                    cwd,
+                    meta_json: None,
+                    mcp_servers: Vec::new(),
                     reply: reply_tx,
                 })
                 .await
-                .map_err(|_| ConduitError::Connection("background task closed".into()))?;
+                .map_err(|_| task_closed("prompt/new_session", None))?;
+            let id = reply_rx
+                .await
+                .map_err(|_| reply_dropped("prompt/new_session", None))??;
 
-            *prompt_reply_rx.lock().await = Some(reply_rx);
-            Ok(())
-        })
-    }
+            // Persist session_id for subsequent prompts.
+            {
+                let mut guard = inner.lock().await;
+                if let Some(client) = guard.as_mut() {
+                    *client.session_id.lock().unwrap() = Some(id.clone());
+                }
+            }
+            id
+        }
+    };
 
-    /// Receive the next streaming update from the agent.
-    ///
-    /// Returns a [`SessionUpdate`] for each chunk (text, thought, tool use,
-    /// mode change, plan, config, commands, usage, session info),
-    /// or `None` when the prompt is complete.
-    fn recv_update<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let update_rx_slot = self.update_rx.clone();
-        let prompt_reply_rx = self.prompt_reply_rx.clone();
+    let turn_id = mint_turn_id(turn_counts, &session_id);
 
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let mut rx_guard = update_rx_slot.lock().await;
-            let update_rx = rx_guard.as_mut().ok_or_else(|| {
-                ConduitError::Connection("update channel not initialized".into())
-            })?;
+    // Send the prompt command to the background task.
+    let (reply_tx, reply_rx) = oneshot::channel();
+    cmd_tx
+        .send(AcpCommand::Prompt {
+            session_id: session_id.clone(),
+            text,
+            content_json,
+            meta,
+            request_id,
+            turn_id,
+            running: RunningTurn::start(&running_turns, &session_id),
+            reply: reply_tx,
+        })
+        .await
+        .map_err(|_| task_closed("prompt", Some(&session_id)))?;
 
-            let su_defaults = || SessionUpdate {
-                kind: UpdateKind::TextDelta,
-                text: None,
-                tool_name: None,
-                tool_input: None,
-                tool_use_id: None,
-                error: None,
-                stop_reason: None,
-                tool_kind: None,
-                tool_status: None,
-                tool_content: None,
-                tool_locations: None,
-                mode_id: None,
-                plan_json: None,
-                config_json: None,
-                commands_json: None,
-                usage_json: None,
-                session_info_json: None,
-                rate_limit_json: None,
+    // Collect streaming updates until the Done sentinel arrives, building
+    // the reply the same way streaming consumers can with
+    // `ContentBlock.from_updates()`.
+    let mut reply = ReplyBlocks::default();
+    let mut stop_reason: Option<String> = None;
+    let mut usage_json = None;
+    let mut cost = None;
+    {
+        let mut rx_guard = update_rx_slot.lock().await;
+        let update_rx = rx_guard.as_mut().ok_or_else(|| {
+            ConduitError::Connection("update channel not initialized".into())
+        })?;
+        loop {
+            let Some(QueuedEvent { event, turn }) = update_rx.recv().await else {
+                break;
             };
-
-            match update_rx.recv().await {
-                Some(StreamEvent::TextDelta(t)) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::TextDelta,
-                    text: Some(t),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ThoughtDelta(t)) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ThoughtDelta,
-                    text: Some(t),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ToolUseStart {
-                    tool_name,
-                    tool_input,
-                    tool_use_id,
-                    tool_kind,
-                    tool_status,
-                }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ToolUseStart,
-                    tool_name: Some(tool_name),
-                    tool_input: Some(tool_input),
-                    tool_use_id: Some(tool_use_id),
-                    tool_kind,
-                    tool_status,
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ToolUseUpdate {
-                    tool_use_id,
-                    tool_status,
-                    tool_content,
-                    tool_locations,
-                }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ToolUseUpdate,
-                    tool_use_id: Some(tool_use_id),
-                    tool_status,
-                    tool_content,
-                    tool_locations,
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ToolUseEnd { tool_use_id }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ToolUseEnd,
-                    tool_use_id: Some(tool_use_id),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ModeChange { mode_id }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ModeChange,
-                    mode_id: Some(mode_id),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::Plan { entries_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::Plan,
-                    plan_json: Some(entries_json),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::ConfigUpdate { config_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::ConfigUpdate,
-                    config_json: Some(config_json),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::CommandsUpdate { commands_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::CommandsUpdate,
-                    commands_json: Some(commands_json),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::Usage { usage_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::Usage,
-                    usage_json: Some(usage_json),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::SessionInfo { info_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::SessionInfo,
-                    session_info_json: Some(info_json),
-                    ..su_defaults()
-                })),
-                Some(StreamEvent::Done { stop_reason }) => {
-                    // Check prompt completion status.
-                    if let Some(reply_rx) = prompt_reply_rx.lock().await.take() {
-                        if let Ok(result) = reply_rx.await {
-                            result?;
-                        }
-                    }
-                    // Return a Done update with stop_reason if caller wants it.
-                    if stop_reason.is_some() {
-                        Ok(Some(SessionUpdate {
-                            kind: UpdateKind::Done,
-                            stop_reason,
-                            ..su_defaults()
-                        }))
-                    } else {
-                        Ok(None)
-                    }
+            let event = match event {
+                StreamEvent::Done { stop_reason: sr } => {
+                    stop_reason = sr;
+                    break;
+                }
+                StreamEvent::Closed { reason } => {
+                    return Err(ConduitError::Connection(reason));
+                }
+                event => event,
+            };
+            tracing::trace!(?event, "turn event");
+            let update = session_update(event, turn);
+            if update.kind == UpdateKind::Usage {
+                // Context usage and cost are both cumulative, so the last
+                // report covers the turn.
+                usage_json = update.usage_json.clone();
+                if update.cost.is_some() {
+                    cost = update.cost.clone();
                 }
-                Some(StreamEvent::RateLimit { method, params_json }) => Ok(Some(SessionUpdate {
-                    kind: UpdateKind::RateLimit,
-                    rate_limit_json: Some(serde_json::json!({
-                        "method": method,
-                        "params": serde_json::from_str::<serde_json::Value>(&params_json).unwrap_or_default(),
-                    }).to_string()),
-                    ..su_defaults()
-                })),
-                None => Ok(None),
             }
-        })
-    }
-
-    /// Return the capabilities received during the initialize handshake.
-    fn capabilities<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let guard = inner.lock().await;
-            let client = guard
-                .as_ref()
-                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-            Ok(client.capabilities.clone())
-        })
+            reply.push(&update);
+        }
     }
 
-    /// Return agent info as a JSON string (name, version, title).
-    fn agent_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let guard = inner.lock().await;
-            let client = guard
-                .as_ref()
-                .ok_or_else(|| ConduitError::Connection("client not connected".into()))?;
-            Ok(client.agent_info_json.clone())
-        })
-    }
+    // Wait for the background task's confirmation that the prompt completed.
+    reply_rx
+        .await
+        .map_err(|_| reply_dropped("prompt", Some(&session_id)))??;
 
-    /// Disconnect from the agent and terminate the subprocess.
-    fn disconnect<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            if let Some(ref mut client) = *inner.lock().await {
-                // Ask the background task to exit its command loop.
-                let _ = client.cmd_tx.send(AcpCommand::Shutdown).await;
-                client.process.kill().await?;
-            }
-            Ok(())
-        })
-    }
+    tracing::debug!(?stop_reason, "turn complete");
+    let blocks = reply.into_blocks();
+    let of_type = |content_type: ContentType| {
+        blocks
+            .iter()
+            .filter(move |block| block.content_type == content_type)
+    };
+    let text = of_type(ContentType::Text)
+        .filter_map(|block| block.text.as_deref())
+        .collect();
+    let tool_calls = of_type(ContentType::ToolUse).cloned().collect();
+    Ok(Turn {
+        session_id,
+        turn_id,
+        text,
+        blocks,
+        tool_calls,
+        usage_json,
+        cost,
+        stop_reason,
+    })
 }
 
 // ---------------------------------------------------------------------------
 // Background task (runs inside JrHandlerChain::with_spawned)
 // ---------------------------------------------------------------------------
 
+/// The command channel from the Python-facing API, shared by the command
+/// loop and, once the connection is gone, the task that fails whatever is
+/// left in it.
+#[derive(Clone)]
+struct CommandInbox {
+    rx: Arc<Mutex<mpsc::Receiver<AcpCommand>>>,
+    /// Cancelled when the connection ends, so the command loop stops
+    /// taking commands.
+    closed: CancellationToken,
+}
+
+impl CommandInbox {
+    /// The command loop's next command, or `None` once the channel or the
+    /// connection is closed. The receiver is only locked while waiting, so
+    /// [`fail_all`](Self::fail_all) can take it over whatever the loop is
+    /// doing.
+    async fn next(&self) -> Option<AcpCommand> {
+        tokio::select! {
+            biased;
+            () = self.closed.cancelled() => None,
+            cmd = async { self.rx.lock().await.recv().await } => cmd,
+        }
+    }
+
+    /// Stop the command loop and answer every command queued or sent
+    /// later with a connection error carrying `reason`.
+    async fn fail_all(&self, reason: &str) {
+        self.closed.cancel();
+        let mut rx = self.rx.lock().await;
+        while let Some(cmd) = rx.recv().await {
+            cmd.fail(reason);
+        }
+    }
+}
+
+/// Per-session state the command loop shares with the notification
+/// handlers.
+struct TaskSessions {
+    /// The turn running in each session.
+    turns: SessionTurns,
+    /// Working directory of each session.
+    cwds: SessionCwds,
+    /// Tool calls still running in each session.
+    tool_calls: ActiveToolCalls,
+}
+
 /// The client task spawned on the ACP connection.
 ///
 /// Performs the initialize handshake, sends the resulting capabilities back
 /// to `connect()` via `caps_tx`, then enters a command loop that processes
 /// [`AcpCommand`] messages from the Python-facing API.
+///
+/// With a pinned `protocol_version`, that version is requested instead of
+/// `LATEST` and the task ends with [`ConduitError::Unsupported`] if the
+/// agent answers with any other. Unpinned, a version newer than `LATEST`
+/// is refused the same way and an older one is accepted with a warning.
+/// The negotiated version is reported in `Capabilities::protocol_version`.
+/// `client_info` is the identity the client reports.
 async fn acp_task(
     cx: sacp::JrConnectionCx,
-    caps_tx: oneshot::Sender<Result<(Capabilities, Option<String>), ConduitError>>,
-    mut cmd_rx: mpsc::Receiver<AcpCommand>,
-    update_tx: mpsc::Sender<StreamEvent>,
+    caps_tx: oneshot::Sender<Result<Handshake, ConduitError>>,
+    commands: CommandInbox,
+    update_tx: EventSender,
+    sessions: TaskSessions,
+    protocol_version: Option<u16>,
+    client_info: Implementation,
+    heartbeat: Option<(Heartbeat, oneshot::Sender<()>)>,
+    log: LogSink,
 ) -> Result<(), sacp::schema::Error> {
     // ---- Initialize handshake ----
-    let init_req = InitializeRequest::new(sacp::schema::ProtocolVersion::LATEST)
-        .client_info(Implementation::new("conduit-agent-sdk", env!("CARGO_PKG_VERSION")));
+    let requested_version = match protocol_version {
+        Some(version) => match serde_json::from_value(serde_json::json!(version)) {
+            Ok(version) => version,
+            Err(e) => {
+                let _ = caps_tx.send(Err(ConduitError::Unsupported(format!(
+                    "protocol version {version}: {e}"
+                ))));
+                return Ok(());
+            }
+        },
+        None => sacp::schema::ProtocolVersion::LATEST,
+    };
+    let init_req = InitializeRequest::new(requested_version).client_info(client_info);
 
+    tracing::debug!(?requested_version, "sending initialize");
     let init_result = cx
         .send_request(init_req)
         .block_task()
@@ -1214,64 +4009,89 @@ async fn acp_task(
     let init_response = match init_result {
         Ok(resp) => resp,
         Err(e) => {
-            let _ = caps_tx.send(Err(ConduitError::Protocol(e.to_string())));
+            let _ = caps_tx.send(Err(ConduitError::from(&e)));
             return Err(e);
         }
     };
 
-    let capabilities = Capabilities::from_acp(&init_response.agent_capabilities);
+    let negotiated = serde_json::to_value(&init_response.protocol_version).unwrap_or_default();
+    if let Some(pinned) = protocol_version {
+        if negotiated != serde_json::json!(pinned) {
+            let _ = caps_tx.send(Err(ConduitError::Unsupported(format!(
+                "protocol version {pinned} is pinned but the agent negotiated {negotiated}"
+            ))));
+            return Ok(());
+        }
+    }
+    let latest = serde_json::to_value(sacp::schema::ProtocolVersion::LATEST).unwrap_or_default();
+    match (negotiated.as_u64(), latest.as_u64()) {
+        (Some(version), Some(latest)) if version > latest => {
+            let _ = caps_tx.send(Err(ConduitError::Unsupported(format!(
+                "agent negotiated protocol version {version}, newer than the latest \
+                 this SDK supports ({latest})"
+            ))));
+            return Ok(());
+        }
+        (Some(version), Some(latest)) if version < latest => {
+            log.warning(format!(
+                "agent negotiated protocol version {version}, older than {latest}"
+            ));
+        }
+        _ => {}
+    }
 
-    // Serialize agent info if available.
-    let agent_info_json = init_response.agent_info.as_ref().map(|info| {
-        serde_json::json!({
-            "name": info.name,
-            "version": info.version,
-            "title": info.title,
-        })
-        .to_string()
+    let mut capabilities = Capabilities::from_acp(&init_response.agent_capabilities, |problem| {
+        log.warning(problem)
     });
+    capabilities.protocol_version = negotiated.to_string();
 
-    let _ = caps_tx.send(Ok((capabilities, agent_info_json)));
+    let agent_info = init_response.agent_info.as_ref().map(AgentInfo::from_acp);
+
+    tracing::debug!(protocol_version = %negotiated, "initialize handshake complete");
+    let _ = caps_tx.send(Ok((capabilities, agent_info, cx.clone())));
+
+    if let Some((heartbeat, dead_tx)) = heartbeat {
+        tokio::spawn(run_heartbeat(cx.clone(), heartbeat, dead_tx, log.clone()));
+    }
 
     // ---- Command loop ----
-    while let Some(cmd) = cmd_rx.recv().await {
+    while let Some(cmd) = commands.next().await {
+        tracing::debug!(method = cmd.method(), "running command");
         match cmd {
             AcpCommand::NewSession {
                 cwd,
                 meta_json,
-                mcp_servers_json,
+                mcp_servers,
                 reply,
             } => {
                 let mut req = NewSessionRequest::new(PathBuf::from(&cwd));
 
-                // Apply _meta if provided.
+                // Apply _meta if provided; it must be a JSON object.
                 if let Some(ref meta_str) = meta_json {
-                    if let Ok(meta) =
-                        serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(
-                            meta_str,
-                        )
-                    {
-                        req = req.meta(meta);
+                    match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(
+                        meta_str,
+                    ) {
+                        Ok(meta) => req = req.meta(meta),
+                        Err(e) => {
+                            let msg = format!("invalid meta_json: {e}");
+                            let _ = reply.send(Err(ConduitError::Protocol(msg)));
+                            continue;
+                        }
                     }
                 }
 
-                // Apply MCP servers if provided.
-                if let Some(ref servers_str) = mcp_servers_json {
-                    // McpServer implements Deserialize via serde, try direct deser
-                    if let Ok(servers) =
-                        serde_json::from_str::<Vec<sacp::schema::McpServer>>(servers_str)
-                    {
-                        req = req.mcp_servers(servers);
-                    }
-                }
+                req = req.mcp_servers(mcp_servers);
 
                 let result = cx.send_request(req).block_task().await;
                 match result {
                     Ok(resp) => {
-                        let _ = reply.send(Ok(resp.session_id.0.to_string()));
+                        let sid = resp.session_id.0.to_string();
+                        tracing::debug!(session_id = %sid, "session created");
+                        record_cwd(&sessions.cwds, &sid, &cwd);
+                        let _ = reply.send(Ok(sid));
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e)));
                     }
                 }
             }
@@ -1287,10 +4107,12 @@ async fn acp_task(
                     .await;
                 match result {
                     Ok(_resp) => {
+                        tracing::debug!(session_id = %sid, "session loaded");
+                        record_cwd(&sessions.cwds, &sid, &cwd);
                         let _ = reply.send(Ok(sid));
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e).in_session(&sid)));
                     }
                 }
             }
@@ -1299,6 +4121,7 @@ async fn acp_task(
                 mode_id,
                 reply,
             } => {
+                let sid = session_id.clone();
                 let result = cx
                     .send_request(SetSessionModeRequest::new(session_id, mode_id))
                     .block_task()
@@ -1308,7 +4131,7 @@ async fn acp_task(
                         let _ = reply.send(Ok(()));
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e).in_session(&sid)));
                     }
                 }
             }
@@ -1333,19 +4156,16 @@ async fn acp_task(
                                 let _ = reply.send(Ok(json));
                             }
                             Err(e) => {
-                                let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                                let error = ConduitError::from(&e).in_session(&session_id);
+                                let _ = reply.send(Err(error));
                             }
                         }
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e)));
                     }
                 }
             }
-            AcpCommand::Cancel { session_id } => {
-                // CancelNotification is a fire-and-forget notification.
-                let _ = cx.send_notification(CancelNotification::new(session_id));
-            }
             AcpCommand::ForkSession {
                 session_id,
                 cwd,
@@ -1364,15 +4184,39 @@ async fn acp_task(
                                     .and_then(|v| v.as_str())
                                     .unwrap_or("")
                                     .to_string();
+                                record_cwd(&sessions.cwds, &sid, &cwd);
                                 let _ = reply.send(Ok(sid));
                             }
                             Err(e) => {
-                                let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                                let error = ConduitError::from(&e).in_session(&session_id);
+                                let _ = reply.send(Err(error));
                             }
                         }
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e)));
+                    }
+                }
+            }
+            AcpCommand::AddMcpServer {
+                session_id,
+                server,
+                reply,
+            } => {
+                let params = serde_json::json!({
+                    "sessionId": session_id,
+                    "mcpServer": server,
+                });
+                match UntypedMessage::new(ADD_MCP_SERVER_METHOD, &params) {
+                    Ok(msg) => {
+                        let result = cx.send_request(msg).block_task().await;
+                        let result = result
+                            .map(|_| ())
+                            .map_err(|e| ConduitError::from(&e).in_session(&session_id));
+                        let _ = reply.send(result);
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(ConduitError::from(&e)));
                     }
                 }
             }
@@ -1391,12 +4235,39 @@ async fn acp_task(
                                 let _ = reply.send(Ok(json));
                             }
                             Err(e) => {
-                                let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                                let _ = reply.send(Err(ConduitError::from(&e)));
                             }
                         }
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e)));
+                    }
+                }
+            }
+            AcpCommand::Raw {
+                method,
+                params,
+                reply,
+            } => match UntypedMessage::new(&method, &params) {
+                Ok(msg) => {
+                    let result = cx.send_request(msg).block_task().await;
+                    let _ = reply.send(match result {
+                        Ok(val) => serde_json::to_string(&val)
+                            .map_err(|e| ConduitError::Protocol(e.to_string())),
+                        Err(e) => Err(ConduitError::from(&e)),
+                    });
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(ConduitError::from(&e)));
+                }
+            },
+            AcpCommand::RawNotify { method, params } => {
+                match UntypedMessage::new(&method, &params) {
+                    Ok(msg) => {
+                        let _ = cx.send_notification(msg);
+                    }
+                    Err(e) => {
+                        log.error(format!("failed to build {method} notification: {e}"));
                     }
                 }
             }
@@ -1414,16 +4285,19 @@ async fn acp_task(
                     Ok(msg) => {
                         let result = cx.send_request(msg).block_task().await;
                         match result {
-                            Ok(_) => {
-                                let _ = reply.send(Ok(sid));
+                            Ok(val) => {
+                                let result = ResumeResult::from_response(sid, &val);
+                                record_cwd(&sessions.cwds, &result.session_id, &cwd);
+                                let _ = reply.send(Ok(result));
                             }
                             Err(e) => {
-                                let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                                let error = ConduitError::from(&e).in_session(&session_id);
+                                let _ = reply.send(Err(error));
                             }
                         }
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e)));
                     }
                 }
             }
@@ -1431,18 +4305,47 @@ async fn acp_task(
                 session_id,
                 text,
                 content_json,
+                mut meta,
+                request_id,
+                turn_id,
+                running: _running,
                 reply,
             } => {
+                // Events from here on in this session belong to this turn.
+                let turn = TurnStamp {
+                    request_id: Some(request_id.clone()),
+                    turn_id: Some(turn_id),
+                };
+                sessions
+                    .turns
+                    .lock()
+                    .unwrap()
+                    .insert(session_id.clone(), turn);
+                let update_tx = update_tx.in_session(&sessions.turns, &session_id);
                 // Build content blocks: use rich content JSON if provided,
                 // otherwise wrap the text string as a single Text block.
                 let content_blocks: Vec<sacp::schema::ContentBlock> = match content_json {
-                    Some(json_str) => {
-                        serde_json::from_str(&json_str).unwrap_or_else(|_| vec![text.into()])
-                    }
+                    Some(json_str) => match serde_json::from_str(&json_str) {
+                        Ok(blocks) => blocks,
+                        Err(e) => {
+                            // End the turn's stream so its collector stops
+                            // waiting, then fail it.
+                            let _ = update_tx
+                                .send(StreamEvent::Done { stop_reason: None })
+                                .await;
+                            let msg = format!("invalid content_json: {e}");
+                            let _ = reply.send(Err(ConduitError::Protocol(msg)));
+                            continue;
+                        }
+                    },
                     None => vec![text.into()],
                 };
+                // sacp keeps its JSON-RPC ids to itself, so the turn's own
+                // request ID travels in `_meta` for agent-side correlation.
+                meta.insert("requestId".into(), request_id.clone().into());
+                let sid = session_id.clone();
                 let result = cx
-                    .send_request(PromptRequest::new(session_id, content_blocks))
+                    .send_request(PromptRequest::new(session_id, content_blocks).meta(meta))
                     .block_task()
                     .await;
                 // Yield to the runtime to let any in-flight notification
@@ -1452,6 +4355,15 @@ async fn acp_task(
                     tokio::task::yield_now().await;
                 }
 
+                // The agent won't report the tool calls of a cancelled turn
+                // as finished, so stop tracking them here.
+                let cancelled = result
+                    .as_ref()
+                    .is_ok_and(|resp| matches!(resp.stop_reason, StopReason::Cancelled));
+                if cancelled {
+                    sessions.tool_calls.lock().unwrap().remove(&sid);
+                }
+
                 // Extract stop_reason from the response.
                 let stop_reason = match &result {
                     Ok(resp) => Some(format!("{:?}", resp.stop_reason)),
@@ -1459,6 +4371,7 @@ async fn acp_task(
                 };
 
                 // Signal prompt completion so the collector loop exits.
+                tracing::debug!(%request_id, ?stop_reason, "prompt replied; ending its stream");
                 let _ = update_tx
                     .send(StreamEvent::Done { stop_reason })
                     .await;
@@ -1468,7 +4381,7 @@ async fn acp_task(
                         let _ = reply.send(Ok(()));
                     }
                     Err(e) => {
-                        let _ = reply.send(Err(ConduitError::Protocol(e.to_string())));
+                        let _ = reply.send(Err(ConduitError::from(&e).in_session(&sid)));
                     }
                 }
             }
@@ -1479,36 +4392,310 @@ async fn acp_task(
     Ok(())
 }
 
+/// Ping the agent every `heartbeat.interval` until the connection ends,
+/// recording each round trip. Fires `dead_tx` once
+/// [`HEARTBEAT_MAX_MISSES`] pings in a row go unanswered.
+async fn run_heartbeat(
+    cx: sacp::JrConnectionCx,
+    heartbeat: Heartbeat,
+    mut dead_tx: oneshot::Sender<()>,
+    log: LogSink,
+) {
+    let mut misses = 0;
+    loop {
+        let beat = async {
+            tokio::time::sleep(heartbeat.interval).await;
+            let ping = UntypedMessage::new(HEARTBEAT_METHOD, serde_json::json!({}))?;
+            let started = Instant::now();
+            let answer = cx.send_request(ping).block_task();
+            let answered = tokio::time::timeout(heartbeat.interval, answer).await;
+            Ok::<_, sacp::Error>(answered.is_ok().then(|| started.elapsed()))
+        };
+        // The serve task drops the receiver when the connection ends.
+        let beat = tokio::select! {
+            _ = dead_tx.closed() => return,
+            beat = beat => beat,
+        };
+        match beat {
+            Ok(Some(round_trip)) => {
+                tracing::trace!(?round_trip, "heartbeat answered");
+                misses = 0;
+                let micros = u64::try_from(round_trip.as_micros()).unwrap_or(NO_LATENCY - 1);
+                heartbeat.latency.store(micros, Ordering::Release);
+            }
+            Ok(None) => {
+                misses += 1;
+                log.warning(format!(
+                    "agent missed heartbeat {misses} of {HEARTBEAT_MAX_MISSES}"
+                ));
+                if misses >= HEARTBEAT_MAX_MISSES {
+                    let _ = dead_tx.send(());
+                    return;
+                }
+            }
+            Err(e) => {
+                log.error(format!("could not send heartbeat: {e}"));
+                return;
+            }
+        }
+    }
+}
+
+/// Working directory of each session the client opened, by session ID.
+type SessionCwds = Arc<std::sync::Mutex<HashMap<String, PathBuf>>>;
+
+/// JSON array of the commands each session last advertised, by session ID.
+type SessionCommands = Arc<std::sync::Mutex<HashMap<String, String>>>;
+
+/// Latest rate-limit status of each session and when it arrived, by
+/// session ID; `""` holds notifications that name no session.
+type RateLimits = Arc<std::sync::Mutex<HashMap<String, (Instant, RateLimitInfo)>>>;
+
+/// An extension method's name as sent on the wire. Routing strips the
+/// leading `_` that marks extension methods; this puts it back.
+fn wire_ext_method(method: &str) -> String {
+    if method.starts_with('_') {
+        method.to_string()
+    } else {
+        format!("_{method}")
+    }
+}
+
+/// Whether an extension notification `method` reports a rate limit, e.g.
+/// `_rate_limit` or `_claude/rate_limit_event`.
+fn is_rate_limit_method(method: &str) -> bool {
+    let name = method.rsplit('/').next().unwrap_or(method);
+    matches!(
+        name.trim_start_matches('_'),
+        "rate_limit" | "rate_limit_event" | "rateLimit" | "rateLimitEvent"
+    )
+}
+
+/// Cache an extension notification's rate-limit fields, if it has any.
+fn record_rate_limit(rate_limits: &RateLimits, method: &str, params_json: &str) {
+    let params = serde_json::from_str::<serde_json::Value>(params_json).unwrap_or_default();
+    if let Some(status) = RateLimitInfo::parse(method, &params) {
+        let session_id = params.get("sessionId").and_then(|id| id.as_str()).unwrap_or("");
+        rate_limits
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), (Instant::now(), status));
+    }
+}
+
+/// The most recent status for `session_id` (any session when `None`),
+/// counting only those that arrived at or after `since`.
+fn latest_rate_limit(
+    rate_limits: &RateLimits,
+    session_id: Option<&str>,
+    since: Option<Instant>,
+) -> Option<RateLimitInfo> {
+    let rate_limits = rate_limits.lock().unwrap();
+    rate_limits
+        .iter()
+        .filter(|(key, _)| key.is_empty() || session_id.map_or(true, |id| id == key.as_str()))
+        .filter(|(_, (at, _))| since.map_or(true, |since| *at >= since))
+        .max_by_key(|(_, (at, _))| *at)
+        .map(|(_, (_, status))| status.clone())
+}
+
+/// Latest `{"title", "updated_at"}` of each session, by session ID.
+type SessionInfos = Arc<std::sync::Mutex<HashMap<String, serde_json::Value>>>;
+
+/// The fields a session info update carried, by their cache names.
+/// Absent fields are left out of the serialized update, so a field found
+/// there was sent, possibly as null.
+fn sent_session_info(
+    info: &sacp::schema::SessionInfoUpdate,
+) -> serde_json::Map<String, serde_json::Value> {
+    let wire = serde_json::to_value(info).unwrap_or_default();
+    [("title", "title"), ("updated_at", "updatedAt")]
+        .into_iter()
+        .filter_map(|(field, key)| Some((field.to_string(), wire.get(key)?.clone())))
+        .collect()
+}
+
+/// Merge the fields a session info update carried into the cache. Fields
+/// it left out keep their previous value; an explicit null clears one.
+fn record_session_info(
+    infos: &SessionInfos,
+    session_id: &str,
+    sent: &serde_json::Map<String, serde_json::Value>,
+) {
+    let mut infos = infos.lock().unwrap();
+    let info = infos
+        .entry(session_id.to_string())
+        .or_insert_with(|| serde_json::json!({ "title": null, "updated_at": null }));
+    for field in ["title", "updated_at"] {
+        if let Some(value) = sent.get(field) {
+            info[field] = value.clone();
+        }
+    }
+}
+
+/// Tool calls still running in each session, by session ID, then tool
+/// call ID.
+type ActiveToolCalls = Arc<std::sync::Mutex<HashMap<String, HashMap<String, ActiveToolCall>>>>;
+
+/// A tool call the agent started and hasn't reported finished.
+#[derive(Clone, Debug, serde::Serialize)]
+struct ActiveToolCall {
+    tool_use_id: String,
+    name: String,
+    /// Last reported status, by its ACP wire name (e.g. `"in_progress"`).
+    status: Option<&'static str>,
+    /// When the call was first seen, in seconds since the Unix epoch.
+    started_at: f64,
+}
+
+/// Apply a tool call or tool call update to the cache: a terminal status
+/// drops the call, anything else adds it or updates its name and status.
+fn track_tool_call(
+    calls: &ActiveToolCalls,
+    session_id: &str,
+    tool_use_id: &str,
+    name: Option<&str>,
+    status: Option<ToolStatus>,
+) {
+    let mut calls = calls.lock().unwrap();
+    let session = calls.entry(session_id.to_string()).or_default();
+    if status.is_some_and(ToolStatus::is_terminal) {
+        session.remove(tool_use_id);
+        return;
+    }
+    let call = session
+        .entry(tool_use_id.to_string())
+        .or_insert_with(|| ActiveToolCall {
+            tool_use_id: tool_use_id.to_string(),
+            name: String::new(),
+            status: None,
+            started_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0.0, |since| since.as_secs_f64()),
+        });
+    if let Some(name) = name {
+        call.name = name.to_string();
+    }
+    if let Some(status) = status {
+        call.status = Some(status.wire_name());
+    }
+}
+
+fn record_cwd(session_cwds: &SessionCwds, session_id: &str, cwd: &str) {
+    if !session_id.is_empty() {
+        session_cwds
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), PathBuf::from(cwd));
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Permission callback support
 // ---------------------------------------------------------------------------
 
+/// How permission requests are answered, set by `set_permission_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum PermissionPolicy {
+    /// Ask the Python permission callback; refuse if none is set.
+    #[default]
+    Prompt,
+    /// Approve every request.
+    AllowAll,
+    /// Refuse every request.
+    DenyAll,
+}
+
+/// How permission requests are answered before reaching the callback, set
+/// by `set_tool_rules` and `set_permission_policy`.
+#[derive(Clone, Debug, Default)]
+struct PermissionRules {
+    /// Patterns of tools approved without asking.
+    allow: Vec<String>,
+    /// Patterns of tools refused without asking; checked before `allow`.
+    deny: Vec<String>,
+    /// Applies to tools matching neither list.
+    policy: PermissionPolicy,
+}
+
+impl PermissionRules {
+    /// The decision for `tool_name` if the rules settle it, or `None` to
+    /// ask the callback.
+    fn decide(&self, tool_name: &str) -> Option<PermissionDecision> {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| glob_match(p, tool_name));
+        if matches(&self.deny) {
+            return Some(PermissionDecision::Deny {
+                reason: Some(format!("tool {tool_name} is denied by the tool rules")),
+            });
+        }
+        if matches(&self.allow) {
+            return Some(PermissionDecision::ALLOW);
+        }
+        match self.policy {
+            PermissionPolicy::AllowAll => Some(PermissionDecision::ALLOW),
+            PermissionPolicy::DenyAll => Some(PermissionDecision::Deny {
+                reason: Some("all tool use denied by policy".into()),
+            }),
+            PermissionPolicy::Prompt => None,
+        }
+    }
+}
+
+/// Whether `name` matches `pattern` in full, where `*` in the pattern
+/// matches any run of characters (including none).
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part.
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
 /// Decision from the Python permission callback.
 enum PermissionDecision {
-    Allow,
-    Deny,
+    /// Allow, preferring the given permission option if the agent offered it.
+    Allow { option_id: Option<String> },
+    /// Deny, telling the agent why when a reason was given.
+    Deny { reason: Option<String> },
+}
+
+impl PermissionDecision {
+    const ALLOW: Self = Self::Allow { option_id: None };
 }
 
-/// Call the Python permission callback, if set.
+/// Run the `PreToolUse` hooks, then apply `rules`, asking the Python
+/// permission callback when they don't settle the request.
 ///
-/// Acquires the GIL to invoke the async callback, awaits the resulting
-/// future, and maps the Python `PermissionResult` to a `PermissionDecision`.
-/// Falls back to `Allow` if no callback is set or if the callback errors.
+/// The hooks see (and may rewrite) the tool input after the input
+/// middleware; a hook returning the deny sentinel denies the tool without
+/// consulting the callback. Acquires the GIL to invoke the callback, awaits
+/// the result if it is awaitable, and maps the returned [`PermissionResult`]
+/// (or an equivalent dict) to a `PermissionDecision`. Denies, logging
+/// why, if no callback is set, if it raises or its awaitable fails, or if
+/// it returns anything else.
 async fn call_permission_callback(
+    log: &LogSink,
     callback_arc: &Arc<std::sync::Mutex<Option<PyObject>>>,
+    rules: &PermissionRules,
+    input_middleware: &InputMiddleware,
+    hooks: Option<&HookDispatcherHandle>,
     request: &RequestPermissionRequest,
 ) -> PermissionDecision {
-    // Clone the Python callback under the GIL (if set).
-    let callback = Python::with_gil(|py| {
-        let guard = callback_arc.lock().unwrap();
-        guard.as_ref().map(|cb| cb.clone_ref(py))
-    });
-
-    let callback = match callback {
-        Some(cb) => cb,
-        None => return PermissionDecision::Allow, // No callback = auto-approve.
-    };
-
     // Extract tool details from the ACP request.
     let tool_name = request
         .tool_call
@@ -1516,6 +4703,7 @@ async fn call_permission_callback(
         .title
         .clone()
         .unwrap_or_default();
+    tracing::debug!(%tool_name, "permission requested");
     let tool_input = request
         .tool_call
         .fields
@@ -1523,9 +4711,53 @@ async fn call_permission_callback(
         .as_ref()
         .map(|v| v.to_string())
         .unwrap_or_else(|| "{}".into());
+    // The callback sees the middleware's rewrite (e.g. with secrets redacted).
+    let tool_input = match apply_input_middleware(input_middleware, &tool_name, tool_input.clone())
+        .await
+    {
+        Ok(rewritten) => rewritten,
+        Err(e) => {
+            log.error(format!("tool input middleware failed for {tool_name}: {e}"));
+            tool_input
+        }
+    };
     let tool_use_id = request.tool_call.tool_call_id.0.to_string();
     let session_id = request.session_id.0.to_string();
 
+    let mut tool_input = tool_input;
+    if let Some(hooks) = hooks {
+        match run_pre_tool_use(hooks, &tool_name, tool_input, &tool_use_id, &session_id).await {
+            Ok(rewritten) => tool_input = rewritten,
+            Err(reason) => {
+                let reason = Some(reason).filter(|r| !r.is_empty());
+                return PermissionDecision::Deny { reason };
+            }
+        }
+    }
+
+    if let Some(decision) = rules.decide(&tool_name) {
+        return decision;
+    }
+
+    // Clone the Python callback under the GIL (if set).
+    let callback = Python::with_gil(|py| {
+        let guard = callback_arc.lock().unwrap();
+        guard.as_ref().map(|cb| cb.clone_ref(py))
+    });
+
+    let callback = match callback {
+        Some(cb) => cb,
+        None => {
+            log.warning(format!(
+                "no permission callback set; denying {tool_name} (choose a permission policy \
+                 to allow tools without one)"
+            ));
+            return PermissionDecision::Deny {
+                reason: Some("no permission callback set".into()),
+            };
+        }
+    };
+
     // Call the Python callback: async def callback(tool_name, tool_input, context) -> PermissionResult
     let future_result = Python::with_gil(|py| -> PyResult<_> {
         // Build a ToolPermissionContext-like dict for the context argument.
@@ -1534,35 +4766,99 @@ async fn call_permission_callback(
         ctx.set_item("tool_input", &tool_input)?;
         ctx.set_item("tool_use_id", &tool_use_id)?;
         ctx.set_item("session_id", &session_id)?;
+        let options: Vec<PermissionOption> = request
+            .options
+            .iter()
+            .map(PermissionOption::from_acp)
+            .collect();
+        ctx.set_item("options", options)?;
 
-        let coro = callback.call1(py, (&tool_name, &tool_input, ctx))?;
-        pyo3_async_runtimes::tokio::into_future(coro.into_bound(py))
+        let result = callback.call1(py, (&tool_name, &tool_input, ctx))?;
+        // A plain function (all `connect_blocking()` accepts) answers directly.
+        if !result.bind(py).hasattr("__await__")? {
+            return Ok(Err(result));
+        }
+        pyo3_async_runtimes::tokio::into_future(result.into_bound(py)).map(Ok)
     });
 
-    let future = match future_result {
-        Ok(f) => f,
-        Err(_) => return PermissionDecision::Allow,
+    let failed = |e: PyErr| {
+        log.error(format!(
+            "permission callback for {tool_name} failed: {e}; denying"
+        ));
+        PermissionDecision::Deny {
+            reason: Some("permission callback failed".into()),
+        }
     };
-
-    let py_result = match future.await {
-        Ok(r) => r,
-        Err(_) => return PermissionDecision::Allow,
+    let py_result = match future_result {
+        Ok(Ok(future)) => match future.await {
+            Ok(r) => r,
+            Err(e) => return failed(e),
+        },
+        Ok(Err(answered)) => answered,
+        Err(e) => return failed(e),
     };
 
-    // Check if the result is a PermissionResultDeny (has .reason attribute).
-    // PermissionResultAllow has no .reason, PermissionResultDeny does.
-    let is_deny = Python::with_gil(|py| {
-        py_result
-            .getattr(py, "reason")
-            .map(|r| !r.is_none(py))
-            .unwrap_or(false)
+    let result = Python::with_gil(|py| {
+        let result = py_result.bind(py);
+        result.extract::<PermissionResult>().ok().or_else(|| {
+            PermissionResult::from_dict(result.downcast::<pyo3::types::PyDict>().ok()?)
+        })
     });
 
-    if is_deny {
-        PermissionDecision::Deny
-    } else {
-        PermissionDecision::Allow
+    match result {
+        Some(result) if result.decision == "allow" => PermissionDecision::Allow {
+            option_id: result.option_id,
+        },
+        Some(result) => PermissionDecision::Deny {
+            reason: result.reason.filter(|r| !r.is_empty()),
+        },
+        None => {
+            log.error(format!(
+                "permission callback for {tool_name} returned neither a \
+                 PermissionResult nor a dict; denying"
+            ));
+            PermissionDecision::Deny {
+                reason: Some("permission callback returned no decision".into()),
+            }
+        }
+    }
+}
+
+/// Dispatch `PreToolUse` hooks for a permission request.
+///
+/// Hooks get `{"tool_name", "tool_input", "tool_use_id", "session_id"}`
+/// with `tool_input` as a JSON value. Returns the (possibly rewritten)
+/// tool input as JSON text, or `Err(reason)` if a hook denied the tool or
+/// aborted dispatch.
+async fn run_pre_tool_use(
+    hooks: &HookDispatcherHandle,
+    tool_name: &str,
+    tool_input: String,
+    tool_use_id: &str,
+    session_id: &str,
+) -> Result<String, String> {
+    let input: serde_json::Value =
+        serde_json::from_str(&tool_input).unwrap_or(serde_json::Value::String(tool_input));
+    let context = serde_json::json!({
+        "tool_name": tool_name,
+        "tool_input": input,
+        "tool_use_id": tool_use_id,
+        "session_id": session_id,
+    });
+    // A hook that fails on a fail-fast dispatcher denies the tool.
+    let context = hooks
+        .dispatch(HookType::PreToolUse, context.to_string())
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(reason) = deny_reason(&context) {
+        return Err(reason);
     }
+
+    let context: serde_json::Value = serde_json::from_str(&context).unwrap_or_default();
+    Ok(match &context["tool_input"] {
+        serde_json::Value::Null => input.to_string(),
+        rewritten => rewritten.to_string(),
+    })
 }
 
 /// Register client types on the Python module.
@@ -10,12 +10,19 @@
 //! ```
 
 use crate::error::ConduitError;
+use crate::log::LogSink;
+use crate::types::{PermissionOption, PermissionResult};
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_async_runtimes::TaskLocals;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::{mpsc, Mutex, Notify};
+use std::time::{Duration, Instant};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
 
 // ---------------------------------------------------------------------------
 // Wire types
@@ -44,6 +51,14 @@ impl ControlMessage {
         }
     }
 
+    /// Like the constructor, but raises `ProtocolError` unless `data` is
+    /// valid JSON.
+    #[staticmethod]
+    fn try_new(request_id: String, subtype: String, data: String) -> PyResult<Self> {
+        parse_data(&data)?;
+        Ok(Self::new(request_id, subtype, data))
+    }
+
     fn __repr__(&self) -> String {
         format!(
             "ControlMessage(id={:?}, subtype={:?})",
@@ -83,11 +98,23 @@ impl ControlResponse {
     }
 }
 
+/// How messages are delimited on the agent's stdin/stdout.
+#[pyclass(eq, eq_int)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// One JSON message per line (NDJSON).
+    LineDelimited,
+    /// LSP-style: `Content-Length: <bytes>` and any other headers, a blank
+    /// line, then exactly that many bytes of JSON, which may span lines.
+    ContentLength,
+}
+
 // ---------------------------------------------------------------------------
 // Internal protocol state
 // ---------------------------------------------------------------------------
 
-/// Classifies a raw JSON line from agent stdout.
+/// Classifies a raw JSON line from agent stdout. Also what the read loop
+/// forwards to `recv_message()`, so Python never has to reclassify it.
 #[derive(Debug)]
 enum AgentOutput {
     /// A control request from the agent (needs a response).
@@ -96,6 +123,17 @@ enum AgentOutput {
     ConversationMessage(String),
 }
 
+/// An item queued for the write loop.
+enum Outbound {
+    /// A message to frame and write to the agent.
+    Message(String),
+    /// Answered once everything queued before it has been written.
+    Flush(oneshot::Sender<()>),
+}
+
+/// How long `stop()` lets queued messages drain before aborting the loops.
+const STOP_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Pending response slot for client-initiated control requests.
 struct PendingRequest {
     notify: Arc<Notify>,
@@ -105,15 +143,74 @@ struct PendingRequest {
 /// Internal state for the control protocol.
 struct ProtocolInner {
     /// Writer to agent stdin.
-    stdin_tx: Option<mpsc::Sender<String>>,
-    /// Channel for conversation messages forwarded from the read loop.
-    conversation_rx: Option<mpsc::Receiver<String>>,
+    stdin_tx: Option<mpsc::Sender<Outbound>>,
     /// Pending client-initiated requests awaiting responses.
     pending: HashMap<String, PendingRequest>,
     /// Auto-incrementing counter for generating request IDs.
     next_id: u64,
     /// Whether the protocol is running.
     running: bool,
+    /// When the last line was read from the agent.
+    last_activity: Instant,
+    /// Stream settings from `start()`, reused by `restart()`.
+    options: StreamOptions,
+}
+
+/// Default cap on a single message read from the agent.
+const DEFAULT_MAX_LINE_BYTES: usize = 4 * 1024 * 1024;
+
+/// How the loops talk to the agent, as configured by `start()`.
+#[derive(Clone, Copy)]
+struct StreamOptions {
+    /// Idle interval after which a keep-alive `ping` is sent.
+    keepalive: Option<Duration>,
+    /// Message delimiting in both directions.
+    framing: Framing,
+    /// Longest message accepted from the agent; longer ones are dropped.
+    max_line_bytes: usize,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            keepalive: None,
+            framing: Framing::LineDelimited,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+        }
+    }
+}
+
+/// Removes an SDK-initiated request's `pending` entry when dropped, so
+/// send failures, timeouts and cancelled callers cannot leak it.
+struct PendingGuard {
+    inner: Arc<Mutex<ProtocolInner>>,
+    request_id: String,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        let request_id = std::mem::take(&mut self.request_id);
+        if let Ok(mut guard) = self.inner.try_lock() {
+            guard.pending.remove(&request_id);
+        } else if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let inner = self.inner.clone();
+            runtime.spawn(async move {
+                inner.lock().await.pending.remove(&request_id);
+            });
+        }
+    }
+}
+
+impl ProtocolInner {
+    /// Mark the protocol stopped and wake every pending request. Their
+    /// response slots stay empty, which waiters report as a stopped protocol.
+    fn fail_pending(&mut self) {
+        self.running = false;
+        self.stdin_tx = None;
+        for (_, pending) in self.pending.drain() {
+            pending.notify.notify_one();
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -128,18 +225,21 @@ struct ProtocolInner {
 #[pyclass]
 pub struct RustControlProtocol {
     inner: Arc<Mutex<ProtocolInner>>,
-    /// Python callback for permission checks.
-    permission_callback: Arc<Mutex<Option<PyObject>>>,
-    /// Python callback for hook dispatch.
-    hook_callback: Arc<Mutex<Option<PyObject>>>,
-    /// Python callback for MCP tool requests.
-    mcp_callback: Arc<Mutex<Option<PyObject>>>,
+    /// Python callbacks answering the agent's control requests.
+    callbacks: Callbacks,
     /// Channel sender for conversation messages (used by read loop).
-    conversation_tx: Arc<Mutex<Option<mpsc::Sender<String>>>>,
+    conversation_tx: Arc<Mutex<Option<mpsc::Sender<AgentOutput>>>>,
+    /// Conversation messages forwarded from the read loop. Separated from
+    /// `inner` so recv_message() can wait without blocking the read loop.
+    conversation_rx: Arc<Mutex<Option<mpsc::Receiver<AgentOutput>>>>,
     /// Handle to the background read task.
     read_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     /// Handle to the background write task.
     write_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Handle to the idle keep-alive task, if enabled.
+    keepalive_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Where the loops report dropped frames and failed callbacks.
+    log: LogSink,
 }
 
 #[pymethods]
@@ -149,137 +249,136 @@ impl RustControlProtocol {
         Self {
             inner: Arc::new(Mutex::new(ProtocolInner {
                 stdin_tx: None,
-                conversation_rx: None,
                 pending: HashMap::new(),
                 next_id: 1,
                 running: false,
+                last_activity: Instant::now(),
+                options: StreamOptions::default(),
             })),
-            permission_callback: Arc::new(Mutex::new(None)),
-            hook_callback: Arc::new(Mutex::new(None)),
-            mcp_callback: Arc::new(Mutex::new(None)),
+            callbacks: Callbacks::default(),
             conversation_tx: Arc::new(Mutex::new(None)),
+            conversation_rx: Arc::new(Mutex::new(None)),
             read_task: Arc::new(Mutex::new(None)),
             write_task: Arc::new(Mutex::new(None)),
+            keepalive_task: Arc::new(Mutex::new(None)),
+            log: LogSink::default(),
         }
     }
 
     /// Start the control protocol read/write loops.
     ///
-    /// Takes ownership of the agent's stdin and stdout streams.
+    /// `stdin_fd` and `stdout_fd` are the agent's stdin (write end) and
+    /// stdout (read end). They are duplicated before this returns, so the
+    /// caller keeps ownership of the FDs it passed and should close them
+    /// when it no longer needs them; the loops close their own copies on
+    /// `stop()` or `restart()`.
+    ///
+    /// When `keepalive_secs` is set, a `ping` control request is sent
+    /// whenever the agent has been silent that long; if it goes unanswered
+    /// within the same interval the protocol is marked stopped and all
+    /// pending requests fail.
+    ///
+    /// `framing` selects how messages are delimited in both directions.
+    /// A message from the agent longer than `max_line_bytes` (4 MiB by
+    /// default) is skipped without being buffered whole, as is one that
+    /// is not UTF-8; either way a `{"type": "error", "message": ...}`
+    /// diagnostic takes its place in `recv_message()`.
+    #[pyo3(signature = (
+        stdin_fd,
+        stdout_fd,
+        keepalive_secs=None,
+        framing=Framing::LineDelimited,
+        max_line_bytes=DEFAULT_MAX_LINE_BYTES,
+    ))]
     fn start<'py>(
         &self,
         py: Python<'py>,
         stdin_fd: i64,
         stdout_fd: i64,
+        keepalive_secs: Option<f64>,
+        framing: Framing,
+        max_line_bytes: usize,
     ) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-        let _permission_cb = self.permission_callback.clone();
-        let _hook_cb = self.hook_callback.clone();
-        let _mcp_cb = self.mcp_callback.clone();
-        let conv_tx_holder = self.conversation_tx.clone();
-        let read_task_holder = self.read_task.clone();
-        let write_task_holder = self.write_task.clone();
-
-        pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(256);
-            let (conv_tx, conv_rx) = mpsc::channel::<String>(256);
-
-            {
-                let mut guard = inner.lock().await;
-                guard.stdin_tx = Some(stdin_tx);
-                guard.conversation_rx = Some(conv_rx);
-                guard.running = true;
+        let loops = self.loops();
+        let locals = pyo3_async_runtimes::tokio::get_current_locals(py).ok();
+        let keepalive = match keepalive_secs {
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(secs) => {
+                return Err(ConduitError::Protocol(format!(
+                    "keepalive_secs must be positive, got {secs}"
+                ))
+                .into())
             }
+            None => None,
+        };
+        if max_line_bytes == 0 {
+            return Err(ConduitError::Protocol("max_line_bytes must be positive".into()).into());
+        }
+        let options = StreamOptions {
+            keepalive,
+            framing,
+            max_line_bytes,
+        };
+        let stdin = dup_stream(stdin_fd, "stdin_fd")?;
+        let stdout = dup_stream(stdout_fd, "stdout_fd")?;
 
-            *conv_tx_holder.lock().await = Some(conv_tx.clone());
-
-            // Background write loop: sends messages to agent stdin.
-            let write_handle = tokio::spawn(async move {
-                #[cfg(unix)]
-                use std::os::fd::FromRawFd;
-                #[cfg(windows)]
-                use std::os::windows::io::FromRawHandle;
-                // Safety: we trust the caller provides valid FDs/handles from the child process.
-                #[cfg(unix)]
-                let stdin_file = unsafe { std::fs::File::from_raw_fd(stdin_fd as i32) };
-                #[cfg(windows)]
-                let stdin_file = unsafe { std::fs::File::from_raw_handle(stdin_fd as *mut std::ffi::c_void) };
-                let mut stdin = tokio::io::BufWriter::new(tokio::fs::File::from_std(stdin_file));
-
-                while let Some(line) = stdin_rx.recv().await {
-                    let data = format!("{}\n", line);
-                    if stdin.write_all(data.as_bytes()).await.is_err() {
-                        break;
-                    }
-                    if stdin.flush().await.is_err() {
-                        break;
-                    }
-                }
-            });
-
-            // Background read loop: reads JSON lines from agent stdout.
-            let inner_read = inner.clone();
-            let read_handle = tokio::spawn(async move {
-                #[cfg(unix)]
-                use std::os::fd::FromRawFd;
-                #[cfg(windows)]
-                use std::os::windows::io::FromRawHandle;
-                #[cfg(unix)]
-                let stdout_file = unsafe { std::fs::File::from_raw_fd(stdout_fd as i32) };
-                #[cfg(windows)]
-                let stdout_file = unsafe { std::fs::File::from_raw_handle(stdout_fd as *mut std::ffi::c_void) };
-                let stdout = tokio::fs::File::from_std(stdout_file);
-                let mut reader = BufReader::new(stdout).lines();
-
-                while let Ok(Some(line)) = reader.next_line().await {
-                    let line = line.trim().to_string();
-                    if line.is_empty() {
-                        continue;
-                    }
-
-                    match classify_message(&line) {
-                        AgentOutput::ControlRequest(msg) => {
-                            // Check if this is a response to a pending request.
-                            let mut guard = inner_read.lock().await;
-                            if let Some(pending) = guard.pending.remove(&msg.request_id) {
-                                *pending.response.lock().await = Some(msg.data.clone());
-                                pending.notify.notify_one();
-                            }
-                            // Otherwise, dispatch to the appropriate callback.
-                            // The Python layer handles this via Query.
-                            drop(guard);
-
-                            // Forward control requests as conversation messages
-                            // so the Python layer can process them.
-                            let _ = conv_tx.send(line).await;
-                        }
-                        AgentOutput::ConversationMessage(raw) => {
-                            let _ = conv_tx.send(raw).await;
-                        }
-                    }
-                }
-            });
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            loops.inner.lock().await.options = options;
+            loops.spawn(stdin, stdout, options, locals).await;
+            Ok(())
+        })
+    }
 
-            *read_task_holder.lock().await = Some(read_handle);
-            *write_task_holder.lock().await = Some(write_handle);
+    /// Replace the read/write loops with fresh ones on new FDs, e.g. after
+    /// the agent process was restarted.
+    ///
+    /// Requests still pending on the old FDs fail as if the protocol had
+    /// stopped. Registered callbacks and the stream settings given to
+    /// `start()` carry over. The new FDs are duplicated as in `start()`.
+    fn restart<'py>(
+        &self,
+        py: Python<'py>,
+        stdin_fd: i64,
+        stdout_fd: i64,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let loops = self.loops();
+        let locals = pyo3_async_runtimes::tokio::get_current_locals(py).ok();
+        let stdin = dup_stream(stdin_fd, "stdin_fd")?;
+        let stdout = dup_stream(stdout_fd, "stdout_fd")?;
 
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let options = {
+                let mut guard = loops.inner.lock().await;
+                guard.fail_pending();
+                guard.options
+            };
+            loops.teardown().await;
+            loops.spawn(stdin, stdout, options, locals).await;
             Ok(())
         })
     }
 
     /// Send a control request from the SDK to the agent.
     ///
-    /// Returns the JSON response data from the agent.
+    /// `data` must be a JSON document; anything else raises `ProtocolError`
+    /// before the request is sent. Waits up to `timeout_secs` (default 30;
+    /// `0` waits indefinitely) for the agent to answer, then raises
+    /// `TimeoutError` and forgets the request. Returns the JSON response
+    /// data from the agent.
+    #[pyo3(signature = (subtype, data, timeout_secs=30.0))]
     fn send_control_request<'py>(
         &self,
         py: Python<'py>,
         subtype: String,
         data: String,
+        timeout_secs: f64,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
+        let timeout = parse_timeout(timeout_secs)?;
+        let data = parse_data(&data)?;
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let (request_id, stdin_tx) = {
+            let (request_id, stdin_tx, notify, response) = {
                 let mut guard = inner.lock().await;
                 let id = format!("sdk_{}", guard.next_id);
                 guard.next_id += 1;
@@ -299,48 +398,54 @@ impl RustControlProtocol {
                     },
                 );
 
-                (id, tx)
+                (id, tx, notify, response)
+            };
+            // However this call ends, its entry must not outlive it.
+            let _pending = PendingGuard {
+                inner: inner.clone(),
+                request_id: request_id.clone(),
             };
 
             let msg = serde_json::json!({
                 "type": "control",
                 "request_id": request_id,
                 "subtype": subtype,
-                "data": serde_json::from_str::<serde_json::Value>(&data)
-                    .unwrap_or(serde_json::Value::String(data.clone())),
+                "data": data,
             });
 
             stdin_tx
-                .send(msg.to_string())
+                .send(Outbound::Message(msg.to_string()))
                 .await
                 .map_err(|_| ConduitError::Protocol("failed to send control request".into()))?;
 
             // Wait for the response (with a timeout).
-            let guard = inner.lock().await;
-            if let Some(pending) = guard.pending.get(&request_id) {
-                let notify = pending.notify.clone();
-                let response = pending.response.clone();
-                drop(guard);
-
-                tokio::time::timeout(std::time::Duration::from_secs(30), notify.notified())
-                    .await
-                    .map_err(|_| {
-                        ConduitError::Timeout(format!(
-                            "control request {:?} timed out",
-                            request_id
+            match timeout {
+                Some(timeout) => {
+                    if tokio::time::timeout(timeout, notify.notified())
+                        .await
+                        .is_err()
+                    {
+                        return Err(ConduitError::Timeout(format!(
+                            "control request {request_id:?} timed out after {timeout:?}"
                         ))
-                    })?;
-
-                let resp = response.lock().await.take().unwrap_or_default();
-                Ok(resp)
-            } else {
-                drop(guard);
-                Err(ConduitError::Protocol("pending request lost".into()).into())
+                        .into());
+                    }
+                }
+                None => notify.notified().await,
             }
+
+            let resp = response
+                .lock()
+                .await
+                .take()
+                .ok_or_else(|| ConduitError::Connection("control protocol stopped".into()))?;
+            Ok(resp)
         })
     }
 
     /// Send a control response from the SDK back to the agent.
+    ///
+    /// `data` must be a JSON document; anything else raises `ProtocolError`.
     fn send_control_response<'py>(
         &self,
         py: Python<'py>,
@@ -349,6 +454,7 @@ impl RustControlProtocol {
         data: String,
     ) -> PyResult<Bound<'py, PyAny>> {
         let inner = self.inner.clone();
+        let data = parse_data(&data)?;
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let stdin_tx = {
@@ -363,12 +469,11 @@ impl RustControlProtocol {
                 "type": "control_response",
                 "request_id": request_id,
                 "subtype": subtype,
-                "data": serde_json::from_str::<serde_json::Value>(&data)
-                    .unwrap_or(serde_json::Value::String(data.clone())),
+                "data": data,
             });
 
             stdin_tx
-                .send(msg.to_string())
+                .send(Outbound::Message(msg.to_string()))
                 .await
                 .map_err(|_| ConduitError::Protocol("failed to send control response".into()))?;
 
@@ -378,54 +483,70 @@ impl RustControlProtocol {
 
     /// Receive the next message from the conversation channel.
     ///
-    /// Returns ``None`` if the channel is closed.
+    /// Control requests not answered by a registered callback come back as
+    /// a `ControlMessage`; everything else as the raw JSON string. Returns
+    /// ``None`` if the channel is closed.
     fn recv_message<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
+        let conversation_rx = self.conversation_rx.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let mut guard = inner.lock().await;
-            if let Some(ref mut rx) = guard.conversation_rx {
-                let msg = rx.recv().await;
-                Ok(msg)
-            } else {
-                Ok(None)
-            }
+            let mut guard = conversation_rx.lock().await;
+            let msg = match *guard {
+                Some(ref mut rx) => rx.recv().await,
+                None => None,
+            };
+            Python::with_gil(|py| -> PyResult<PyObject> {
+                Ok(match msg {
+                    Some(AgentOutput::ControlRequest(msg)) => Py::new(py, msg)?.into_any(),
+                    Some(AgentOutput::ConversationMessage(raw)) => {
+                        raw.into_pyobject(py)?.into_any().unbind()
+                    }
+                    None => py.None(),
+                })
+            })
         })
     }
 
     /// Register the permission check callback.
+    ///
+    /// Called as `callback(tool_name, tool_input_json, context)` for each
+    /// `can_use_tool` request; may be async. Its `PermissionResult` (or an
+    /// equivalent dict) is sent back as `{"decision", "reason"}`, plus
+    /// `option_id` when an allow names one.
     fn set_permission_callback(&self, callback: PyObject) {
-        // Block briefly to set the callback. This is called during setup,
-        // not in the hot path.
-        let cb = self.permission_callback.clone();
-        tokio::task::block_in_place(|| {
-            let rt = tokio::runtime::Handle::current();
-            rt.block_on(async {
-                *cb.lock().await = Some(callback);
-            });
-        });
+        *self.callbacks.permission.lock().unwrap() = Some(callback);
     }
 
     /// Register the hook dispatch callback.
+    ///
+    /// Called with the decoded `data` of each `hook_callback` request; may
+    /// be async. Its return value (`{}` for `None`) is the response data.
     fn set_hook_callback(&self, callback: PyObject) {
-        let cb = self.hook_callback.clone();
-        tokio::task::block_in_place(|| {
-            let rt = tokio::runtime::Handle::current();
-            rt.block_on(async {
-                *cb.lock().await = Some(callback);
-            });
-        });
+        *self.callbacks.hook.lock().unwrap() = Some(callback);
     }
 
     /// Register the MCP tool request callback.
+    ///
+    /// Called like the hook callback, for each `mcp_message` request.
     fn set_mcp_callback(&self, callback: PyObject) {
-        let cb = self.mcp_callback.clone();
-        tokio::task::block_in_place(|| {
-            let rt = tokio::runtime::Handle::current();
-            rt.block_on(async {
-                *cb.lock().await = Some(callback);
-            });
-        });
+        *self.callbacks.mcp.lock().unwrap() = Some(callback);
+    }
+
+    /// Route the protocol's internal log (dropped messages, failed
+    /// callbacks, unanswered keep-alives) to `callback(level, message)`, or
+    /// buffer it again with `None`. Works like
+    /// `RustClient.set_log_callback()`.
+    #[pyo3(signature = (callback=None))]
+    fn set_log_callback(&self, py: Python<'_>, callback: Option<PyObject>) {
+        self.log.set_callback(py, callback);
+    }
+
+    /// Number of SDK-initiated requests still awaiting a response.
+    fn pending_count<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            Ok(inner.lock().await.pending.len())
+        })
     }
 
     /// Whether the protocol is currently running.
@@ -436,29 +557,229 @@ impl RustControlProtocol {
         })
     }
 
+    /// Stop accepting sends and wait for the messages already queued to be
+    /// written to the agent and flushed.
+    ///
+    /// Waits up to `timeout_secs` (default 5; `0` waits indefinitely).
+    /// Returns whether everything was written in time; `False` also if the
+    /// write loop has died. Sends made after this fail as if the protocol
+    /// were not started, though the read loop keeps running until `stop()`.
+    #[pyo3(signature = (timeout_secs=5.0))]
+    fn flush<'py>(&self, py: Python<'py>, timeout_secs: f64) -> PyResult<Bound<'py, PyAny>> {
+        let loops = self.loops();
+        let timeout = parse_timeout(timeout_secs)?;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let drained = loops.flush(timeout).await;
+            Ok(drained)
+        })
+    }
+
     /// Shut down the control protocol read/write loops.
+    ///
+    /// Messages still queued get a short `flush()` first; requests still
+    /// waiting on a reply then raise `ConnectionError`.
     fn stop<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
-        let inner = self.inner.clone();
-        let read_task = self.read_task.clone();
-        let write_task = self.write_task.clone();
+        let loops = self.loops();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            {
-                let mut guard = inner.lock().await;
-                guard.running = false;
-                guard.stdin_tx = None; // Dropping sender closes the write loop.
-            }
+            loops.flush(Some(STOP_FLUSH_TIMEOUT)).await;
+            loops.inner.lock().await.fail_pending();
+
+            loops.abort().await;
+            Ok(())
+        })
+    }
+}
 
-            // Abort the background tasks.
-            if let Some(handle) = read_task.lock().await.take() {
-                handle.abort();
+impl RustControlProtocol {
+    fn loops(&self) -> Loops {
+        Loops {
+            inner: self.inner.clone(),
+            callbacks: self.callbacks.clone(),
+            conversation_tx: self.conversation_tx.clone(),
+            conversation_rx: self.conversation_rx.clone(),
+            read_task: self.read_task.clone(),
+            write_task: self.write_task.clone(),
+            keepalive_task: self.keepalive_task.clone(),
+            log: self.log.clone(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Background loops
+// ---------------------------------------------------------------------------
+
+/// The protocol state the background loops share, cloned out of
+/// `RustControlProtocol` so `start`/`restart`/`stop` can move it into
+/// their futures.
+struct Loops {
+    inner: Arc<Mutex<ProtocolInner>>,
+    callbacks: Callbacks,
+    conversation_tx: Arc<Mutex<Option<mpsc::Sender<AgentOutput>>>>,
+    conversation_rx: Arc<Mutex<Option<mpsc::Receiver<AgentOutput>>>>,
+    read_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    write_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    keepalive_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    log: LogSink,
+}
+
+impl Loops {
+    /// Spawn the read/write loops (and keep-alive, if set) on the agent's
+    /// stdin and stdout, which they own from here on.
+    ///
+    /// `locals` is the event loop async callbacks are awaited on.
+    async fn spawn(
+        &self,
+        stdin: std::fs::File,
+        stdout: std::fs::File,
+        options: StreamOptions,
+        locals: Option<TaskLocals>,
+    ) {
+        let framing = options.framing;
+        let (stdin_tx, mut stdin_rx) = mpsc::channel::<Outbound>(256);
+        let (conv_tx, conv_rx) = mpsc::channel::<AgentOutput>(256);
+
+        {
+            let mut guard = self.inner.lock().await;
+            guard.stdin_tx = Some(stdin_tx);
+            guard.running = true;
+            guard.last_activity = Instant::now();
+        }
+
+        *self.conversation_tx.lock().await = Some(conv_tx.clone());
+        *self.conversation_rx.lock().await = Some(conv_rx);
+
+        // Background write loop: sends messages to agent stdin.
+        let write_handle = tokio::spawn(async move {
+            let mut stdin = tokio::io::BufWriter::new(tokio::fs::File::from_std(stdin));
+
+            while let Some(item) = stdin_rx.recv().await {
+                match item {
+                    Outbound::Message(message) => {
+                        if write_frame(&mut stdin, framing, &message).await.is_err() {
+                            break;
+                        }
+                    }
+                    Outbound::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
             }
-            if let Some(handle) = write_task.lock().await.take() {
-                handle.abort();
+        });
+
+        // Background read loop: reads JSON messages from agent stdout.
+        let inner_read = self.inner.clone();
+        let callbacks = self.callbacks.clone();
+        let log = self.log.clone();
+        let read_handle = tokio::spawn(async move {
+            let stdout = tokio::fs::File::from_std(stdout);
+            let mut reader = BufReader::new(stdout);
+
+            while let Ok(Some(frame)) =
+                read_frame(&mut reader, framing, options.max_line_bytes).await
+            {
+                let line = match frame {
+                    Frame::Message(line) => line.trim().to_string(),
+                    Frame::Dropped(reason) => {
+                        log.warning(format!("control protocol {reason}"));
+                        let diagnostic = serde_json::json!({ "type": "error", "message": reason });
+                        let diagnostic = AgentOutput::ConversationMessage(diagnostic.to_string());
+                        let _ = conv_tx.send(diagnostic).await;
+                        continue;
+                    }
+                };
+                if line.is_empty() {
+                    continue;
+                }
+
+                let output = classify_message(&line);
+                match &output {
+                    AgentOutput::ControlRequest(msg) => {
+                        // Check if this is a response to a pending request.
+                        let mut guard = inner_read.lock().await;
+                        guard.last_activity = Instant::now();
+                        let pending = guard.pending.remove(&msg.request_id);
+                        drop(guard);
+                        if let Some(pending) = &pending {
+                            *pending.response.lock().await = Some(msg.data.clone());
+                            pending.notify.notify_one();
+                        } else if let Some(callback) = callbacks.for_subtype(&msg.subtype) {
+                            // Otherwise, answer it with the registered callback,
+                            // off the read loop so slow callbacks don't stall it.
+                            let inner = inner_read.clone();
+                            tokio::spawn(answer_control_request(
+                                inner,
+                                callback,
+                                msg.clone(),
+                                locals.clone(),
+                                log.clone(),
+                            ));
+                            continue;
+                        }
+                    }
+                    AgentOutput::ConversationMessage(_) => {
+                        inner_read.lock().await.last_activity = Instant::now();
+                    }
+                }
+
+                // Forward everything else, in arrival order, so the Python
+                // layer (e.g. Query) can process it.
+                let _ = conv_tx.send(output).await;
             }
 
-            Ok(())
-        })
+            // The agent closed stdout; nobody is left to answer what is pending.
+            inner_read.lock().await.fail_pending();
+        });
+
+        *self.read_task.lock().await = Some(read_handle);
+        *self.write_task.lock().await = Some(write_handle);
+        if let Some(interval) = options.keepalive {
+            let keepalive = keepalive_loop(self.inner.clone(), interval, self.log.clone());
+            let handle = tokio::spawn(keepalive);
+            *self.keepalive_task.lock().await = Some(handle);
+        }
+    }
+
+    /// Close the outbound queue and wait up to `timeout` (`None`: no limit)
+    /// for the write loop to get through what was already in it. Each
+    /// message is flushed as it is written, so reaching the marker queued
+    /// here means everything before it has reached the agent's stdin.
+    async fn flush(&self, timeout: Option<Duration>) -> bool {
+        let Some(stdin_tx) = self.inner.lock().await.stdin_tx.take() else {
+            return true;
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        let drained = async move {
+            stdin_tx.send(Outbound::Flush(done_tx)).await.is_ok() && done_rx.await.is_ok()
+        };
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, drained)
+                .await
+                .unwrap_or(false),
+            None => drained.await,
+        }
+    }
+
+    /// Abort the background tasks.
+    async fn abort(&self) {
+        if let Some(handle) = self.keepalive_task.lock().await.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.read_task.lock().await.take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.write_task.lock().await.take() {
+            handle.abort();
+        }
+    }
+
+    /// Abort the loops and close their conversation channel, so a
+    /// `recv_message()` waiting on it returns `None` and releases it.
+    async fn teardown(&self) {
+        *self.conversation_tx.lock().await = None;
+        self.abort().await;
     }
 }
 
@@ -466,6 +787,426 @@ impl RustControlProtocol {
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Parse the JSON `data` payload of a control message.
+fn parse_data(data: &str) -> Result<serde_json::Value, ConduitError> {
+    serde_json::from_str(data)
+        .map_err(|e| ConduitError::Protocol(format!("control data is not valid JSON: {e}")))
+}
+
+/// Parse a `timeout_secs` argument: `0` means no limit.
+fn parse_timeout(secs: f64) -> PyResult<Option<Duration>> {
+    match secs {
+        secs if secs == 0.0 => Ok(None),
+        secs if secs.is_finite() && secs > 0.0 => Ok(Some(Duration::from_secs_f64(secs))),
+        secs => Err(ConduitError::Protocol(format!(
+            "timeout_secs must be positive or 0, got {secs}"
+        ))
+        .into()),
+    }
+}
+
+/// Duplicate a caller-owned FD (a `HANDLE` on Windows) into a file the
+/// loops own outright, so the caller closing its FD, or the loops closing
+/// theirs, can never close someone else's file.
+fn dup_stream(fd: i64, name: &str) -> PyResult<std::fs::File> {
+    let invalid =
+        |reason: String| ConduitError::Transport(format!("invalid {name} {fd}: {reason}"));
+
+    #[cfg(unix)]
+    let owned = {
+        use std::os::fd::{BorrowedFd, RawFd};
+        let raw = RawFd::try_from(fd)
+            .ok()
+            .filter(|raw| *raw >= 0)
+            .ok_or_else(|| invalid("not a file descriptor".into()))?;
+        // Safety: the FD is only borrowed for the `dup` call, which fails
+        // with `EBADF` rather than touching anything if it is not open.
+        unsafe { BorrowedFd::borrow_raw(raw) }.try_clone_to_owned()
+    };
+    #[cfg(windows)]
+    let owned = {
+        use std::os::windows::io::{BorrowedHandle, RawHandle};
+        // Safety: the handle is only borrowed for `DuplicateHandle`, which
+        // fails rather than touching anything if it is not open.
+        unsafe { BorrowedHandle::borrow_raw(fd as RawHandle) }.try_clone_to_owned()
+    };
+
+    let owned = owned.map_err(|e| invalid(e.to_string()))?;
+    Ok(std::fs::File::from(owned))
+}
+
+/// Ping the agent whenever it has been idle for `interval`, stopping the
+/// protocol if a ping cannot be sent or goes unanswered.
+async fn keepalive_loop(inner: Arc<Mutex<ProtocolInner>>, interval: Duration, log: LogSink) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let (request_id, notify, stdin_tx) = {
+            let mut guard = inner.lock().await;
+            if !guard.running {
+                return;
+            }
+            if guard.last_activity.elapsed() < interval {
+                continue;
+            }
+            let Some(tx) = guard.stdin_tx.clone() else {
+                return;
+            };
+            let id = format!("sdk_{}", guard.next_id);
+            guard.next_id += 1;
+            let notify = Arc::new(Notify::new());
+            guard.pending.insert(
+                id.clone(),
+                PendingRequest {
+                    notify: notify.clone(),
+                    response: Arc::new(Mutex::new(None)),
+                },
+            );
+            (id, notify, tx)
+        };
+
+        let ping = serde_json::json!({
+            "type": "control",
+            "request_id": request_id,
+            "subtype": "ping",
+            "data": {},
+        });
+        let sent = stdin_tx
+            .send(Outbound::Message(ping.to_string()))
+            .await
+            .is_ok();
+        if sent {
+            let _ = tokio::time::timeout(interval, notify.notified()).await;
+        }
+
+        let mut guard = inner.lock().await;
+        // A pong removes the pending entry; if it is still here, nobody answered.
+        if !sent || guard.pending.remove(&request_id).is_some() {
+            log.error("control keepalive got no reply, stopping protocol");
+            guard.fail_pending();
+            return;
+        }
+    }
+}
+
+/// Python callbacks for agent-initiated control requests, by subtype.
+#[derive(Clone, Default)]
+struct Callbacks {
+    permission: Arc<std::sync::Mutex<Option<PyObject>>>,
+    hook: Arc<std::sync::Mutex<Option<PyObject>>>,
+    mcp: Arc<std::sync::Mutex<Option<PyObject>>>,
+}
+
+impl Callbacks {
+    /// The callback registered for `subtype`, if any.
+    fn for_subtype(&self, subtype: &str) -> Option<PyObject> {
+        let slot = match subtype {
+            "can_use_tool" => &self.permission,
+            "hook_callback" => &self.hook,
+            "mcp_message" => &self.mcp,
+            _ => return None,
+        };
+        Python::with_gil(|py| slot.lock().unwrap().as_ref().map(|cb| cb.clone_ref(py)))
+    }
+}
+
+/// Answer an agent control request with its Python callback and send the
+/// `control_response`.
+async fn answer_control_request(
+    inner: Arc<Mutex<ProtocolInner>>,
+    callback: PyObject,
+    msg: ControlMessage,
+    locals: Option<TaskLocals>,
+    log: LogSink,
+) {
+    let data = if msg.subtype == "can_use_tool" {
+        permission_response(&callback, &msg.data, locals.as_ref(), &log).await
+    } else {
+        callback_response(&callback, &msg.data, locals.as_ref()).await
+    };
+
+    let Some(stdin_tx) = inner.lock().await.stdin_tx.clone() else {
+        return;
+    };
+    let response = serde_json::json!({
+        "type": "control_response",
+        "request_id": msg.request_id,
+        "subtype": msg.subtype,
+        "data": data,
+    });
+    let _ = stdin_tx.send(Outbound::Message(response.to_string())).await;
+}
+
+/// Run the permission callback for a `can_use_tool` request. Only an
+/// `"allow"` decision allows; any other decision denies, and so, as on the
+/// ACP permission path, does a callback that fails or returns neither a
+/// `PermissionResult` nor a dict.
+async fn permission_response(
+    callback: &PyObject,
+    data: &str,
+    locals: Option<&TaskLocals>,
+    log: &LogSink,
+) -> serde_json::Value {
+    let request: serde_json::Value = serde_json::from_str(data).unwrap_or_default();
+    let tool_name = request["tool_name"].as_str().unwrap_or_default().to_owned();
+    let tool_input = request
+        .get("tool_input")
+        .map_or_else(|| "{}".to_owned(), |input| input.to_string());
+
+    let called = Python::with_gil(|py| -> PyResult<PyObject> {
+        let ctx = PyDict::new(py);
+        ctx.set_item("tool_name", &tool_name)?;
+        ctx.set_item("tool_input", &tool_input)?;
+        ctx.set_item("tool_use_id", request["tool_use_id"].as_str())?;
+        ctx.set_item("session_id", request["session_id"].as_str())?;
+        ctx.set_item("options", permission_options(&request["options"]))?;
+        callback.call1(py, (&tool_name, &tool_input, ctx))
+    });
+    let result = match called {
+        Ok(result) => settle(result, locals).await,
+        Err(e) => Err(e),
+    };
+
+    let decision = result.map(|result| {
+        Python::with_gil(|py| {
+            let result = result.bind(py);
+            result
+                .extract::<PermissionResult>()
+                .ok()
+                .or_else(|| PermissionResult::from_dict(result.downcast::<PyDict>().ok()?))
+        })
+    });
+    match decision {
+        Ok(Some(result)) if result.decision == "allow" => match result.option_id {
+            Some(option_id) => serde_json::json!({ "decision": "allow", "option_id": option_id }),
+            None => serde_json::json!({ "decision": "allow" }),
+        },
+        Ok(Some(result)) => {
+            serde_json::json!({ "decision": "deny", "reason": result.reason })
+        }
+        Ok(None) => {
+            log.error(format!(
+                "control permission callback for {tool_name} returned neither \
+                 a PermissionResult nor a dict; denying"
+            ));
+            serde_json::json!({
+                "decision": "deny",
+                "reason": "permission callback returned no decision",
+            })
+        }
+        Err(e) => {
+            log.error(format!(
+                "control permission callback for {tool_name} failed: {e}; denying"
+            ));
+            serde_json::json!({ "decision": "deny", "reason": "permission callback failed" })
+        }
+    }
+}
+
+/// The ACP permission options (`{"optionId", "name", "kind"}`) of a
+/// `can_use_tool` request; ones that do not parse are skipped.
+fn permission_options(options: &serde_json::Value) -> Vec<PermissionOption> {
+    options
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|option| sacp::schema::PermissionOption::deserialize(option).ok())
+        .map(|option| PermissionOption::from_acp(&option))
+        .collect()
+}
+
+/// Run a hook or MCP callback on the request's decoded `data` and return
+/// its result as the response data (`{}` for `None`, `{"error": ...}` if
+/// it raised).
+async fn callback_response(
+    callback: &PyObject,
+    data: &str,
+    locals: Option<&TaskLocals>,
+) -> serde_json::Value {
+    let called = Python::with_gil(|py| -> PyResult<PyObject> {
+        let data = py.import("json")?.call_method1("loads", (data,))?;
+        callback.call1(py, (data,))
+    });
+    let result = match called {
+        Ok(result) => settle(result, locals).await,
+        Err(e) => Err(e),
+    };
+
+    let encoded = result.and_then(|result| {
+        Python::with_gil(|py| -> PyResult<Option<String>> {
+            if result.is_none(py) {
+                return Ok(None);
+            }
+            let json_mod = py.import("json")?;
+            json_mod
+                .call_method1("dumps", (result.bind(py),))?
+                .extract()
+                .map(Some)
+        })
+    });
+    match encoded {
+        Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
+        Ok(None) => serde_json::json!({}),
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    }
+}
+
+/// Await a callback's return value if it is awaitable, on `locals`' event
+/// loop (or the current one).
+async fn settle(result: PyObject, locals: Option<&TaskLocals>) -> PyResult<PyObject> {
+    let future = Python::with_gil(|py| -> PyResult<_> {
+        let result = result.into_bound(py);
+        if !result.hasattr("__await__")? {
+            return Ok(Err(result.unbind()));
+        }
+        let locals = match locals {
+            Some(locals) => locals.clone(),
+            None => pyo3_async_runtimes::tokio::get_current_locals(py)?,
+        };
+        pyo3_async_runtimes::into_future_with_locals(&locals, result).map(Ok)
+    })?;
+    match future {
+        Ok(future) => future.await,
+        Err(value) => Ok(value),
+    }
+}
+
+/// Write one message to the agent, framed as `framing` says, and flush it.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    framing: Framing,
+    message: &str,
+) -> std::io::Result<()> {
+    match framing {
+        Framing::LineDelimited => {
+            writer.write_all(message.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+        Framing::ContentLength => {
+            let header = format!("Content-Length: {}\r\n\r\n", message.len());
+            writer.write_all(header.as_bytes()).await?;
+            writer.write_all(message.as_bytes()).await?;
+        }
+    }
+    writer.flush().await
+}
+
+/// One message slot read from the agent by `read_frame`.
+enum Frame {
+    /// A complete message.
+    Message(String),
+    /// A message that was skipped, and why.
+    Dropped(String),
+}
+
+/// Read the next message from the agent, or `None` at end of stream.
+///
+/// Messages over `max_bytes` or not UTF-8 come back as `Frame::Dropped`;
+/// an over-long one is discarded as it streams in rather than buffered.
+/// A `ContentLength` body is read by its exact byte count, so newlines
+/// inside it are part of the message. Malformed headers are an error,
+/// since the stream cannot be resynchronised after them.
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    framing: Framing,
+    max_bytes: usize,
+) -> std::io::Result<Option<Frame>> {
+    let too_long = |len: usize| {
+        Frame::Dropped(format!(
+            "dropped a {len}-byte message over the {max_bytes}-byte limit"
+        ))
+    };
+    let decode = |bytes: Vec<u8>| match String::from_utf8(bytes) {
+        Ok(message) => Frame::Message(message),
+        Err(_) => Frame::Dropped("dropped a message that is not valid UTF-8".into()),
+    };
+
+    if framing == Framing::LineDelimited {
+        let line = read_bounded_line(reader, max_bytes).await?;
+        return Ok(line.map(|line| line.map_or_else(too_long, decode)));
+    }
+
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    let mut length = None;
+    let mut in_headers = false;
+    loop {
+        let Some(line) = read_bounded_line(reader, max_bytes).await? else {
+            if in_headers {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            return Ok(None);
+        };
+        let line = line.map_err(|len| invalid(format!("{len}-byte header line")))?;
+        let line = String::from_utf8(line).map_err(|_| invalid("header is not UTF-8".into()))?;
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            if !in_headers {
+                // Stray blank line between messages.
+                continue;
+            }
+            break;
+        }
+        in_headers = true;
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("malformed header {header:?}")))?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            let value = value.trim();
+            let parsed = value.parse::<usize>();
+            length = Some(parsed.map_err(|_| invalid(format!("bad Content-Length {value:?}")))?);
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid("message without Content-Length".into()))?;
+    if length > max_bytes {
+        let mut body = (&mut *reader).take(length as u64);
+        if tokio::io::copy(&mut body, &mut tokio::io::sink()).await? < length as u64 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        return Ok(Some(too_long(length)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(decode(body)))
+}
+
+/// Read through the next `\n` (or to end of stream), or `None` if the
+/// stream is already at its end.
+///
+/// A line over `max_bytes`, newline included, is consumed but not kept:
+/// it comes back as `Err` with its length, and the next call resumes at
+/// the following line.
+async fn read_bounded_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_bytes: usize,
+) -> std::io::Result<Option<Result<Vec<u8>, usize>>> {
+    let mut line = Vec::new();
+    let mut len = 0;
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            if len == 0 {
+                return Ok(None);
+            }
+            break;
+        }
+        let newline = buf.iter().position(|&b| b == b'\n');
+        let chunk = &buf[..newline.map_or(buf.len(), |at| at + 1)];
+        len += chunk.len();
+        if len <= max_bytes {
+            line.extend_from_slice(chunk);
+        } else if !line.is_empty() {
+            line = Vec::new();
+        }
+        let consumed = chunk.len();
+        reader.consume(consumed);
+        if newline.is_some() {
+            break;
+        }
+    }
+    Ok(Some(if len <= max_bytes { Ok(line) } else { Err(len) }))
+}
+
 /// Classify a raw JSON line from agent stdout.
 fn classify_message(line: &str) -> AgentOutput {
     if let Ok(value) = serde_json::from_str::<serde_json::Value>(line) {
@@ -495,6 +1236,7 @@ fn classify_message(line: &str) -> AgentOutput {
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ControlMessage>()?;
     m.add_class::<ControlResponse>()?;
+    m.add_class::<Framing>()?;
     m.add_class::<RustControlProtocol>()?;
     Ok(())
 }
@@ -1,5 +1,9 @@
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// ACP error code for "authentication required".
+const AUTH_REQUIRED_CODE: i64 = -32000;
 
 /// Core error type for the conduit SDK.
 ///
@@ -19,6 +23,26 @@ pub enum ConduitError {
     #[error("protocol error: {0}")]
     Protocol(String),
 
+    /// A JSON-RPC error returned by the agent, with its code and
+    /// JSON-encoded `data` preserved, and the session the failed request
+    /// was about when there was one.
+    #[error("protocol error {code}: {message}")]
+    ProtocolCoded {
+        code: i64,
+        message: String,
+        data: Option<String>,
+        session_id: Option<String>,
+    },
+
+    /// The agent requires authentication before it will serve the request.
+    /// `auth_methods` lists the method ids it will accept, when it sent them.
+    #[error("authentication required: {message}")]
+    AuthRequired {
+        message: String,
+        auth_methods: Vec<String>,
+        session_id: Option<String>,
+    },
+
     #[error("tool error: {0}")]
     Tool(String),
 
@@ -37,10 +61,26 @@ pub enum ConduitError {
     #[error("cancelled")]
     Cancelled,
 
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+impl ConduitError {
+    /// Record the session an agent error is about, raised to Python as the
+    /// exception's `session_id`. Other variants are returned unchanged.
+    pub fn in_session(mut self, id: &str) -> Self {
+        if let ConduitError::ProtocolCoded { session_id, .. }
+        | ConduitError::AuthRequired { session_id, .. } = &mut self
+        {
+            *session_id = Some(id.to_string());
+        }
+        self
+    }
+}
+
 impl From<ConduitError> for PyErr {
     fn from(err: ConduitError) -> PyErr {
         // Map each ConduitError variant to its corresponding Python exception
@@ -51,13 +91,15 @@ impl From<ConduitError> for PyErr {
                 ConduitError::Connection(_) => "ConnectionError",
                 ConduitError::Session(_) => "SessionError",
                 ConduitError::Transport(_) => "TransportError",
-                ConduitError::Protocol(_) => "ProtocolError",
+                ConduitError::Protocol(_) | ConduitError::ProtocolCoded { .. } => "ProtocolError",
+                ConduitError::AuthRequired { .. } => "AuthRequiredError",
                 ConduitError::Tool(_) => "ToolError",
                 ConduitError::Hook(_) => "HookError",
                 ConduitError::Proxy(_) => "ProxyError",
                 ConduitError::Timeout(_) => "TimeoutError",
                 ConduitError::PermissionDenied(_) => "PermissionError",
                 ConduitError::Cancelled => "CancelledError",
+                ConduitError::Unsupported(_) => "UnsupportedError",
                 ConduitError::Other(_) => "ConduitError",
             };
             // Try to import the exception class from conduit_sdk.exceptions.
@@ -66,7 +108,37 @@ impl From<ConduitError> for PyErr {
                 .and_then(|m| m.getattr(class_name))
             {
                 Ok(exc_class) => {
-                    match exc_class.call1((msg.clone(),)) {
+                    let instance = match &err {
+                        // Coded errors expose `code`, `data` and `session_id`
+                        // as attributes.
+                        ConduitError::ProtocolCoded {
+                            code,
+                            data,
+                            session_id,
+                            ..
+                        } => {
+                            let kwargs = PyDict::new(py);
+                            kwargs
+                                .set_item("code", code)
+                                .and_then(|_| kwargs.set_item("data", data))
+                                .and_then(|_| kwargs.set_item("session_id", session_id))
+                                .and_then(|_| exc_class.call((msg.clone(),), Some(&kwargs)))
+                        }
+                        ConduitError::AuthRequired {
+                            auth_methods,
+                            session_id,
+                            ..
+                        } => {
+                            let kwargs = PyDict::new(py);
+                            kwargs
+                                .set_item("code", AUTH_REQUIRED_CODE)
+                                .and_then(|_| kwargs.set_item("auth_methods", auth_methods))
+                                .and_then(|_| kwargs.set_item("session_id", session_id))
+                                .and_then(|_| exc_class.call((msg.clone(),), Some(&kwargs)))
+                        }
+                        _ => exc_class.call1((msg.clone(),)),
+                    };
+                    match instance {
                         Ok(instance) => PyErr::from_value(instance),
                         Err(_) => PyRuntimeError::new_err(msg),
                     }
@@ -83,6 +155,47 @@ impl From<serde_json::Error> for ConduitError {
     }
 }
 
+impl From<&sacp::Error> for ConduitError {
+    fn from(err: &sacp::Error) -> Self {
+        // Go through the wire form so we read the same `code`/`message`/`data`
+        // triple the agent sent.
+        let value = serde_json::to_value(err).unwrap_or_default();
+        match value["code"].as_i64() {
+            Some(AUTH_REQUIRED_CODE) => ConduitError::AuthRequired {
+                message: value["message"]
+                    .as_str()
+                    .map_or_else(|| err.to_string(), str::to_owned),
+                auth_methods: auth_method_ids(&value["data"]),
+                session_id: None,
+            },
+            Some(code) => ConduitError::ProtocolCoded {
+                code,
+                message: value["message"]
+                    .as_str()
+                    .map_or_else(|| err.to_string(), str::to_owned),
+                data: value
+                    .get("data")
+                    .filter(|data| !data.is_null())
+                    .map(|data| data.to_string()),
+                session_id: None,
+            },
+            None => ConduitError::Protocol(err.to_string()),
+        }
+    }
+}
+
+/// Method ids from an auth-required error's `data.authMethods`, which
+/// agents send either as bare ids or as `AuthMethod` objects.
+fn auth_method_ids(data: &serde_json::Value) -> Vec<String> {
+    data["authMethods"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|method| method.as_str().or_else(|| method["id"].as_str()))
+        .map(str::to_owned)
+        .collect()
+}
+
 impl From<std::io::Error> for ConduitError {
     fn from(err: std::io::Error) -> Self {
         ConduitError::Transport(format!("I/O error: {err}"))
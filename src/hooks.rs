@@ -3,8 +3,17 @@
 //! Hooks allow Python code to intercept and modify ACP protocol events.
 //! Hooks are registered on the client and dispatched at specific points
 //! in the request/response lifecycle.
+//!
+//! Hooks for events that precede an operation ([`HookType::can_veto`]) may
+//! also abort it by returning the deny sentinel
+//! `{"__action__": "deny", "reason": "..."}`.
 
+use crate::error::ConduitError;
+use crate::log::LogSink;
 use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyModule};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -30,32 +39,109 @@ pub enum HookType {
     Disconnected,
 }
 
+impl HookType {
+    /// Whether hooks of this type run before the operation and may veto it.
+    pub fn can_veto(&self) -> bool {
+        matches!(self, HookType::PreToolUse | HookType::PromptSubmit)
+    }
+}
+
+/// Context key a hook sets to `"deny"` to veto the operation.
+const ACTION_KEY: &str = "__action__";
+
+/// The deny reason if the Python context `ctx` is a deny sentinel.
+fn py_deny_reason(ctx: &Bound<'_, PyAny>) -> Option<String> {
+    let ctx = ctx.downcast::<PyDict>().ok()?;
+    let action = ctx.get_item(ACTION_KEY).ok()??;
+    if action.extract::<String>().ok()? != "deny" {
+        return None;
+    }
+    let reason = ctx.get_item("reason").ok().flatten();
+    Some(reason.and_then(|r| r.extract().ok()).unwrap_or_default())
+}
+
+/// The reason carried by a deny sentinel (empty if it gave none), or
+/// `None` if `context` is not one.
+pub(crate) fn deny_reason(context: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(context).ok()?;
+    if value[ACTION_KEY] != "deny" {
+        return None;
+    }
+    Some(value["reason"].as_str().unwrap_or_default().to_owned())
+}
+
 /// A registered hook with its Python callback.
 struct RegisteredHook {
-    hook_type: HookType,
     /// Python callable: `async def hook(context: dict) -> dict | None`
     #[allow(dead_code)]
     callback: PyObject,
     /// Priority for ordering (lower = earlier).
     priority: i32,
+    /// Registration sequence number; breaks priority ties (earlier first)
+    /// and doubles as the handle returned by `register()`.
+    seq: u64,
+}
+
+/// Registered hooks, kept per hook type in dispatch order.
+#[derive(Default)]
+struct HookTable {
+    /// Hooks of each type, sorted by `(priority, seq)`.
+    by_type: HashMap<HookType, Vec<RegisteredHook>>,
+    /// Sequence number for the next registration.
+    next_seq: u64,
+}
+
+/// What `dispatch` does when a hook raises.
+struct ErrorPolicy {
+    /// Abort dispatch with `ConduitError::Hook` instead of passing the
+    /// context on unchanged.
+    fail_fast: bool,
+    /// Python callable `error_sink(hook_type, message)`, told about errors
+    /// that are passed through.
+    error_sink: Option<PyObject>,
 }
 
 /// Rust-side hook dispatcher exposed to Python.
 #[pyclass]
 pub struct RustHookDispatcher {
-    hooks: Arc<Mutex<Vec<RegisteredHook>>>,
+    hooks: Arc<Mutex<HookTable>>,
+    errors: Arc<ErrorPolicy>,
+    /// Where dispatch reports an error sink that raised.
+    log: LogSink,
 }
 
 #[pymethods]
 impl RustHookDispatcher {
+    /// With `fail_fast`, a hook that raises aborts `dispatch()` with a
+    /// `HookError` carrying the exception and its traceback. Otherwise the
+    /// hook is skipped (the context passes through unchanged) and, if set,
+    /// `error_sink(hook_type, message)` is called with the same message.
     #[new]
-    fn new() -> Self {
+    #[pyo3(signature = (fail_fast=false, error_sink=None))]
+    fn new(fail_fast: bool, error_sink: Option<PyObject>) -> Self {
         Self {
-            hooks: Arc::new(Mutex::new(Vec::new())),
+            hooks: Arc::new(Mutex::new(HookTable::default())),
+            errors: Arc::new(ErrorPolicy {
+                fail_fast,
+                error_sink,
+            }),
+            log: LogSink::default(),
         }
     }
 
+    /// Route the dispatcher's internal log (an `error_sink` that raised)
+    /// to `callback(level, message)`, or buffer it again with `None`. Works
+    /// like `RustClient.set_log_callback()`; `Client.connect()` routes it
+    /// to the client's logger.
+    #[pyo3(signature = (callback=None))]
+    fn set_log_callback(&self, py: Python<'_>, callback: Option<PyObject>) {
+        self.log.set_callback(py, callback);
+    }
+
     /// Register a hook callback for the given hook type.
+    ///
+    /// Hooks run in ascending `priority`; hooks with equal priority run in
+    /// registration order. Returns an opaque handle for `unregister()`.
     #[pyo3(signature = (hook_type, callback, priority=0))]
     fn register<'py>(
         &self,
@@ -67,84 +153,65 @@ impl RustHookDispatcher {
         let hooks = self.hooks.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let mut list = hooks.lock().await;
-            list.push(RegisteredHook {
-                hook_type,
-                callback,
-                priority,
-            });
-            // Keep sorted by priority.
-            list.sort_by_key(|h| h.priority);
-            Ok(())
+            let mut table = hooks.lock().await;
+            let seq = table.next_seq;
+            table.next_seq += 1;
+            let list = table.by_type.entry(hook_type).or_default();
+            let at = list.partition_point(|h| (h.priority, h.seq) < (priority, seq));
+            list.insert(
+                at,
+                RegisteredHook {
+                    callback,
+                    priority,
+                    seq,
+                },
+            );
+            Ok(seq)
+        })
+    }
+
+    /// Remove the single hook `handle` (from `register()`) refers to.
+    ///
+    /// Returns `False` if no such hook is registered, e.g. because it was
+    /// already unregistered or cleared.
+    fn unregister<'py>(&self, py: Python<'py>, handle: u64) -> PyResult<Bound<'py, PyAny>> {
+        let hooks = self.hooks.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut table = hooks.lock().await;
+            for list in table.by_type.values_mut() {
+                if let Some(at) = list.iter().position(|h| h.seq == handle) {
+                    list.remove(at);
+                    return Ok(true);
+                }
+            }
+            Ok(false)
         })
     }
 
     /// Dispatch all hooks of the given type with the provided context.
     ///
     /// Returns the (possibly modified) context dict after all hooks run.
-    /// Hooks are invoked in priority order. A hook may return `None` to
-    /// pass the context through unchanged, or return a modified dict.
+    /// Hooks are invoked in priority order, ties in registration order. The
+    /// context is decoded once and each hook gets a deep copy of the dict,
+    /// so it may edit it in place and return `None`, or return a
+    /// replacement dict. A hook that raises has its edits discarded. The
+    /// context is encoded back to JSON once, at the end.
+    ///
+    /// For veto-capable hook types, a hook returning the deny sentinel
+    /// stops dispatch: the remaining hooks are skipped and the sentinel
+    /// (normalized to `{"__action__": "deny", "reason": ...}`) is returned
+    /// in place of the context. Other hook types treat it as plain context.
     fn dispatch<'py>(
         &self,
         py: Python<'py>,
         hook_type: HookType,
         context_json: String,
     ) -> PyResult<Bound<'py, PyAny>> {
-        let hooks = self.hooks.clone();
+        let handle = self.handle();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let list = hooks.lock().await;
-            let matching: Vec<&RegisteredHook> = list
-                .iter()
-                .filter(|h| h.hook_type == hook_type)
-                .collect();
-
-            // For each matching hook, acquire the GIL, parse the JSON
-            // context, call the Python callback, and serialize back.
-            let mut context = context_json;
-            for hook in &matching {
-                let py_result = Python::with_gil(|py| -> PyResult<_> {
-                    let cb = hook.callback.clone_ref(py);
-                    // Parse JSON string into Python dict via json.loads
-                    let json_mod = py.import("json")?;
-                    let py_ctx = json_mod.call_method1("loads", (&context,))?;
-                    // Call the hook callback with the context dict
-                    let result = cb.call1(py, (py_ctx,))?;
-                    // If the callback is a coroutine, await it
-                    if result.bind(py).hasattr("__await__")? {
-                        let future = pyo3_async_runtimes::tokio::into_future(result.into_bound(py))?;
-                        return Ok(Some(future));
-                    }
-                    // Synchronous callback — serialize result back to JSON
-                    if result.is_none(py) {
-                        return Ok(None);
-                    }
-                    let json_str = json_mod.call_method1("dumps", (result.bind(py),))?;
-                    context = json_str.extract::<String>()?;
-                    Ok(None)
-                });
-                match py_result {
-                    Ok(Some(future)) => {
-                        // Await the async callback result
-                        match future.await {
-                            Ok(py_obj) => {
-                                Python::with_gil(|py| -> PyResult<()> {
-                                    if !py_obj.is_none(py) {
-                                        let json_mod = py.import("json")?;
-                                        let json_str = json_mod.call_method1("dumps", (py_obj.bind(py),))?;
-                                        context = json_str.extract::<String>()?;
-                                    }
-                                    Ok(())
-                                }).ok();
-                            }
-                            Err(_) => {}
-                        }
-                    }
-                    Ok(None) => {} // Sync callback already updated context
-                    Err(_) => {}   // Callback error — pass context through unchanged
-                }
-            }
-            Ok(context)
+            Ok(handle.dispatch(hook_type, context_json).await?)
         })
     }
 
@@ -153,13 +220,178 @@ impl RustHookDispatcher {
         let hooks = self.hooks.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let mut list = hooks.lock().await;
-            list.retain(|h| h.hook_type != hook_type);
+            hooks.lock().await.by_type.remove(&hook_type);
             Ok(())
         })
     }
 }
 
+impl RustHookDispatcher {
+    /// A shareable view of this dispatcher's hooks for the client bridge.
+    pub(crate) fn handle(&self) -> HookDispatcherHandle {
+        HookDispatcherHandle {
+            hooks: self.hooks.clone(),
+            errors: self.errors.clone(),
+            log: self.log.clone(),
+        }
+    }
+
+    /// Whether any registered hook is an `async def` function, which can
+    /// only run with an event loop to await it on.
+    pub(crate) fn has_async_hook(&self, py: Python<'_>) -> PyResult<bool> {
+        let hooks = self.hooks.clone();
+        // A dispatch holds the table while it waits for the GIL.
+        let table = py.allow_threads(move || hooks.blocking_lock_owned());
+        for hook in table.by_type.values().flatten() {
+            if is_coroutine_function(hook.callback.bind(py))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Shared view of a dispatcher's hooks, handed to a connected client so
+/// lifecycle events it handles in Rust run the registered Python hooks.
+#[derive(Clone)]
+pub(crate) struct HookDispatcherHandle {
+    hooks: Arc<Mutex<HookTable>>,
+    errors: Arc<ErrorPolicy>,
+    log: LogSink,
+}
+
+impl HookDispatcherHandle {
+    /// Run the hooks of `hook_type` over `context` (a JSON object) and
+    /// return the resulting context, or the deny sentinel if one vetoed.
+    /// Fails only for a raising hook on a `fail_fast` dispatcher, or if the
+    /// context is not JSON in or out. See `RustHookDispatcher.dispatch`.
+    pub(crate) async fn dispatch(
+        &self,
+        hook_type: HookType,
+        context: String,
+    ) -> Result<String, ConduitError> {
+        let table = self.hooks.lock().await;
+        let matching = table.by_type.get(&hook_type).map_or(&[][..], Vec::as_slice);
+        if matching.is_empty() {
+            return Ok(context);
+        }
+
+        // Decode once; the hooks then pass the same Python object along.
+        let mut ctx = Python::with_gil(|py| -> PyResult<PyObject> {
+            Ok(json_module(py)?
+                .call_method1("loads", (&context,))?
+                .unbind())
+        })
+        .map_err(|e| ConduitError::Hook(format!("invalid hook context: {e}")))?;
+
+        for hook in matching {
+            // Each hook edits its own copy, so one that raises halfway
+            // through leaves nothing behind.
+            let mut attempt = Python::with_gil(|py| deep_copy(ctx.bind(py)))
+                .map_err(|e| ConduitError::Hook(format!("hook context: {}", describe(&e))))?;
+            match run_hook(hook, &mut attempt).await {
+                Ok(()) => ctx = attempt,
+                Err(err) => {
+                    let message = format!("{hook_type:?} hook failed: {}", describe(&err));
+                    if self.errors.fail_fast {
+                        return Err(ConduitError::Hook(message));
+                    }
+                    self.report(&hook_type, &message);
+                }
+            }
+            if hook_type.can_veto() {
+                if let Some(reason) = Python::with_gil(|py| py_deny_reason(ctx.bind(py))) {
+                    let denied = serde_json::json!({ ACTION_KEY: "deny", "reason": reason });
+                    return Ok(denied.to_string());
+                }
+            }
+        }
+
+        Python::with_gil(|py| -> PyResult<String> {
+            json_module(py)?
+                .call_method1("dumps", (ctx.bind(py),))?
+                .extract()
+        })
+        .map_err(|e| ConduitError::Hook(format!("hook context is not JSON: {}", describe(&e))))
+    }
+
+    /// Hand a passed-through hook error to the error sink, if one is set.
+    fn report(&self, hook_type: &HookType, message: &str) {
+        let Some(sink) = &self.errors.error_sink else {
+            return;
+        };
+        Python::with_gil(|py| {
+            if let Err(e) = sink.call1(py, (hook_type.clone(), message)) {
+                self.log.error(format!("hook error sink raised: {e}"));
+            }
+        });
+    }
+}
+
+/// Call one hook with the context object, replacing it with the hook's
+/// return value unless that is `None`. Awaits coroutine-returning callbacks.
+async fn run_hook(hook: &RegisteredHook, ctx: &mut PyObject) -> PyResult<()> {
+    let pending = Python::with_gil(|py| -> PyResult<_> {
+        let result = hook.callback.call1(py, (ctx.clone_ref(py),))?;
+        // If the callback is a coroutine, await it
+        if result.bind(py).hasattr("__await__")? {
+            let future = pyo3_async_runtimes::tokio::into_future(result.into_bound(py))?;
+            return Ok(Some(future));
+        }
+        if !result.is_none(py) {
+            *ctx = result;
+        }
+        Ok(None)
+    })?;
+
+    if let Some(future) = pending {
+        let result = future.await?;
+        Python::with_gil(|py| {
+            if !result.is_none(py) {
+                *ctx = result;
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The `json` module, imported once per process.
+fn json_module(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    static JSON: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+    JSON.get_or_try_init(py, || Ok(py.import("json")?.unbind()))
+        .map(|json| json.bind(py))
+}
+
+/// `copy.deepcopy(value)`.
+fn deep_copy(value: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+    static COPY: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+    let py = value.py();
+    let copy = COPY.get_or_try_init(py, || Ok::<_, PyErr>(py.import("copy")?.unbind()))?;
+    Ok(copy.bind(py).call_method1("deepcopy", (value,))?.unbind())
+}
+
+/// `inspect.iscoroutinefunction(callback)`.
+pub(crate) fn is_coroutine_function(callback: &Bound<'_, PyAny>) -> PyResult<bool> {
+    static INSPECT: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+    let py = callback.py();
+    let inspect = INSPECT.get_or_try_init(py, || Ok::<_, PyErr>(py.import("inspect")?.unbind()))?;
+    inspect
+        .bind(py)
+        .call_method1("iscoroutinefunction", (callback,))?
+        .is_truthy()
+}
+
+/// A hook's exception with its traceback, when Python has one.
+fn describe(err: &PyErr) -> String {
+    Python::with_gil(|py| {
+        let traceback = err
+            .traceback(py)
+            .and_then(|tb| tb.format().ok())
+            .unwrap_or_default();
+        format!("{traceback}{err}")
+    })
+}
+
 /// Register hook types on the Python module.
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<HookType>()?;
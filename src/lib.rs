@@ -9,9 +9,11 @@ mod client;
 mod control;
 mod error;
 mod hooks;
+mod log;
 mod proxy;
 mod session;
 mod tools;
+mod trace;
 mod transport;
 mod types;
 
@@ -30,6 +32,7 @@ fn _conduit_sdk(m: &Bound<'_, PyModule>) -> PyResult<()> {
     tools::register(m)?;
     hooks::register(m)?;
     proxy::register(m)?;
+    trace::register(m)?;
 
     Ok(())
 }
@@ -0,0 +1,80 @@
+//! Internal log shared by the Rust objects exposed to Python.
+//!
+//! Background tasks have no caller to raise to, so what goes wrong in them
+//! (a failed spawn, a callback that raised, a dropped frame) is logged to a
+//! [`LogSink`]. Each Python-facing object that runs such tasks owns one and
+//! exposes it as `set_log_callback()`; the Python layer routes it to the
+//! `logging` module.
+
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Log lines kept while no log callback is set; the oldest go first.
+const LOG_BUFFER_LIMIT: usize = 256;
+
+/// An object's internal log: lines go to the Python `callback(level,
+/// message)` set with `set_log_callback()`, and are buffered until one is.
+#[derive(Clone, Default)]
+pub(crate) struct LogSink(Arc<std::sync::Mutex<LogSinkState>>);
+
+#[derive(Default)]
+struct LogSinkState {
+    callback: Option<PyObject>,
+    pending: VecDeque<(&'static str, String)>,
+}
+
+impl LogSink {
+    /// Route lines to `callback`, first handing it everything buffered.
+    /// `None` goes back to buffering.
+    pub(crate) fn set_callback(&self, py: Python<'_>, callback: Option<PyObject>) {
+        let pending = {
+            let mut state = self.0.lock().unwrap();
+            state.callback = callback.as_ref().map(|cb| cb.clone_ref(py));
+            if callback.is_none() {
+                return;
+            }
+            std::mem::take(&mut state.pending)
+        };
+        if let Some(callback) = callback {
+            for (level, message) in pending {
+                Self::call(py, &callback, level, &message);
+            }
+        }
+    }
+
+    pub(crate) fn warning(&self, message: impl Into<String>) {
+        self.log("warning", message.into());
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) {
+        self.log("error", message.into());
+    }
+
+    fn log(&self, level: &'static str, message: String) {
+        Python::with_gil(|py| {
+            let callback = {
+                let mut state = self.0.lock().unwrap();
+                let Some(callback) = &state.callback else {
+                    if state.pending.len() == LOG_BUFFER_LIMIT {
+                        state.pending.pop_front();
+                    }
+                    state.pending.push_back((level, message));
+                    return;
+                };
+                callback.clone_ref(py)
+            };
+            Self::call(py, &callback, level, &message);
+        });
+    }
+
+    fn call(py: Python<'_>, callback: &PyObject, level: &str, message: &str) {
+        // Nowhere else to report to when the log callback itself fails but
+        // `sys.unraisablehook`, with the dropped line as context.
+        if let Err(e) = callback.call1(py, (level, message)) {
+            let dropped = PyString::new(py, &format!("{level}: {message}"));
+            e.write_unraisable(py, Some(dropped.as_any()));
+        }
+    }
+}
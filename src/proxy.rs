@@ -5,9 +5,18 @@
 //! conductor (from sacp-conductor).
 
 use crate::error::ConduitError;
+use crate::log::LogSink;
+use crate::transport::{expand_command, resolve_executable, AgentProcess};
 use pyo3::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
 /// Configuration for a single proxy in the chain.
 #[pyclass(get_all)]
@@ -17,13 +26,30 @@ pub struct ProxyConfig {
     pub name: String,
     /// Shell command to spawn the proxy subprocess.
     pub command: Vec<String>,
+    /// Respawn the proxy (and replay its handshake) if it exits mid-run,
+    /// backing off exponentially between attempts and giving up (failing
+    /// the chain) after `MAX_RESTARTS` restarts.
+    pub restart: bool,
+    /// Additional environment variables for the proxy subprocess.
+    pub env: HashMap<String, String>,
 }
 
 #[pymethods]
 impl ProxyConfig {
     #[new]
-    fn new(name: String, command: Vec<String>) -> Self {
-        Self { name, command }
+    #[pyo3(signature = (name, command, restart=false, env=HashMap::new()))]
+    fn new(
+        name: String,
+        command: Vec<String>,
+        restart: bool,
+        env: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            name,
+            command,
+            restart,
+            env,
+        }
     }
 
     fn __repr__(&self) -> String {
@@ -39,6 +65,10 @@ impl ProxyConfig {
 #[pyclass]
 pub struct RustProxyChain {
     proxies: Arc<Mutex<Vec<ProxyConfig>>>,
+    /// Health of the most recently built chain, updated by its relays.
+    health: Arc<Mutex<ChainHealth>>,
+    /// Where the chain's relays and conductor report what goes wrong.
+    log: LogSink,
 }
 
 #[pymethods]
@@ -47,9 +77,19 @@ impl RustProxyChain {
     fn new() -> Self {
         Self {
             proxies: Arc::new(Mutex::new(Vec::new())),
+            health: Arc::new(Mutex::new(ChainHealth::default())),
+            log: LogSink::default(),
         }
     }
 
+    /// Route the chain's internal log (failed or restarted hops, conductor
+    /// errors) to `callback(level, message)`, or buffer it again with
+    /// `None`. Works like `RustClient.set_log_callback()`.
+    #[pyo3(signature = (callback=None))]
+    fn set_log_callback(&self, py: Python<'_>, callback: Option<PyObject>) {
+        self.log.set_callback(py, callback);
+    }
+
     /// Append a proxy to the end of the chain.
     fn add<'py>(&self, py: Python<'py>, proxy: ProxyConfig) -> PyResult<Bound<'py, PyAny>> {
         let proxies = self.proxies.clone();
@@ -83,6 +123,61 @@ impl RustProxyChain {
         })
     }
 
+    /// Remove the proxy with the given name. Returns whether one was removed.
+    fn remove<'py>(&self, py: Python<'py>, name: String) -> PyResult<Bound<'py, PyAny>> {
+        let proxies = self.proxies.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut chain = proxies.lock().await;
+            match chain.iter().position(|p| p.name == name) {
+                Some(index) => {
+                    chain.remove(index);
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        })
+    }
+
+    /// Move the named proxy to `index`, preserving the relative order of
+    /// the others.
+    fn move_to<'py>(
+        &self,
+        py: Python<'py>,
+        name: String,
+        index: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let proxies = self.proxies.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut chain = proxies.lock().await;
+            let from = chain
+                .iter()
+                .position(|p| p.name == name)
+                .ok_or_else(|| ConduitError::Proxy(format!("no proxy named {name:?}")))?;
+            if index >= chain.len() {
+                return Err(ConduitError::Proxy(format!(
+                    "index {index} out of range (chain length: {})",
+                    chain.len()
+                ))
+                .into());
+            }
+            let proxy = chain.remove(from);
+            chain.insert(index, proxy);
+            Ok(())
+        })
+    }
+
+    /// Position of the named proxy in the chain, or `None` if absent.
+    fn index_of<'py>(&self, py: Python<'py>, name: String) -> PyResult<Bound<'py, PyAny>> {
+        let proxies = self.proxies.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let chain = proxies.lock().await;
+            Ok(chain.iter().position(|p| p.name == name))
+        })
+    }
+
     /// Return the current chain as a list of proxy configs.
     fn list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
         let proxies = self.proxies.clone();
@@ -103,29 +198,482 @@ impl RustProxyChain {
         })
     }
 
-    /// Build and activate the proxy chain.
+    /// Check every proxy's command without spawning anything.
     ///
-    /// This spawns each proxy subprocess, connects them via the
-    /// conductor, and performs the capability handshake.
-    fn build<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    /// Returns a list of human-readable problems (empty if the chain looks
+    /// runnable): empty commands, and executables that are neither an
+    /// existing absolute/relative path nor found on `PATH`. A proxy's own
+    /// `env` may override `PATH` for its lookup.
+    fn validate<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
         let proxies = self.proxies.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let chain = proxies.lock().await;
+            let problems: Vec<String> = chain
+                .iter()
+                .filter_map(|p| {
+                    let command = expand_command(&p.command, &p.env, None);
+                    let Some(program) = command.first() else {
+                        return Some(format!("{}: command is empty", p.name));
+                    };
+                    let path_var = p.env.get("PATH").map(OsString::from);
+                    let path_var = path_var.or_else(|| std::env::var_os("PATH"));
+                    if resolve_executable(program, path_var.as_deref()).is_none() {
+                        return Some(format!("{}: executable {program:?} not found", p.name));
+                    }
+                    None
+                })
+                .collect();
+            Ok(problems)
+        })
+    }
+
+    /// Build and activate the proxy chain in front of an agent.
+    ///
+    /// This spawns each proxy subprocess followed by the agent, connects
+    /// them via the conductor, and returns a [`RustProxyChainHandle`] that
+    /// keeps the subprocesses alive. Messages flow
+    /// client → proxy₁ → … → agent using the `_proxy/successor/*` protocol.
+    ///
+    /// Each hop is fronted by a relay task that watches the subprocess and,
+    /// for proxies configured with `restart=True`, respawns it on exit.
+    #[pyo3(signature = (agent_command, cwd=None, env=HashMap::new()))]
+    fn build<'py>(
+        &self,
+        py: Python<'py>,
+        agent_command: Vec<String>,
+        cwd: Option<String>,
+        env: HashMap<String, String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let proxies = self.proxies.clone();
+        let health = self.health.clone();
+        let log = self.log.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let chain = proxies.lock().await.clone();
             if chain.is_empty() {
                 return Err(ConduitError::Proxy("proxy chain is empty".into()).into());
             }
-            // TODO: Use sacp-conductor to spawn and connect the proxy chain.
-            // Each proxy is started as a subprocess, connected via ByteStreams,
-            // and the conductor routes messages using _proxy/successor/* protocol.
+
+            // Every hop in chain order: proxies first, agent last.
+            let specs: Vec<HopSpec> = chain
+                .iter()
+                .map(|p| HopSpec {
+                    name: p.name.clone(),
+                    command: p.command.clone(),
+                    cwd: None,
+                    env: p.env.clone(),
+                    restart: p.restart,
+                })
+                .chain(std::iter::once(HopSpec {
+                    name: "agent".into(),
+                    command: agent_command,
+                    cwd,
+                    env,
+                    restart: false,
+                }))
+                .collect();
+
+            let mut processes: Vec<AgentProcess> = Vec::with_capacity(specs.len());
+            for spec in &specs {
+                match AgentProcess::spawn(&spec.command, spec.cwd.as_deref(), &spec.env, &log).await
+                {
+                    Ok(process) => processes.push(process),
+                    Err(e) => {
+                        while let Some(mut process) = processes.pop() {
+                            let _ = process.kill().await;
+                        }
+                        return Err(ConduitError::Proxy(format!("{}: {e}", spec.name)).into());
+                    }
+                }
+            }
+
+            *health.lock().await = ChainHealth {
+                hops: specs
+                    .iter()
+                    .zip(&processes)
+                    .map(|(spec, process)| HopHealth {
+                        name: spec.name.clone(),
+                        pid: process.child.id(),
+                        alive: true,
+                        restarts: 0,
+                    })
+                    .collect(),
+                failed: None,
+            };
+
+            // Each hop gets an in-memory pipe: the conductor talks to one end,
+            // a relay task pumps the other end to the subprocess's stdio.
+            let mut components = Vec::with_capacity(specs.len());
+            let mut relays = Vec::with_capacity(specs.len());
+            for (index, (spec, process)) in specs.into_iter().zip(processes).enumerate() {
+                let (conductor_end, relay_end) = tokio::io::duplex(64 * 1024);
+                let (hop_read, hop_write) = tokio::io::split(conductor_end);
+                components.push(sacp::ByteStreams::new(
+                    hop_write.compat_write(),
+                    hop_read.compat(),
+                ));
+
+                let (shutdown_tx, shutdown_rx) = oneshot::channel();
+                let task = tokio::spawn(run_relay(
+                    index,
+                    spec,
+                    process,
+                    relay_end,
+                    health.clone(),
+                    shutdown_rx,
+                    log.clone(),
+                ));
+                relays.push(Relay {
+                    shutdown: shutdown_tx,
+                    task,
+                });
+            }
+
+            // The conductor speaks ACP to the client over an in-memory pipe;
+            // the client end is handed out via `RustClient.connect_via()`.
+            let (client_side, conductor_side) = tokio::io::duplex(64 * 1024);
+            let (conductor_read, conductor_write) = tokio::io::split(conductor_side);
+            let conductor = sacp_conductor::Conductor::new("conduit-proxy-chain", components, None);
+            let conductor_task = tokio::spawn(async move {
+                let transport =
+                    sacp::ByteStreams::new(conductor_write.compat_write(), conductor_read.compat());
+                if let Err(e) = conductor.run(transport).await {
+                    log.error(format!("proxy conductor error: {e}"));
+                }
+            });
+
+            Ok(RustProxyChainHandle {
+                relays: Arc::new(Mutex::new(relays)),
+                health: health.clone(),
+                conductor_task: Arc::new(Mutex::new(Some(conductor_task))),
+                client_stream: Arc::new(Mutex::new(Some(client_side))),
+            })
+        })
+    }
+
+    /// Per-hop health of the built chain as a JSON array of
+    /// `{name, pid, alive, restarts}` objects (empty before `build()`).
+    fn status<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let health = self.health.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let json =
+                serde_json::to_string(&health.lock().await.hops).map_err(ConduitError::from)?;
+            Ok(json)
+        })
+    }
+
+    /// Raise `ProxyError` if a hop died without a restart policy.
+    fn ensure_healthy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let health = self.health.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            match health.lock().await.failed.clone() {
+                Some(reason) => Err(ConduitError::Proxy(reason).into()),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Built chain: handle, health, and per-hop relays
+// ---------------------------------------------------------------------------
+
+/// Restarts allowed per hop before the chain is marked failed.
+const MAX_RESTARTS: u32 = 5;
+
+/// Delay before the first restart; doubled for each later one.
+const RESTART_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Upper bound on the delay between restarts.
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Spawn-time description of one hop, kept so its relay can respawn it.
+struct HopSpec {
+    name: String,
+    command: Vec<String>,
+    cwd: Option<String>,
+    env: HashMap<String, String>,
+    restart: bool,
+}
+
+/// Health of a single hop, as reported by `RustProxyChain.status()`.
+#[derive(Clone, Debug, Serialize)]
+struct HopHealth {
+    name: String,
+    pid: Option<u32>,
+    alive: bool,
+    restarts: u32,
+}
+
+/// Health of the whole chain.
+#[derive(Default)]
+struct ChainHealth {
+    hops: Vec<HopHealth>,
+    /// Set when a hop exits and is not allowed to restart.
+    failed: Option<String>,
+}
+
+/// A running relay task and the signal that stops it.
+struct Relay {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// A built, running proxy chain.
+///
+/// Owns the conductor task and the relay for every hop (proxies in order,
+/// then the agent); each relay owns its subprocess. Pass it to
+/// `RustClient.connect_via()` to talk to the agent through the chain, and
+/// call `teardown()` when done.
+#[pyclass]
+pub struct RustProxyChainHandle {
+    /// Relays in chain order.
+    relays: Arc<Mutex<Vec<Relay>>>,
+    /// Shared with the [`RustProxyChain`] that built this handle.
+    health: Arc<Mutex<ChainHealth>>,
+    /// Background task running the conductor.
+    conductor_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Client end of the conductor pipe, taken on connect.
+    client_stream: Arc<Mutex<Option<DuplexStream>>>,
+}
+
+impl RustProxyChainHandle {
+    /// Shared slot holding the client end of the conductor pipe.
+    pub(crate) fn client_stream(&self) -> Arc<Mutex<Option<DuplexStream>>> {
+        self.client_stream.clone()
+    }
+}
+
+#[pymethods]
+impl RustProxyChainHandle {
+    /// Names of the hops in chain order (the agent is last).
+    fn names<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let health = self.health.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let names: Vec<String> = health
+                .lock()
+                .await
+                .hops
+                .iter()
+                .map(|hop| hop.name.clone())
+                .collect();
+            Ok(names)
+        })
+    }
+
+    /// Stop the conductor and kill every subprocess in reverse chain order.
+    ///
+    /// Safe to call more than once.
+    fn teardown<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let relays = self.relays.clone();
+        let conductor_task = self.conductor_task.clone();
+        let client_stream = self.client_stream.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            if let Some(handle) = conductor_task.lock().await.take() {
+                handle.abort();
+            }
+            client_stream.lock().await.take();
+
+            let mut relays = relays.lock().await;
+            while let Some(relay) = relays.pop() {
+                // The relay kills its subprocess on shutdown; if it already
+                // finished on its own the send simply fails.
+                let _ = relay.shutdown.send(());
+                let _ = relay.task.await;
+            }
             Ok(())
         })
     }
 }
 
+/// Pump newline-delimited ACP messages between the conductor and one hop.
+///
+/// Records the first message the conductor sends (the proxy handshake) so
+/// a restarted subprocess can be re-initialized before traffic resumes.
+/// Restarts back off exponentially, up to `MAX_RESTARTS` of them. On exit
+/// without a restart policy, after the last restart, or on any I/O error,
+/// marks the chain failed and returns, which closes the hop's pipe and
+/// errors the conductor.
+async fn run_relay(
+    index: usize,
+    spec: HopSpec,
+    mut process: AgentProcess,
+    upstream: DuplexStream,
+    health: Arc<Mutex<ChainHealth>>,
+    mut shutdown: oneshot::Receiver<()>,
+    log: LogSink,
+) {
+    let (up_read, mut up_write) = tokio::io::split(upstream);
+    let mut up_lines = BufReader::new(up_read).lines();
+    let mut handshake: Option<String> = None;
+    let mut replay = false;
+    let mut restarts: u32 = 0;
+
+    loop {
+        let (mut stdin, stdout) = match (process.take_stdin(), process.take_stdout()) {
+            (Ok(stdin), Ok(stdout)) => (stdin, stdout),
+            (Err(e), _) | (_, Err(e)) => {
+                let _ = process.kill().await;
+                let reason = format!("proxy {:?} has no usable stdio: {e}", spec.name);
+                mark_failed(&health, index, reason, &log).await;
+                return;
+            }
+        };
+        let mut child_lines = BufReader::new(stdout).lines();
+
+        // Re-handshake a respawned hop, swallowing its reply to the
+        // replayed request; anything else it sends is forwarded as usual.
+        let mut awaiting_reply = None;
+        if replay {
+            if let Some(init) = &handshake {
+                if write_line(&mut stdin, init).await.is_ok() {
+                    awaiting_reply = request_id(init);
+                }
+            }
+        }
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => {
+                    let _ = process.kill().await;
+                    return;
+                }
+                line = up_lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        if handshake.is_none() {
+                            handshake = Some(line.clone());
+                        }
+                        if write_line(&mut stdin, &line).await.is_err() {
+                            break;
+                        }
+                    }
+                    // The conductor went away: nothing left to relay.
+                    Ok(None) => {
+                        let _ = process.kill().await;
+                        return;
+                    }
+                    Err(e) => {
+                        let _ = process.kill().await;
+                        let reason =
+                            format!("proxy {:?}: reading from the chain failed: {e}", spec.name);
+                        mark_failed(&health, index, reason, &log).await;
+                        return;
+                    }
+                },
+                line = child_lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        if awaiting_reply.is_some() && response_id(&line) == awaiting_reply {
+                            awaiting_reply = None;
+                            continue;
+                        }
+                        if let Err(e) = write_line(&mut up_write, &line).await {
+                            let _ = process.kill().await;
+                            let reason = format!(
+                                "proxy {:?}: writing to the chain failed: {e}",
+                                spec.name
+                            );
+                            mark_failed(&health, index, reason, &log).await;
+                            return;
+                        }
+                    }
+                    // Subprocess closed stdout: treat it as dead.
+                    _ => break,
+                },
+            }
+        }
+
+        // Reap the dead subprocess and decide whether to bring it back.
+        let _ = process.kill().await;
+        if !spec.restart {
+            let reason = format!("proxy {:?} exited and restart is disabled", spec.name);
+            mark_failed(&health, index, reason, &log).await;
+            return;
+        }
+        if restarts == MAX_RESTARTS {
+            let reason = format!("proxy {:?} exited after {MAX_RESTARTS} restarts", spec.name);
+            mark_failed(&health, index, reason, &log).await;
+            return;
+        }
+        if let Some(hop) = health.lock().await.hops.get_mut(index) {
+            hop.alive = false;
+            hop.pid = None;
+        }
+        let backoff = RESTART_BACKOFF
+            .saturating_mul(1 << restarts.min(16))
+            .min(MAX_RESTART_BACKOFF);
+        tokio::select! {
+            _ = &mut shutdown => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        restarts += 1;
+
+        match AgentProcess::spawn(&spec.command, spec.cwd.as_deref(), &spec.env, &log).await {
+            Ok(respawned) => {
+                let mut guard = health.lock().await;
+                if let Some(hop) = guard.hops.get_mut(index) {
+                    hop.restarts += 1;
+                    hop.alive = true;
+                    hop.pid = respawned.child.id();
+                    log.warning(format!(
+                        "proxy {:?} exited; restarted (restart #{})",
+                        spec.name, hop.restarts
+                    ));
+                }
+                process = respawned;
+                replay = true;
+            }
+            Err(e) => {
+                let reason = format!("proxy {:?} could not be restarted: {e}", spec.name);
+                mark_failed(&health, index, reason, &log).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Record that hop `index` is down for good and the chain has failed.
+async fn mark_failed(health: &Mutex<ChainHealth>, index: usize, reason: String, log: &LogSink) {
+    log.error(reason.clone());
+    let mut guard = health.lock().await;
+    if let Some(hop) = guard.hops.get_mut(index) {
+        hop.alive = false;
+        hop.pid = None;
+    }
+    guard.failed.get_or_insert(reason);
+}
+
+/// Write one newline-terminated message and flush it.
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, line: &str) -> std::io::Result<()> {
+    writer.write_all(line.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await
+}
+
+/// The JSON-RPC `id` of a request line, if it is a request.
+fn request_id(line: &str) -> Option<serde_json::Value> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    value.get("method")?;
+    value.get("id").cloned()
+}
+
+/// The JSON-RPC `id` of a response line, if it is a response.
+fn response_id(line: &str) -> Option<serde_json::Value> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    if value.get("method").is_some() {
+        return None;
+    }
+    value.get("id").cloned()
+}
+
 /// Register proxy types on the Python module.
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ProxyConfig>()?;
     m.add_class::<RustProxyChain>()?;
+    m.add_class::<RustProxyChainHandle>()?;
     Ok(())
 }
@@ -7,16 +7,65 @@
 use crate::error::ConduitError;
 use crate::types::ToolDefinition;
 use pyo3::prelude::*;
+use pyo3::types::PyCFunction;
+use serde::Serialize;
+use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex, Semaphore};
+
+/// Optional Python hook that may rewrite a tool's input before it is used:
+/// `middleware(tool_name: str, tool_input: dict) -> dict | None`.
+///
+/// Shared between the tool registry (SDK-hosted tools) and the client's
+/// permission flow (inputs forwarded from the agent).
+pub(crate) type InputMiddleware = Arc<std::sync::Mutex<Option<Arc<PyObject>>>>;
 
 /// A registered tool with its Python callback.
 struct RegisteredTool {
-    #[allow(dead_code)]
     definition: ToolDefinition,
-    /// Python callable: `async def handler(input: dict) -> str`
-    callback: PyObject,
+    /// Python callable: `async def handler(**input) -> str`, called with the
+    /// input object's fields as keyword arguments.
+    ///
+    /// Shared via `Arc` so invocations can take it out of the map without
+    /// holding the registry lock or the GIL.
+    callback: Arc<PyObject>,
+    /// Compiled `input_schema`, or `None` unless registered with
+    /// `strict_schema=True`.
+    validator: Option<jsonschema::Validator>,
+    /// Caps concurrent invocations when `max_concurrency` is set.
+    semaphore: Option<Arc<Semaphore>>,
+    max_concurrency: Option<usize>,
+    /// Per-invocation deadline for the callback.
+    timeout: Option<Duration>,
+    /// Invocations currently running the callback.
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Per-tool numbers reported by `RustToolRegistry.stats()`.
+#[derive(Serialize)]
+struct ToolStats {
+    in_flight: usize,
+    max_concurrency: Option<usize>,
+    timeout_secs: Option<f64>,
+}
+
+/// Counts one running invocation for as long as it is alive.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn enter(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 /// Rust-side tool registry exposed to Python.
@@ -27,6 +76,7 @@ struct RegisteredTool {
 #[pyclass]
 pub struct RustToolRegistry {
     tools: Arc<Mutex<HashMap<String, RegisteredTool>>>,
+    input_middleware: InputMiddleware,
 }
 
 #[pymethods]
@@ -35,23 +85,76 @@ impl RustToolRegistry {
     fn new() -> Self {
         Self {
             tools: Arc::new(Mutex::new(HashMap::new())),
+            input_middleware: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
+    /// Set (or clear, with `None`) the tool input middleware.
+    ///
+    /// The callable receives `(tool_name, tool_input)` with the input as a
+    /// dict and returns the rewritten dict, or `None` to leave it as-is. It
+    /// may be sync or async, and runs before schema validation.
+    #[pyo3(signature = (callback=None))]
+    fn set_input_middleware(&self, callback: Option<PyObject>) {
+        *self.input_middleware.lock().unwrap() = callback.map(Arc::new);
+    }
+
     /// Register a tool with its definition and Python callback.
+    ///
+    /// With `strict_schema` every invocation's input is validated against
+    /// `definition.input_schema` before the callback runs, so the schema
+    /// itself must be valid JSON Schema. Off by default: input is passed
+    /// through unchecked.
+    ///
+    /// `max_concurrency` caps how many invocations of this tool run at
+    /// once (extra calls wait for a slot); `timeout_secs` bounds each
+    /// callback, cancelling it and failing the invocation when exceeded.
+    #[pyo3(signature = (definition, callback, strict_schema=false, max_concurrency=None, timeout_secs=None))]
     fn register<'py>(
         &self,
         py: Python<'py>,
         definition: ToolDefinition,
         callback: PyObject,
+        strict_schema: bool,
+        max_concurrency: Option<usize>,
+        timeout_secs: Option<f64>,
     ) -> PyResult<Bound<'py, PyAny>> {
         let tools = self.tools.clone();
         let name = definition.name.clone();
 
+        if max_concurrency == Some(0) {
+            return Err(ConduitError::Tool("max_concurrency must be at least 1".into()).into());
+        }
+        let timeout = match timeout_secs {
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(secs) => {
+                return Err(ConduitError::Tool(format!(
+                    "timeout_secs must be positive, got {secs}"
+                ))
+                .into())
+            }
+            None => None,
+        };
+
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let validator = if strict_schema {
+                let schema: Value =
+                    serde_json::from_str(&definition.input_schema).map_err(ConduitError::from)?;
+                let validator = jsonschema::validator_for(&schema).map_err(|e| {
+                    ConduitError::Tool(format!("invalid input schema for tool {name}: {e}"))
+                })?;
+                Some(validator)
+            } else {
+                None
+            };
             let tool = RegisteredTool {
                 definition,
-                callback,
+                callback: Arc::new(callback),
+                validator,
+                semaphore: max_concurrency.map(|n| Arc::new(Semaphore::new(n))),
+                max_concurrency,
+                timeout,
+                in_flight: Arc::new(AtomicUsize::new(0)),
             };
             tools.lock().await.insert(name, tool);
             Ok(())
@@ -78,6 +181,59 @@ impl RustToolRegistry {
         })
     }
 
+    /// The registered tools in MCP `tools/list` shape, as a JSON array of
+    /// `{"name", "description", "inputSchema"}` objects sorted by name.
+    ///
+    /// `inputSchema` is embedded as an object, not a string. Fails if any
+    /// stored schema is not valid JSON.
+    fn manifest<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let tools = self.tools.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let map = tools.lock().await;
+            let mut names: Vec<&String> = map.keys().collect();
+            names.sort();
+
+            let mut entries = Vec::with_capacity(names.len());
+            for name in names {
+                let definition = &map[name].definition;
+                let schema: Value =
+                    serde_json::from_str(&definition.input_schema).map_err(|e| {
+                        ConduitError::Tool(format!("tool {name} has an invalid input schema: {e}"))
+                    })?;
+                entries.push(json!({
+                    "name": definition.name,
+                    "description": definition.description,
+                    "inputSchema": schema,
+                }));
+            }
+            Ok(Value::Array(entries).to_string())
+        })
+    }
+
+    /// Per-tool invocation stats as a JSON object keyed by tool name:
+    /// `{"in_flight": int, "max_concurrency": int | null, "timeout_secs": float | null}`.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let tools = self.tools.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let stats: HashMap<String, ToolStats> = tools
+                .lock()
+                .await
+                .iter()
+                .map(|(name, tool)| {
+                    let stats = ToolStats {
+                        in_flight: tool.in_flight.load(Ordering::SeqCst),
+                        max_concurrency: tool.max_concurrency,
+                        timeout_secs: tool.timeout.map(|t| t.as_secs_f64()),
+                    };
+                    (name.clone(), stats)
+                })
+                .collect();
+            Ok(serde_json::to_string(&stats).map_err(ConduitError::from)?)
+        })
+    }
+
     /// Invoke a tool by name with the given JSON input string.
     ///
     /// Parses `input_json` as a dict and calls the registered async Python
@@ -89,27 +245,20 @@ impl RustToolRegistry {
         input_json: String,
     ) -> PyResult<Bound<'py, PyAny>> {
         let tools = self.tools.clone();
+        let middleware = self.input_middleware.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            // Get the callback and start the coroutine under the GIL,
-            // then await outside the GIL.
-            let result_future = Python::with_gil(|py| -> PyResult<_> {
-                let map = tools.blocking_lock();
-                let tool = map.get(&name).ok_or_else(|| {
-                    ConduitError::Tool(format!("tool not found: {name}"))
-                })?;
-
-                // Parse JSON input to a Python dict for **kwargs.
-                let json_mod = py.import("json")?;
-                let parsed = json_mod.call_method1("loads", (&input_json,))?;
-                let kwargs = parsed.downcast::<pyo3::types::PyDict>()?;
-
-                // Call the async callback → get a coroutine → convert to Rust future.
-                let coro = tool.callback.bind(py).call((), Some(kwargs))?;
-                pyo3_async_runtimes::tokio::into_future(coro)
-            })?;
+            let input_json = apply_input_middleware(&middleware, &name, input_json).await?;
+            let problems = input_problems(&tools, &name, &input_json).await;
+            if !problems.is_empty() {
+                return Err(ConduitError::Tool(format!(
+                    "invalid input for tool {name}: {}",
+                    problems.join("; ")
+                ))
+                .into());
+            }
 
-            let result_obj = result_future.await?;
+            let result_obj = run_tool(&tools, &name, &input_json).await??;
 
             // Convert the Python result to a JSON string.
             Python::with_gil(|py| -> PyResult<String> {
@@ -121,6 +270,371 @@ impl RustToolRegistry {
             })
         })
     }
+
+    /// Invoke a tool and return an MCP-style tool result as a JSON string.
+    ///
+    /// The result has the shape `{"content": [...], "is_error": bool}`.
+    /// String results become a single text block; dicts that already carry
+    /// a `content` list are passed through; anything else is serialized to
+    /// JSON text. An exception raised by the callback yields
+    /// `is_error: true` with the message as a text block instead of
+    /// propagating, and so does input that fails schema validation (the
+    /// callback is not called). Unknown tools and non-object input still
+    /// raise.
+    fn invoke_structured<'py>(
+        &self,
+        py: Python<'py>,
+        name: String,
+        input_json: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.handle();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let result = handle.invoke_structured(&name, input_json).await?;
+            Ok(result.to_string())
+        })
+    }
+}
+
+impl RustToolRegistry {
+    /// A shareable view of this registry's tools for the client bridge.
+    pub(crate) fn handle(&self) -> ToolRegistryHandle {
+        ToolRegistryHandle {
+            tools: self.tools.clone(),
+            input_middleware: self.input_middleware.clone(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Client bridge
+// ---------------------------------------------------------------------------
+
+/// Shared view of a registry's tools, handed to a connected client so the
+/// agent's MCP `tools/call` requests run the registered Python callbacks.
+#[derive(Clone)]
+pub(crate) struct ToolRegistryHandle {
+    tools: Arc<Mutex<HashMap<String, RegisteredTool>>>,
+    input_middleware: InputMiddleware,
+}
+
+impl ToolRegistryHandle {
+    /// Whether a tool named `name` is registered.
+    pub(crate) async fn contains(&self, name: &str) -> bool {
+        self.tools.lock().await.contains_key(name)
+    }
+
+    /// This view with a client's own input middleware in place of the
+    /// registry's, if the client has one set.
+    pub(crate) fn with_input_middleware(mut self, middleware: &InputMiddleware) -> Self {
+        if middleware.lock().unwrap().is_some() {
+            self.input_middleware = middleware.clone();
+        }
+        self
+    }
+
+    /// Invoke a tool, folding middleware, validation, callback and timeout
+    /// failures into a `{"content", "is_error"}` result.
+    pub(crate) async fn invoke_structured(
+        &self,
+        name: &str,
+        input_json: String,
+    ) -> PyResult<Value> {
+        let input_json =
+            match apply_input_middleware(&self.input_middleware, name, input_json).await {
+                Ok(input_json) => input_json,
+                Err(e) => {
+                    let message = Python::with_gil(|py| e.value(py).to_string());
+                    return Ok(error_result(&message));
+                }
+            };
+        let problems = input_problems(&self.tools, name, &input_json).await;
+        if !problems.is_empty() {
+            let message = format!("invalid input: {}", problems.join("; "));
+            return Ok(error_result(&message));
+        }
+
+        let outcome = run_tool(&self.tools, name, &input_json).await?;
+
+        Python::with_gil(|py| match outcome {
+            Ok(result_obj) => structured_result(result_obj.bind(py)),
+            Err(e) => Ok(error_result(&e.value(py).to_string())),
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Where a [`PyTask`] reports its outcome.
+type TaskDone = Arc<std::sync::Mutex<Option<oneshot::Sender<PyResult<PyObject>>>>>;
+
+/// A Python awaitable running as a task on its event loop.
+///
+/// Unlike the future from `into_future`, which leaves the coroutine running
+/// when dropped, this keeps the task so a caller that gives up on it can
+/// cancel it and wait for it to unwind.
+pub(crate) struct PyTask {
+    event_loop: PyObject,
+    task: Arc<std::sync::Mutex<Option<PyObject>>>,
+    done: oneshot::Receiver<PyResult<PyObject>>,
+}
+
+impl PyTask {
+    /// Schedule `awaitable` on the event loop of the current task locals.
+    pub(crate) fn spawn(awaitable: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let py = awaitable.py();
+        let event_loop = pyo3_async_runtimes::tokio::get_current_locals(py)?
+            .event_loop(py)
+            .unbind();
+        let task = Arc::new(std::sync::Mutex::new(None));
+        let (done_tx, done) = oneshot::channel();
+        let done_tx: TaskDone = Arc::new(std::sync::Mutex::new(Some(done_tx)));
+
+        let finish = done_tx.clone();
+        let on_done = PyCFunction::new_closure(py, None, None, move |args, _kwargs| {
+            let result = args.get_item(0)?.call_method0("result").map(Bound::unbind);
+            if let Some(tx) = finish.lock().unwrap().take() {
+                let _ = tx.send(result);
+            }
+            PyResult::Ok(())
+        })?
+        .unbind();
+        let slot = task.clone();
+        let awaitable = awaitable.clone().unbind();
+        // `ensure_future` has to run on the loop's own thread.
+        let start = PyCFunction::new_closure(py, None, None, move |args, _kwargs| {
+            let py = args.py();
+            let started = py
+                .import("asyncio")?
+                .call_method1("ensure_future", (&awaitable,))
+                .and_then(|task| {
+                    task.call_method1("add_done_callback", (&on_done,))?;
+                    Ok(task)
+                });
+            match started {
+                Ok(task) => *slot.lock().unwrap() = Some(task.unbind()),
+                Err(e) => {
+                    if let Some(tx) = done_tx.lock().unwrap().take() {
+                        let _ = tx.send(Err(e));
+                    }
+                }
+            }
+            PyResult::Ok(())
+        })?;
+        event_loop
+            .bind(py)
+            .call_method1("call_soon_threadsafe", (start,))?;
+        Ok(Self {
+            event_loop,
+            task,
+            done,
+        })
+    }
+
+    /// Wait for the task's result.
+    pub(crate) async fn wait(self) -> PyResult<PyObject> {
+        self.done.await.unwrap_or_else(|_| {
+            Err(ConduitError::Other("task was dropped before it finished".into()).into())
+        })
+    }
+
+    /// Wait up to `limit` for the task's result. Past that, cancel the task
+    /// and return `None` once it has unwound.
+    pub(crate) async fn wait_for(mut self, limit: Duration) -> Option<PyResult<PyObject>> {
+        if let Ok(result) = tokio::time::timeout(limit, &mut self.done).await {
+            return Some(result.unwrap_or_else(|_| {
+                Err(ConduitError::Other("task was dropped before it finished".into()).into())
+            }));
+        }
+        // If the loop is gone the task never finishes, so don't wait on it.
+        if self.cancel().is_ok() {
+            let _ = self.done.await;
+        }
+        None
+    }
+
+    /// Ask the loop to cancel the task.
+    fn cancel(&self) -> PyResult<()> {
+        Python::with_gil(|py| {
+            let slot = self.task.clone();
+            // Queued behind the task's start, so the slot is filled by then.
+            let cancel = PyCFunction::new_closure(py, None, None, move |args, _kwargs| {
+                if let Some(task) = slot.lock().unwrap().as_ref() {
+                    task.bind(args.py()).call_method0("cancel")?;
+                }
+                PyResult::Ok(())
+            })?;
+            self.event_loop
+                .bind(py)
+                .call_method1("call_soon_threadsafe", (cancel,))?;
+            Ok(())
+        })
+    }
+}
+
+/// Run `input_json` through the input middleware, if one is set.
+pub(crate) async fn apply_input_middleware(
+    middleware: &InputMiddleware,
+    tool_name: &str,
+    input_json: String,
+) -> PyResult<String> {
+    let Some(callback) = middleware.lock().unwrap().clone() else {
+        return Ok(input_json);
+    };
+
+    let mut rewritten: Option<PyObject> = None;
+    let pending = Python::with_gil(|py| -> PyResult<_> {
+        let json_mod = py.import("json")?;
+        let input = json_mod.call_method1("loads", (&input_json,))?;
+        let result = callback.bind(py).call1((tool_name, input))?;
+        // If the middleware is a coroutine function, await it outside the GIL.
+        if result.hasattr("__await__")? {
+            return Ok(Some(pyo3_async_runtimes::tokio::into_future(result)?));
+        }
+        rewritten = Some(result.unbind());
+        Ok(None)
+    })?;
+    if let Some(future) = pending {
+        rewritten = Some(future.await?);
+    }
+
+    Python::with_gil(|py| match rewritten {
+        Some(obj) if !obj.is_none(py) => {
+            let json_mod = py.import("json")?;
+            json_mod.call_method1("dumps", (obj.bind(py),))?.extract()
+        }
+        _ => Ok(input_json),
+    })
+}
+
+/// Schema violations in `input_json` for a strictly-validated tool.
+///
+/// Empty when the input conforms, the tool is lenient or unknown, or the
+/// input is not JSON (the latter two are reported by `run_tool`).
+async fn input_problems(
+    tools: &Mutex<HashMap<String, RegisteredTool>>,
+    name: &str,
+    input_json: &str,
+) -> Vec<String> {
+    let Ok(input) = serde_json::from_str::<Value>(input_json) else {
+        return Vec::new();
+    };
+    let map = tools.lock().await;
+    let Some(validator) = map.get(name).and_then(|t| t.validator.as_ref()) else {
+        return Vec::new();
+    };
+    validator
+        .iter_errors(&input)
+        .map(|e| {
+            let path = e.instance_path.to_string();
+            if path.is_empty() {
+                e.to_string()
+            } else {
+                format!("{path}: {e}")
+            }
+        })
+        .collect()
+}
+
+/// Look up `name` and run its callback with `input_json` as kwargs,
+/// honouring the tool's concurrency limit and timeout.
+///
+/// The outer error covers an unknown tool or unparseable input; the inner
+/// one is the callback's own outcome (an exception it raised, or a
+/// timeout).
+async fn run_tool(
+    tools: &Mutex<HashMap<String, RegisteredTool>>,
+    name: &str,
+    input_json: &str,
+) -> PyResult<PyResult<PyObject>> {
+    // Clone what we need out under a short async lock so no registry lock
+    // is held (or blocked on) while we hold the GIL.
+    let (callback, semaphore, timeout, in_flight) = {
+        let map = tools.lock().await;
+        let tool = map
+            .get(name)
+            .ok_or_else(|| ConduitError::Tool(format!("tool not found: {name}")))?;
+        (
+            tool.callback.clone(),
+            tool.semaphore.clone(),
+            tool.timeout,
+            tool.in_flight.clone(),
+        )
+    };
+
+    // The permit and in-flight marker are released on every exit path, and
+    // not before the callback has finished; a timed-out one is cancelled
+    // and keeps them until it has unwound.
+    let _permit = match semaphore {
+        Some(semaphore) => Some(
+            semaphore
+                .acquire_owned()
+                .await
+                .map_err(|_| ConduitError::Tool(format!("tool {name} is shutting down")))?,
+        ),
+        None => None,
+    };
+    let _in_flight = InFlight::enter(&in_flight);
+
+    // Start the coroutine as a task under the GIL, then await outside it.
+    let started = Python::with_gil(|py| -> PyResult<_> {
+        // Parse JSON input to a Python dict for **kwargs.
+        let json_mod = py.import("json")?;
+        let parsed = json_mod.call_method1("loads", (input_json,))?;
+        let kwargs = parsed.downcast::<pyo3::types::PyDict>()?;
+
+        // Call the async callback → get a coroutine → run it as a task.
+        Ok(callback
+            .bind(py)
+            .call((), Some(kwargs))
+            .and_then(|coro| PyTask::spawn(&coro)))
+    })?;
+    let task = match started {
+        Ok(task) => task,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    Ok(match timeout {
+        Some(limit) => match task.wait_for(limit).await {
+            Some(result) => result,
+            None => Err(ConduitError::Timeout(format!(
+                "tool {name} timed out after {}s",
+                limit.as_secs_f64()
+            ))
+            .into()),
+        },
+        None => task.wait().await,
+    })
+}
+
+/// Shape a successful callback result as an MCP tool result.
+fn structured_result(result: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if let Ok(text) = result.extract::<String>() {
+        return Ok(json!({ "content": [{ "type": "text", "text": text }], "is_error": false }));
+    }
+
+    let json_mod = result.py().import("json")?;
+    let dumped: String = json_mod.call_method1("dumps", (result,))?.extract()?;
+    let value: Value = serde_json::from_str(&dumped).map_err(ConduitError::from)?;
+
+    // Already MCP-shaped: keep the callback's content blocks as-is.
+    if let Some(content) = value.get("content").filter(|c| c.is_array()) {
+        let is_error = value
+            .get("is_error")
+            .or_else(|| value.get("isError"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        return Ok(json!({ "content": content, "is_error": is_error }));
+    }
+
+    Ok(json!({ "content": [{ "type": "text", "text": dumped }], "is_error": false }))
+}
+
+/// An MCP tool result reporting `message` as an error.
+fn error_result(message: &str) -> Value {
+    json!({ "content": [{ "type": "text", "text": message }], "is_error": true })
 }
 
 /// Register tool types on the Python module.
@@ -0,0 +1,150 @@
+//! `tracing` integration.
+//!
+//! The crate emits `tracing` spans and events around the request/response
+//! lifecycle, session operations and tool dispatch. Nothing is recorded
+//! until `enable_tracing()` installs a subscriber, which formats each event
+//! and forwards it to Python's `logging` under the `conduit_sdk.trace`
+//! logger.
+
+use std::io;
+use std::sync::Mutex;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::PyString;
+use tracing::{Level, Metadata};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, Registry};
+
+use crate::error::ConduitError;
+
+/// Python logger the formatted events go to.
+const PYTHON_LOGGER: &str = "conduit_sdk.trace";
+
+/// Handle to the installed subscriber's filter, set by the first
+/// `enable_tracing()`; later calls only change the level.
+static FILTER: Mutex<Option<reload::Handle<Targets, Registry>>> = Mutex::new(None);
+
+/// Forward this crate's `tracing` events at `level` and above to Python's
+/// `logging` (logger `conduit_sdk.trace`).
+///
+/// `level` is `"trace"`, `"debug"`, `"info"`, `"warning"`, `"error"` or
+/// `"off"`. The subscriber is installed process-wide on the first call;
+/// calling again just changes the level. Raises `ConduitError` if another
+/// library already installed a global `tracing` subscriber.
+#[pyfunction]
+#[pyo3(signature = (level="info"))]
+fn enable_tracing(level: &str) -> PyResult<()> {
+    let level = match level.to_ascii_lowercase().as_str() {
+        "warning" => LevelFilter::WARN,
+        other => other
+            .parse::<LevelFilter>()
+            .map_err(|_| PyValueError::new_err(format!("unknown tracing level: {level:?}")))?,
+    };
+    let targets = Targets::new().with_target(env!("CARGO_CRATE_NAME"), level);
+
+    let mut filter = FILTER.lock().unwrap();
+    if let Some(handle) = filter.as_ref() {
+        return handle.reload(targets).map_err(|e| {
+            ConduitError::Other(format!("could not change tracing level: {e}")).into()
+        });
+    }
+    let (targets, handle) = reload::Layer::new(targets);
+    let subscriber = Registry::default().with(targets).with(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .without_time()
+            .with_level(false)
+            .with_writer(PythonLogger),
+    );
+    tracing::subscriber::set_global_default(subscriber).map_err(|_| {
+        ConduitError::Other("a global tracing subscriber is already installed".into())
+    })?;
+    *filter = Some(handle);
+    Ok(())
+}
+
+/// Hands each formatted event to the Python logger at the event's level.
+struct PythonLogger;
+
+impl<'a> MakeWriter<'a> for PythonLogger {
+    type Writer = PythonLogLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        PythonLogLine::new(&Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        PythonLogLine::new(meta.level())
+    }
+}
+
+/// One formatted event, logged when the formatter drops it.
+struct PythonLogLine {
+    /// Python `logging` level number.
+    level: u8,
+    line: Vec<u8>,
+}
+
+impl PythonLogLine {
+    fn new(level: &Level) -> Self {
+        // `logging` has no TRACE; 5 sits below DEBUG as is customary.
+        let level = match *level {
+            Level::TRACE => 5,
+            Level::DEBUG => 10,
+            Level::INFO => 20,
+            Level::WARN => 30,
+            Level::ERROR => 40,
+        };
+        Self {
+            level,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl io::Write for PythonLogLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PythonLogLine {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.line);
+        let line = line.trim_end();
+        if line.is_empty() {
+            return;
+        }
+        static LOGGER: GILOnceCell<PyObject> = GILOnceCell::new();
+        Python::with_gil(|py| {
+            let logged = LOGGER
+                .get_or_try_init(py, || {
+                    let logger = py
+                        .import("logging")?
+                        .call_method1("getLogger", (PYTHON_LOGGER,))?;
+                    Ok::<_, PyErr>(logger.unbind())
+                })
+                .and_then(|logger| logger.call_method1(py, "log", (self.level, line)));
+            // This is the route into Python logging, so a failure here can
+            // only go to `sys.unraisablehook`, with the line as context.
+            if let Err(e) = logged {
+                e.write_unraisable(py, Some(PyString::new(py, line).as_any()));
+            }
+        });
+    }
+}
+
+/// Register the tracing functions on the Python module.
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(enable_tracing, m)?)?;
+    Ok(())
+}
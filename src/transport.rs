@@ -1,28 +1,61 @@
 //! Transport layer: manages byte-stream connections to agent subprocesses.
 //!
 //! Wraps sacp's `ByteStreams` and provides subprocess management for spawning
-//! ACP-compatible agents. The Python layer never touches transport directly;
-//! it goes through [`crate::client::RustClient`].
+//! ACP-compatible agents, plus [`TcpTransport`] and (on Unix)
+//! [`UnixSocketTransport`] for agents that are already running, and
+//! [`ReplayTransport`] for playing back a recorded agent, and
+//! [`WireRecorder`] for capturing the traffic of a live one. The Python
+//! layer never touches transport directly; it goes through
+//! [`crate::client::RustClient`].
 
 use crate::error::{ConduitError, Result};
-use std::collections::HashMap;
+use crate::log::LogSink;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf, ReadHalf,
+    WriteHalf,
+};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
 use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
 
 /// Handle to a running agent subprocess and its I/O streams.
+///
+/// The agent runs in its own process group (a Job object on Windows, which
+/// it is put in while still suspended), so [`kill`](Self::kill) also takes
+/// down anything it spawned.
 pub struct AgentProcess {
     pub child: Child,
+    /// The agent's process group, whose ID is the agent's PID.
+    #[cfg(unix)]
+    process_group: Option<u32>,
+    /// Job object holding the agent and its descendants.
+    #[cfg(windows)]
+    job: Option<job::Job>,
 }
 
 impl AgentProcess {
     /// Spawn an agent subprocess from the given command and environment.
     ///
     /// The subprocess is started with stdin/stdout piped for ACP byte-stream
-    /// communication. Stderr is inherited for debug logging.
+    /// communication. Stderr is inherited for debug logging. Variables in
+    /// `command` are expanded first; see [`expand_command`]. Problems that
+    /// don't stop the spawn are logged to `log`.
     pub async fn spawn(
         command: &[String],
         cwd: Option<&str>,
         env: &HashMap<String, String>,
+        log: &LogSink,
     ) -> Result<Self> {
         if command.is_empty() {
             return Err(ConduitError::Connection(
@@ -30,10 +63,62 @@ impl AgentProcess {
             ));
         }
 
+        let command = expand_command(command, env, Some(log));
         let mut cmd = Command::new(&command[0]);
-        if command.len() > 1 {
-            cmd.args(&command[1..]);
+        cmd.args(&command[1..]);
+        Self::spawn_command(cmd, cwd, env, log)
+    }
+
+    /// Like [`spawn`](Self::spawn), but runs `command` through the platform
+    /// shell (`sh -c`, or `cmd /C` on Windows) so pipes, redirects and the
+    /// like work.
+    ///
+    /// The elements are joined with single spaces and handed to the shell
+    /// verbatim: nothing is quoted or escaped, and variables are left for
+    /// the shell to expand. Any argument that may contain spaces or shell
+    /// metacharacters must be quoted by the caller, and untrusted input
+    /// must never reach it. On Windows the line goes after `/C` as a raw
+    /// argument, so `cmd` sees exactly what was joined rather than a copy
+    /// re-quoted under the C runtime's rules, which `cmd` does not follow.
+    pub async fn spawn_shell(
+        command: &[String],
+        cwd: Option<&str>,
+        env: &HashMap<String, String>,
+        log: &LogSink,
+    ) -> Result<Self> {
+        if command.is_empty() {
+            return Err(ConduitError::Connection(
+                "agent command must not be empty".into(),
+            ));
         }
+
+        let line = command.join(" ");
+        #[cfg(windows)]
+        let cmd = {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C").raw_arg(&line);
+            cmd
+        };
+        #[cfg(not(windows))]
+        let cmd = {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(&line);
+            cmd
+        };
+        Self::spawn_command(cmd, cwd, env, log)
+    }
+
+    fn spawn_command(
+        mut cmd: Command,
+        cwd: Option<&str>,
+        env: &HashMap<String, String>,
+        #[cfg_attr(not(windows), allow(unused_variables))] log: &LogSink,
+    ) -> Result<Self> {
+        #[cfg(unix)]
+        cmd.process_group(0);
+        // Held until the agent is in its job, so nothing it starts escapes.
+        #[cfg(windows)]
+        cmd.creation_flags(windows_sys::Win32::System::Threading::CREATE_SUSPENDED);
         if let Some(dir) = cwd {
             cmd.current_dir(dir);
         }
@@ -44,11 +129,30 @@ impl AgentProcess {
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit());
 
-        let child = cmd
+        #[cfg_attr(not(windows), allow(unused_mut))]
+        let mut child = cmd
             .spawn()
             .map_err(|e| ConduitError::Connection(format!("failed to spawn agent: {e}")))?;
 
-        Ok(Self { child })
+        #[cfg(windows)]
+        let job = job::Job::assign(&child)
+            .map_err(|e| log.warning(format!("agent not placed in a job object: {e}")))
+            .ok();
+        #[cfg(windows)]
+        if let Err(e) = job::resume(&child) {
+            let _ = child.start_kill();
+            return Err(ConduitError::Connection(format!(
+                "failed to start agent: {e}"
+            )));
+        }
+
+        Ok(Self {
+            #[cfg(unix)]
+            process_group: child.id(),
+            #[cfg(windows)]
+            job,
+            child,
+        })
     }
 
     /// Take ownership of the child's stdin (for writing ACP messages).
@@ -67,11 +171,668 @@ impl AgentProcess {
             .ok_or_else(|| ConduitError::Transport("agent stdout already taken".into()))
     }
 
-    /// Terminate the agent subprocess.
+    /// Terminate the agent subprocess and every process it started.
+    ///
+    /// The whole group is signalled even if the agent itself already
+    /// exited, so children it left running go too, but only until the
+    /// agent is reaped: after that its PID, and with it the group ID, may
+    /// belong to an unrelated process.
     pub async fn kill(&mut self) -> Result<()> {
+        #[cfg(unix)]
+        if let Some(pgid) = self.process_group.take() {
+            // `id()` is `None` once the child has been waited on. Until
+            // then it is at worst a zombie, which keeps its PID reserved.
+            if self.child.id().is_some() {
+                // Safety: plain syscall; ESRCH (the group is already gone) is fine.
+                unsafe { libc::killpg(pgid as libc::pid_t, libc::SIGKILL) };
+            }
+        }
+        #[cfg(windows)]
+        if let Some(job) = self.job.take() {
+            job.terminate();
+        }
         self.child
             .kill()
             .await
             .map_err(|e| ConduitError::Transport(format!("failed to kill agent: {e}")))
     }
 }
+
+/// A TCP connection to an agent served over the network rather than
+/// spawned as a subprocess.
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Connect to the agent listening at `endpoint` (`host:port`).
+    pub async fn connect(endpoint: &str, log: &LogSink) -> Result<Self> {
+        let stream = TcpStream::connect(endpoint).await.map_err(|e| {
+            ConduitError::Connection(format!("failed to connect to agent at {endpoint}: {e}"))
+        })?;
+        // ACP messages are small and latency-sensitive.
+        if let Err(e) = stream.set_nodelay(true) {
+            log.warning(format!("could not set TCP_NODELAY: {e}"));
+        }
+        Ok(Self { stream })
+    }
+
+    /// Split into the read and write halves the ACP byte-stream transport
+    /// is built from.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        self.stream.into_split()
+    }
+}
+
+/// A Unix domain socket connection to an agent running on the same host.
+#[cfg(unix)]
+pub struct UnixSocketTransport {
+    stream: tokio::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    /// Connect to the agent listening on the socket at `path`.
+    pub async fn connect(path: &str) -> Result<Self> {
+        let stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+            let reason = match e.kind() {
+                std::io::ErrorKind::ConnectionRefused => "nothing is listening on it".into(),
+                std::io::ErrorKind::NotFound => "no such socket".into(),
+                _ => e.to_string(),
+            };
+            ConduitError::Connection(format!(
+                "failed to connect to agent socket {path}: {reason}"
+            ))
+        })?;
+        Ok(Self { stream })
+    }
+
+    /// Split into the read and write halves the ACP byte-stream transport
+    /// is built from.
+    pub fn into_split(
+        self,
+    ) -> (
+        tokio::net::unix::OwnedReadHalf,
+        tokio::net::unix::OwnedWriteHalf,
+    ) {
+        self.stream.into_split()
+    }
+}
+
+/// An in-process agent that plays back a recording, for deterministic tests.
+///
+/// The recording is newline-delimited JSON: the messages an agent sent, in
+/// order. Each request the client sends is answered by writing out the
+/// recording up to and including its next response, re-addressed to the
+/// request's ID, so the notifications recorded before a response arrive
+/// just as they did live. `initialize` gets a stub answer unless the
+/// recording opens with an `initialize` response. Once the recording runs
+/// out, requests are answered with an error.
+///
+/// A file written by [`WireRecorder`] works as a recording too: its
+/// inbound messages are played back and the rest ignored.
+pub struct ReplayTransport {
+    stream: DuplexStream,
+}
+
+impl ReplayTransport {
+    /// Load the recording at `path` and start playing it back.
+    pub async fn open(path: &str) -> Result<Self> {
+        let text = tokio::fs::read_to_string(path).await.map_err(|e| {
+            ConduitError::Connection(format!("failed to read replay recording {path}: {e}"))
+        })?;
+        let mut recording = VecDeque::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut message = serde_json::from_str::<Value>(line)
+                .ok()
+                .filter(Value::is_object)
+                .ok_or_else(|| {
+                    ConduitError::Protocol(format!(
+                        "replay recording {path}, line {}: expected a JSON object",
+                        n + 1
+                    ))
+                })?;
+            // An entry of a wire recording rather than a bare message.
+            if let Some(direction) = message.get("direction") {
+                if direction != Direction::In.as_str() {
+                    continue;
+                }
+                message = message["message"].take();
+            }
+            recording.push_back(message);
+        }
+        let (client, agent) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(play_back(agent, recording));
+        Ok(Self { stream: client })
+    }
+
+    /// Split into the read and write halves the ACP byte-stream transport
+    /// is built from.
+    pub fn into_split(self) -> (ReadHalf<DuplexStream>, WriteHalf<DuplexStream>) {
+        tokio::io::split(self.stream)
+    }
+}
+
+/// The agent side of a [`ReplayTransport`]: answer each request from the
+/// client out of `recording` until either side hangs up.
+async fn play_back(agent: DuplexStream, mut recording: VecDeque<Value>) {
+    let (read, mut write) = tokio::io::split(agent);
+    let mut lines = tokio::io::BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        // Notifications and responses from the client need no answer.
+        let (Some(id), Some(method)) = (message.get("id"), message["method"].as_str()) else {
+            continue;
+        };
+        let mut out = Vec::new();
+        if method == "initialize" && !opens_with_initialize(&recording) {
+            let version =
+                serde_json::to_value(sacp::schema::ProtocolVersion::LATEST).unwrap_or_default();
+            out.push(serde_json::json!({
+                "id": id,
+                "result": {"protocolVersion": version, "agentCapabilities": {}, "authMethods": []},
+            }));
+        } else {
+            loop {
+                let Some(mut recorded) = recording.pop_front() else {
+                    let message = format!("replay recording has no response left for {method}");
+                    out.push(serde_json::json!({
+                        "id": id,
+                        "error": {"code": -32603, "message": message},
+                    }));
+                    break;
+                };
+                let is_response = recorded.get("method").is_none();
+                if is_response {
+                    recorded["id"] = id.clone();
+                }
+                out.push(recorded);
+                if is_response {
+                    break;
+                }
+            }
+        }
+        for mut message in out {
+            message["jsonrpc"] = "2.0".into();
+            let mut line = message.to_string();
+            line.push('\n');
+            if write.write_all(line.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Whether the next recorded response answers `initialize`.
+fn opens_with_initialize(recording: &VecDeque<Value>) -> bool {
+    recording
+        .iter()
+        .find(|message| message.get("method").is_none())
+        .is_some_and(|response| response["result"].get("protocolVersion").is_some())
+}
+
+/// Which way a message crossed the wire, from the client's side.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Direction {
+    /// Sent by the agent.
+    In,
+    /// Sent by the client.
+    Out,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+/// Tees ACP traffic into a newline-delimited JSON file, one
+/// `{"direction", "timestamp", "message"}` object per message.
+///
+/// The transport only hands complete lines to a channel; a dedicated task
+/// parses and writes them through a buffer, flushing whenever it catches
+/// up, so a slow disk never holds up the connection. Entries are appended,
+/// so a file kept across reconnects holds every connection in turn.
+#[derive(Clone)]
+pub(crate) struct WireRecorder {
+    tx: mpsc::UnboundedSender<RecorderMessage>,
+}
+
+/// What the transport hands the writer task.
+enum RecorderMessage {
+    /// One line that crossed the wire, with its Unix timestamp.
+    Line(Direction, f64, Vec<u8>),
+    /// Flush, stop, and answer once everything before it is on disk.
+    Close(oneshot::Sender<()>),
+}
+
+impl WireRecorder {
+    /// Open (or create) the file at `path` and start the writer task.
+    pub(crate) async fn create(path: &str, log: LogSink) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| {
+                ConduitError::Connection(format!("failed to open record file {path}: {e}"))
+            })?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_recording(file, rx, log));
+        Ok(Self { tx })
+    }
+
+    fn record(&self, direction: Direction, line: Vec<u8>) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        let line = RecorderMessage::Line(direction, timestamp, line);
+        let _ = self.tx.send(line);
+    }
+
+    /// Write out everything recorded so far and stop the writer task,
+    /// returning once it has. Lines recorded afterwards are dropped.
+    pub(crate) async fn close(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(RecorderMessage::Close(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+/// The writer task behind a [`WireRecorder`]; ends, flushed, once every
+/// sender is gone or on [`WireRecorder::close`].
+async fn write_recording(
+    file: tokio::fs::File,
+    mut rx: mpsc::UnboundedReceiver<RecorderMessage>,
+    log: LogSink,
+) {
+    let mut out = tokio::io::BufWriter::new(file);
+    while let Some(first) = rx.recv().await {
+        let mut next = Some(first);
+        while let Some(message) = next {
+            let (direction, timestamp, line) = match message {
+                RecorderMessage::Line(direction, timestamp, line) => (direction, timestamp, line),
+                RecorderMessage::Close(done) => {
+                    if let Err(e) = out.flush().await {
+                        log.error(format!("could not flush wire recording: {e}"));
+                    }
+                    let _ = done.send(());
+                    return;
+                }
+            };
+            // Keep lines that aren't JSON, as strings, rather than lose them.
+            let message = serde_json::from_slice::<Value>(&line)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&line).into_owned().into());
+            let mut entry = serde_json::json!({
+                "direction": direction.as_str(),
+                "timestamp": timestamp,
+                "message": message,
+            })
+            .to_string();
+            entry.push('\n');
+            if let Err(e) = out.write_all(entry.as_bytes()).await {
+                log.error(format!("could not write wire recording: {e}"));
+                return;
+            }
+            next = rx.try_recv().ok();
+        }
+        if let Err(e) = out.flush().await {
+            log.error(format!("could not flush wire recording: {e}"));
+            return;
+        }
+    }
+}
+
+/// Wraps one half of an agent connection and hands each complete line that
+/// passes through it to a [`WireRecorder`] and, if tapped, to a channel.
+/// With neither it is a plain pass-through.
+pub(crate) struct Recorded<T> {
+    inner: T,
+    recorder: Option<WireRecorder>,
+    /// Also gets each complete line, exactly as it crossed the wire.
+    tap: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    direction: Direction,
+    /// Bytes of a line not yet terminated.
+    partial: Vec<u8>,
+}
+
+impl<T> Recorded<T> {
+    pub(crate) fn new(inner: T, recorder: Option<WireRecorder>, direction: Direction) -> Self {
+        Self {
+            inner,
+            recorder,
+            tap: None,
+            direction,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Also send each complete line to `tap`.
+    pub(crate) fn tapped(mut self, tap: mpsc::UnboundedSender<Vec<u8>>) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    fn note(&mut self, bytes: &[u8]) {
+        if self.recorder.is_none() && self.tap.is_none() {
+            return;
+        }
+        self.partial.extend_from_slice(bytes);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let rest = self.partial.split_off(end + 1);
+            let mut line = std::mem::replace(&mut self.partial, rest);
+            line.pop();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            if let Some(tap) = &self.tap {
+                let _ = tap.send(line.clone());
+            }
+            if let Some(recorder) = &self.recorder {
+                recorder.record(self.direction, line);
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Recorded<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.note(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Recorded<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.note(&buf[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Wraps the writer to an agent and raises `broken` once a write fails
+/// because the agent closed its end (`ErrorKind::BrokenPipe`).
+pub(crate) struct WatchBrokenPipe<W> {
+    inner: W,
+    broken: Arc<AtomicBool>,
+}
+
+impl<W> WatchBrokenPipe<W> {
+    pub(crate) fn new(inner: W, broken: Arc<AtomicBool>) -> Self {
+        Self { inner, broken }
+    }
+
+    fn note<T>(&self, poll: Poll<std::io::Result<T>>) -> Poll<std::io::Result<T>> {
+        if let Poll::Ready(Err(e)) = &poll {
+            if e.kind() == std::io::ErrorKind::BrokenPipe {
+                self.broken.store(true, Ordering::Release);
+            }
+        }
+        poll
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for WatchBrokenPipe<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.note(poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.note(poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.note(poll)
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use std::io;
+    use tokio::process::Child;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+    use windows_sys::Win32::System::Threading::{OpenThread, ResumeThread, THREAD_SUSPEND_RESUME};
+
+    /// A Job object that kills its processes when terminated or closed.
+    pub struct Job(HANDLE);
+
+    // Safety: a job handle may be used and closed from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        /// Put `child` (and so everything it spawns later) in a new job.
+        pub fn assign(child: &Child) -> io::Result<Self> {
+            let process = child
+                .raw_handle()
+                .ok_or_else(|| io::Error::other("agent already exited"))?;
+            // Safety: FFI calls on a handle we own and the live child's handle.
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = Self(handle);
+                let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+                limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let set = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    std::ptr::addr_of!(limits).cast(),
+                    std::mem::size_of_val(&limits) as u32,
+                );
+                if set == 0 || AssignProcessToJobObject(job.0, process as HANDLE) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+
+        /// Kill every process in the job.
+        pub fn terminate(&self) {
+            // Safety: `self.0` is a live job handle.
+            unsafe { TerminateJobObject(self.0, 1) };
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // Safety: closing the handle we own; kill-on-close ends the job.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// Let `child`, spawned with `CREATE_SUSPENDED`, run: resume its
+    /// threads, of which a new process has just the one.
+    pub fn resume(child: &Child) -> io::Result<()> {
+        let pid = child
+            .id()
+            .ok_or_else(|| io::Error::other("agent already exited"))?;
+        // Safety: FFI calls on a snapshot and thread handles we own and
+        // close, with `entry` sized as the API requires.
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+            let mut entry: THREADENTRY32 = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+            let mut resumed = Err(io::Error::other("agent has no thread to resume"));
+            let mut more = Thread32First(snapshot, &mut entry) != 0;
+            while more {
+                if entry.th32OwnerProcessID == pid {
+                    let thread = OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID);
+                    resumed = if thread.is_null() || ResumeThread(thread) == u32::MAX {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(())
+                    };
+                    if !thread.is_null() {
+                        CloseHandle(thread);
+                    }
+                }
+                more = Thread32Next(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+            resumed
+        }
+    }
+}
+
+/// Expand `$VAR` and `${VAR}` in each element of `command`, looking names
+/// up in `env` first, then in the process environment. `$$` is a literal
+/// `$`. A variable set in neither is left as written, with a warning to
+/// `log` if given.
+pub(crate) fn expand_command(
+    command: &[String],
+    env: &HashMap<String, String>,
+    log: Option<&LogSink>,
+) -> Vec<String> {
+    let lookup = |name: &str| env.get(name).cloned().or_else(|| std::env::var(name).ok());
+    command
+        .iter()
+        .map(|arg| expand_vars(arg, lookup, log))
+        .collect()
+}
+
+fn expand_vars(
+    arg: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    log: Option<&LogSink>,
+) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        let after = &rest[at + 1..];
+        if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let Some((name, len)) = var_token(after) else {
+            // A `$` not followed by a name stays as it is.
+            out.push('$');
+            rest = after;
+            continue;
+        };
+        match lookup(name) {
+            Some(value) => out.push_str(&value),
+            None => {
+                let token = &rest[at..=at + len];
+                if let Some(log) = log {
+                    log.warning(format!(
+                        "{name} is not set; leaving {token} in the command as is"
+                    ));
+                }
+                out.push_str(token);
+            }
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The variable named at the start of `s` (the text after a `$`), as
+/// `NAME` or `{NAME}`, and the length of that token.
+fn var_token(s: &str) -> Option<(&str, usize)> {
+    if let Some(braced) = s.strip_prefix('{') {
+        let end = braced.find('}')?;
+        let name = &braced[..end];
+        return is_var_name(name).then_some((name, end + 2));
+    }
+    let len = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    let name = &s[..len];
+    is_var_name(name).then_some((name, len))
+}
+
+fn is_var_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Locate `program` the way `execvp` would: paths containing a separator
+/// are checked directly, bare names are searched for in `path_var`. On
+/// Windows `program.exe` is tried too, so `node` finds `node.exe` and
+/// `python3.12` finds `python3.12.exe`.
+pub(crate) fn resolve_executable(program: &str, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    let exe = format!("{program}.exe");
+    let names = if cfg!(windows) {
+        vec![program, &exe]
+    } else {
+        vec![program]
+    };
+    let path = Path::new(program);
+    if path.components().count() > 1 || path.is_absolute() {
+        return names
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|p| is_executable(p));
+    }
+    std::env::split_paths(path_var?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|p| is_executable(p))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -1,14 +1,17 @@
 use pyo3::prelude::*;
+use pyo3::types::PyString;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 
 // ---------------------------------------------------------------------------
 // Capabilities — exchanged during the ACP initialize handshake
 // ---------------------------------------------------------------------------
 
 /// Agent capabilities advertised during the `initialize` handshake.
-#[pyclass(get_all)]
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Capabilities {
     /// Whether the agent supports session management.
     pub sessions: bool,
@@ -20,31 +23,90 @@ pub struct Capabilities {
     pub modes: Vec<String>,
     /// Supported model identifiers.
     pub models: Vec<String>,
+    /// Whether MCP servers can be added to a running session
+    /// (advertised as `_meta.mcpHotAdd` in the agent capabilities).
+    pub mcp_hot_add: bool,
+    /// ACP protocol version the agent negotiated in `initialize` (e.g. `"1"`).
+    pub protocol_version: String,
 }
 
 impl Capabilities {
     /// Construct from ACP agent capabilities received during the initialize handshake.
-    pub fn from_acp(agent_caps: &sacp::schema::AgentCapabilities) -> Self {
+    ///
+    /// `mcpHotAdd` is read from `_meta`; the fields only this SDK knows
+    /// (`tools`, `proxy`, `modes`, `models`) from `_meta.conduit`, as
+    /// written by [`to_acp`](Self::to_acp). These are all optional, so a
+    /// field of the wrong type falls back to its default and is described
+    /// to `on_invalid` instead of failing the handshake.
+    pub fn from_acp(
+        agent_caps: &sacp::schema::AgentCapabilities,
+        mut on_invalid: impl FnMut(String),
+    ) -> Self {
+        let caps = serde_json::to_value(agent_caps).unwrap_or_default();
+        let meta = &caps["_meta"];
+        let conduit = &meta["conduit"];
+        let on_invalid = &mut on_invalid;
         Self {
             sessions: agent_caps.load_session,
-            tools: true, // ACP agents always support basic tool use
-            proxy: false,
-            modes: vec![],
-            models: vec![],
+            // ACP agents always support basic tool use.
+            tools: Self::meta_field(conduit, "_meta.conduit.tools", on_invalid).unwrap_or(true),
+            proxy: Self::meta_field(conduit, "_meta.conduit.proxy", on_invalid).unwrap_or(false),
+            modes: Self::meta_field(conduit, "_meta.conduit.modes", on_invalid).unwrap_or_default(),
+            models: Self::meta_field(conduit, "_meta.conduit.models", on_invalid)
+                .unwrap_or_default(),
+            mcp_hot_add: Self::meta_field(meta, "_meta.mcpHotAdd", on_invalid).unwrap_or(false),
+            protocol_version: String::new(),
         }
     }
+
+    /// Read the last segment of `path` out of `object`; `None` if it is
+    /// absent, null or of the wrong type (which is reported to `on_invalid`).
+    fn meta_field<T: serde::de::DeserializeOwned>(
+        object: &serde_json::Value,
+        path: &str,
+        on_invalid: &mut impl FnMut(String),
+    ) -> Option<T> {
+        let key = path.rsplit('.').next().unwrap_or(path);
+        Option::<T>::deserialize(&object[key]).unwrap_or_else(|e| {
+            on_invalid(format!(
+                "ignoring invalid agentCapabilities field {path}: {e}"
+            ));
+            None
+        })
+    }
+
+    /// The ACP form of these capabilities, as an agent advertises them in
+    /// its `initialize` response. `protocol_version` is negotiated
+    /// separately and left out.
+    pub fn to_acp(&self) -> Result<sacp::schema::AgentCapabilities, crate::error::ConduitError> {
+        let caps = serde_json::json!({
+            "loadSession": self.sessions,
+            "_meta": {
+                "mcpHotAdd": self.mcp_hot_add,
+                "conduit": {
+                    "tools": self.tools,
+                    "proxy": self.proxy,
+                    "modes": self.modes,
+                    "models": self.models,
+                },
+            },
+        });
+        Ok(serde_json::from_value(caps)?)
+    }
 }
 
 #[pymethods]
 impl Capabilities {
     #[new]
-    #[pyo3(signature = (sessions=false, tools=false, proxy=false, modes=vec![], models=vec![]))]
+    #[pyo3(signature = (sessions=false, tools=true, proxy=false, modes=vec![], models=vec![], mcp_hot_add=false, protocol_version=String::new()))]
     fn new(
         sessions: bool,
         tools: bool,
         proxy: bool,
         modes: Vec<String>,
         models: Vec<String>,
+        mcp_hot_add: bool,
+        protocol_version: String,
     ) -> Self {
         Self {
             sessions,
@@ -52,17 +114,166 @@ impl Capabilities {
             proxy,
             modes,
             models,
+            mcp_hot_add,
+            protocol_version,
         }
     }
 
+    /// The capabilities as ACP `agentCapabilities` JSON (see `to_acp`).
+    fn to_json(&self) -> PyResult<String> {
+        Ok(serde_json::to_string(&self.to_acp()?).map_err(crate::error::ConduitError::from)?)
+    }
+
+    /// Parse ACP `agentCapabilities` JSON, as sent in an `initialize`
+    /// response. Optional fields of the wrong type take their defaults.
+    #[staticmethod]
+    fn from_json(raw_json: &str) -> PyResult<Self> {
+        let caps: sacp::schema::AgentCapabilities =
+            serde_json::from_str(raw_json).map_err(crate::error::ConduitError::from)?;
+        Ok(Self::from_acp(&caps, |_| {}))
+    }
+
     fn __repr__(&self) -> String {
         format!(
-            "Capabilities(sessions={}, tools={}, proxy={}, modes={:?}, models={:?})",
-            self.sessions, self.tools, self.proxy, self.modes, self.models
+            "Capabilities(sessions={}, tools={}, proxy={}, modes={:?}, models={:?}, \
+             mcp_hot_add={}, protocol_version={:?})",
+            self.sessions,
+            self.tools,
+            self.proxy,
+            self.modes,
+            self.models,
+            self.mcp_hot_add,
+            self.protocol_version
         )
     }
 }
 
+/// Who the agent says it is, from its `initialize` response.
+///
+/// Also readable like the `{"name", "version", "title"}` dict that
+/// `Client.agent_info` returned before it was typed: `info["name"]`,
+/// `info.get("title")`, `"version" in info`, `info.keys()` and
+/// `info.to_dict()` all work.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentInfo {
+    /// Programmatic name of the agent.
+    pub name: String,
+    /// Version of the agent, as reported; `""` if it sent none.
+    pub version: String,
+    /// Human-readable name of the agent, if reported.
+    pub title: Option<String>,
+}
+
+impl AgentInfo {
+    /// The keys of the dict form, in order.
+    const KEYS: [&'static str; 3] = ["name", "version", "title"];
+
+    /// Construct from the `agentInfo` of an ACP initialize response.
+    pub fn from_acp(info: &sacp::schema::Implementation) -> Self {
+        Self {
+            name: info.name.clone(),
+            version: info.version.clone(),
+            title: info.title.clone(),
+        }
+    }
+
+    /// The value under `key` in the dict form; `None` for a key it lacks.
+    fn field(&self, key: &str) -> Option<Option<&str>> {
+        match key {
+            "name" => Some(Some(&self.name)),
+            "version" => Some(Some(&self.version)),
+            "title" => Some(self.title.as_deref()),
+            _ => None,
+        }
+    }
+}
+
+#[pymethods]
+impl AgentInfo {
+    #[new]
+    #[pyo3(signature = (name, version=String::new(), title=None))]
+    fn new(name: String, version: String, title: Option<String>) -> Self {
+        Self {
+            name,
+            version,
+            title,
+        }
+    }
+
+    /// The info as a `{"name", "version", "title"}` dict.
+    fn to_dict(&self) -> HashMap<&'static str, Option<String>> {
+        Self::KEYS
+            .into_iter()
+            .map(|key| (key, self.field(key).flatten().map(str::to_owned)))
+            .collect()
+    }
+
+    fn keys(&self) -> Vec<&'static str> {
+        Self::KEYS.to_vec()
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get<'py>(
+        &self,
+        py: Python<'py>,
+        key: &str,
+        default: Option<Bound<'py, PyAny>>,
+    ) -> Option<Bound<'py, PyAny>> {
+        match self.field(key) {
+            Some(value) => value.map(|value| PyString::new(py, value).into_any()),
+            None => default,
+        }
+    }
+
+    fn __getitem__(&self, key: &str) -> PyResult<Option<String>> {
+        self.field(key)
+            .map(|value| value.map(str::to_owned))
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(key.to_owned()))
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.field(key).is_some()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "AgentInfo(name={:?}, version={:?}, title={:?})",
+            self.name, self.version, self.title
+        )
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ConnectionState — lifecycle of a client connection
+// ---------------------------------------------------------------------------
+
+/// Where a client is in its connection lifecycle.
+#[pyclass(eq, eq_int)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    /// Never connected, or disconnected.
+    Disconnected,
+    /// A `connect()` handshake is in flight.
+    Connecting,
+    /// Handshake done; the client can create sessions and prompt.
+    Connected,
+    /// The last connect attempt failed.
+    Failed,
+}
+
+impl ConnectionState {
+    /// Inverse of `state as u8`, for states kept in an atomic.
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Disconnected,
+            1 => Self::Connecting,
+            2 => Self::Connected,
+            _ => Self::Failed,
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Message — a single message in the ACP conversation stream
 // ---------------------------------------------------------------------------
@@ -86,6 +297,10 @@ pub enum ContentType {
     ToolResult,
     Image,
     Error,
+    ResourceLink,
+    Diff,
+    /// An embedded resource: `uri` plus its `text` or base64 `data`.
+    Resource,
 }
 
 /// A single content block inside a [`Message`].
@@ -97,18 +312,124 @@ pub struct ContentBlock {
     pub tool_name: Option<String>,
     pub tool_input: Option<String>,
     pub tool_use_id: Option<String>,
+    /// MIME type of image or (linked) resource content.
+    pub mime_type: Option<String>,
+    /// Base64-encoded image data, or an embedded resource's blob.
+    pub data: Option<String>,
+    /// URI of a (linked) resource, or the source of an image.
+    pub uri: Option<String>,
+    /// File a diff applies to.
+    pub path: Option<String>,
+    /// Diff text before the change (`None` for a new file).
+    pub old_text: Option<String>,
+    /// Diff text after the change.
+    pub new_text: Option<String>,
+}
+
+impl ContentBlock {
+    /// An empty block of the given type.
+    pub fn of(content_type: ContentType) -> Self {
+        Self {
+            content_type,
+            text: None,
+            tool_name: None,
+            tool_input: None,
+            tool_use_id: None,
+            mime_type: None,
+            data: None,
+            uri: None,
+            path: None,
+            old_text: None,
+            new_text: None,
+        }
+    }
+
+    /// Parse a JSON array of ACP tool call content entries into blocks.
+    ///
+    /// Text, image, resource, resource link and diff entries are kept;
+    /// other kinds (e.g. terminals) are skipped, as is anything that is not
+    /// an array. A resource link's `name` goes in `text`.
+    pub fn parse_tool_content(content_json: &str) -> Vec<Self> {
+        match serde_json::from_str::<serde_json::Value>(content_json) {
+            Ok(serde_json::Value::Array(items)) => {
+                items.iter().filter_map(Self::from_tool_content).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Map one ACP content block, or tool call content entry, as
+    /// [`parse_tool_content`](Self::parse_tool_content) does; `None` for
+    /// kinds it skips.
+    pub fn from_tool_content(entry: &serde_json::Value) -> Option<Self> {
+        let block = Self::unwrap_tool_content(entry)?;
+        let field = |key: &str| block.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let content = match block.get("type").and_then(|t| t.as_str())? {
+            "text" => Self {
+                text: field("text"),
+                ..Self::of(ContentType::Text)
+            },
+            "image" => Self {
+                mime_type: field("mimeType").or_else(|| field("mime_type")),
+                data: field("data"),
+                uri: field("uri"),
+                ..Self::of(ContentType::Image)
+            },
+            "resource_link" => Self {
+                text: field("name"),
+                mime_type: field("mimeType"),
+                uri: field("uri"),
+                ..Self::of(ContentType::ResourceLink)
+            },
+            "resource" => {
+                let resource = block.get("resource")?;
+                let field = |key: &str| resource.get(key)?.as_str().map(str::to_string);
+                Self {
+                    text: field("text"),
+                    mime_type: field("mimeType"),
+                    data: field("blob"),
+                    uri: field("uri"),
+                    ..Self::of(ContentType::Resource)
+                }
+            }
+            "diff" => Self {
+                path: field("path"),
+                old_text: field("oldText"),
+                new_text: field("newText"),
+                ..Self::of(ContentType::Diff)
+            },
+            _ => return None,
+        };
+        Some(content)
+    }
+
+    /// The content block inside a tool call content entry: the entry
+    /// itself, or what a `{"type": "content", "content": {...}}` wrapper
+    /// holds (`None` if the wrapper is empty).
+    fn unwrap_tool_content(entry: &serde_json::Value) -> Option<&serde_json::Value> {
+        match entry.get("type").and_then(|t| t.as_str()) {
+            Some("content") => entry.get("content"),
+            _ => Some(entry),
+        }
+    }
 }
 
 #[pymethods]
 impl ContentBlock {
     #[new]
-    #[pyo3(signature = (content_type, text=None, tool_name=None, tool_input=None, tool_use_id=None))]
+    #[pyo3(signature = (content_type, text=None, tool_name=None, tool_input=None, tool_use_id=None, mime_type=None, data=None, uri=None, path=None, old_text=None, new_text=None))]
     fn new(
         content_type: ContentType,
         text: Option<String>,
         tool_name: Option<String>,
         tool_input: Option<String>,
         tool_use_id: Option<String>,
+        mime_type: Option<String>,
+        data: Option<String>,
+        uri: Option<String>,
+        path: Option<String>,
+        old_text: Option<String>,
+        new_text: Option<String>,
     ) -> Self {
         Self {
             content_type,
@@ -116,9 +437,26 @@ impl ContentBlock {
             tool_name,
             tool_input,
             tool_use_id,
+            mime_type,
+            data,
+            uri,
+            path,
+            old_text,
+            new_text,
         }
     }
 
+    /// Rebuild a reply's content blocks from its streamed updates, exactly
+    /// as `prompt()` assembles them; see [`ReplyBlocks`] for the mapping.
+    #[staticmethod]
+    fn from_updates(updates: Vec<SessionUpdate>) -> Vec<ContentBlock> {
+        let mut reply = ReplyBlocks::default();
+        for update in &updates {
+            reply.push(update);
+        }
+        reply.into_blocks()
+    }
+
     fn __repr__(&self) -> String {
         format!("ContentBlock(type={:?})", self.content_type)
     }
@@ -133,18 +471,28 @@ pub struct Message {
     pub session_id: Option<String>,
     /// Why the prompt turn ended (e.g. "EndTurn", "Cancelled").
     pub stop_reason: Option<String>,
+    /// Number of the prompt turn that produced this message within its
+    /// session (1 for the session's first turn).
+    pub turn_id: Option<u64>,
 }
 
 #[pymethods]
 impl Message {
     #[new]
-    #[pyo3(signature = (role, content, session_id=None, stop_reason=None))]
-    fn new(role: MessageRole, content: Vec<ContentBlock>, session_id: Option<String>, stop_reason: Option<String>) -> Self {
+    #[pyo3(signature = (role, content, session_id=None, stop_reason=None, turn_id=None))]
+    fn new(
+        role: MessageRole,
+        content: Vec<ContentBlock>,
+        session_id: Option<String>,
+        stop_reason: Option<String>,
+        turn_id: Option<u64>,
+    ) -> Self {
         Self {
             role,
             content,
             session_id,
             stop_reason,
+            turn_id,
         }
     }
 
@@ -205,6 +553,93 @@ pub enum UpdateKind {
     Error,
     /// Rate limit event from the agent (extension notification).
     RateLimit,
+    /// The agent asked for a yes/no confirmation (answered by the
+    /// confirmation callback; this update is informational).
+    Confirmation,
+    /// The agent asked permission to run a tool (decided by the permission
+    /// callback; this update is informational).
+    Permission,
+    /// Any other extension notification; `method` and `params_json` carry
+    /// it unchanged.
+    Extension,
+    /// Agent message content other than text (an image, resource or
+    /// resource link), carried in `content`.
+    Content,
+}
+
+/// What a tool call does, as the agent classified it.
+#[pyclass(eq, eq_int)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolKind {
+    Read,
+    Edit,
+    Delete,
+    Move,
+    Search,
+    Execute,
+    Think,
+    Fetch,
+    /// Anything else, including kinds newer than this SDK.
+    Other,
+}
+
+impl ToolKind {
+    /// Map an ACP tool kind by its wire name; unknown kinds are `Other`.
+    pub fn from_acp(kind: &sacp::schema::ToolKind) -> Self {
+        let name = serde_json::to_value(kind).ok();
+        match name.as_ref().and_then(|v| v.as_str()) {
+            Some("read") => Self::Read,
+            Some("edit") => Self::Edit,
+            Some("delete") => Self::Delete,
+            Some("move") => Self::Move,
+            Some("search") => Self::Search,
+            Some("execute") => Self::Execute,
+            Some("think") => Self::Think,
+            Some("fetch") => Self::Fetch,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Where a tool call is in its lifecycle.
+#[pyclass(eq, eq_int)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolStatus {
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+}
+
+impl ToolStatus {
+    /// Map an ACP tool call status by its wire name; `None` if this SDK
+    /// doesn't know it.
+    pub fn from_acp(status: &sacp::schema::ToolCallStatus) -> Option<Self> {
+        let name = serde_json::to_value(status).ok()?;
+        [
+            Self::Pending,
+            Self::InProgress,
+            Self::Completed,
+            Self::Failed,
+        ]
+        .into_iter()
+        .find(|status| name == status.wire_name())
+    }
+
+    /// The status's ACP wire name, e.g. `"in_progress"`.
+    pub fn wire_name(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::InProgress => "in_progress",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// The call has finished, successfully or not.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
 }
 
 /// A real-time streaming update from the agent during a session.
@@ -219,10 +654,10 @@ pub struct SessionUpdate {
     pub error: Option<String>,
     /// Why the prompt turn ended (end_turn, max_tokens, cancelled, etc.).
     pub stop_reason: Option<String>,
-    /// Tool kind (read, edit, execute, etc.).
-    pub tool_kind: Option<String>,
-    /// Tool status (pending, in_progress, completed, failed).
-    pub tool_status: Option<String>,
+    /// What the tool call does (read, edit, execute, etc.).
+    pub tool_kind: Option<ToolKind>,
+    /// Where the tool call is in its lifecycle.
+    pub tool_status: Option<ToolStatus>,
     /// Tool output content as JSON string.
     pub tool_content: Option<String>,
     /// Tool file locations as JSON string.
@@ -235,18 +670,43 @@ pub struct SessionUpdate {
     pub config_json: Option<String>,
     /// Available commands as JSON string.
     pub commands_json: Option<String>,
-    /// Token usage data as JSON string.
+    /// Token usage data as JSON string. `cost.amount` is a decimal string.
     pub usage_json: Option<String>,
     /// Session info as JSON string.
     pub session_info_json: Option<String>,
     /// Rate limit event data as JSON string.
     pub rate_limit_json: Option<String>,
+    /// Confirmation request params as JSON string; `text` holds the message.
+    pub confirmation_json: Option<String>,
+    /// Permission options offered by the agent as JSON array.
+    pub permission_options_json: Option<String>,
+    /// Request ID of the prompt turn this update belongs to (also sent to
+    /// the agent as `_meta.requestId` on `session/prompt`).
+    pub request_id: Option<String>,
+    /// Number of that turn within its session, as on the turn's
+    /// `Message`s and `ResultMessage`.
+    pub turn_id: Option<u64>,
+    /// Typed view of `tool_locations`, with relative paths resolved
+    /// against the session's working directory.
+    pub locations: Vec<ToolLocation>,
+    /// Typed view of `plan_json`.
+    pub plan_entries: Vec<PlanEntry>,
+    /// Typed view of `rate_limit_json`, when it carries rate-limit fields.
+    pub rate_limit: Option<RateLimitInfo>,
+    /// Extension notification method (e.g. `_vendor/event`).
+    pub method: Option<String>,
+    /// Extension notification params as JSON string.
+    pub params_json: Option<String>,
+    /// Typed view of the cost in `usage_json`, if the agent reported one.
+    pub cost: Option<Cost>,
+    /// The block of a `Content` update.
+    pub content: Option<ContentBlock>,
 }
 
 #[pymethods]
 impl SessionUpdate {
     #[new]
-    #[pyo3(signature = (kind, text=None, tool_name=None, tool_input=None, tool_use_id=None, error=None, stop_reason=None, tool_kind=None, tool_status=None, tool_content=None, tool_locations=None, mode_id=None, plan_json=None, config_json=None, commands_json=None, usage_json=None, session_info_json=None, rate_limit_json=None))]
+    #[pyo3(signature = (kind, text=None, tool_name=None, tool_input=None, tool_use_id=None, error=None, stop_reason=None, tool_kind=None, tool_status=None, tool_content=None, tool_locations=None, mode_id=None, plan_json=None, config_json=None, commands_json=None, usage_json=None, session_info_json=None, rate_limit_json=None, confirmation_json=None, permission_options_json=None, request_id=None, locations=Vec::new(), plan_entries=Vec::new(), rate_limit=None, method=None, params_json=None, cost=None, content=None, turn_id=None))]
     fn new(
         kind: UpdateKind,
         text: Option<String>,
@@ -255,8 +715,8 @@ impl SessionUpdate {
         tool_use_id: Option<String>,
         error: Option<String>,
         stop_reason: Option<String>,
-        tool_kind: Option<String>,
-        tool_status: Option<String>,
+        tool_kind: Option<ToolKind>,
+        tool_status: Option<ToolStatus>,
         tool_content: Option<String>,
         tool_locations: Option<String>,
         mode_id: Option<String>,
@@ -266,6 +726,17 @@ impl SessionUpdate {
         usage_json: Option<String>,
         session_info_json: Option<String>,
         rate_limit_json: Option<String>,
+        confirmation_json: Option<String>,
+        permission_options_json: Option<String>,
+        request_id: Option<String>,
+        locations: Vec<ToolLocation>,
+        plan_entries: Vec<PlanEntry>,
+        rate_limit: Option<RateLimitInfo>,
+        method: Option<String>,
+        params_json: Option<String>,
+        cost: Option<Cost>,
+        content: Option<ContentBlock>,
+        turn_id: Option<u64>,
     ) -> Self {
         Self {
             kind,
@@ -286,13 +757,652 @@ impl SessionUpdate {
             usage_json,
             session_info_json,
             rate_limit_json,
+            confirmation_json,
+            permission_options_json,
+            request_id,
+            turn_id,
+            locations,
+            plan_entries,
+            rate_limit,
+            method,
+            params_json,
+            cost,
+            content,
         }
     }
+    /// Typed view of `tool_content`: one [`ToolContent`] per entry.
+    ///
+    /// Empty when there is no tool content or it is not a JSON array.
+    #[getter]
+    fn tool_contents(&self) -> Vec<ToolContent> {
+        self.tool_content
+            .as_deref()
+            .map(ToolContent::parse_list)
+            .unwrap_or_default()
+    }
+
+    /// `tool_content` as typed [`ContentBlock`]s (text, image, resource
+    /// link, diff); see [`ContentBlock::parse_tool_content`].
+    #[getter]
+    fn tool_content_blocks(&self) -> Vec<ContentBlock> {
+        self.tool_content
+            .as_deref()
+            .map(ContentBlock::parse_tool_content)
+            .unwrap_or_default()
+    }
+
+    /// The file changes in `tool_content`, one [`FileEdit`] per diff entry.
+    #[getter]
+    fn file_edits(&self) -> Vec<FileEdit> {
+        self.tool_content
+            .as_deref()
+            .map(FileEdit::parse_list)
+            .unwrap_or_default()
+    }
+
     fn __repr__(&self) -> String {
         format!("SessionUpdate(kind={:?})", self.kind)
     }
 }
 
+/// Builds a reply's content blocks from its updates, in stream order.
+///
+/// Each update maps to blocks as follows:
+/// - `TextDelta` extends the trailing `Text` block, or starts a new one.
+/// - `ThoughtDelta` is treated as text until the first `TextDelta` (so an
+///   agent that only thinks still has a reply) and dropped after it.
+/// - `ToolUseStart` adds a `ToolUse` block with the name, input and id.
+/// - `ToolUseUpdate` adds nothing; its `tool_content` is kept for the
+///   call's result.
+/// - `ToolUseEnd` adds a `ToolResult` block holding the text of the last
+///   content reported for that call.
+/// - `Content` adds its block as-is.
+/// - Every other kind adds nothing.
+#[derive(Debug, Default)]
+pub struct ReplyBlocks {
+    blocks: Vec<ContentBlock>,
+    /// A `TextDelta` has arrived, so thoughts no longer count as text.
+    got_message: bool,
+    /// Latest content reported for each running tool call.
+    tool_output: HashMap<String, String>,
+}
+
+impl ReplyBlocks {
+    /// Apply one update.
+    pub fn push(&mut self, update: &SessionUpdate) {
+        match update.kind {
+            UpdateKind::TextDelta => {
+                self.got_message = true;
+                self.push_text(update.text.as_deref().unwrap_or_default());
+            }
+            UpdateKind::ThoughtDelta if !self.got_message => {
+                self.push_text(update.text.as_deref().unwrap_or_default());
+            }
+            UpdateKind::Content => self.blocks.extend(update.content.clone()),
+            UpdateKind::ToolUseStart => self.blocks.push(ContentBlock {
+                tool_name: update.tool_name.clone(),
+                tool_input: update.tool_input.clone(),
+                tool_use_id: update.tool_use_id.clone(),
+                ..ContentBlock::of(ContentType::ToolUse)
+            }),
+            UpdateKind::ToolUseUpdate => {
+                if let (Some(id), Some(content)) = (&update.tool_use_id, &update.tool_content) {
+                    self.tool_output.insert(id.clone(), content.clone());
+                }
+            }
+            UpdateKind::ToolUseEnd => {
+                let output = update
+                    .tool_use_id
+                    .as_ref()
+                    .and_then(|id| self.tool_output.remove(id));
+                let text: String = output
+                    .as_deref()
+                    .map(ContentBlock::parse_tool_content)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|block| block.content_type == ContentType::Text)
+                    .filter_map(|block| block.text)
+                    .collect();
+                self.blocks.push(ContentBlock {
+                    text: (!text.is_empty()).then_some(text),
+                    tool_use_id: update.tool_use_id.clone(),
+                    ..ContentBlock::of(ContentType::ToolResult)
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// The blocks built so far.
+    pub fn into_blocks(self) -> Vec<ContentBlock> {
+        self.blocks
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if let Some(ContentBlock {
+            content_type: ContentType::Text,
+            text: Some(run),
+            ..
+        }) = self.blocks.last_mut()
+        {
+            run.push_str(text);
+            return;
+        }
+        self.blocks.push(ContentBlock {
+            text: Some(text.to_owned()),
+            ..ContentBlock::of(ContentType::Text)
+        });
+    }
+}
+
+/// One entry of a tool call's output, parsed from ACP `ToolCallContent`.
+///
+/// `kind` is `"text"` or `"image"` for content blocks, otherwise the raw
+/// type tag (`"diff"`, `"terminal"`, `"resource_link"`, ...). The original
+/// entry is always kept in `raw_json`.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToolContent {
+    pub kind: String,
+    pub text: Option<String>,
+    /// MIME type of image content (e.g. `"image/png"`).
+    pub mime_type: Option<String>,
+    /// Base64-encoded image data.
+    pub data: Option<String>,
+    /// Optional URI the image was loaded from.
+    pub uri: Option<String>,
+    /// JSON of the original content entry.
+    pub raw_json: String,
+}
+
+impl ToolContent {
+    /// Parse a JSON array of ACP tool call content entries.
+    pub fn parse_list(content_json: &str) -> Vec<Self> {
+        match serde_json::from_str::<serde_json::Value>(content_json) {
+            Ok(serde_json::Value::Array(items)) => items.iter().map(Self::from_value).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Built on [`ContentBlock::from_tool_content`], keeping its fields
+    /// for text and image entries only.
+    fn from_value(entry: &serde_json::Value) -> Self {
+        let kind = ContentBlock::unwrap_tool_content(entry)
+            .unwrap_or(entry)
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let (text, mime_type, data, uri) = match ContentBlock::from_tool_content(entry) {
+            Some(block) if matches!(block.content_type, ContentType::Text | ContentType::Image) => {
+                (block.text, block.mime_type, block.data, block.uri)
+            }
+            _ => Default::default(),
+        };
+
+        Self {
+            kind,
+            text,
+            mime_type,
+            data,
+            uri,
+            raw_json: entry.to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl ToolContent {
+    #[new]
+    #[pyo3(signature = (kind, text=None, mime_type=None, data=None, uri=None, raw_json="{}".to_string()))]
+    fn new(
+        kind: String,
+        text: Option<String>,
+        mime_type: Option<String>,
+        data: Option<String>,
+        uri: Option<String>,
+        raw_json: String,
+    ) -> Self {
+        Self {
+            kind,
+            text,
+            mime_type,
+            data,
+            uri,
+            raw_json,
+        }
+    }
+
+    /// Parse a tool content JSON array (as found in `SessionUpdate.tool_content`).
+    #[staticmethod]
+    #[pyo3(name = "parse_list")]
+    fn py_parse_list(content_json: &str) -> Vec<Self> {
+        Self::parse_list(content_json)
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.mime_type {
+            Some(mime) => format!("ToolContent(kind={:?}, mime_type={:?})", self.kind, mime),
+            None => format!("ToolContent(kind={:?})", self.kind),
+        }
+    }
+}
+
+/// A file a tool call touches, parsed from ACP `ToolCallLocation`.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolLocation {
+    pub path: String,
+    /// 1-based line number, when the agent gave one.
+    pub line: Option<u32>,
+}
+
+impl ToolLocation {
+    /// Build a location, joining a relative `path` onto `cwd` when known.
+    pub fn resolve(path: &Path, line: Option<u32>, cwd: Option<&Path>) -> Self {
+        let path = match cwd {
+            Some(cwd) if path.is_relative() => cwd.join(path),
+            _ => path.to_path_buf(),
+        };
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            line,
+        }
+    }
+}
+
+#[pymethods]
+impl ToolLocation {
+    #[new]
+    #[pyo3(signature = (path, line=None))]
+    fn new(path: String, line: Option<u32>) -> Self {
+        Self { path, line }
+    }
+
+    fn __repr__(&self) -> String {
+        match self.line {
+            Some(line) => format!("ToolLocation(path={:?}, line={})", self.path, line),
+            None => format!("ToolLocation(path={:?})", self.path),
+        }
+    }
+}
+
+/// A file change carried by a tool call, parsed from ACP diff content
+/// (`{"type": "diff", "path", "oldText", "newText"}`).
+///
+/// `old_text` is `None` when the tool creates the file and `new_text` is
+/// `None` when it deletes it.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEdit {
+    pub path: String,
+    pub old_text: Option<String>,
+    pub new_text: Option<String>,
+}
+
+impl FileEdit {
+    /// Parse the diff entries of a JSON array of ACP tool call content,
+    /// skipping other kinds and diffs without a path. Empty if
+    /// `content_json` is not a JSON array.
+    pub fn parse_list(content_json: &str) -> Vec<Self> {
+        serde_json::from_str::<Vec<serde_json::Value>>(content_json)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(ContentBlock::from_tool_content)
+            .filter(|block| block.content_type == ContentType::Diff)
+            .filter_map(|block| {
+                Some(Self {
+                    path: block.path?,
+                    old_text: block.old_text,
+                    new_text: block.new_text,
+                })
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl FileEdit {
+    #[new]
+    #[pyo3(signature = (path, old_text=None, new_text=None))]
+    fn new(path: String, old_text: Option<String>, new_text: Option<String>) -> Self {
+        Self {
+            path,
+            old_text,
+            new_text,
+        }
+    }
+
+    /// Parse a tool content JSON array (as found in `SessionUpdate.tool_content`).
+    #[staticmethod]
+    #[pyo3(name = "parse_list")]
+    fn py_parse_list(content_json: &str) -> Vec<Self> {
+        Self::parse_list(content_json)
+    }
+
+    fn __repr__(&self) -> String {
+        let change = match (&self.old_text, &self.new_text) {
+            (None, _) => "create",
+            (_, None) => "delete",
+            _ => "modify",
+        };
+        format!("FileEdit(path={:?}, {})", self.path, change)
+    }
+}
+
+/// One step of an agent's plan, parsed from ACP `PlanEntry`.
+///
+/// `priority` and `status` are the wire strings (e.g. `"high"`,
+/// `"in_progress"`), passed through as-is so values newer than this SDK
+/// still arrive. An entry missing either gets the same default as the
+/// constructor's.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanEntry {
+    /// What the step does, as the agent describes it.
+    pub content: String,
+    /// How important the step is: `"high"`, `"medium"` or `"low"`.
+    pub priority: String,
+    /// Where the step stands: `"pending"`, `"in_progress"` or
+    /// `"completed"`.
+    pub status: String,
+}
+
+impl Default for PlanEntry {
+    fn default() -> Self {
+        Self {
+            content: String::new(),
+            priority: Self::DEFAULT_PRIORITY.into(),
+            status: Self::DEFAULT_STATUS.into(),
+        }
+    }
+}
+
+impl PlanEntry {
+    const DEFAULT_PRIORITY: &'static str = "medium";
+    const DEFAULT_STATUS: &'static str = "pending";
+
+    /// Parse a plan's `entries` array, skipping entries that aren't objects
+    /// of strings. Empty if `entries_json` is not a JSON array.
+    pub fn parse_list(entries_json: &str) -> Vec<Self> {
+        serde_json::from_str::<Vec<serde_json::Value>>(entries_json)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|entry| serde_json::from_value(entry).ok())
+            .collect()
+    }
+}
+
+#[pymethods]
+impl PlanEntry {
+    #[new]
+    #[pyo3(signature = (
+        content,
+        priority=PlanEntry::DEFAULT_PRIORITY.into(),
+        status=PlanEntry::DEFAULT_STATUS.into(),
+    ))]
+    fn new(content: String, priority: String, status: String) -> Self {
+        Self {
+            content,
+            priority,
+            status,
+        }
+    }
+
+    /// Parse a plan entries JSON array (as found in `SessionUpdate.plan_json`).
+    #[staticmethod]
+    #[pyo3(name = "parse_list")]
+    fn py_parse_list(entries_json: &str) -> Vec<Self> {
+        Self::parse_list(entries_json)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PlanEntry(content={:?}, priority={:?}, status={:?})",
+            self.content, self.priority, self.status
+        )
+    }
+}
+
+/// Rate-limit details parsed from an agent's extension notification.
+///
+/// Covers the generic fields (`retry_after_secs`, `limit`, `remaining`,
+/// `reset_at`) and those of Claude's `rate_limit_event` (`status`,
+/// `rate_limit_type`, `utilization`, ...). Fields the payload doesn't carry
+/// are `None`.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RateLimitInfo {
+    /// Method of the notification it came from.
+    pub method: String,
+    /// How long the agent asks the client to wait before retrying.
+    pub retry_after_secs: Option<f64>,
+    /// Requests allowed in the current window.
+    pub limit: Option<u64>,
+    /// Requests left in the current window.
+    pub remaining: Option<u64>,
+    /// When the window resets, as the agent sent it (a timestamp string,
+    /// or a Unix time in seconds rendered as a string).
+    pub reset_at: Option<String>,
+    /// Rate-limit status, e.g. `"allowed_warning"`.
+    pub status: Option<String>,
+    /// Which limit this is about, e.g. `"seven_day"`.
+    pub rate_limit_type: Option<String>,
+    /// How much of the limit is used, from 0.0 to 1.0.
+    pub utilization: Option<f64>,
+    /// Whether usage beyond the limit is being billed as overage.
+    pub is_using_overage: Option<bool>,
+    /// The utilization threshold that was crossed, e.g. 0.75.
+    pub surpassed_threshold: Option<f64>,
+    /// The notification params as sent, for fields not parsed here.
+    pub raw_json: String,
+}
+
+impl RateLimitInfo {
+    /// Parse the rate-limit fields from notification `params`, at the top
+    /// level or under `rate_limit_info`. `None` if there are none.
+    pub fn parse(method: &str, params: &serde_json::Value) -> Option<Self> {
+        let info = Self::parse_fields(method, params);
+        let any = info.retry_after_secs.is_some()
+            || info.limit.or(info.remaining).is_some()
+            || info.reset_at.is_some()
+            || info.status.is_some()
+            || info.utilization.is_some();
+        any.then_some(info)
+    }
+
+    /// Every field `params` carries, leaving the others `None`.
+    fn parse_fields(method: &str, params: &serde_json::Value) -> Self {
+        let number = |value: &serde_json::Value| {
+            value
+                .as_f64()
+                .or_else(|| value.as_str()?.trim().parse().ok())
+        };
+        let count = |keys: &[&str]| {
+            let n = number(rate_limit_field(params, keys)?)?;
+            (n >= 0.0).then_some(n as u64)
+        };
+
+        let retry_after_secs = rate_limit_field(params, RETRY_AFTER_KEYS)
+            .and_then(number)
+            .filter(|secs| secs.is_finite() && *secs >= 0.0);
+        let text = |keys: &[&str]| Some(rate_limit_field(params, keys)?.as_str()?.to_string());
+        let fraction =
+            |keys: &[&str]| number(rate_limit_field(params, keys)?).filter(|n| n.is_finite());
+
+        Self {
+            method: method.to_string(),
+            retry_after_secs,
+            limit: count(&["limit"]),
+            remaining: count(&["remaining"]),
+            reset_at: rate_limit_field(params, RESET_AT_KEYS).map(|value| match value.as_str() {
+                Some(s) => s.to_string(),
+                None => value.to_string(),
+            }),
+            status: text(&["status"]),
+            rate_limit_type: text(&["rate_limit_type", "rateLimitType"]),
+            utilization: fraction(&["utilization"]),
+            is_using_overage: rate_limit_field(params, &["is_using_overage", "isUsingOverage"])
+                .and_then(serde_json::Value::as_bool),
+            surpassed_threshold: fraction(&["surpassed_threshold", "surpassedThreshold"]),
+            raw_json: params.to_string(),
+        }
+    }
+}
+
+const RETRY_AFTER_KEYS: &[&str] = &[
+    "retry_after_seconds",
+    "retryAfterSeconds",
+    "retry_after",
+    "retryAfter",
+];
+const RESET_AT_KEYS: &[&str] = &["reset_at", "resetAt", "resets_at", "resetsAt"];
+
+/// The first non-null value under any of `keys`, in `params` or its
+/// `rate_limit_info`/`rateLimitInfo` object.
+fn rate_limit_field<'a>(
+    params: &'a serde_json::Value,
+    keys: &[&str],
+) -> Option<&'a serde_json::Value> {
+    let nested = ["rate_limit_info", "rateLimitInfo"]
+        .iter()
+        .filter_map(|key| params.get(key));
+    nested
+        .chain(std::iter::once(params))
+        .flat_map(|scope| keys.iter().filter_map(move |key| scope.get(key)))
+        .find(|value| !value.is_null())
+}
+
+#[pymethods]
+impl RateLimitInfo {
+    #[new]
+    #[pyo3(signature = (method=String::new(), retry_after_secs=None, limit=None, remaining=None, reset_at=None, status=None, rate_limit_type=None, utilization=None, is_using_overage=None, surpassed_threshold=None, raw_json=String::new()))]
+    fn new(
+        method: String,
+        retry_after_secs: Option<f64>,
+        limit: Option<u64>,
+        remaining: Option<u64>,
+        reset_at: Option<String>,
+        status: Option<String>,
+        rate_limit_type: Option<String>,
+        utilization: Option<f64>,
+        is_using_overage: Option<bool>,
+        surpassed_threshold: Option<f64>,
+        raw_json: String,
+    ) -> Self {
+        Self {
+            method,
+            retry_after_secs,
+            limit,
+            remaining,
+            reset_at,
+            status,
+            rate_limit_type,
+            utilization,
+            is_using_overage,
+            surpassed_threshold,
+            raw_json,
+        }
+    }
+
+    /// Parse `SessionUpdate.rate_limit_json` (`{"method", "params"}`).
+    ///
+    /// Unlike `SessionUpdate.rate_limit`, always returns an instance, with
+    /// `None` for every field the params don't carry.
+    #[staticmethod]
+    fn from_json(json_str: &str) -> PyResult<Self> {
+        let event: serde_json::Value = serde_json::from_str(json_str).map_err(|e| {
+            pyo3::exceptions::PyValueError::new_err(format!("invalid rate limit JSON: {e}"))
+        })?;
+        let method = event["method"].as_str().unwrap_or_default();
+        Ok(Self::parse_fields(method, &event["params"]))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RateLimitInfo(retry_after_secs={:?}, limit={:?}, remaining={:?}, reset_at={:?}, \
+             status={:?}, utilization={:?})",
+            self.retry_after_secs,
+            self.limit,
+            self.remaining,
+            self.reset_at,
+            self.status,
+            self.utilization
+        )
+    }
+}
+
+/// A cost reported in a usage update: the session's cumulative cost so
+/// far, not the cost of the update's own turn.
+///
+/// The amount stays a decimal string, so it can be handed to
+/// `decimal.Decimal` without float rounding. `currency` is normalized to an
+/// uppercase ISO 4217 code, so `"usd"` and `"USD"` compare equal.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cost {
+    /// The amount as a decimal string, e.g. `"0.0021"`.
+    pub amount: String,
+    /// Uppercase ISO 4217 currency code, e.g. `"USD"`.
+    pub currency: String,
+}
+
+impl Cost {
+    /// Parse the `cost` object of a usage update. The amount may be a
+    /// decimal string or a number; `None` if either field is missing or the
+    /// amount is not a finite number.
+    pub fn parse(cost: &serde_json::Value) -> Option<Self> {
+        let amount = match &cost["amount"] {
+            serde_json::Value::String(amount) => amount.clone(),
+            serde_json::Value::Number(amount) => amount.to_string(),
+            _ => return None,
+        };
+        Self::normalized(&amount, cost["currency"].as_str()?)
+    }
+
+    /// `None` unless `amount` is a finite decimal number.
+    fn normalized(amount: &str, currency: &str) -> Option<Self> {
+        let amount = amount.trim();
+        let decimal = amount
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+        let finite = amount.parse::<f64>().is_ok_and(f64::is_finite);
+        (decimal && finite).then(|| Self {
+            amount: amount.to_string(),
+            currency: currency.trim().to_ascii_uppercase(),
+        })
+    }
+
+    /// The amount as a float, for fields that report it as one.
+    pub fn amount_f64(&self) -> f64 {
+        self.amount.parse().unwrap_or_default()
+    }
+}
+
+#[pymethods]
+impl Cost {
+    /// `amount` may be a decimal string or any number (`int`, `float`,
+    /// `Decimal`); it is kept as its `str()`. Raises `ValueError` if that is
+    /// not a finite decimal number.
+    #[new]
+    fn new(amount: &Bound<'_, PyAny>, currency: &str) -> PyResult<Self> {
+        let text = amount.str()?.to_string();
+        Self::normalized(&text, currency).ok_or_else(|| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "cost amount must be a finite decimal number, got {text:?}"
+            ))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Cost(amount={:?}, currency={:?})",
+            self.amount, self.currency
+        )
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ClientConfig
 // ---------------------------------------------------------------------------
@@ -307,30 +1417,260 @@ pub struct ClientConfig {
     pub cwd: Option<String>,
     /// Additional environment variables passed to the agent.
     pub env: HashMap<String, String>,
-    /// Connection timeout in seconds.
+    /// Connection timeout in seconds: how long to wait for the agent to
+    /// answer `initialize`. 0 waits forever.
     pub timeout_secs: u64,
+    /// ACP protocol version to request in `initialize`. When set, the
+    /// connection fails if the agent negotiates a different version;
+    /// `None` requests the latest version and accepts the agent's answer.
+    pub protocol_version: Option<u16>,
+    /// Run `command` through the shell (`sh -c` / `cmd /C`), its elements
+    /// joined with spaces and not quoted. Needed for pipes and redirects;
+    /// anything that is not a trusted literal must be quoted by the caller.
+    pub use_shell: bool,
+    /// When a prompt fails after the agent reported a rate limit with a
+    /// retry hint, wait that long and send the prompt once more.
+    pub retry_on_rate_limit: bool,
+    /// Longest a rate-limit retry waits, whatever the agent's hint says.
+    pub max_rate_limit_wait_secs: f64,
+    /// `host:port` of an agent served over TCP. When set, `connect()`
+    /// connects there instead of spawning `command`.
+    pub endpoint: Option<String>,
+    /// Path of a Unix domain socket an agent listens on (Unix only). When
+    /// set, `connect()` connects there instead of spawning `command`.
+    pub socket_path: Option<String>,
+    /// What a `prompt()` does while another is running in its session:
+    /// `"queue"` waits its turn, `"error"` raises `SessionError`.
+    pub on_busy: String,
+    /// Seconds between liveness pings to the agent; 0 disables them. After
+    /// three pings in a row go unanswered the connection is marked
+    /// `Failed` and pending calls fail with `ConnectionError`.
+    pub heartbeat_secs: f64,
+    /// Name the client reports to the agent in `initialize`.
+    pub client_name: String,
+    /// Version the client reports to the agent in `initialize`.
+    pub client_version: String,
+    /// Human-readable client name reported in `initialize`, if any.
+    pub client_title: Option<String>,
+    /// File to append every ACP message to, as newline-delimited JSON with
+    /// direction and timestamp; `None` records nothing.
+    pub record_path: Option<String>,
 }
 
 #[pymethods]
 impl ClientConfig {
     #[new]
-    #[pyo3(signature = (command, cwd=None, env=HashMap::new(), timeout_secs=30))]
+    #[pyo3(signature = (command, cwd=None, env=HashMap::new(), timeout_secs=30, protocol_version=None, use_shell=false, retry_on_rate_limit=false, endpoint=None, socket_path=None, on_busy=String::from("queue"), heartbeat_secs=0.0, client_name=None, client_version=None, client_title=None, record_path=None, max_rate_limit_wait_secs=60.0))]
     fn new(
         command: Vec<String>,
         cwd: Option<String>,
         env: HashMap<String, String>,
         timeout_secs: u64,
-    ) -> Self {
-        Self {
+        protocol_version: Option<u16>,
+        use_shell: bool,
+        retry_on_rate_limit: bool,
+        endpoint: Option<String>,
+        socket_path: Option<String>,
+        on_busy: String,
+        heartbeat_secs: f64,
+        client_name: Option<String>,
+        client_version: Option<String>,
+        client_title: Option<String>,
+        record_path: Option<String>,
+        max_rate_limit_wait_secs: f64,
+    ) -> PyResult<Self> {
+        if on_busy != "queue" && on_busy != "error" {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "on_busy must be \"queue\" or \"error\", got {on_busy:?}"
+            )));
+        }
+        // Also rules out values too large for a `Duration`.
+        if Duration::try_from_secs_f64(heartbeat_secs).is_err() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "heartbeat_secs must be 0 or a positive number of seconds, got {heartbeat_secs}"
+            )));
+        }
+        if !(max_rate_limit_wait_secs >= 0.0 && max_rate_limit_wait_secs.is_finite()) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "max_rate_limit_wait_secs must be 0 or positive, got {max_rate_limit_wait_secs}"
+            )));
+        }
+        // An empty command is fine for endpoint, socket and replay clients,
+        // but a blank program never is.
+        let program = command.first().map(|program| program.trim());
+        if program == Some("") {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "the first element of command (the program) must not be empty",
+            ));
+        }
+        Ok(Self {
             command,
             cwd,
             env,
             timeout_secs,
+            protocol_version,
+            use_shell,
+            retry_on_rate_limit,
+            max_rate_limit_wait_secs,
+            endpoint,
+            socket_path,
+            on_busy,
+            heartbeat_secs,
+            client_name: client_name.unwrap_or_else(|| "conduit-agent-sdk".into()),
+            client_version: client_version.unwrap_or_else(|| env!("CARGO_PKG_VERSION").into()),
+            client_title,
+            record_path,
+        })
+    }
+
+    /// Check the config can spawn an agent, raising `ValueError` if not.
+    ///
+    /// Without `endpoint` or `socket_path`, `command` must not be empty,
+    /// and `cwd`, if set, must be an existing directory. With `check_path`,
+    /// the program (after variable expansion) must also be an executable
+    /// file, or found on `PATH` (the one in `env`, if set) when given as a
+    /// bare name; skipped with `use_shell`, where the shell finds it.
+    #[pyo3(signature = (check_path=false))]
+    pub(crate) fn validate(&self, check_path: bool) -> PyResult<()> {
+        let invalid = |msg: String| Err(pyo3::exceptions::PyValueError::new_err(msg));
+        if self.endpoint.is_some() || self.socket_path.is_some() {
+            return Ok(());
+        }
+        if self.command.is_empty() {
+            return invalid("command must not be empty without endpoint or socket_path".into());
+        }
+        if let Some(cwd) = &self.cwd {
+            if !Path::new(cwd).is_dir() {
+                return invalid(format!("cwd {cwd:?} is not an existing directory"));
+            }
+        }
+        if check_path && !self.use_shell {
+            let command = crate::transport::expand_command(&self.command[..1], &self.env, None);
+            let program = &command[0];
+            let path_var = self.env.get("PATH").map(std::ffi::OsString::from);
+            let path_var = path_var.or_else(|| std::env::var_os("PATH"));
+            if crate::transport::resolve_executable(program, path_var.as_deref()).is_none() {
+                return invalid(format!("agent program {program:?} not found"));
+            }
+        }
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        match (&self.endpoint, &self.socket_path) {
+            (Some(endpoint), _) => format!("ClientConfig(endpoint={endpoint:?})"),
+            (None, Some(path)) => format!("ClientConfig(socket_path={path:?})"),
+            (None, None) => format!("ClientConfig(command={:?})", self.command),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// McpServerConfig
+// ---------------------------------------------------------------------------
+
+/// An MCP server to attach to a new session.
+///
+/// Set `command` (with `args` and `env`) for a stdio server, or `url` (with
+/// `headers`) for one reached over HTTP.
+#[pyclass(get_all, set_all)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Name of the server, unique within the session.
+    pub name: String,
+    /// Executable that runs a stdio server.
+    pub command: Option<String>,
+    /// Arguments for `command`.
+    pub args: Vec<String>,
+    /// Environment variables for `command`.
+    pub env: HashMap<String, String>,
+    /// Endpoint of an HTTP server.
+    pub url: Option<String>,
+    /// HTTP headers sent to `url`.
+    pub headers: HashMap<String, String>,
+}
+
+impl McpServerConfig {
+    /// The ACP `McpServer` wire form, checked against the schema.
+    pub fn to_acp(&self) -> Result<serde_json::Value, crate::error::ConduitError> {
+        let invalid = |reason: String| {
+            crate::error::ConduitError::Session(format!(
+                "invalid MCP server config {:?}: {reason}",
+                self.name
+            ))
+        };
+        // ACP sends both maps as `[{"name", "value"}]`; sorted for a stable wire form.
+        let pairs = |map: &HashMap<String, String>| {
+            let mut pairs: Vec<_> = map.iter().collect();
+            pairs.sort();
+            pairs
+                .into_iter()
+                .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        };
+
+        if self.name.is_empty() {
+            return Err(invalid("name is empty".into()));
+        }
+        let server = match (&self.command, &self.url) {
+            (Some(command), None) => serde_json::json!({
+                "name": self.name,
+                "command": command,
+                "args": self.args,
+                "env": pairs(&self.env),
+            }),
+            (None, Some(url)) => serde_json::json!({
+                "type": "http",
+                "name": self.name,
+                "url": url,
+                "headers": pairs(&self.headers),
+            }),
+            (Some(_), Some(_)) => return Err(invalid("set command or url, not both".into())),
+            (None, None) => return Err(invalid("needs a command or a url".into())),
+        };
+        serde_json::from_value::<sacp::schema::McpServer>(server.clone())
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(server)
+    }
+}
+
+#[pymethods]
+impl McpServerConfig {
+    #[new]
+    #[pyo3(signature = (name, command=None, args=vec![], env=HashMap::new(), url=None, headers=HashMap::new()))]
+    fn new(
+        name: String,
+        command: Option<String>,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+        url: Option<String>,
+        headers: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            name,
+            command,
+            args,
+            env,
+            url,
+            headers,
         }
     }
 
+    /// The server as ACP JSON, as sent in `session/new`.
+    ///
+    /// Raises `SessionError` if the config is incomplete or invalid.
+    fn to_json(&self) -> PyResult<String> {
+        Ok(self.to_acp()?.to_string())
+    }
+
     fn __repr__(&self) -> String {
-        format!("ClientConfig(command={:?})", self.command)
+        match (&self.command, &self.url) {
+            (_, Some(url)) => format!("McpServerConfig(name={:?}, url={:?})", self.name, url),
+            (command, None) => format!(
+                "McpServerConfig(name={:?}, command={:?})",
+                self.name, command
+            ),
+        }
     }
 }
 
@@ -428,6 +1768,110 @@ impl PermissionResponse {
     }
 }
 
+/// A permission callback's decision on a tool use request.
+///
+/// Subclassed in Python by `PermissionResultAllow` and
+/// `PermissionResultDeny`. The decision is explicit, so either outcome may
+/// carry a reason.
+#[pyclass(subclass, get_all)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PermissionResult {
+    /// "allow" or "deny".
+    pub decision: String,
+    /// Permission option to select when allowing; the agent's first allow
+    /// option is used if unset or not offered.
+    pub option_id: Option<String>,
+    /// Explanation for the decision.
+    pub reason: Option<String>,
+}
+
+impl PermissionResult {
+    /// Read a decision from a dict with the same keys, as returned by
+    /// callbacks that don't build a `PermissionResult`.
+    pub fn from_dict(dict: &Bound<'_, pyo3::types::PyDict>) -> Option<Self> {
+        let get = |key: &str| -> Option<String> { dict.get_item(key).ok()??.extract().ok() };
+        Some(Self {
+            decision: get("decision")?,
+            option_id: get("option_id"),
+            reason: get("reason"),
+        })
+    }
+}
+
+#[pymethods]
+impl PermissionResult {
+    #[new]
+    #[pyo3(signature = (decision, option_id=None, reason=None))]
+    fn new(decision: String, option_id: Option<String>, reason: Option<String>) -> PyResult<Self> {
+        if decision != "allow" && decision != "deny" {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "decision must be \"allow\" or \"deny\", got {decision:?}"
+            )));
+        }
+        Ok(Self {
+            decision,
+            option_id,
+            reason,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PermissionResult(decision={:?}, reason={:?})",
+            self.decision, self.reason
+        )
+    }
+}
+
+/// A choice the agent offers when asking permission to run a tool, passed
+/// to the permission callback in `context["options"]`.
+///
+/// `kind` is the ACP wire name (`"allow_once"`, `"allow_always"`,
+/// `"reject_once"`, `"reject_always"`), passed through as-is so kinds
+/// newer than this SDK still arrive. Return `option_id` in a
+/// `PermissionResult` to select the option.
+#[pyclass(eq, get_all)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionOption {
+    pub option_id: String,
+    pub name: String,
+    pub kind: String,
+}
+
+impl PermissionOption {
+    pub fn from_acp(option: &sacp::schema::PermissionOption) -> Self {
+        let kind = serde_json::to_value(&option.kind).ok();
+        Self {
+            option_id: option.option_id.0.to_string(),
+            name: option.name.clone(),
+            kind: kind
+                .as_ref()
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl PermissionOption {
+    #[new]
+    fn new(option_id: String, name: String, kind: String) -> Self {
+        Self {
+            option_id,
+            name,
+            kind,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PermissionOption(option_id={:?}, kind={:?})",
+            self.option_id, self.kind
+        )
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ResultMessage — final result from agent at the end of a query
 // ---------------------------------------------------------------------------
@@ -446,16 +1890,24 @@ pub struct ResultMessage {
     pub num_turns: u32,
     /// Session identifier.
     pub session_id: String,
-    /// Total cost in USD (if available).
+    /// The session's cumulative cost in USD as of the turn's last usage
+    /// report, if the agent reported one in USD.
     pub total_cost_usd: Option<f64>,
     /// The final result text (if available).
     pub result: Option<String>,
+    /// Currency the agent reported the cost in. `total_cost_usd` is only
+    /// set when this is `"USD"`; amounts in other currencies are not
+    /// converted.
+    pub currency: Option<String>,
+    /// Number of the prompt turn summarized, within its session; matches
+    /// the `turn_id` of the turn's messages and updates.
+    pub turn_id: Option<u64>,
 }
 
 #[pymethods]
 impl ResultMessage {
     #[new]
-    #[pyo3(signature = (subtype, duration_ms, is_error, num_turns, session_id, total_cost_usd=None, result=None))]
+    #[pyo3(signature = (subtype, duration_ms, is_error, num_turns, session_id, total_cost_usd=None, result=None, currency=None, turn_id=None))]
     fn new(
         subtype: String,
         duration_ms: u64,
@@ -464,6 +1916,8 @@ impl ResultMessage {
         session_id: String,
         total_cost_usd: Option<f64>,
         result: Option<String>,
+        currency: Option<String>,
+        turn_id: Option<u64>,
     ) -> Self {
         Self {
             subtype,
@@ -473,6 +1927,8 @@ impl ResultMessage {
             session_id,
             total_cost_usd,
             result,
+            currency,
+            turn_id,
         }
     }
 
@@ -484,6 +1940,158 @@ impl ResultMessage {
     }
 }
 
+// ---------------------------------------------------------------------------
+// PromptResult — one prompt turn, aggregated
+// ---------------------------------------------------------------------------
+
+/// Everything a prompt turn produced, returned by `prompt_full()`.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PromptResult {
+    /// The assembled assistant messages (as returned by `prompt()`).
+    pub messages: Vec<Message>,
+    pub session_id: String,
+    /// One `ToolUse` block per tool call the agent started, in order.
+    pub tool_calls: Vec<ContentBlock>,
+    /// The last usage report of the turn as JSON, if the agent sent one.
+    pub usage_json: Option<String>,
+    /// Why the turn ended (e.g. "EndTurn", "Cancelled").
+    pub stop_reason: Option<String>,
+    /// Wall-clock duration of the turn in seconds.
+    pub duration_secs: f64,
+    /// The turn summarized as a result: duration, turn count and cost.
+    pub result_message: Option<ResultMessage>,
+}
+
+#[pymethods]
+impl PromptResult {
+    #[new]
+    #[pyo3(signature = (session_id, messages=vec![], tool_calls=vec![], usage_json=None, stop_reason=None, duration_secs=0.0, result_message=None))]
+    fn new(
+        session_id: String,
+        messages: Vec<Message>,
+        tool_calls: Vec<ContentBlock>,
+        usage_json: Option<String>,
+        stop_reason: Option<String>,
+        duration_secs: f64,
+        result_message: Option<ResultMessage>,
+    ) -> Self {
+        Self {
+            messages,
+            session_id,
+            tool_calls,
+            usage_json,
+            stop_reason,
+            duration_secs,
+            result_message,
+        }
+    }
+
+    /// Concatenated text of all messages.
+    fn text(&self) -> String {
+        self.messages.iter().map(Message::text).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PromptResult(session_id={:?}, tool_calls={}, stop_reason={:?})",
+            self.session_id,
+            self.tool_calls.len(),
+            self.stop_reason
+        )
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ResumeResult — parsed `session/resume` response
+// ---------------------------------------------------------------------------
+
+/// Session state restored by the agent in response to `session/resume`.
+#[pyclass(get_all)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResumeResult {
+    /// The resumed session's ID.
+    pub session_id: String,
+    /// Restored current mode ID, if the agent reported one.
+    pub mode: Option<String>,
+    /// Restored current model ID, if the agent reported one.
+    pub model: Option<String>,
+    /// When the session was last updated (ISO 8601), if reported.
+    pub updated_at: Option<String>,
+    /// The raw response as a JSON string.
+    pub raw_json: String,
+}
+
+impl ResumeResult {
+    /// Parse a `session/resume` response body.
+    ///
+    /// Accepts both the camelCase wire format and snake_case keys. The
+    /// session ID is the one in the response, falling back to the
+    /// requested `session_id` when the agent doesn't send one.
+    pub fn from_response(session_id: String, response: &serde_json::Value) -> Self {
+        Self {
+            session_id: str_at(response, &[&["sessionId", "session_id"]]).unwrap_or(session_id),
+            mode: str_at(
+                response,
+                &[&["modes"], &["currentModeId", "current_mode_id"]],
+            ),
+            model: str_at(
+                response,
+                &[&["models"], &["currentModelId", "current_model_id"]],
+            ),
+            updated_at: str_at(response, &[&["updatedAt", "updated_at"]])
+                .or_else(|| str_at(response, &[&["_meta"], &["updatedAt", "updated_at"]])),
+            raw_json: response.to_string(),
+        }
+    }
+}
+
+/// Follow `path` into `value`, trying each alternative key at every step,
+/// and return the string found at the end.
+fn str_at(value: &serde_json::Value, path: &[&[&str]]) -> Option<String> {
+    let mut current = value;
+    for keys in path {
+        current = keys.iter().find_map(|k| current.get(*k))?;
+    }
+    current.as_str().map(str::to_string)
+}
+
+#[pymethods]
+impl ResumeResult {
+    #[new]
+    #[pyo3(signature = (session_id, mode=None, model=None, updated_at=None, raw_json="{}".to_string()))]
+    fn new(
+        session_id: String,
+        mode: Option<String>,
+        model: Option<String>,
+        updated_at: Option<String>,
+        raw_json: String,
+    ) -> Self {
+        Self {
+            session_id,
+            mode,
+            model,
+            updated_at,
+            raw_json,
+        }
+    }
+
+    /// Parse a raw `session/resume` response JSON string.
+    #[staticmethod]
+    fn from_json(session_id: String, raw_json: &str) -> PyResult<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(raw_json).map_err(crate::error::ConduitError::from)?;
+        Ok(Self::from_response(session_id, &value))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ResumeResult(session_id={:?}, mode={:?}, model={:?})",
+            self.session_id, self.mode, self.model
+        )
+    }
+}
+
 // ---------------------------------------------------------------------------
 // StreamEvent — real-time streaming event from the agent control protocol
 // ---------------------------------------------------------------------------
@@ -519,17 +2127,32 @@ impl StreamEvent {
 /// Register all types on the Python module.
 pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Capabilities>()?;
+    m.add_class::<AgentInfo>()?;
+    m.add_class::<ConnectionState>()?;
     m.add_class::<MessageRole>()?;
     m.add_class::<ContentType>()?;
     m.add_class::<ContentBlock>()?;
     m.add_class::<Message>()?;
     m.add_class::<UpdateKind>()?;
+    m.add_class::<ToolKind>()?;
+    m.add_class::<ToolStatus>()?;
     m.add_class::<SessionUpdate>()?;
+    m.add_class::<ToolContent>()?;
+    m.add_class::<FileEdit>()?;
+    m.add_class::<ToolLocation>()?;
+    m.add_class::<PlanEntry>()?;
+    m.add_class::<RateLimitInfo>()?;
+    m.add_class::<Cost>()?;
     m.add_class::<ClientConfig>()?;
+    m.add_class::<McpServerConfig>()?;
     m.add_class::<ToolDefinition>()?;
     m.add_class::<PermissionRequest>()?;
     m.add_class::<PermissionResponse>()?;
+    m.add_class::<PermissionResult>()?;
+    m.add_class::<PermissionOption>()?;
     m.add_class::<ResultMessage>()?;
+    m.add_class::<PromptResult>()?;
+    m.add_class::<ResumeResult>()?;
     m.add_class::<StreamEvent>()?;
     Ok(())
 }
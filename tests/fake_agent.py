@@ -0,0 +1,203 @@
+"""Scriptable stand-in for an ACP agent, used by the client tests.
+
+Usage: ``python fake_agent.py LOG_PATH``. Every message received on stdin
+is appended to ``LOG_PATH`` as a JSON line. Behaviour is tuned through
+environment variables:
+
+``FAKE_AGENT_META``
+    JSON object advertised as ``agentCapabilities._meta``.
+``FAKE_AGENT_INFO``
+    JSON object answered as ``agentInfo`` to ``initialize``.
+``FAKE_AGENT_PROTOCOL_VERSION``
+    Protocol version answered to ``initialize`` (default ``1``).
+``FAKE_AGENT_EXIT_ON``
+    Method name on which the agent exits without replying.
+``FAKE_AGENT_IGNORE``
+    Method name the agent never replies to.
+``FAKE_AGENT_HANG_ON``
+    Method name on which the agent stops reading and replying altogether,
+    without exiting.
+``FAKE_AGENT_CLOSE_INPUT_ON``
+    Method name on which the agent closes its stdin, replies, and then
+    idles without reading anything more.
+``FAKE_AGENT_ERRORS``
+    JSON object mapping method names to JSON-RPC ``error`` objects the
+    agent replies with instead of handling the request.
+``FAKE_AGENT_ECHO``
+    When set, every ``session/prompt`` first sends its prompt text back as
+    an ``agent_message_chunk``.
+``FAKE_AGENT_NOTIFICATIONS``
+    JSON list of ``session/update`` ``update`` objects, sent first on every
+    ``session/prompt``.
+``FAKE_AGENT_STOP_REASON``
+    Stop reason answered to every ``session/prompt`` (default
+    ``end_turn``).
+``FAKE_AGENT_EXT_NOTIFICATIONS``
+    JSON list of ``{"method", "params"}`` extension notifications, sent
+    after the ``session/update`` ones on every ``session/prompt``.
+``FAKE_AGENT_CONFIRM_REQUEST``
+    JSON ``_conduit/request_confirmation`` params (minus ``sessionId``),
+    sent on ``session/prompt`` as request ``confirm-1`` after any
+    permission request.
+``FAKE_AGENT_RATE_LIMIT``
+    JSON params for a ``_rate_limit`` extension notification. The first
+    ``session/prompt`` sends it (with ``sessionId`` added) and then fails
+    with a ``rate limited`` error; later prompts are handled normally.
+``FAKE_AGENT_TOOL_CALLS``
+    JSON list of ``{"name", "arguments"}`` objects. On ``session/prompt``
+    the agent sends each as an MCP ``tools/call`` request (ids ``tool-1``,
+    ``tool-2``, ...) and waits for its response before ending the turn.
+``FAKE_AGENT_HOLD_PROMPT``
+    When set, ``session/prompt`` is left open until the client sends
+    ``session/cancel`` for its session (the turn then ends ``cancelled``)
+    or ``_conduit/cancel_tool`` (answered, then the turn ends normally).
+``FAKE_AGENT_PERMISSION_REQUEST``
+    JSON ``session/request_permission`` params (minus ``sessionId``). On
+    ``session/prompt`` the agent sends it as request ``permission-1`` and
+    waits for the response, before any tool calls.
+"""
+
+from __future__ import annotations
+
+import json
+import os
+import sys
+import time
+
+
+def reply(msg_id, result=None, error=None) -> None:
+    out = {"jsonrpc": "2.0", "id": msg_id}
+    if error is not None:
+        out["error"] = error
+    else:
+        out["result"] = result if result is not None else {}
+    print(json.dumps(out), flush=True)
+
+
+def receive(log_path: str) -> dict | None:
+    line = sys.stdin.readline()
+    if not line:
+        return None
+    msg = json.loads(line)
+    with open(log_path, "a") as log:
+        log.write(json.dumps(msg) + "\n")
+    return msg
+
+
+def request(log_path: str, request_id: str, method: str, params: dict) -> None:
+    """Send a request to the client and wait for its response."""
+    out = {"jsonrpc": "2.0", "id": request_id, "method": method, "params": params}
+    print(json.dumps(out), flush=True)
+    while (msg := receive(log_path)) is not None and msg.get("id") != request_id:
+        pass
+
+
+def main() -> None:
+    log_path = sys.argv[1]
+    meta = json.loads(os.environ.get("FAKE_AGENT_META", "{}"))
+    agent_info = os.environ.get("FAKE_AGENT_INFO")
+    protocol_version = int(os.environ.get("FAKE_AGENT_PROTOCOL_VERSION", "1"))
+    exit_on = os.environ.get("FAKE_AGENT_EXIT_ON")
+    ignore = os.environ.get("FAKE_AGENT_IGNORE")
+    hang_on = os.environ.get("FAKE_AGENT_HANG_ON")
+    close_input_on = os.environ.get("FAKE_AGENT_CLOSE_INPUT_ON")
+    errors = json.loads(os.environ.get("FAKE_AGENT_ERRORS", "{}"))
+    tool_calls = json.loads(os.environ.get("FAKE_AGENT_TOOL_CALLS", "[]"))
+    permission = os.environ.get("FAKE_AGENT_PERMISSION_REQUEST")
+    updates = json.loads(os.environ.get("FAKE_AGENT_NOTIFICATIONS", "[]"))
+    ext_notifications = json.loads(os.environ.get("FAKE_AGENT_EXT_NOTIFICATIONS", "[]"))
+    confirm = os.environ.get("FAKE_AGENT_CONFIRM_REQUEST")
+    rate_limit = os.environ.get("FAKE_AGENT_RATE_LIMIT")
+    echo = "FAKE_AGENT_ECHO" in os.environ
+    stop_reason = os.environ.get("FAKE_AGENT_STOP_REASON", "end_turn")
+    hold_prompt = "FAKE_AGENT_HOLD_PROMPT" in os.environ
+    sessions = 0
+
+    while (msg := receive(log_path)) is not None:
+        method = msg.get("method")
+        if "id" not in msg or method is None:
+            continue  # notification or response
+        if method == exit_on:
+            sys.exit(1)
+        if method == ignore:
+            continue
+        if method == hang_on:
+            while True:
+                time.sleep(60)
+        if method == close_input_on:
+            # Close the input before replying, so the client's next write
+            # is guaranteed to hit a closed pipe.
+            sys.stdin.close()
+            os.close(0)
+            reply(msg["id"], {"sessionId": "sess-1"} if method == "session/new" else {})
+            while True:
+                time.sleep(60)
+        if method in errors:
+            reply(msg["id"], error=errors[method])
+            continue
+        if method == "initialize":
+            result = {
+                "protocolVersion": protocol_version,
+                "agentCapabilities": {"_meta": meta},
+                "authMethods": [],
+            }
+            if agent_info is not None:
+                result["agentInfo"] = json.loads(agent_info)
+            reply(msg["id"], result)
+        elif method == "session/new":
+            sessions += 1
+            reply(msg["id"], {"sessionId": f"sess-{sessions}"})
+        elif method == "session/load":
+            reply(msg["id"], {})
+        elif method == "session/list":
+            cwd = msg["params"].get("cwd")
+            listed = [{"sessionId": f"sess-{n}", "cwd": cwd} for n in range(1, sessions + 1)]
+            reply(msg["id"], {"sessions": listed})
+        elif method in ("_conduit/add_mcp_server", "_conduit/cancel_tool"):
+            reply(msg["id"], {})
+        elif method == "session/prompt":
+            if rate_limit is not None:
+                params = {**json.loads(rate_limit), "sessionId": msg["params"]["sessionId"]}
+                notification = {"jsonrpc": "2.0", "method": "_rate_limit", "params": params}
+                print(json.dumps(notification), flush=True)
+                reply(msg["id"], error={"code": -32000, "message": "rate limited"})
+                rate_limit = None
+                continue
+            turn_updates = list(updates)
+            if echo:
+                text = "".join(b.get("text", "") for b in msg["params"]["prompt"])
+                content = {"type": "text", "text": text}
+                turn_updates.insert(0, {"sessionUpdate": "agent_message_chunk", "content": content})
+            for update in turn_updates:
+                params = {"sessionId": msg["params"]["sessionId"], "update": update}
+                notification = {"jsonrpc": "2.0", "method": "session/update", "params": params}
+                print(json.dumps(notification), flush=True)
+            for ext in ext_notifications:
+                notification = {"jsonrpc": "2.0", **ext}
+                print(json.dumps(notification), flush=True)
+            if permission is not None:
+                params = {**json.loads(permission), "sessionId": msg["params"]["sessionId"]}
+                request(log_path, "permission-1", "session/request_permission", params)
+            if confirm is not None:
+                params = {**json.loads(confirm), "sessionId": msg["params"]["sessionId"]}
+                request(log_path, "confirm-1", "_conduit/request_confirmation", params)
+            for n, call in enumerate(tool_calls, start=1):
+                request(log_path, f"tool-{n}", "tools/call", call)
+            turn_stop_reason = stop_reason
+            while hold_prompt and (held := receive(log_path)) is not None:
+                params = held.get("params") or {}
+                if held.get("method") == "_conduit/cancel_tool":
+                    reply(held["id"], {})
+                    break
+                if held.get("method") == "session/cancel" and (
+                    params.get("sessionId") == msg["params"]["sessionId"]
+                ):
+                    turn_stop_reason = "cancelled"
+                    break
+            reply(msg["id"], {"stopReason": turn_stop_reason})
+        else:
+            reply(msg["id"], error={"code": -32601, "message": f"unknown method {method}"})
+
+
+if __name__ == "__main__":
+    main()
@@ -2,12 +2,58 @@
 
 from __future__ import annotations
 
+import asyncio
+import json
+import logging
+import os
+import select
+import shlex
+import signal
+import socket
+import subprocess
+import sys
+import textwrap
+import threading
+import time
+from decimal import Decimal
+from pathlib import Path
+
 import pytest
 
-from conduit_sdk import Client
-from conduit_sdk.exceptions import ConnectionError
+from conduit_sdk import Client, enable_tracing
+from conduit_sdk._conduit_sdk import (
+    AgentInfo,
+    ClientConfig,
+    ConnectionState,
+    ContentBlock,
+    ContentType,
+    Cost,
+    FileEdit,
+    HookType,
+    McpServerConfig,
+    PermissionOption,
+    PlanEntry,
+    RateLimitInfo,
+    RustClient,
+    RustHookDispatcher,
+    RustToolRegistry,
+    ToolDefinition,
+    ToolKind,
+    ToolStatus,
+    UpdateKind,
+)
+from conduit_sdk.exceptions import (
+    AuthRequiredError,
+    ConduitError,
+    ConnectionError,
+    ProtocolError,
+    SessionError,
+    TimeoutError,
+    UnsupportedError,
+)
 from conduit_sdk.options import AgentOptions
 from conduit_sdk.permissions import PermissionResultAllow, PermissionResultDeny
+from conduit_sdk.types import TextBlock
 
 
 class TestClientInit:
@@ -82,3 +128,2580 @@ class TestClientRepr:
     def test_disconnected(self):
         client = Client(["agent"])
         assert "disconnected" in repr(client)
+
+
+class TestClientAclose:
+    @pytest.mark.asyncio
+    async def test_aclose_cancels_turns_and_closes_sessions(self, tmp_path, monkeypatch):
+        # The agent holds the prompt open until it reads the cancel.
+        monkeypatch.setenv("FAKE_AGENT_HOLD_PROMPT", "1")
+        log = tmp_path / "agent.log"
+        client = Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)])
+        await client.connect()
+        busy = await client.new_session()
+        idle = await client.new_session()
+        turn = asyncio.create_task(busy.prompt("long running"))
+        while not _logged_requests(log, "session/prompt"):
+            await asyncio.sleep(0.05)
+
+        await client.aclose(grace_secs=10)
+        await asyncio.wait_for(turn, timeout=1)
+
+        cancelled = {n["params"]["sessionId"] for n in _logged_requests(log, "session/cancel")}
+        assert busy.session_id in cancelled
+        assert busy.closed and idle.closed
+        assert client.sessions == []
+        assert not client.connected
+
+    @pytest.mark.asyncio
+    async def test_aclose_is_idempotent(self, tmp_path):
+        client = Client([sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")])
+        await client.connect()
+
+        await client.aclose()
+        await client.aclose()
+        assert not client.connected
+
+
+# Minimal ACP agent: records its pid, then answers ``initialize`` and idles.
+_FAKE_AGENT = textwrap.dedent(
+    """
+    import json, os, sys
+    with open(sys.argv[1], "a") as f:
+        f.write(f"{os.getpid()}\\n")
+    for line in sys.stdin:
+        msg = json.loads(line)
+        if msg.get("method") == "initialize":
+            result = {"protocolVersion": 1, "agentCapabilities": {}, "authMethods": []}
+            print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "result": result}), flush=True)
+    """
+)
+
+
+class TestClientConcurrentConnect:
+    @pytest.mark.asyncio
+    async def test_second_connect_errors_without_spawning(self, tmp_path):
+        script = tmp_path / "agent.py"
+        script.write_text(_FAKE_AGENT)
+        pids = tmp_path / "pids"
+        client = Client([sys.executable, str(script), str(pids)])
+
+        await client.connect()
+        try:
+            with pytest.raises(ConnectionError, match="already connected"):
+                await client._rust_client.connect()
+        finally:
+            await client.disconnect()
+        assert len(pids.read_text().splitlines()) == 1
+
+    @pytest.mark.asyncio
+    async def test_concurrent_connects_spawn_one_child(self, tmp_path):
+        script = tmp_path / "agent.py"
+        script.write_text(_FAKE_AGENT)
+        pids = tmp_path / "pids"
+        client = Client([sys.executable, str(script), str(pids)])
+
+        results = await asyncio.gather(
+            client._rust_client.connect(),
+            client._rust_client.connect(),
+            return_exceptions=True,
+        )
+        try:
+            errors = [r for r in results if isinstance(r, ConnectionError)]
+            assert len(errors) == 1
+            assert "already connected/connecting" in str(errors[0])
+        finally:
+            await client._rust_client.disconnect()
+        assert len(pids.read_text().splitlines()) == 1
+
+
+_FAKE_AGENT_PATH = Path(__file__).with_name("fake_agent.py")
+
+
+def _logged_requests(log: Path, method: str) -> list[dict]:
+    messages = [json.loads(line) for line in log.read_text().splitlines()]
+    return [m for m in messages if m.get("method") == method]
+
+
+class TestReplyDropContext:
+    @pytest.mark.asyncio
+    async def test_new_session_drop_distinct_from_prompt_drop(self, tmp_path, monkeypatch):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+
+        monkeypatch.setenv("FAKE_AGENT_EXIT_ON", "session/new")
+        async with Client(command) as client:
+            with pytest.raises(ConnectionError) as new_session_err:
+                await client.new_session()
+
+        monkeypatch.setenv("FAKE_AGENT_EXIT_ON", "session/prompt")
+        async with Client(command) as client:
+            session = await client.new_session()
+            with pytest.raises(ConnectionError) as prompt_err:
+                await session.prompt("hello")
+
+        assert "new_session" in str(new_session_err.value)
+        assert f"prompt (session {session.session_id})" in str(prompt_err.value)
+        assert str(new_session_err.value) != str(prompt_err.value)
+
+
+class TestAgentErrorCodes:
+    @pytest.mark.asyncio
+    async def test_code_and_data_reach_python(self, tmp_path, monkeypatch):
+        error = {"code": -32002, "message": "resource not found", "data": {"uri": "file:///x"}}
+        monkeypatch.setenv("FAKE_AGENT_ERRORS", json.dumps({"session/prompt": error}))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(ProtocolError, match="resource not found") as exc_info:
+                await client.prompt_full("hello")
+
+        assert exc_info.value.code == -32002
+        assert json.loads(exc_info.value.data) == {"uri": "file:///x"}
+
+    @pytest.mark.asyncio
+    async def test_error_carries_the_session_id(self, tmp_path, monkeypatch):
+        error = {"code": -32602, "message": "unknown session"}
+        monkeypatch.setenv("FAKE_AGENT_ERRORS", json.dumps({"session/prompt": error}))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(ProtocolError, match="unknown session") as exc_info:
+                await client.prompt_full("hello", session_id="sess-missing")
+            with pytest.raises(ProtocolError) as raw_err:
+                await client.raw_request("_vendor/frobnicate")
+
+        assert exc_info.value.session_id == "sess-missing"
+        assert raw_err.value.session_id is None
+
+    @pytest.mark.asyncio
+    async def test_auth_required_is_its_own_exception(self, tmp_path, monkeypatch):
+        error = {
+            "code": -32000,
+            "message": "Authentication required",
+            "data": {"authMethods": [{"id": "oauth", "name": "OAuth"}, "api-key"]},
+        }
+        monkeypatch.setenv("FAKE_AGENT_ERRORS", json.dumps({"session/new": error}))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(AuthRequiredError) as exc_info:
+                await client.new_session()
+
+        assert isinstance(exc_info.value, ProtocolError)
+        assert exc_info.value.code == -32000
+        assert exc_info.value.auth_methods == ["oauth", "api-key"]
+
+
+class TestRawRequest:
+    @pytest.mark.asyncio
+    async def test_session_list_round_trips(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command) as client:
+            await client.new_session()
+            result = await client.raw_request("session/list", {"cwd": "/work"})
+
+        assert result == {"sessions": [{"sessionId": "sess-1", "cwd": "/work"}]}
+        [request] = _logged_requests(log, "session/list")
+        assert request["params"] == {"cwd": "/work"}
+
+    @pytest.mark.asyncio
+    async def test_unknown_method_raises_coded_error(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(ProtocolError, match="unknown method") as exc_info:
+                await client.raw_request("_vendor/frobnicate")
+
+        assert exc_info.value.code == -32601
+
+    @pytest.mark.asyncio
+    async def test_malformed_params_json_raises(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(ProtocolError, match="invalid params_json"):
+                await client._rust_client.raw_request("session/list", "{not json")
+
+    @pytest.mark.asyncio
+    async def test_notification_reaches_agent(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        params = {"event": "opened", "_meta": {"source": "ide"}}
+        async with Client(command) as client:
+            await client.raw_notification("_vendor/event", params)
+            # The agent handles messages in order, so a reply to a later
+            # request means the notification has been read.
+            await client.new_session()
+
+        [notification] = _logged_requests(log, "_vendor/event")
+        assert "id" not in notification
+        assert notification["params"] == params
+
+
+class TestProtocolVersionPin:
+    @pytest.mark.asyncio
+    async def test_matching_pin_connects(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, protocol_version=1) as client:
+            assert client.connected
+
+        initialize = json.loads(log.read_text().splitlines()[0])
+        assert initialize["params"]["protocolVersion"] == 1
+
+    @pytest.mark.asyncio
+    async def test_unsupported_pin_fails_clearly(self, tmp_path):
+        # The fake agent only speaks version 1.
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command, protocol_version=2)
+        with pytest.raises(UnsupportedError, match="protocol version 2 is pinned"):
+            await client.connect()
+        assert not client.connected
+
+    @pytest.mark.asyncio
+    async def test_negotiated_version_is_reported(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            assert client.capabilities.protocol_version == "1"
+
+    @pytest.mark.asyncio
+    async def test_newer_negotiated_version_is_refused(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_PROTOCOL_VERSION", "99")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command)
+        with pytest.raises(UnsupportedError, match="negotiated protocol version 99"):
+            await client.connect()
+        assert not client.connected
+
+
+class TestCommandExpansion:
+    @pytest.mark.asyncio
+    async def test_variables_expanded_from_env(self, tmp_path):
+        command = ["$PYTHON", str(_FAKE_AGENT_PATH), "${LOG_DIR}/agent.log"]
+        env = {"PYTHON": sys.executable, "LOG_DIR": str(tmp_path)}
+        async with Client(command, env=env) as client:
+            assert client.connected
+        assert (tmp_path / "agent.log").exists()
+
+    @pytest.mark.asyncio
+    async def test_double_dollar_is_literal(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "a$$LOG.log")]
+        async with Client(command, env={"LOG": "expanded"}):
+            pass
+        assert (tmp_path / "a$LOG.log").exists()
+
+    @pytest.mark.asyncio
+    async def test_unknown_variable_left_literal_with_warning(self, tmp_path, caplog):
+        caplog.set_level(logging.WARNING, logger="conduit_sdk.client")
+        log = str(tmp_path / "${CONDUIT_TEST_UNSET_VAR}.log")
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), log]):
+            pass
+        assert (tmp_path / "${CONDUIT_TEST_UNSET_VAR}.log").exists()
+        warnings = [r.getMessage() for r in caplog.records if r.levelno == logging.WARNING]
+        assert any("CONDUIT_TEST_UNSET_VAR is not set" in w for w in warnings)
+
+
+class TestShellCommand:
+    @pytest.mark.asyncio
+    async def test_shell_expands_variables_and_redirects(self, tmp_path):
+        log = tmp_path / "agent.log"
+        stderr = tmp_path / "stderr.txt"
+        agent = shlex.quote(str(_FAKE_AGENT_PATH))
+        line = f'"$AGENT_PYTHON" {agent} "$AGENT_LOG" 2>{shlex.quote(str(stderr))}'
+        env = {"AGENT_PYTHON": sys.executable, "AGENT_LOG": str(log)}
+        async with Client([line], env=env, use_shell=True) as client:
+            assert client.connected
+        assert log.exists()
+        assert stderr.exists()
+
+
+class TestProcessGroupKill:
+    @pytest.mark.asyncio
+    @pytest.mark.skipif(os.name != "posix", reason="process groups are Unix-only")
+    async def test_disconnect_kills_agent_children(self, tmp_path):
+        log = tmp_path / "agent.log"
+        agent = " ".join(shlex.quote(str(p)) for p in (sys.executable, _FAKE_AGENT_PATH, log))
+        line = f"sleep 300 & exec {agent}"
+        # The agent and its child inherit the write end, so the read end
+        # only sees EOF once both have exited (a zombie holds no fds).
+        read_fd, write_fd = os.pipe()
+        os.set_inheritable(write_fd, True)
+        try:
+            async with Client([line], use_shell=True) as client:
+                os.close(write_fd)
+                assert client.connected
+
+            ready, _, _ = await asyncio.to_thread(select.select, [read_fd], [], [], 5)
+            assert ready, "agent's child survived disconnect"
+            assert os.read(read_fd, 1) == b""
+        finally:
+            os.close(read_fd)
+
+
+class TestConnectTimeout:
+    @pytest.mark.asyncio
+    @pytest.mark.skipif(os.name != "posix", reason="uses sh to record the agent's pid")
+    async def test_silent_handshake_times_out_and_kills_agent(self, tmp_path):
+        pidfile = tmp_path / "agent.pid"
+        log = tmp_path / "agent.log"
+        agent = " ".join(shlex.quote(str(p)) for p in (sys.executable, _FAKE_AGENT_PATH, log))
+        line = f"echo $$ > {shlex.quote(str(pidfile))}; exec {agent}"
+        env = {"FAKE_AGENT_IGNORE": "initialize"}
+        client = Client([line], env=env, timeout=1, use_shell=True)
+        with pytest.raises(TimeoutError, match="initialize handshake timed out"):
+            await client.connect()
+        assert not client.connected
+        assert client.state == ConnectionState.Disconnected
+        assert _logged_requests(log, "initialize")
+
+        pid = int(pidfile.read_text())
+        for _ in range(100):
+            try:
+                os.kill(pid, 0)
+            except ProcessLookupError:
+                break
+            await asyncio.sleep(0.05)
+        else:
+            pytest.fail("agent survived the handshake timeout")
+
+
+class TestLogCallback:
+    @pytest.mark.asyncio
+    async def test_spawn_failure_is_logged_to_late_callback(self, tmp_path):
+        rust = RustClient(ClientConfig(command=[str(tmp_path / "no-such-agent")]))
+        with pytest.raises(ConnectionError, match="failed to spawn agent"):
+            await rust.connect()
+
+        # Logged before a callback was set, so it was buffered until now.
+        logged = []
+        rust.set_log_callback(lambda level, message: logged.append((level, message)))
+        [(level, message)] = logged
+        assert level == "error"
+        assert "failed to spawn agent" in message
+
+
+class TestConnectValidation:
+    @pytest.mark.asyncio
+    async def test_invalid_config_raises_before_spawning(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        rust = RustClient(ClientConfig(command=command, cwd=str(tmp_path / "missing")))
+        with pytest.raises(ValueError, match="not an existing directory"):
+            await rust.connect()
+        with pytest.raises(ValueError, match="not an existing directory"):
+            rust.connect_blocking()
+        assert not log.exists()
+
+    @pytest.mark.asyncio
+    async def test_empty_command_raises(self):
+        with pytest.raises(ValueError, match="command must not be empty"):
+            await Client([]).connect()
+
+
+class TestTracing:
+    @pytest.mark.asyncio
+    async def test_prompt_events_reach_python_logging(self, tmp_path, caplog):
+        caplog.set_level(logging.DEBUG, logger="conduit_sdk.trace")
+        enable_tracing("debug")
+        enable_tracing("debug")  # installing again is a no-op
+        try:
+            command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+            async with Client(command) as client:
+                await client.prompt_full("hi")
+        finally:
+            enable_tracing("off")
+
+        messages = [r.getMessage() for r in caplog.records if r.name == "conduit_sdk.trace"]
+        assert any("session/prompt" in message for message in messages)
+
+    def test_unknown_level_is_rejected(self):
+        with pytest.raises(ValueError, match="unknown tracing level"):
+            enable_tracing("loud")
+
+
+class TestStaleEvents:
+    @pytest.mark.asyncio
+    async def test_next_prompt_drains_abandoned_stream(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        stale = []
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        options = AgentOptions(stale_update_callback=stale.append)
+        async with Client(command, options=options) as client:
+            # Start a streamed turn and never read its updates. The background
+            # task runs requests in order, so once a later request returns
+            # every event of that turn is buffered.
+            await client._rust_client.send_prompt("one")
+            await client.raw_request("session/list")
+
+            [message] = [m async for m in client.prompt("two")]
+
+        assert message.text() == "two"
+        assert [u.kind for u in stale] == [UpdateKind.TextDelta, UpdateKind.Done]
+        assert stale[0].text == "one"
+        # Stamped when queued, so they keep the abandoned turn's IDs.
+        assert {u.turn_id for u in stale} == {1}
+        assert len({u.request_id for u in stale}) == 1
+        assert stale[0].request_id is not None
+
+
+class TestPromptQueue:
+    @pytest.mark.asyncio
+    async def test_overlapping_prompts_run_in_call_order(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            session = await client.new_session()
+            results = await asyncio.gather(
+                *(client.prompt_full(text, session_id=session.session_id) for text in "abc")
+            )
+
+        assert [r.text() for r in results] == ["a", "b", "c"]
+
+    @pytest.mark.asyncio
+    async def test_busy_session_rejects_with_on_busy_error(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, on_busy="error") as client:
+            session = await client.new_session()
+            first, second = await asyncio.gather(
+                client.prompt_full("a", session_id=session.session_id),
+                client.prompt_full("b", session_id=session.session_id),
+                return_exceptions=True,
+            )
+            assert first.text() == "a"
+            assert isinstance(second, SessionError)
+            assert "busy" in str(second)
+            # Once the first is done the session takes prompts again.
+            assert (await client.prompt_full("c", session_id=session.session_id)).text() == "c"
+
+        # The rejected turn didn't use up a request ID.
+        prompts = _logged_requests(log, "session/prompt")
+        assert [p["params"]["_meta"]["requestId"] for p in prompts] == ["prompt-1", "prompt-2"]
+
+    @pytest.mark.asyncio
+    async def test_unnamed_session_queues_with_the_default_session(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command, on_busy="error") as client:
+            session = await client.new_session()
+            first, second = await asyncio.gather(
+                client.prompt_full("a"),
+                client.prompt_full("b", session_id=session.session_id),
+                return_exceptions=True,
+            )
+            assert first.text() == "a"
+            assert isinstance(second, SessionError)
+
+    def test_unknown_on_busy_is_rejected(self):
+        with pytest.raises(ValueError, match="on_busy"):
+            ClientConfig(command=["agent"], on_busy="drop")
+
+
+class TestHeartbeat:
+    @pytest.mark.asyncio
+    async def test_answered_heartbeats_report_latency(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, heartbeat=0.1) as client:
+            assert client.heartbeat_latency is None
+            await asyncio.sleep(0.5)
+            assert client.heartbeat_latency is not None
+            assert 0 <= client.heartbeat_latency < 0.1
+            assert client.state == ConnectionState.Connected
+
+        assert _logged_requests(log, "_conduit/ping")
+
+    @pytest.mark.asyncio
+    async def test_unresponsive_agent_trips_the_heartbeat(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_HANG_ON", "session/prompt")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command, heartbeat=0.1) as client:
+            session = await client.new_session()
+            with pytest.raises(ConnectionError):
+                await asyncio.wait_for(client.prompt_full("hi", session_id=session.session_id), 5)
+            assert client.state == ConnectionState.Failed
+            with pytest.raises(ConnectionError):
+                await client.new_session()
+
+    @pytest.mark.parametrize("secs", [-1.0, float("nan"), float("inf"), 1e300])
+    def test_invalid_heartbeat_is_rejected(self, secs):
+        with pytest.raises(ValueError, match="heartbeat_secs"):
+            ClientConfig(command=["agent"], heartbeat_secs=secs)
+
+    @pytest.mark.asyncio
+    async def test_huge_heartbeat_set_after_validation_is_clamped(self, tmp_path):
+        config = ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "a")])
+        config.heartbeat_secs = 1e300
+        rust = RustClient(config)
+        await rust.connect()
+        try:
+            assert rust.state() == ConnectionState.Connected
+        finally:
+            await rust.disconnect()
+
+
+class TestClientIdentity:
+    @pytest.mark.asyncio
+    async def test_custom_identity_reaches_initialize(self, tmp_path):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(
+            command, client_name="my-app", client_version="2.1.0", client_title="My App"
+        ):
+            pass
+
+        [init] = _logged_requests(log, "initialize")
+        info = init["params"]["clientInfo"]
+        assert (info["name"], info["version"], info["title"]) == ("my-app", "2.1.0", "My App")
+
+    def test_identity_defaults_to_the_sdk(self):
+        config = ClientConfig(command=["agent"])
+        assert config.client_name == "conduit-agent-sdk"
+        assert config.client_version
+        assert config.client_title is None
+
+
+class TestTcpTransport:
+    @pytest.mark.skipif(os.name != "posix", reason="hands a socket to the agent as its stdio")
+    @pytest.mark.asyncio
+    async def test_connects_to_agent_over_tcp(self, tmp_path):
+        log = tmp_path / "agent.log"
+        server = socket.create_server(("127.0.0.1", 0))
+        port = server.getsockname()[1]
+        agents = []
+
+        def serve():
+            conn, _ = server.accept()
+            with conn:
+                command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+                agents.append(subprocess.Popen(command, stdin=conn, stdout=conn))
+
+        thread = threading.Thread(target=serve, daemon=True)
+        thread.start()
+        try:
+            async with Client([], endpoint=f"127.0.0.1:{port}") as client:
+                session = await client.new_session()
+                assert session.session_id == "sess-1"
+        finally:
+            server.close()
+            thread.join(timeout=5)
+        # Disconnecting closes the socket, which ends the agent's input.
+        [agent] = agents
+        assert agent.wait(timeout=5) == 0
+        assert len(_logged_requests(log, "initialize")) == 1
+
+    @pytest.mark.asyncio
+    async def test_unreachable_endpoint_raises(self):
+        server = socket.create_server(("127.0.0.1", 0))
+        port = server.getsockname()[1]
+        server.close()
+        with pytest.raises(ConnectionError, match="failed to connect to agent"):
+            await Client([], endpoint=f"127.0.0.1:{port}").connect()
+
+
+@pytest.mark.skipif(os.name != "posix", reason="Unix domain sockets")
+class TestUnixSocketTransport:
+    @pytest.mark.asyncio
+    async def test_connects_to_agent_on_socket(self, tmp_path):
+        log = tmp_path / "agent.log"
+        path = str(tmp_path / "agent.sock")
+        server = socket.socket(socket.AF_UNIX)
+        server.bind(path)
+        server.listen()
+        agents = []
+
+        def serve():
+            conn, _ = server.accept()
+            with conn:
+                command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+                agents.append(subprocess.Popen(command, stdin=conn, stdout=conn))
+
+        thread = threading.Thread(target=serve, daemon=True)
+        thread.start()
+        try:
+            async with Client([], socket_path=path) as client:
+                session = await client.new_session()
+                assert session.session_id == "sess-1"
+        finally:
+            server.close()
+            thread.join(timeout=5)
+        [agent] = agents
+        assert agent.wait(timeout=5) == 0
+        assert len(_logged_requests(log, "initialize")) == 1
+
+    @pytest.mark.asyncio
+    async def test_refused_connection_is_explained(self, tmp_path):
+        path = str(tmp_path / "agent.sock")
+        # Bound but not listening: connecting is refused.
+        server = socket.socket(socket.AF_UNIX)
+        server.bind(path)
+        try:
+            with pytest.raises(ConnectionError, match="nothing is listening"):
+                await Client([], socket_path=path).connect()
+        finally:
+            server.close()
+
+    @pytest.mark.asyncio
+    async def test_endpoint_and_socket_path_are_exclusive(self, tmp_path):
+        client = Client([], endpoint="127.0.0.1:1", socket_path=str(tmp_path / "agent.sock"))
+        with pytest.raises(ConnectionError, match="not both"):
+            await client.connect()
+
+
+class TestAgentClosedInput:
+    @pytest.mark.asyncio
+    async def test_prompt_fails_with_connection_error(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_CLOSE_INPUT_ON", "session/new")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            session = await client.new_session()
+            with pytest.raises(ConnectionError, match="agent closed its input"):
+                await session.prompt("hi")
+            # Later calls fail the same way rather than hanging.
+            with pytest.raises(ConnectionError, match="agent closed its input"):
+                await client.new_session()
+
+    @pytest.mark.asyncio
+    @pytest.mark.skipif(os.name != "posix", reason="uses sh to record the agent's pid")
+    async def test_killed_agent_ends_the_stream(self, tmp_path, monkeypatch):
+        pidfile = tmp_path / "agent.pid"
+        log = tmp_path / "agent.log"
+        agent = " ".join(shlex.quote(str(p)) for p in (sys.executable, _FAKE_AGENT_PATH, log))
+        line = f"echo $$ > {shlex.quote(str(pidfile))}; exec {agent}"
+        monkeypatch.setenv("FAKE_AGENT_HANG_ON", "session/prompt")
+        async with Client([line], use_shell=True) as client:
+            session = await client.new_session()
+            turn = asyncio.ensure_future(session.prompt("hi"))
+            while not _logged_requests(log, "session/prompt"):
+                await asyncio.sleep(0.05)
+            os.kill(int(pidfile.read_text()), signal.SIGKILL)
+
+            with pytest.raises(ConnectionError, match="agent closed the connection"):
+                await asyncio.wait_for(turn, 5)
+            with pytest.raises(ConnectionError):
+                await asyncio.wait_for(client.new_session(), 5)
+
+
+class TestUpdateCallback:
+    @pytest.mark.asyncio
+    async def test_callback_sees_raw_text_chunk(self, tmp_path, monkeypatch):
+        chunk = {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}}
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([chunk]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        seen = []
+
+        async def observe(raw):
+            # A slow observer must not hold up the turn.
+            await asyncio.sleep(0.2)
+            seen.append(json.loads(raw))
+
+        async with Client(command) as client:
+            client.set_update_callback(observe)
+            session = await client.new_session()
+            result = await client.prompt_full("hi", session_id=session.session_id)
+            assert result.text() == "hi"
+            for _ in range(50):
+                if seen:
+                    break
+                await asyncio.sleep(0.05)
+
+        [raw] = seen
+        assert raw["sessionId"] == session.session_id
+        assert raw["update"]["sessionUpdate"] == "agent_message_chunk"
+        assert raw["update"]["content"]["text"] == "hi"
+
+    @pytest.mark.asyncio
+    async def test_callback_sees_fields_the_typed_mapping_drops(self, tmp_path, monkeypatch):
+        chunk = {
+            "sessionUpdate": "agent_message_chunk",
+            "content": {"type": "text", "text": "hi"},
+            "vendorTag": "x-1",
+        }
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([chunk]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        seen: list[str] = []
+
+        async with Client(command) as client:
+            client.set_update_callback(seen.append)
+            session = await client.new_session()
+            await session.prompt("go")
+            for _ in range(50):
+                if seen:
+                    break
+                await asyncio.sleep(0.05)
+
+        assert seen == [json.dumps({"sessionId": session.session_id, "update": chunk})]
+
+
+class TestReplay:
+    @pytest.mark.asyncio
+    async def test_recording_replays_the_same_updates(self, tmp_path):
+        recording = tmp_path / "recording.jsonl"
+        messages = [
+            {"jsonrpc": "2.0", "id": 1, "result": {"sessionId": "rec-1"}},
+            {
+                "jsonrpc": "2.0",
+                "method": "session/update",
+                "params": {
+                    "sessionId": "rec-1",
+                    "update": {
+                        "sessionUpdate": "agent_message_chunk",
+                        "content": {"type": "text", "text": "Hello"},
+                    },
+                },
+            },
+            {
+                "jsonrpc": "2.0",
+                "method": "session/update",
+                "params": {
+                    "sessionId": "rec-1",
+                    "update": {
+                        "sessionUpdate": "tool_call",
+                        "toolCallId": "call-1",
+                        "title": "Read file",
+                        "kind": "read",
+                        "status": "pending",
+                    },
+                },
+            },
+            {"jsonrpc": "2.0", "id": 2, "result": {"stopReason": "end_turn"}},
+        ]
+        recording.write_text("".join(json.dumps(m) + "\n" for m in messages))
+
+        runs = []
+        for _ in range(2):
+            client = Client([])
+            await client.connect_replay(recording)
+            try:
+                session = await client.new_session()
+                assert session.session_id == "rec-1"
+                updates = [u async for u in client.prompt_stream("hi")]
+            finally:
+                await client.disconnect()
+            runs.append([(u.kind, u.text, u.tool_use_id, u.tool_kind) for u in updates])
+
+        assert runs[0] == runs[1]
+        assert runs[0] == [
+            (UpdateKind.TextDelta, "Hello", None, None),
+            (UpdateKind.ToolUseStart, None, "call-1", ToolKind.Read),
+            (UpdateKind.Done, None, None, None),
+        ]
+
+    @pytest.mark.asyncio
+    async def test_exhausted_recording_answers_with_an_error(self, tmp_path):
+        recording = tmp_path / "recording.jsonl"
+        recording.write_text("")
+        client = Client([])
+        await client.connect_replay(recording)
+        try:
+            with pytest.raises(ConduitError, match="no response left for session/new"):
+                await client.new_session()
+        finally:
+            await client.disconnect()
+
+
+class TestWireRecording:
+    @pytest.mark.asyncio
+    async def test_prompt_is_recorded_both_ways(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        recording = tmp_path / "wire.jsonl"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command, record_path=recording) as client:
+            await client.prompt_full("hi")
+
+        entries = [json.loads(line) for line in recording.read_text().splitlines()]
+        assert {e["direction"] for e in entries} == {"in", "out"}
+        assert all(isinstance(e["timestamp"], float) for e in entries)
+        sent = [e["message"].get("method") for e in entries if e["direction"] == "out"]
+        assert sent[0] == "initialize"
+        assert "session/prompt" in sent
+        received = [e["message"] for e in entries if e["direction"] == "in"]
+        assert any(m.get("method") == "session/update" for m in received)
+
+    @pytest.mark.asyncio
+    async def test_disconnect_flushes_final_response(self, tmp_path):
+        recording = tmp_path / "wire.jsonl"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        rust = RustClient(ClientConfig(command=command, record_path=str(recording)))
+        await rust.connect()
+        await rust.prompt("hi", None)
+        await rust.disconnect()
+
+        # Read straight after disconnect(): nothing may still be buffered.
+        entries = [json.loads(line) for line in recording.read_text().splitlines()]
+        received = [e["message"] for e in entries if e["direction"] == "in"]
+        assert received[-1]["result"] == {"stopReason": "end_turn"}
+
+    @pytest.mark.asyncio
+    async def test_recording_replays(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        recording = tmp_path / "wire.jsonl"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command, record_path=recording) as client:
+            session = await client.new_session()
+            live = (await client.prompt_full("hi", session_id=session.session_id)).text()
+
+        client = Client([])
+        await client.connect_replay(recording)
+        try:
+            session = await client.new_session()
+            replayed = await client.prompt_full("anything", session_id=session.session_id)
+            assert replayed.text() == live == "hi"
+        finally:
+            await client.disconnect()
+
+
+class TestPromptMeta:
+    @pytest.mark.asyncio
+    async def test_meta_reaches_the_prompt_request(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_ECHO", "1")
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command) as client:
+            result = await client.prompt_full(
+                [TextBlock("rich"), TextBlock(" text")], meta={"traceId": "abc-123"}
+            )
+            assert result.text() == "rich text"
+
+        [prompt] = _logged_requests(log, "session/prompt")
+        meta = prompt["params"]["_meta"]
+        assert meta["traceId"] == "abc-123"
+        assert meta["requestId"]
+        assert [b["text"] for b in prompt["params"]["prompt"]] == ["rich", " text"]
+
+    @pytest.mark.asyncio
+    async def test_meta_must_be_an_object(self, tmp_path):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        try:
+            with pytest.raises(ProtocolError, match="invalid meta_json"):
+                await rust.prompt("hi", None, None, "[1, 2]")
+        finally:
+            await rust.disconnect()
+        assert not _logged_requests(log, "session/prompt")
+
+
+class TestAgentInfo:
+    @pytest.mark.asyncio
+    async def test_agent_info_is_typed(self, tmp_path, monkeypatch):
+        info = {"name": "fake-agent", "version": "0.3.1", "title": "Fake Agent"}
+        monkeypatch.setenv("FAKE_AGENT_INFO", json.dumps(info))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            agent = await client.agent_info
+            assert isinstance(agent, AgentInfo)
+            assert (agent.name, agent.version, agent.title) == ("fake-agent", "0.3.1", "Fake Agent")
+            assert agent.to_dict() == info
+
+    @pytest.mark.asyncio
+    async def test_agent_info_reads_like_a_dict(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_INFO", json.dumps({"name": "fake-agent", "version": ""}))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            agent = await client.agent_info
+            assert agent["name"] == "fake-agent"
+            assert agent["version"] == ""
+            assert agent.get("title", "untitled") is None
+            assert agent.get("capabilities", "none") == "none"
+            assert "version" in agent and "capabilities" not in agent
+            assert agent.keys() == ["name", "version", "title"]
+            with pytest.raises(KeyError):
+                agent["capabilities"]
+
+    @pytest.mark.asyncio
+    async def test_agent_info_is_none_when_not_reported(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            assert await client.agent_info is None
+
+
+class TestContentJson:
+    @pytest.mark.asyncio
+    async def test_wrong_shape_is_a_protocol_error(self, tmp_path):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        try:
+            bad = json.dumps({"type": "text", "text": "not a list"})
+            with pytest.raises(ProtocolError, match="invalid content_json"):
+                await rust.prompt("fallback", None, bad)
+            # The connection is still usable afterwards.
+            await rust.prompt("hi")
+        finally:
+            await rust.disconnect()
+        texts = [p["params"]["prompt"][0]["text"] for p in _logged_requests(log, "session/prompt")]
+        assert texts == ["hi"]
+
+
+class TestTurnRequestId:
+    @pytest.mark.asyncio
+    async def test_turn_updates_share_request_id(self, tmp_path, monkeypatch):
+        updates = [
+            {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}},
+            {"sessionUpdate": "agent_thought_chunk", "content": {"type": "text", "text": "hm"}},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command) as client:
+            turns = []
+            for text in ("one", "two"):
+                turns.append([update.request_id async for update in client.prompt_stream(text)])
+
+        prompts = [
+            msg["params"]["_meta"]["requestId"]
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("method") == "session/prompt"
+        ]
+        assert len(prompts) == 2
+        assert prompts[0] != prompts[1]
+        for turn, request_id in zip(turns, prompts):
+            assert len(turn) == 3  # two chunks and Done
+            assert set(turn) == {request_id}
+
+
+class TestTurnId:
+    @pytest.mark.asyncio
+    async def test_turn_events_share_turn_id(self, tmp_path, monkeypatch):
+        chunk = {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}}
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([chunk, chunk]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            streamed = [update.turn_id async for update in client.prompt_stream("one")]
+            full = await client.prompt_full("two")
+            other = await client.new_session()
+            other_full = await client.prompt_full("three", session_id=other.session_id)
+
+        assert len(streamed) == 3  # two chunks and Done
+        assert set(streamed) == {1}
+        assert [m.turn_id for m in full.messages] == [2]
+        assert full.result_message.turn_id == 2
+        # Turn IDs count per session.
+        assert other_full.result_message.turn_id == 1
+        assert [m.turn_id for m in other_full.messages] == [1]
+
+
+class TestSessionCwd:
+    @pytest.mark.asyncio
+    async def test_cwd_is_recorded_per_session(self, tmp_path):
+        log = tmp_path / "agent.log"
+        first = tmp_path / "first"
+        second = tmp_path / "second"
+        first.mkdir()
+        second.mkdir()
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session(cwd=str(first))
+            other = await client.new_session(cwd=str(second))
+            assert await session.cwd() == str(first)
+            assert await other.cwd() == str(second)
+            assert await client.session_cwd("unknown") is None
+
+
+class TestAvailableCommands:
+    @pytest.mark.asyncio
+    async def test_last_advertised_commands_are_cached(self, tmp_path, monkeypatch):
+        commands = [
+            {"name": "web", "description": "Search the web"},
+            {"name": "test", "description": "Run the tests"},
+        ]
+        updates = [
+            {"sessionUpdate": "available_commands_update", "availableCommands": commands[:1]},
+            {"sessionUpdate": "available_commands_update", "availableCommands": commands},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            other = await client.new_session()
+            assert await session.available_commands() == []
+
+            await session.prompt("hi")
+            cached = await session.available_commands()
+            assert [(c["name"], c["description"]) for c in cached] == [
+                (c["name"], c["description"]) for c in commands
+            ]
+            assert await other.available_commands() == []
+
+    @pytest.mark.asyncio
+    async def test_run_command_sends_slash_prompt(self, tmp_path, monkeypatch):
+        update = {
+            "sessionUpdate": "available_commands_update",
+            "availableCommands": [{"name": "web", "description": "Search the web"}],
+        }
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([update]))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            with pytest.raises(ProtocolError, match="not advertised command \"web\""):
+                [u async for u in session.run_command("web", "rust async")]
+
+            await session.prompt("hi")
+            updates = [u async for u in session.run_command("/web", "rust async")]
+            assert updates[0].kind == UpdateKind.CommandsUpdate
+            assert updates[-1].kind == UpdateKind.Done
+
+            with pytest.raises(ProtocolError, match="not advertised command \"deploy\""):
+                [u async for u in session.run_command("deploy")]
+
+        prompts = _logged_requests(log, "session/prompt")
+        texts = [block["text"] for p in prompts for block in p["params"]["prompt"]]
+        assert texts == ["hi", "/web rust async"]
+        # Rejected commands don't use up a request ID.
+        assert [p["params"]["_meta"]["requestId"] for p in prompts] == ["prompt-1", "prompt-2"]
+
+
+class TestSessionInfo:
+    @pytest.mark.asyncio
+    async def test_last_reported_info_is_cached(self, tmp_path, monkeypatch):
+        updates = [
+            {
+                "sessionUpdate": "session_info_update",
+                "title": "Fix the parser",
+                "updatedAt": "2026-01-01T00:00:00Z",
+            },
+            {"sessionUpdate": "session_info_update", "updatedAt": "2026-01-02T00:00:00Z"},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            other = await client.new_session()
+            assert await session.session_info() == {"title": None, "updated_at": None}
+
+            await session.prompt("hi")
+            assert await session.session_info() == {
+                "title": "Fix the parser",
+                "updated_at": "2026-01-02T00:00:00Z",
+            }
+            assert await other.session_info() == {"title": None, "updated_at": None}
+
+    @pytest.mark.asyncio
+    async def test_null_title_clears_the_cached_title(self, tmp_path, monkeypatch):
+        updates = [
+            {"sessionUpdate": "session_info_update", "title": "Fix the parser"},
+            {"sessionUpdate": "session_info_update", "title": None, "updatedAt": "2026-01-02"},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            await session.prompt("hi")
+            assert await session.session_info() == {"title": None, "updated_at": "2026-01-02"}
+
+
+class TestActiveToolCalls:
+    @pytest.mark.asyncio
+    async def test_completed_calls_are_dropped(self, tmp_path, monkeypatch):
+        def start(call_id, title):
+            return {
+                "sessionUpdate": "tool_call",
+                "toolCallId": call_id,
+                "title": title,
+                "kind": "read",
+                "status": "pending",
+            }
+
+        updates = [
+            start("call-1", "Read a.txt"),
+            start("call-2", "Read b.txt"),
+            {"sessionUpdate": "tool_call_update", "toolCallId": "call-2", "status": "in_progress"},
+            {"sessionUpdate": "tool_call_update", "toolCallId": "call-1", "status": "completed"},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            other = await client.new_session()
+            assert await session.active_tool_calls() == []
+
+            await session.prompt("hi")
+            [active] = await session.active_tool_calls()
+            assert active["tool_use_id"] == "call-2"
+            assert active["name"] == "Read b.txt"
+            assert active["status"] == "in_progress"
+            assert active["started_at"] <= time.time()
+            assert await other.active_tool_calls() == []
+
+    @pytest.mark.asyncio
+    async def test_cancelled_turn_drops_its_calls(self, tmp_path, monkeypatch):
+        start = {
+            "sessionUpdate": "tool_call",
+            "toolCallId": "call-1",
+            "title": "Run tests",
+            "kind": "execute",
+            "status": "in_progress",
+        }
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([start]))
+        monkeypatch.setenv("FAKE_AGENT_STOP_REASON", "cancelled")
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            await session.prompt("hi")
+            assert await session.active_tool_calls() == []
+
+
+class TestRateLimits:
+    _LIMIT = {
+        "retry_after_seconds": 0.2,
+        "limit": 50,
+        "remaining": 0,
+        "reset_at": "2026-01-01T00:01:00Z",
+    }
+
+    @pytest.mark.asyncio
+    async def test_rate_limit_fields_are_parsed_and_cached(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps(self._LIMIT))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            assert await session.rate_limit_status() is None
+
+            updates = []
+            with pytest.raises(ConduitError, match="rate limited"):
+                async for update in client.prompt_stream("hi", session_id=session.session_id):
+                    updates.append(update)
+
+            [event] = [u for u in updates if u.kind == UpdateKind.RateLimit]
+            status = await session.rate_limit_status()
+            for parsed in (event.rate_limit, status):
+                assert parsed.retry_after_secs == 0.2
+                assert (parsed.limit, parsed.remaining) == (50, 0)
+                assert parsed.reset_at == "2026-01-01T00:01:00Z"
+            assert status.method == "_rate_limit"
+            assert json.loads(status.raw_json)["limit"] == 50
+            assert await client.rate_limit_status() is not None
+
+    @pytest.mark.asyncio
+    async def test_prompt_retries_once_after_retry_hint(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps(self._LIMIT))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, retry_on_rate_limit=True) as client:
+            session = await client.new_session()
+            started = time.monotonic()
+            await session.prompt("hi")
+            assert time.monotonic() - started >= 0.2
+
+        assert len(_logged_requests(log, "session/prompt")) == 2
+
+    @pytest.mark.asyncio
+    async def test_retry_wait_is_capped(self, tmp_path, monkeypatch):
+        limit = {**self._LIMIT, "retry_after_seconds": 3600}
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps(limit))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, retry_on_rate_limit=True, max_rate_limit_wait=0.1) as client:
+            session = await client.new_session()
+            await asyncio.wait_for(session.prompt("hi"), 5)
+
+        assert len(_logged_requests(log, "session/prompt")) == 2
+
+    @pytest.mark.asyncio
+    async def test_invalid_retry_hint_is_ignored(self, tmp_path, monkeypatch):
+        limit = {**self._LIMIT, "retry_after_seconds": 1e300}
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps(limit))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, retry_on_rate_limit=True) as client:
+            session = await client.new_session()
+            with pytest.raises(ConduitError, match="rate limited"):
+                await session.prompt("hi")
+
+        assert len(_logged_requests(log, "session/prompt")) == 1
+
+    def test_negative_max_wait_is_rejected(self):
+        with pytest.raises(ValueError, match="max_rate_limit_wait_secs"):
+            ClientConfig(command=["agent"], max_rate_limit_wait_secs=-1.0)
+
+    def test_claude_rate_limit_event_fields(self):
+        event = {
+            "method": "_claude/rate_limit_event",
+            "params": {
+                "rate_limit_info": {
+                    "status": "allowed_warning",
+                    "resetsAt": 1767225600,
+                    "rateLimitType": "seven_day",
+                    "utilization": 0.8,
+                    "isUsingOverage": False,
+                    "surpassedThreshold": 0.75,
+                }
+            },
+        }
+        info = RateLimitInfo.from_json(json.dumps(event))
+        assert info.method == "_claude/rate_limit_event"
+        assert (info.status, info.rate_limit_type) == ("allowed_warning", "seven_day")
+        assert (info.utilization, info.surpassed_threshold) == (0.8, 0.75)
+        assert info.is_using_overage is False
+        assert info.reset_at == "1767225600"
+        assert info.retry_after_secs is None
+
+    @pytest.mark.asyncio
+    async def test_no_retry_without_opt_in(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps(self._LIMIT))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            with pytest.raises(ConduitError, match="rate limited"):
+                await session.prompt("hi")
+
+        assert len(_logged_requests(log, "session/prompt")) == 1
+
+
+class TestExtensionNotifications:
+    @pytest.mark.asyncio
+    async def test_other_methods_keep_their_tag(self, tmp_path, monkeypatch):
+        params = {"step": 2, "_meta": {"source": "indexer"}}
+        ext = [{"method": "_vendor/progress", "params": params}]
+        monkeypatch.setenv("FAKE_AGENT_EXT_NOTIFICATIONS", json.dumps(ext))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            session = await client.new_session()
+            updates = [u async for u in client.prompt_stream("hi", session_id=session.session_id)]
+            assert await session.rate_limit_status() is None
+
+        [event] = [u for u in updates if u.kind == UpdateKind.Extension]
+        assert event.method == "_vendor/progress"
+        assert json.loads(event.params_json) == params
+        assert event.rate_limit is None
+        assert not [u for u in updates if u.kind == UpdateKind.RateLimit]
+
+
+class TestToolLocations:
+    @pytest.mark.asyncio
+    async def test_locations_are_typed_and_resolved(self, tmp_path, monkeypatch):
+        absolute = str(tmp_path / "lib" / "a.py")
+        update = {
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "call-1",
+            "status": "in_progress",
+            "locations": [{"path": absolute, "line": 12}, {"path": "src/b.py"}],
+        }
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([update]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            session = await client.new_session(cwd=str(tmp_path))
+            [tool_update] = [
+                u
+                async for u in client.prompt_stream("go", session_id=session.session_id)
+                if u.kind == UpdateKind.ToolUseUpdate
+            ]
+
+        first, second = tool_update.locations
+        assert (first.path, first.line) == (absolute, 12)
+        assert (second.path, second.line) == (str(tmp_path / "src" / "b.py"), None)
+        # The raw JSON stays available.
+        assert json.loads(tool_update.tool_locations) == update["locations"]
+
+
+class TestToolKindAndStatus:
+    _KINDS = {
+        "read": ToolKind.Read,
+        "edit": ToolKind.Edit,
+        "delete": ToolKind.Delete,
+        "move": ToolKind.Move,
+        "search": ToolKind.Search,
+        "execute": ToolKind.Execute,
+        "think": ToolKind.Think,
+        "fetch": ToolKind.Fetch,
+        "other": ToolKind.Other,
+    }
+    _STATUSES = {
+        "pending": ToolStatus.Pending,
+        "in_progress": ToolStatus.InProgress,
+        "completed": ToolStatus.Completed,
+        "failed": ToolStatus.Failed,
+    }
+
+    async def _stream(self, tmp_path, monkeypatch, updates):
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            return [u async for u in client.prompt_stream("go")]
+
+    @pytest.mark.asyncio
+    async def test_each_kind_is_mapped(self, tmp_path, monkeypatch):
+        updates = [
+            {
+                "sessionUpdate": "tool_call",
+                "toolCallId": f"call-{kind}",
+                "title": kind,
+                "kind": kind,
+                "status": "pending",
+            }
+            for kind in self._KINDS
+        ]
+        streamed = await self._stream(tmp_path, monkeypatch, updates)
+        starts = [u for u in streamed if u.kind == UpdateKind.ToolUseStart]
+        assert [u.tool_kind for u in starts] == list(self._KINDS.values())
+        assert all(u.tool_status == ToolStatus.Pending for u in starts)
+
+    @pytest.mark.asyncio
+    async def test_each_status_is_mapped(self, tmp_path, monkeypatch):
+        updates = [
+            {"sessionUpdate": "tool_call_update", "toolCallId": f"call-{status}", "status": status}
+            for status in self._STATUSES
+        ]
+        streamed = await self._stream(tmp_path, monkeypatch, updates)
+        tool_updates = [u for u in streamed if u.kind == UpdateKind.ToolUseUpdate]
+        assert [u.tool_status for u in tool_updates] == list(self._STATUSES.values())
+
+
+class TestPlanEntries:
+    @pytest.mark.asyncio
+    async def test_plan_entries_are_typed(self, tmp_path, monkeypatch):
+        entries = [
+            {"content": "Read the code", "priority": "high", "status": "completed"},
+            {"content": "Write the fix", "priority": "low", "status": "in_progress"},
+        ]
+        update = {"sessionUpdate": "plan", "entries": entries}
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([update]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            [plan] = [u async for u in client.prompt_stream("go") if u.kind == UpdateKind.Plan]
+
+        assert plan.plan_entries == [
+            PlanEntry("Read the code", "high", "completed"),
+            PlanEntry("Write the fix", "low", "in_progress"),
+        ]
+        # The raw JSON stays available.
+        assert json.loads(plan.plan_json) == entries
+
+
+class TestPromptFull:
+    @pytest.mark.asyncio
+    async def test_aggregates_tool_call_and_usage(self, tmp_path, monkeypatch):
+        updates = [
+            {
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call-1",
+                "title": "Read",
+                "kind": "read",
+                "status": "pending",
+                "rawInput": {"path": "a.py"},
+            },
+            {"sessionUpdate": "usage_update", "used": 1200, "size": 200000},
+            {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "done"}},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            result = await client.prompt_full("go")
+
+        assert result.text() == "done"
+        [call] = result.tool_calls
+        assert call.content_type == ContentType.ToolUse
+        assert (call.tool_name, call.tool_use_id) == ("Read", "call-1")
+        assert json.loads(call.tool_input) == {"path": "a.py"}
+        usage = json.loads(result.usage_json)
+        assert (usage["used"], usage["size"]) == (1200, 200000)
+        assert result.stop_reason == "EndTurn"
+        assert result.duration_secs >= 0
+        [message] = result.messages
+        assert message.session_id == result.session_id
+
+    @pytest.mark.asyncio
+    async def test_message_blocks_follow_the_turn(self, tmp_path, monkeypatch):
+        def text(t):
+            content = {"type": "text", "text": t}
+            return {"sessionUpdate": "agent_message_chunk", "content": content}
+
+        updates = [
+            text("Let me "),
+            text("look."),
+            {
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call-1",
+                "title": "Read",
+                "kind": "read",
+                "status": "pending",
+                "rawInput": {"path": "a.py"},
+            },
+            {
+                "sessionUpdate": "tool_call_update",
+                "toolCallId": "call-1",
+                "status": "completed",
+                "content": [{"type": "content", "content": {"type": "text", "text": "x = 1"}}],
+            },
+            text("done"),
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            result = await client.prompt_full("go")
+
+        [message] = result.messages
+        said, use, output, reply = message.content
+        assert (said.content_type, said.text) == (ContentType.Text, "Let me look.")
+        assert use.content_type == ContentType.ToolUse
+        assert (use.tool_name, use.tool_use_id) == ("Read", "call-1")
+        assert json.loads(use.tool_input) == {"path": "a.py"}
+        assert output.content_type == ContentType.ToolResult
+        assert (output.tool_use_id, output.text) == ("call-1", "x = 1")
+        assert (reply.content_type, reply.text) == (ContentType.Text, "done")
+        assert message.text() == "Let me look.done"
+
+    @pytest.mark.asyncio
+    async def test_interleaved_blocks_match_the_stream(self, tmp_path, monkeypatch):
+        def chunk(kind, t):
+            return {"sessionUpdate": kind, "content": {"type": "text", "text": t}}
+
+        def call(call_id, title):
+            return {"sessionUpdate": "tool_call", "toolCallId": call_id, "title": title}
+
+        def finish(call_id, output):
+            content = [{"type": "content", "content": {"type": "text", "text": output}}]
+            return {
+                "sessionUpdate": "tool_call_update",
+                "toolCallId": call_id,
+                "status": "completed",
+                "content": content,
+            }
+
+        updates = [
+            chunk("agent_thought_chunk", "planning"),
+            call("call-1", "Read"),
+            chunk("agent_message_chunk", "one "),
+            chunk("agent_thought_chunk", "hidden"),
+            call("call-2", "Grep"),
+            finish("call-2", "hit"),
+            chunk("agent_message_chunk", "two"),
+            finish("call-1", "x = 1"),
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            [message] = [m async for m in client.prompt("go")]
+            streamed = [u async for u in client.prompt_stream("go")]
+
+        def shape(blocks):
+            return [(b.content_type, b.text, b.tool_use_id) for b in blocks]
+
+        assert shape(message.content) == [
+            (ContentType.Text, "planning", None),
+            (ContentType.ToolUse, None, "call-1"),
+            (ContentType.Text, "one ", None),
+            (ContentType.ToolUse, None, "call-2"),
+            (ContentType.ToolResult, "hit", "call-2"),
+            (ContentType.Text, "two", None),
+            (ContentType.ToolResult, "x = 1", "call-1"),
+        ]
+        assert shape(ContentBlock.from_updates(streamed)) == shape(message.content)
+
+    @pytest.mark.asyncio
+    async def test_result_message_carries_cost_and_duration(self, tmp_path, monkeypatch):
+        tool_call = {
+            "sessionUpdate": "tool_call",
+            "toolCallId": "call-1",
+            "title": "Read",
+            "kind": "read",
+            "status": "pending",
+        }
+        cost = {"amount": 0.0021, "currency": "EUR"}
+        updates = [
+            tool_call,
+            {"sessionUpdate": "usage_update", "used": 10, "size": 100},
+            {"sessionUpdate": "usage_update", "used": 20, "size": 100, "cost": cost},
+            {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "done"}},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            result = await client.prompt_full("go")
+
+        summary = result.result_message
+        assert summary.session_id == result.session_id
+        assert summary.total_cost_usd is None
+        assert summary.currency == "EUR"
+        assert abs(summary.duration_ms - result.duration_secs * 1000) <= 1
+        assert summary.num_turns == 2
+        assert summary.result == "done"
+        assert not summary.is_error
+
+    @pytest.mark.asyncio
+    async def test_costs_are_normalized_and_last_one_wins(self, tmp_path, monkeypatch):
+        updates = [
+            {
+                "sessionUpdate": "usage_update",
+                "used": 10,
+                "size": 100,
+                "cost": {"amount": "0.5", "currency": "usd"},
+            },
+            {
+                "sessionUpdate": "usage_update",
+                "used": 20,
+                "size": 100,
+                "cost": {"amount": 0.25, "currency": " Usd"},
+            },
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            usage = [u async for u in client.prompt_stream("go") if u.kind == UpdateKind.Usage]
+            result = await client.prompt_full("go")
+
+        assert [u.cost for u in usage] == [Cost("0.5", "USD"), Cost("0.25", "USD")]
+        assert Decimal(usage[0].cost.amount) == Decimal("0.5")
+        # Costs are cumulative, so the turn reports the last one, not a sum.
+        summary = result.result_message
+        assert summary.total_cost_usd == pytest.approx(0.25)
+        assert summary.currency == "USD"
+
+
+class TestBlockingApi:
+    def test_connect_and_prompt_without_event_loop(self, tmp_path, monkeypatch):
+        chunk = {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}}
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps([chunk]))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command)
+        try:
+            client.connect_blocking()
+            assert client.connected
+            messages = client.prompt_blocking("go")
+        finally:
+            asyncio.run(client.disconnect())
+
+        assert [m.text() for m in messages] == ["hi"]
+
+    @pytest.mark.asyncio
+    async def test_refused_inside_running_loop(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command)
+        with pytest.raises(ConduitError, match="running event loop"):
+            client.connect_blocking()
+        assert not client.connected
+
+    def test_async_permission_callback_rejected(self, tmp_path):
+        async def policy(tool_name, tool_input, context):
+            return PermissionResultAllow()
+
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command, options=AgentOptions(can_use_tool=policy))
+        with pytest.raises(ValueError, match="async permission callback"):
+            client.connect_blocking()
+        assert not client.connected
+        assert not (tmp_path / "agent.log").exists()
+
+    @pytest.mark.parametrize(
+        ("option", "name"),
+        [
+            ("confirm_callback", "confirmation callback"),
+            ("raw_notification_callback", "raw notification callback"),
+            ("stale_update_callback", "stale update callback"),
+        ],
+    )
+    def test_async_option_callback_rejected(self, tmp_path, option, name):
+        async def callback(*args):
+            return True
+
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command, options=AgentOptions(**{option: callback}))
+        with pytest.raises(ValueError, match=f"async {name}"):
+            client.connect_blocking()
+        assert not client.connected
+        assert not (tmp_path / "agent.log").exists()
+
+    def test_async_update_callback_rejected(self, tmp_path):
+        async def on_update(update_json):
+            return None
+
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command)
+        client.set_update_callback(on_update)
+        with pytest.raises(ValueError, match="async update callback"):
+            client.connect_blocking()
+        assert not client.connected
+
+    def test_async_hook_rejected(self, tmp_path):
+        async def hook(context):
+            return None
+
+        hooks = RustHookDispatcher()
+        asyncio.run(hooks.register(HookType.PreToolUse, hook))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command)
+        with pytest.raises(ValueError, match="async hooks"):
+            client.connect_blocking(hooks=hooks)
+        assert not client.connected
+
+    def test_sync_permission_callback_is_answered(self, tmp_path, monkeypatch):
+        def policy(tool_name, tool_input, context):
+            return PermissionResultDeny("no")
+
+        request = {
+            "toolCall": {"toolCallId": "call-1", "title": "Bash", "rawInput": {"cmd": "ls"}},
+            "options": [
+                {"optionId": "allow-once", "name": "Allow", "kind": "allow_once"},
+                {"optionId": "reject-once", "name": "Reject", "kind": "reject_once"},
+            ],
+        }
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(request))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        client = Client(command, options=AgentOptions(can_use_tool=policy))
+        try:
+            client.connect_blocking()
+            client.prompt_blocking("go")
+        finally:
+            asyncio.run(client.disconnect())
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        assert response["result"]["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+
+
+class TestReconnect:
+    @pytest.mark.asyncio
+    async def test_reconnect_after_agent_crash(self, tmp_path, monkeypatch):
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+
+        monkeypatch.setenv("FAKE_AGENT_EXIT_ON", "session/prompt")
+        async with Client(command) as client:
+            session = await client.new_session()
+            with pytest.raises(ConnectionError):
+                await session.prompt("hello")
+
+            # The respawned agent inherits the environment without the crash.
+            monkeypatch.delenv("FAKE_AGENT_EXIT_ON")
+            await client.reconnect()
+            assert client.connected
+            await client.prompt_full("hello again")
+
+        methods = [
+            (msg.get("method"), msg.get("params", {}).get("sessionId"))
+            for msg in map(json.loads, log.read_text().splitlines())
+        ]
+        reload = methods.index(("session/load", session.session_id))
+        assert ("session/prompt", session.session_id) in methods[reload:]
+
+
+class TestConnectionState:
+    @pytest.mark.asyncio
+    async def test_connect_transitions(self, tmp_path):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        assert rust.state() == ConnectionState.Disconnected
+
+        seen = [rust.state()]
+        connecting = asyncio.ensure_future(rust.connect())
+        while not connecting.done():
+            state = rust.state()
+            if state != seen[-1]:
+                seen.append(state)
+            await asyncio.sleep(0)
+        await connecting
+        if rust.state() != seen[-1]:
+            seen.append(rust.state())
+
+        assert seen == [
+            ConnectionState.Disconnected,
+            ConnectionState.Connecting,
+            ConnectionState.Connected,
+        ]
+        await rust.disconnect()
+        assert rust.state() == ConnectionState.Disconnected
+
+    @pytest.mark.asyncio
+    async def test_prompt_requires_connected_state(self, tmp_path):
+        client = Client([sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")])
+        assert client.state == ConnectionState.Disconnected
+        with pytest.raises(ConnectionError, match="not Connected"):
+            await client._rust_client.prompt("hello")
+
+    @pytest.mark.asyncio
+    async def test_agent_exit_fails_the_connection(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_EXIT_ON", "session/prompt")
+        rust = RustClient(
+            ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "log")])
+        )
+        await rust.connect()
+        session_id = await rust.new_session()
+        await rust.send_prompt("hello", session_id)
+        for _ in range(100):
+            if rust.state() != ConnectionState.Connected:
+                break
+            await asyncio.sleep(0.05)
+
+        assert rust.state() == ConnectionState.Failed
+        with pytest.raises(ConnectionError, match="not Connected"):
+            await rust.send_prompt("again", session_id)
+        await rust.disconnect()
+
+
+class TestRawNotificationTap:
+    _UPDATES = [
+        {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}},
+        {
+            "sessionUpdate": "plan",
+            "entries": [{"content": "look around", "priority": "high", "status": "pending"}],
+        },
+    ]
+
+    @pytest.mark.asyncio
+    async def test_tap_receives_wire_json(self, tmp_path, monkeypatch):
+        received: list[str] = []
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(self._UPDATES))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        options = AgentOptions(raw_notification_callback=received.append)
+        async with Client(command, options=options) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+
+        assert [json.loads(raw) for raw in received] == [
+            {"sessionId": session.session_id, "update": update} for update in self._UPDATES
+        ]
+
+    @pytest.mark.asyncio
+    async def test_tap_sees_bytes_as_sent_even_if_unparseable(self, tmp_path, monkeypatch):
+        updates = [
+            {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "hi"}},
+            {"sessionUpdate": "vendor_gizmo", "spin": 3},
+        ]
+        received: list[str] = []
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        options = AgentOptions(raw_notification_callback=received.append)
+        async with Client(command, options=options) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+            # Nothing waits on the tap for a line the typed mapping drops.
+            for _ in range(50):
+                if len(received) == len(updates):
+                    break
+                await asyncio.sleep(0.05)
+
+        assert received == [
+            json.dumps({"sessionId": session.session_id, "update": update}) for update in updates
+        ]
+
+
+class TestConfirmationRequests:
+    async def _answer(self, tmp_path, monkeypatch, **options) -> dict:
+        request = {"message": "Proceed with 12 edits?", "edits": 12}
+        monkeypatch.setenv("FAKE_AGENT_CONFIRM_REQUEST", json.dumps(request))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, options=AgentOptions(**options)) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "confirm-1"
+        ]
+        return response["result"]
+
+    @pytest.mark.asyncio
+    async def test_callback_answer_is_returned(self, tmp_path, monkeypatch):
+        asked: list[tuple[str, dict]] = []
+
+        async def confirm(message, params):
+            asked.append((message, params))
+            return True
+
+        result = await self._answer(tmp_path, monkeypatch, confirm_callback=confirm)
+        assert result == {"confirmed": True}
+        [(message, params)] = asked
+        assert message == "Proceed with 12 edits?"
+        assert params["edits"] == 12
+
+    @pytest.mark.asyncio
+    async def test_timeout_answers_default(self, tmp_path, monkeypatch):
+        cancelled = False
+
+        async def slow(message, params):
+            nonlocal cancelled
+            try:
+                await asyncio.sleep(5)
+            except asyncio.CancelledError:
+                cancelled = True
+                raise
+            return False
+
+        result = await self._answer(
+            tmp_path,
+            monkeypatch,
+            confirm_callback=slow,
+            confirm_timeout_secs=0.05,
+            confirm_default=True,
+        )
+        assert result == {"confirmed": True}
+        assert cancelled
+
+    @pytest.mark.parametrize("secs", [0, -1.0, float("nan")])
+    @pytest.mark.asyncio
+    async def test_invalid_timeout_is_rejected(self, tmp_path, secs):
+        log = tmp_path / "agent.log"
+        options = AgentOptions(confirm_timeout_secs=secs)
+        client = Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)], options=options)
+        with pytest.raises(ValueError, match="timeout_secs"):
+            await client.connect()
+        assert not client.connected
+        assert not log.exists()
+
+
+class TestPermissionDecisions:
+    _REQUEST = {
+        "toolCall": {"toolCallId": "call-1", "title": "Bash", "rawInput": {"cmd": "ls"}},
+        "options": [
+            {"optionId": "allow-once", "name": "Allow", "kind": "allow_once"},
+            {"optionId": "reject-once", "name": "Reject", "kind": "reject_once"},
+        ],
+    }
+
+    async def _response(self, tmp_path, monkeypatch, result, request=None, policy=None) -> dict:
+        async def answer(tool_name, tool_input, context):
+            return result
+
+        policy = policy or answer
+        request = request or self._REQUEST
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(request))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, options=AgentOptions(can_use_tool=policy)) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        return response["result"]
+
+    @pytest.mark.asyncio
+    async def test_allow_with_reason_selects_allow_option(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, PermissionResultAllow(reason="ok"))
+        assert response["outcome"] == {"outcome": "selected", "optionId": "allow-once"}
+
+    @pytest.mark.asyncio
+    async def test_deny_with_reason_selects_reject_option(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, PermissionResultDeny("no"))
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "no"}
+
+    @pytest.mark.asyncio
+    async def test_raising_callback_denies(self, tmp_path, monkeypatch):
+        async def policy(tool_name, tool_input, context):
+            raise RuntimeError("policy store unavailable")
+
+        response = await self._response(tmp_path, monkeypatch, None, policy=policy)
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "permission callback failed"}
+
+    @pytest.mark.asyncio
+    async def test_unusable_return_denies(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, "yes")
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "permission callback returned no decision"}
+
+    @pytest.mark.asyncio
+    async def test_request_is_streamed_before_decision(self, tmp_path, monkeypatch):
+        streamed = asyncio.Event()
+
+        async def policy(tool_name, tool_input, context):
+            # Blocks the tool until the stream has surfaced the request.
+            await asyncio.wait_for(streamed.wait(), timeout=5)
+            return PermissionResultAllow()
+
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(self._REQUEST))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        permissions = []
+        async with Client(command, options=AgentOptions(can_use_tool=policy)) as client:
+            async for update in client.prompt_stream("go"):
+                if update.kind == UpdateKind.Permission:
+                    permissions.append(update)
+                    streamed.set()
+
+        [update] = permissions
+        assert update.tool_name == "Bash"
+        assert json.loads(update.tool_input) == {"cmd": "ls"}
+        assert update.tool_use_id == "call-1"
+        options = json.loads(update.permission_options_json)
+        assert [o["optionId"] for o in options] == ["allow-once", "reject-once"]
+
+    @pytest.mark.asyncio
+    async def test_callback_receives_typed_options(self, tmp_path, monkeypatch):
+        contexts = []
+
+        async def policy(tool_name, tool_input, context):
+            contexts.append(context)
+            return PermissionResultAllow(option_id="allow-always")
+
+        options = [
+            {"optionId": "allow-once", "name": "Allow", "kind": "allow_once"},
+            {"optionId": "allow-always", "name": "Always allow", "kind": "allow_always"},
+            {"optionId": "reject-once", "name": "Reject", "kind": "reject_once"},
+            {"optionId": "reject-always", "name": "Never allow", "kind": "reject_always"},
+        ]
+        request = {**self._REQUEST, "options": options}
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(request))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, options=AgentOptions(can_use_tool=policy)) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+
+        [context] = contexts
+        assert context["options"] == [
+            PermissionOption("allow-once", "Allow", "allow_once"),
+            PermissionOption("allow-always", "Always allow", "allow_always"),
+            PermissionOption("reject-once", "Reject", "reject_once"),
+            PermissionOption("reject-always", "Never allow", "reject_always"),
+        ]
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        assert response["result"]["outcome"] == {"outcome": "selected", "optionId": "allow-always"}
+
+    @pytest.mark.asyncio
+    async def test_pre_tool_use_hook_rewrites_callback_input(self, tmp_path, monkeypatch):
+        seen = []
+
+        async def policy(tool_name, tool_input, context):
+            seen.append((json.loads(tool_input), json.loads(context["tool_input"])))
+            return PermissionResultAllow()
+
+        def add_flag(ctx):
+            ctx["tool_input"]["cmd"] += " -la"
+            return ctx
+
+        hooks = RustHookDispatcher()
+        await hooks.register(HookType.PreToolUse, add_flag)
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(self._REQUEST))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        client = Client(command, options=AgentOptions(can_use_tool=policy))
+        await client.connect(hooks=hooks)
+        try:
+            await client.prompt_full("go")
+        finally:
+            await client.disconnect()
+
+        assert seen == [({"cmd": "ls -la"}, {"cmd": "ls -la"})]
+
+    @pytest.mark.asyncio
+    async def test_pre_tool_use_hook_denies_before_callback(self, tmp_path, monkeypatch):
+        async def policy(tool_name, tool_input, context):
+            raise AssertionError("callback must not run after a hook denies")
+
+        def veto(ctx):
+            return {"__action__": "deny", "reason": "blocked by hook"}
+
+        hooks = RustHookDispatcher()
+        await hooks.register(HookType.PreToolUse, veto)
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(self._REQUEST))
+        log = tmp_path / "agent.log"
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        client = Client(command, options=AgentOptions(can_use_tool=policy))
+        await client.connect(hooks=hooks)
+        try:
+            await client.prompt_full("go")
+        finally:
+            await client.disconnect()
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        assert response["result"]["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["result"]["_meta"] == {"reason": "blocked by hook"}
+
+    @pytest.mark.asyncio
+    async def test_deny_without_reject_option_cancels(self, tmp_path, monkeypatch):
+        request = {**self._REQUEST, "options": self._REQUEST["options"][:1]}
+        response = await self._response(
+            tmp_path, monkeypatch, PermissionResultDeny("no"), request=request
+        )
+        assert response["outcome"] == {"outcome": "cancelled"}
+        assert response["_meta"] == {"reason": "no"}
+
+    @pytest.mark.asyncio
+    async def test_dict_fallback(self, tmp_path, monkeypatch):
+        response = await self._response(
+            tmp_path, monkeypatch, {"decision": "deny", "reason": "from a dict"}
+        )
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "from a dict"}
+
+
+class TestPermissionPolicy:
+    async def _response(self, tmp_path, monkeypatch, policy, can_use_tool=None) -> dict:
+        monkeypatch.setenv(
+            "FAKE_AGENT_PERMISSION_REQUEST", json.dumps(TestPermissionDecisions._REQUEST)
+        )
+        log = tmp_path / "agent.log"
+        options = AgentOptions(can_use_tool=can_use_tool, permission_policy=policy)
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(log)]
+        async with Client(command, options=options) as client:
+            session = await client.new_session()
+            await session.prompt("go")
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        return response["result"]
+
+    @pytest.mark.asyncio
+    async def test_prompt_without_callback_denies(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, None)
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "no permission callback set"}
+
+    @pytest.mark.asyncio
+    async def test_prompt_asks_callback(self, tmp_path, monkeypatch):
+        async def policy(tool_name, tool_input, context):
+            return PermissionResultAllow()
+
+        response = await self._response(tmp_path, monkeypatch, "prompt", can_use_tool=policy)
+        assert response["outcome"] == {"outcome": "selected", "optionId": "allow-once"}
+
+    @pytest.mark.asyncio
+    async def test_allow_all_skips_callback(self, tmp_path, monkeypatch):
+        async def policy(tool_name, tool_input, context):
+            raise AssertionError("allow_all must not ask the callback")
+
+        response = await self._response(tmp_path, monkeypatch, "allow_all", can_use_tool=policy)
+        assert response["outcome"] == {"outcome": "selected", "optionId": "allow-once"}
+
+    @pytest.mark.asyncio
+    async def test_deny_all_selects_reject_option(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, "deny_all")
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "all tool use denied by policy"}
+
+    @pytest.mark.asyncio
+    async def test_explicit_prompt_without_callback_denies(self, tmp_path, monkeypatch):
+        # The callback is looked for when the request arrives, not when the
+        # policy is chosen.
+        response = await self._response(tmp_path, monkeypatch, "prompt")
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "no permission callback set"}
+
+    def test_unknown_policy_raises(self):
+        rust = RustClient(ClientConfig(command=["unused"]))
+        with pytest.raises(ValueError, match="permission policy must be"):
+            rust.set_permission_policy("ask")
+
+
+class TestToolRules:
+    async def _response(self, tmp_path, monkeypatch, title, allow=(), deny=()) -> dict:
+        asked = []
+
+        async def policy(tool_name, tool_input, context):
+            asked.append(tool_name)
+            return PermissionResultDeny("asked")
+
+        tool_call = {**TestPermissionDecisions._REQUEST["toolCall"], "title": title}
+        request = {**TestPermissionDecisions._REQUEST, "toolCall": tool_call}
+        monkeypatch.setenv("FAKE_AGENT_PERMISSION_REQUEST", json.dumps(request))
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        rust.set_permission_callback(policy)
+        rust.set_tool_rules(list(allow), list(deny))
+        await rust.connect()
+        try:
+            await rust.prompt("go", None)
+        finally:
+            await rust.disconnect()
+
+        [response] = [
+            msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if msg.get("id") == "permission-1"
+        ]
+        return {"asked": asked, **response["result"]}
+
+    @pytest.mark.asyncio
+    async def test_allow_match_approves_without_callback(self, tmp_path, monkeypatch):
+        response = await self._response(tmp_path, monkeypatch, "ReadFile", allow=["Read*"])
+        assert response["asked"] == []
+        assert response["outcome"] == {"outcome": "selected", "optionId": "allow-once"}
+
+    @pytest.mark.asyncio
+    async def test_deny_match_refuses_and_wins_over_allow(self, tmp_path, monkeypatch):
+        response = await self._response(
+            tmp_path, monkeypatch, "rm_tree", allow=["*"], deny=["rm_*", "Bash"]
+        )
+        assert response["asked"] == []
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "tool rm_tree is denied by the tool rules"}
+
+    @pytest.mark.asyncio
+    async def test_unmatched_tool_falls_through_to_callback(self, tmp_path, monkeypatch):
+        response = await self._response(
+            tmp_path, monkeypatch, "Bash", allow=["Read*"], deny=["*_delete", "Bas"]
+        )
+        assert response["asked"] == ["Bash"]
+        assert response["outcome"] == {"outcome": "selected", "optionId": "reject-once"}
+        assert response["_meta"] == {"reason": "asked"}
+
+
+class TestMcpToolBridge:
+    @pytest.mark.asyncio
+    async def test_agent_tools_call_runs_registry_tool(self, tmp_path, monkeypatch):
+        async def add(a: int, b: int) -> str:
+            return str(a + b)
+
+        registry = RustToolRegistry()
+        await registry.register(ToolDefinition("add", "Add two numbers", '{"type": "object"}'), add)
+        monkeypatch.setenv(
+            "FAKE_AGENT_TOOL_CALLS",
+            json.dumps(
+                [
+                    {"name": "add", "arguments": {"a": 2, "b": 3}},
+                    {"name": "missing", "arguments": {}},
+                ]
+            ),
+        )
+        log = tmp_path / "agent.log"
+        client = Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)])
+        await client.connect(registry)
+        try:
+            session = await client.new_session()
+            await session.prompt("go")
+        finally:
+            await client.disconnect()
+
+        responses = {
+            msg["id"]: msg
+            for msg in map(json.loads, log.read_text().splitlines())
+            if str(msg.get("id", "")).startswith("tool-")
+        }
+        assert responses["tool-1"]["result"] == {
+            "content": [{"type": "text", "text": "5"}],
+            "isError": False,
+        }
+        assert responses["tool-2"]["error"]["code"] == -32602
+        assert "missing" in responses["tool-2"]["error"]["message"]
+
+    @pytest.mark.asyncio
+    async def test_input_middleware_stays_with_its_client(self, tmp_path, monkeypatch):
+        seen: list = []
+
+        async def echo(text: str) -> str:
+            seen.append(text)
+            return text
+
+        def shout(tool_name: str, tool_input: dict) -> dict:
+            return {"text": tool_input["text"].upper()}
+
+        registry = RustToolRegistry()
+        await registry.register(ToolDefinition("echo", "Echo", '{"type": "object"}'), echo)
+        monkeypatch.setenv(
+            "FAKE_AGENT_TOOL_CALLS", json.dumps([{"name": "echo", "arguments": {"text": "hi"}}])
+        )
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        for options in (AgentOptions(tool_input_middleware=shout), None):
+            client = Client(command, options=options)
+            await client.connect(registry)
+            try:
+                session = await client.new_session()
+                await session.prompt("go")
+            finally:
+                await client.disconnect()
+
+        assert seen == ["HI", "hi"]
+
+
+class TestAddMcpServer:
+    _SERVER = {"command": "/usr/bin/env", "args": ["mcp-echo"], "env": []}
+
+    @pytest.mark.asyncio
+    async def test_hot_add_sends_request(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_META", json.dumps({"mcpHotAdd": True}))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            assert client.capabilities.mcp_hot_add
+            session = await client.new_session()
+            await session.add_mcp_server("echo", self._SERVER)
+
+            with pytest.raises(SessionError, match="already attached"):
+                await session.add_mcp_server("echo", self._SERVER)
+
+        [request] = _logged_requests(log, "_conduit/add_mcp_server")
+        assert request["params"] == {
+            "sessionId": session.session_id,
+            "mcpServer": {**self._SERVER, "name": "echo"},
+        }
+
+    @pytest.mark.asyncio
+    async def test_requires_capability(self, tmp_path, monkeypatch):
+        monkeypatch.delenv("FAKE_AGENT_META", raising=False)
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            with pytest.raises(ProtocolError, match="does not support"):
+                await session.add_mcp_server("echo", self._SERVER)
+
+        assert _logged_requests(log, "_conduit/add_mcp_server") == []
+
+    @pytest.mark.asyncio
+    async def test_malformed_capability_is_ignored(self, tmp_path, monkeypatch, caplog):
+        caplog.set_level(logging.WARNING, logger="conduit_sdk.client")
+        monkeypatch.setenv("FAKE_AGENT_META", json.dumps({"mcpHotAdd": "yes"}))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            assert client.capabilities.mcp_hot_add is False
+
+        warnings = [r.getMessage() for r in caplog.records if r.levelno == logging.WARNING]
+        assert any("_meta.mcpHotAdd" in w for w in warnings)
+
+    @pytest.mark.asyncio
+    async def test_failed_add_releases_the_name(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_META", json.dumps({"mcpHotAdd": True}))
+        error = {"code": -32603, "message": "server failed to start"}
+        monkeypatch.setenv("FAKE_AGENT_ERRORS", json.dumps({"_conduit/add_mcp_server": error}))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            for _ in range(2):
+                with pytest.raises(ConduitError, match="server failed to start"):
+                    await session.add_mcp_server("echo", self._SERVER)
+
+        assert len(_logged_requests(log, "_conduit/add_mcp_server")) == 2
+
+
+class TestNewSessionServers:
+    @pytest.mark.asyncio
+    async def test_typed_servers_are_sent(self, tmp_path):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        servers = [
+            McpServerConfig("echo", command="/usr/bin/env", args=["mcp-echo"]),
+            McpServerConfig("remote", url="https://mcp.example.com"),
+        ]
+        await rust.new_session_with_servers(servers)
+        await rust.disconnect()
+
+        [request] = _logged_requests(log, "session/new")
+        assert request["params"]["mcpServers"] == [json.loads(s.to_json()) for s in servers]
+
+    @pytest.mark.asyncio
+    async def test_invalid_servers_raise(self, tmp_path):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        with pytest.raises(SessionError, match="needs a command or a url"):
+            await rust.new_session_with_servers([McpServerConfig("bare")])
+        await rust.disconnect()
+
+        assert _logged_requests(log, "session/new") == []
+
+    @pytest.mark.asyncio
+    @pytest.mark.parametrize(
+        ("meta_json", "mcp_servers_json", "field"),
+        [
+            ("{not json", None, "meta_json"),
+            ('["not", "an", "object"]', None, "meta_json"),
+            (None, "{not json", "mcp_servers_json"),
+            (None, json.dumps([{"name": "no-command"}]), "mcp_servers_json"),
+        ],
+    )
+    async def test_malformed_json_raises(self, tmp_path, meta_json, mcp_servers_json, field):
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        with pytest.raises(ProtocolError, match=f"invalid {field}"):
+            await rust.new_session(None, meta_json, mcp_servers_json)
+        # The connection is still usable afterwards.
+        assert await rust.new_session() == "sess-1"
+        await rust.disconnect()
+
+        assert len(_logged_requests(log, "session/new")) == 1
+
+
+class TestFileEdits:
+    @pytest.mark.asyncio
+    async def test_diff_content_is_parsed(self, tmp_path, monkeypatch):
+        def diff(path, old, new):
+            entry = {"type": "diff", "path": path, "oldText": old, "newText": new}
+            return {k: v for k, v in entry.items() if v is not None}
+
+        content = [
+            diff("/src/lib.rs", "fn a() {}", "fn b() {}"),
+            diff("/src/new.rs", None, "mod new;"),
+            {"type": "content", "content": {"type": "text", "text": "edited"}},
+        ]
+        updates = [
+            {
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call-1",
+                "title": "Edit files",
+                "kind": "edit",
+                "status": "pending",
+            },
+            {
+                "sessionUpdate": "tool_call_update",
+                "toolCallId": "call-1",
+                "status": "in_progress",
+                "content": content,
+            },
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            streamed = [u async for u in client.prompt_stream("edit")]
+
+        [update] = [u for u in streamed if u.kind == UpdateKind.ToolUseUpdate]
+        assert update.file_edits == [
+            FileEdit("/src/lib.rs", "fn a() {}", "fn b() {}"),
+            FileEdit("/src/new.rs", new_text="mod new;"),
+        ]
+        assert streamed[0].file_edits == []
+
+
+class TestCancelAll:
+    @pytest.mark.asyncio
+    async def test_every_session_is_cancelled(self, tmp_path):
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            first = await client.new_session()
+            second = await client.new_session()
+            cancelled = await client.cancel_all(grace_secs=0)
+            # Round-trip a request so the agent has read both cancels.
+            await client.list_sessions()
+
+        expected = sorted([first.session_id, second.session_id])
+        assert cancelled == expected
+        notifications = _logged_requests(log, "session/cancel")
+        assert sorted(n["params"]["sessionId"] for n in notifications) == expected
+
+    @pytest.mark.asyncio
+    async def test_closed_session_is_not_cancelled(self, tmp_path):
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            kept = await client.new_session()
+            closed = await client.new_session()
+            await closed.close()
+            assert await client.cancel_all(grace_secs=0) == [kept.session_id]
+
+    @pytest.mark.asyncio
+    async def test_closed_session_state_is_dropped(self, tmp_path, monkeypatch):
+        updates = [
+            {
+                "sessionUpdate": "available_commands_update",
+                "availableCommands": [{"name": "web", "description": "Search the web"}],
+            },
+            {"sessionUpdate": "session_info_update", "title": "Fix the parser"},
+            {
+                "sessionUpdate": "tool_call",
+                "toolCallId": "call-1",
+                "title": "Bash",
+                "status": "in_progress",
+            },
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        monkeypatch.setenv("FAKE_AGENT_RATE_LIMIT", json.dumps({"limit": 50, "remaining": 0}))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            session_id = session.session_id
+            with pytest.raises(ConduitError, match="rate limited"):
+                await session.prompt("hi")
+            await session.prompt("hi")
+            assert await client.available_commands(session_id) != []
+            assert (await client.session_info(session_id))["title"] == "Fix the parser"
+            assert await client.active_tool_calls(session_id) != []
+            assert await client.rate_limit_status(session_id) is not None
+
+            await session.close()
+            assert await client.available_commands(session_id) == []
+            assert (await client.session_info(session_id))["title"] is None
+            assert await client.active_tool_calls(session_id) == []
+            assert await client.rate_limit_status(session_id) is None
+
+    @pytest.mark.asyncio
+    async def test_cancel_reaches_a_running_send_prompt_turn(self, tmp_path, monkeypatch):
+        # The agent holds the prompt open until it reads the cancel, so the
+        # turn ends within the grace period only if the cancel is sent
+        # while the prompt is pending.
+        monkeypatch.setenv("FAKE_AGENT_HOLD_PROMPT", "1")
+        log = tmp_path / "agent.log"
+        rust = RustClient(ClientConfig(command=[sys.executable, str(_FAKE_AGENT_PATH), str(log)]))
+        await rust.connect()
+        try:
+            session_id = await rust.new_session()
+            await rust.send_prompt("hi", session_id)
+            while not _logged_requests(log, "session/prompt"):
+                await asyncio.sleep(0.05)
+            assert await rust.cancel_all(10) == [session_id]
+            updates = []
+            while (update := await asyncio.wait_for(rust.recv_update(), 5)) is not None:
+                updates.append(update)
+                if update.kind == UpdateKind.Done:
+                    break
+        finally:
+            await rust.disconnect()
+
+        assert updates[-1].stop_reason == "Cancelled"
+        [cancel] = _logged_requests(log, "session/cancel")
+        assert cancel["params"]["sessionId"] == session_id
+
+    @pytest.mark.asyncio
+    async def test_grace_period_is_spent_on_an_unanswered_turn(self, tmp_path, monkeypatch):
+        monkeypatch.setenv("FAKE_AGENT_HANG_ON", "session/prompt")
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        rust = RustClient(ClientConfig(command=command))
+        await rust.connect()
+        try:
+            session_id = await rust.new_session()
+            await rust.send_prompt("hi", session_id)
+            loop = asyncio.get_running_loop()
+            started = loop.time()
+            assert await rust.cancel_all(0.3) == [session_id]
+            assert loop.time() - started >= 0.3
+        finally:
+            await rust.disconnect()
+
+    @pytest.mark.asyncio
+    async def test_grace_period_ends_with_the_last_turn(self, tmp_path):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        rust = RustClient(ClientConfig(command=command))
+        await rust.connect()
+        try:
+            session_id = await rust.new_session()
+            await rust.send_prompt("hi", session_id)
+            loop = asyncio.get_running_loop()
+            started = loop.time()
+            assert await rust.cancel_all(30) == [session_id]
+            assert loop.time() - started < 10
+        finally:
+            await rust.disconnect()
+
+    @pytest.mark.parametrize("secs", [-1.0, float("nan"), float("inf")])
+    @pytest.mark.asyncio
+    async def test_invalid_grace_is_rejected(self, tmp_path, secs):
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            with pytest.raises(ValueError, match="grace_secs"):
+                await client.cancel_all(grace_secs=secs)
+
+
+class TestCancelTool:
+    @pytest.mark.asyncio
+    async def test_agent_acknowledges_cancel(self, tmp_path):
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            await session.cancel_tool("call-1")
+
+        [request] = _logged_requests(log, "_conduit/cancel_tool")
+        assert request["params"] == {"sessionId": session.session_id, "toolCallId": "call-1"}
+
+    @pytest.mark.asyncio
+    async def test_unsupported_agent_raises_protocol_error(self, tmp_path, monkeypatch):
+        error = {"code": -32601, "message": "method not found"}
+        monkeypatch.setenv("FAKE_AGENT_ERRORS", json.dumps({"_conduit/cancel_tool": error}))
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            with pytest.raises(ProtocolError, match="does not support cancelling a single tool"):
+                await session.cancel_tool("call-1")
+
+    @pytest.mark.asyncio
+    async def test_cancel_reaches_the_agent_mid_turn(self, tmp_path, monkeypatch):
+        # The agent holds the prompt open until the cancel arrives, so this
+        # only finishes if the cancel is not queued behind the turn.
+        monkeypatch.setenv("FAKE_AGENT_HOLD_PROMPT", "1")
+        log = tmp_path / "agent.log"
+        async with Client([sys.executable, str(_FAKE_AGENT_PATH), str(log)]) as client:
+            session = await client.new_session()
+            turn = asyncio.ensure_future(client.prompt_full("go", session_id=session.session_id))
+            while not _logged_requests(log, "session/prompt"):
+                await asyncio.sleep(0.05)
+            await asyncio.wait_for(session.cancel_tool("call-1"), 5)
+            result = await asyncio.wait_for(turn, 5)
+
+        assert result.stop_reason == "EndTurn"
+        methods = [json.loads(line).get("method") for line in log.read_text().splitlines()]
+        assert methods.index("_conduit/cancel_tool") > methods.index("session/prompt")
+
+
+class TestContentErrors:
+    @pytest.mark.asyncio
+    async def test_audio_is_skipped_without_ending_turn(self, tmp_path, monkeypatch, caplog):
+        caplog.set_level(logging.WARNING, logger="conduit_sdk.client")
+
+        def chunk(content):
+            return {"sessionUpdate": "agent_message_chunk", "content": content}
+
+        updates = [
+            chunk({"type": "text", "text": "before "}),
+            chunk({"type": "audio", "mimeType": "audio/wav", "data": "UklGRg=="}),
+            chunk({"type": "text", "text": "after"}),
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            streamed = [u async for u in client.prompt_stream("go")]
+            result = await client.prompt_full("again")
+
+        assert [u.kind for u in streamed] == [
+            UpdateKind.TextDelta,
+            UpdateKind.TextDelta,
+            UpdateKind.Done,
+        ]
+        assert result.text() == "before after"
+        warnings = [r.getMessage() for r in caplog.records if r.levelno == logging.WARNING]
+        assert any("skipping audio/wav audio" in w for w in warnings)
+
+
+class TestResourceContent:
+    async def _content(self, tmp_path, monkeypatch, block):
+        updates = [
+            {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "see "}},
+            {"sessionUpdate": "agent_message_chunk", "content": block},
+        ]
+        monkeypatch.setenv("FAKE_AGENT_NOTIFICATIONS", json.dumps(updates))
+        command = [sys.executable, str(_FAKE_AGENT_PATH), str(tmp_path / "agent.log")]
+        async with Client(command) as client:
+            streamed = [u async for u in client.prompt_stream("go")]
+
+        assert [u.kind for u in streamed] == [
+            UpdateKind.TextDelta,
+            UpdateKind.Content,
+            UpdateKind.Done,
+        ]
+        return streamed[1].content
+
+    @pytest.mark.asyncio
+    async def test_embedded_resource(self, tmp_path, monkeypatch):
+        resource = {"uri": "file:///src/lib.rs", "mimeType": "text/x-rust", "text": "fn main() {}"}
+        content = await self._content(
+            tmp_path, monkeypatch, {"type": "resource", "resource": resource}
+        )
+        assert content.content_type == ContentType.Resource
+        assert content.uri == "file:///src/lib.rs"
+        assert content.mime_type == "text/x-rust"
+        assert content.text == "fn main() {}"
+        assert content.data is None
+
+    @pytest.mark.asyncio
+    async def test_image(self, tmp_path, monkeypatch):
+        image = {"type": "image", "mimeType": "image/png", "data": "iVBORw0KGgo="}
+        content = await self._content(tmp_path, monkeypatch, image)
+        assert content.content_type == ContentType.Image
+        assert content.mime_type == "image/png"
+        assert content.data == "iVBORw0KGgo="
+
+    @pytest.mark.asyncio
+    async def test_resource_link(self, tmp_path, monkeypatch):
+        link = {
+            "type": "resource_link",
+            "uri": "file:///docs/guide.pdf",
+            "name": "guide.pdf",
+            "mimeType": "application/pdf",
+        }
+        content = await self._content(tmp_path, monkeypatch, link)
+        assert content.content_type == ContentType.ResourceLink
+        assert content.uri == "file:///docs/guide.pdf"
+        assert content.mime_type == "application/pdf"
+        assert content.text == "guide.pdf"
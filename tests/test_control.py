@@ -2,9 +2,25 @@
 
 from __future__ import annotations
 
+import asyncio
 import json
+import os
 
-from conduit_sdk._conduit_sdk import ControlMessage, ControlResponse, RustControlProtocol
+import pytest
+
+from conduit_sdk._conduit_sdk import (
+    ControlMessage,
+    ControlResponse,
+    Framing,
+    RustControlProtocol,
+)
+from conduit_sdk.exceptions import (
+    ConnectionError,
+    ProtocolError,
+    TimeoutError,
+    TransportError,
+)
+from conduit_sdk.permissions import PermissionResultAllow, PermissionResultDeny
 
 
 class TestControlMessage:
@@ -24,6 +40,16 @@ class TestControlMessage:
         assert "req_1" in r
         assert "can_use_tool" in r
 
+    def test_try_new_accepts_json_data(self):
+        msg = ControlMessage.try_new("req_1", "can_use_tool", '{"tool_name": "Bash"}')
+        assert json.loads(msg.data) == {"tool_name": "Bash"}
+
+    def test_try_new_rejects_non_json_data(self):
+        with pytest.raises(ProtocolError, match="not valid JSON"):
+            ControlMessage.try_new("req_1", "can_use_tool", "tool_name=Bash")
+        # The plain constructor stays lenient.
+        assert ControlMessage("req_1", "can_use_tool", "tool_name=Bash").data == "tool_name=Bash"
+
 
 class TestControlResponse:
     def test_creation(self):
@@ -47,3 +73,467 @@ class TestRustControlProtocol:
     def test_instantiation(self):
         protocol = RustControlProtocol()
         assert protocol is not None
+
+
+class TestControlKeepalive:
+    @pytest.mark.asyncio
+    async def test_dead_agent_marks_protocol_stopped(self):
+        # The protocol holds its own copies of the agent-side fds; closing
+        # the other ends leaves it talking to an agent that never answers.
+        agent_stdin_r, agent_stdin_w = os.pipe()
+        agent_stdout_r, agent_stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(agent_stdin_w, agent_stdout_r, keepalive_secs=0.05)
+        os.close(agent_stdin_r)
+        os.close(agent_stdout_w)
+        assert await protocol.is_running()
+
+        for _ in range(100):
+            if not await protocol.is_running():
+                break
+            await asyncio.sleep(0.02)
+        assert not await protocol.is_running()
+
+        with pytest.raises(ProtocolError, match="not started"):
+            await protocol.send_control_request("initialize", "{}")
+        await protocol.stop()
+
+    @pytest.mark.asyncio
+    async def test_rejects_non_positive_keepalive(self):
+        protocol = RustControlProtocol()
+        with pytest.raises(ProtocolError, match="keepalive_secs"):
+            await protocol.start(-1, -1, keepalive_secs=0)
+
+
+class TestControlRequestTimeout:
+    @pytest.mark.asyncio
+    async def test_timeout_raises_and_forgets_request(self):
+        # Nobody ever answers on these pipes.
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+
+        with pytest.raises(TimeoutError, match="timed out"):
+            await protocol.send_control_request("initialize", "{}", timeout_secs=1)
+        assert await protocol.pending_count() == 0
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+    @pytest.mark.asyncio
+    async def test_rejects_negative_timeout(self):
+        protocol = RustControlProtocol()
+        with pytest.raises(ProtocolError, match="timeout_secs"):
+            await protocol.send_control_request("initialize", "{}", timeout_secs=-1)
+
+    @pytest.mark.asyncio
+    async def test_rejects_non_json_data(self):
+        protocol = RustControlProtocol()
+        with pytest.raises(ProtocolError, match="not valid JSON"):
+            await protocol.send_control_request("initialize", "mode=plan")
+        with pytest.raises(ProtocolError, match="not valid JSON"):
+            await protocol.send_control_response("req_1", "can_use_tool", "allow")
+
+
+class TestControlRequestCleanup:
+    @pytest.mark.asyncio
+    async def test_send_failure_forgets_request(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+        # With the agent's end gone, the first write hits EPIPE and the write
+        # loop exits; every later send then fails outright.
+        os.close(stdin_r)
+        with pytest.raises(TimeoutError):
+            await protocol.send_control_request("initialize", "{}", timeout_secs=0.2)
+
+        with pytest.raises(ProtocolError, match="failed to send"):
+            await protocol.send_control_request("initialize", "{}")
+        assert await protocol.pending_count() == 0
+
+        await protocol.stop()
+        os.close(stdout_w)
+
+    @pytest.mark.asyncio
+    async def test_cancelled_request_is_forgotten(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+
+        with pytest.raises(asyncio.TimeoutError):
+            await asyncio.wait_for(protocol.send_control_request("initialize", "{}"), 0.2)
+        await asyncio.sleep(0.05)
+        assert await protocol.pending_count() == 0
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+    @pytest.mark.asyncio
+    async def test_unbounded_request_fails_when_agent_closes_stdout(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+        request = asyncio.create_task(
+            protocol.send_control_request("initialize", "{}", timeout_secs=0)
+        )
+        await asyncio.sleep(0.05)
+
+        os.close(stdout_w)
+        with pytest.raises(ConnectionError, match="stopped"):
+            await asyncio.wait_for(request, 5)
+        assert await protocol.pending_count() == 0
+
+        await protocol.stop()
+        os.close(stdin_r)
+
+    @pytest.mark.asyncio
+    async def test_unbounded_request_fails_on_stop(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+        request = asyncio.create_task(
+            protocol.send_control_request("initialize", "{}", timeout_secs=0)
+        )
+        await asyncio.sleep(0.05)
+
+        await protocol.stop()
+        with pytest.raises(ConnectionError, match="stopped"):
+            await asyncio.wait_for(request, 5)
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+
+def _answer_one(stdin_r: int, stdout_w: int, data: dict) -> None:
+    """Play the agent for one control request: read it, answer with ``data``."""
+    with os.fdopen(stdin_r) as requests, os.fdopen(stdout_w, "w") as responses:
+        request = json.loads(requests.readline())
+        response = {
+            "type": "control",
+            "request_id": request["request_id"],
+            "subtype": request["subtype"],
+            "data": data,
+        }
+        responses.write(json.dumps(response) + "\n")
+        responses.flush()
+
+
+class TestControlRestart:
+    @pytest.mark.asyncio
+    async def test_restart_reestablishes_request_cycle(self):
+        old_stdin_r, old_stdin_w = os.pipe()
+        old_stdout_r, old_stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(old_stdin_w, old_stdout_r)
+        # The old agent never answers; its request fails once we restart.
+        stranded = asyncio.create_task(protocol.send_control_request("initialize", "{}"))
+        await asyncio.sleep(0.05)
+
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        await protocol.restart(stdin_w, stdout_r)
+        with pytest.raises(ConnectionError, match="stopped"):
+            await stranded
+        os.close(old_stdin_r)
+        os.close(old_stdout_w)
+
+        agent = asyncio.create_task(asyncio.to_thread(_answer_one, stdin_r, stdout_w, {"ok": 1}))
+        response = await protocol.send_control_request("initialize", "{}")
+        await agent
+        assert json.loads(response) == {"ok": 1}
+        assert await protocol.is_running()
+        await protocol.stop()
+
+
+def _answer_one_content_length(stdin_r: int, stdout_w: int, data: dict) -> dict:
+    """Like ``_answer_one`` with ``Content-Length`` framing; returns the request."""
+    with os.fdopen(stdin_r, "rb") as requests, os.fdopen(stdout_w, "wb") as responses:
+        header = requests.readline()
+        assert header.startswith(b"Content-Length: ")
+        assert requests.readline() == b"\r\n"
+        request = json.loads(requests.read(int(header.split(b":")[1])))
+        response = {
+            "type": "control",
+            "request_id": request["request_id"],
+            "subtype": request["subtype"],
+            "data": data,
+        }
+        # Pretty-printed, so the body spans several lines.
+        body = json.dumps(response, indent=2).encode()
+        responses.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+        responses.flush()
+        return request
+
+
+class TestControlFraming:
+    @pytest.mark.asyncio
+    async def test_line_delimited_round_trip(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r, framing=Framing.LineDelimited)
+
+        data = {"text": "two\nlines"}
+        agent = asyncio.create_task(asyncio.to_thread(_answer_one, stdin_r, stdout_w, data))
+        response = await protocol.send_control_request("initialize", json.dumps(data))
+        await agent
+        assert json.loads(response) == data
+        await protocol.stop()
+
+    @pytest.mark.asyncio
+    async def test_content_length_round_trip(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r, framing=Framing.ContentLength)
+
+        data = {"text": "two\nlines", "nested": {"ok": True}}
+        agent = asyncio.create_task(
+            asyncio.to_thread(_answer_one_content_length, stdin_r, stdout_w, data)
+        )
+        response = await protocol.send_control_request("initialize", json.dumps(data))
+        request = await agent
+        assert request["data"] == data
+        assert json.loads(response) == data
+        await protocol.stop()
+
+
+class TestControlReadLimits:
+    @pytest.mark.asyncio
+    async def test_oversized_and_non_utf8_lines_are_skipped(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r, max_line_bytes=64)
+
+        follow_up = json.dumps({"type": "assistant", "text": "still here"})
+        os.write(stdout_w, b'{"type": "assistant", "text": "' + b"x" * 10_000 + b'"}\n')
+        os.write(stdout_w, b"\xff\xfe not utf-8\n")
+        os.write(stdout_w, follow_up.encode() + b"\n")
+
+        first = json.loads(await protocol.recv_message())
+        assert first["type"] == "error"
+        assert "64-byte limit" in first["message"]
+        second = json.loads(await protocol.recv_message())
+        assert second["type"] == "error"
+        assert "UTF-8" in second["message"]
+        assert await protocol.recv_message() == follow_up
+        assert await protocol.is_running()
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+    @pytest.mark.asyncio
+    async def test_rejects_zero_max_line_bytes(self):
+        protocol = RustControlProtocol()
+        with pytest.raises(ProtocolError, match="max_line_bytes"):
+            await protocol.start(-1, -1, max_line_bytes=0)
+
+
+class TestControlFlush:
+    @pytest.mark.asyncio
+    async def test_flush_writes_queued_messages(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+
+        for i in range(5):
+            await protocol.send_control_response(f"req_{i}", "hook_callback", "{}")
+        assert await protocol.flush(timeout_secs=5) is True
+
+        os.set_blocking(stdin_r, False)
+        written = os.read(stdin_r, 65536).decode().splitlines()
+        assert [json.loads(line)["request_id"] for line in written] == [
+            f"req_{i}" for i in range(5)
+        ]
+        with pytest.raises(ProtocolError, match="not started"):
+            await protocol.send_control_response("req_5", "hook_callback", "{}")
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+    @pytest.mark.asyncio
+    async def test_flush_reports_stalled_writes(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+
+        # Nobody reads stdin, so the pipe fills and the write loop blocks.
+        big = json.dumps({"blob": "x" * 65536})
+        for i in range(4):
+            await protocol.send_control_response(f"req_{i}", "hook_callback", big)
+        assert await protocol.flush(timeout_secs=0.2) is False
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+
+class TestControlRecvMessage:
+    @pytest.mark.asyncio
+    async def test_control_requests_arrive_typed(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+
+        # No callback is registered for this subtype, so it is forwarded.
+        control = {
+            "type": "control",
+            "request_id": "a_1",
+            "subtype": "set_model",
+            "data": {},
+        }
+        conversation = json.dumps({"type": "assistant", "text": "hi"})
+        os.write(stdout_w, (json.dumps(control) + "\n" + conversation + "\n").encode())
+
+        first = await protocol.recv_message()
+        assert isinstance(first, ControlMessage)
+        assert (first.request_id, first.subtype) == ("a_1", "set_model")
+        assert json.loads(first.data) == {}
+        second = await protocol.recv_message()
+        assert second == conversation
+
+        await protocol.stop()
+        os.close(stdin_r)
+        os.close(stdout_w)
+
+
+class TestControlStreamOwnership:
+    @pytest.mark.skipif(os.name != "posix", reason="exercises Unix fd reuse")
+    @pytest.mark.asyncio
+    async def test_protocol_owns_duplicates_of_caller_fds(self):
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        await protocol.start(stdin_w, stdout_r)
+        # Ours can go right away; a new pipe is likely to reuse their numbers.
+        os.close(stdin_w)
+        os.close(stdout_r)
+        bystander_r, bystander_w = os.pipe()
+
+        agent = asyncio.create_task(asyncio.to_thread(_answer_one, stdin_r, stdout_w, {"ok": 1}))
+        response = await protocol.send_control_request("initialize", "{}")
+        await agent
+        assert json.loads(response) == {"ok": 1}
+        await protocol.stop()
+        await asyncio.sleep(0.05)
+
+        # Stopping closed the protocol's copies, not whatever now holds the
+        # numbers the caller passed in.
+        os.write(bystander_w, b"x")
+        assert os.read(bystander_r, 1) == b"x"
+        os.close(bystander_r)
+        os.close(bystander_w)
+
+    @pytest.mark.asyncio
+    async def test_rejects_invalid_fd(self):
+        protocol = RustControlProtocol()
+        with pytest.raises(TransportError, match="stdin_fd"):
+            await protocol.start(-1, -1)
+        assert not await protocol.is_running()
+
+
+def _send_request(stdin_r: int, stdout_w: int, request: dict) -> dict:
+    """Play the agent sending one control request; return the SDK's reply."""
+    with os.fdopen(stdin_r) as replies, os.fdopen(stdout_w, "w") as requests:
+        requests.write(json.dumps({"type": "control", **request}) + "\n")
+        requests.flush()
+        return json.loads(replies.readline())
+
+
+class TestControlRequestDispatch:
+    @pytest.mark.asyncio
+    async def test_can_use_tool_is_answered_by_callback(self):
+        calls = []
+
+        async def policy(tool_name, tool_input, context):
+            calls.append((tool_name, json.loads(tool_input), context["tool_use_id"]))
+            return PermissionResultDeny("not today")
+
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        protocol.set_permission_callback(policy)
+        await protocol.start(stdin_w, stdout_r)
+
+        request = {
+            "request_id": "agent_1",
+            "subtype": "can_use_tool",
+            "data": {"tool_name": "Bash", "tool_input": {"cmd": "rm -rf /"}, "tool_use_id": "t1"},
+        }
+        reply = await asyncio.to_thread(_send_request, stdin_r, stdout_w, request)
+        await protocol.stop()
+
+        assert calls == [("Bash", {"cmd": "rm -rf /"}, "t1")]
+        assert reply == {
+            "type": "control_response",
+            "request_id": "agent_1",
+            "subtype": "can_use_tool",
+            "data": {"decision": "deny", "reason": "not today"},
+        }
+
+    @pytest.mark.parametrize(
+        ("result", "data"),
+        [
+            (
+                PermissionResultAllow(option_id="allow-always"),
+                {"decision": "allow", "option_id": "allow-always"},
+            ),
+            ({"decision": "ask"}, {"decision": "deny", "reason": None}),
+            (
+                "yes",
+                {"decision": "deny", "reason": "permission callback returned no decision"},
+            ),
+            (
+                RuntimeError("policy store unavailable"),
+                {"decision": "deny", "reason": "permission callback failed"},
+            ),
+        ],
+    )
+    @pytest.mark.asyncio
+    async def test_only_an_allow_decision_allows(self, result, data):
+        async def policy(tool_name, tool_input, context):
+            if isinstance(result, Exception):
+                raise result
+            return result
+
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        protocol.set_permission_callback(policy)
+        await protocol.start(stdin_w, stdout_r)
+
+        request = {"request_id": "agent_1", "subtype": "can_use_tool", "data": {"tool_name": "Bash"}}
+        reply = await asyncio.to_thread(_send_request, stdin_r, stdout_w, request)
+        await protocol.stop()
+
+        assert reply["data"] == data
+
+    @pytest.mark.asyncio
+    async def test_mcp_message_is_answered_by_callback(self):
+        async def mcp(data):
+            return {"echo": data["method"]}
+
+        stdin_r, stdin_w = os.pipe()
+        stdout_r, stdout_w = os.pipe()
+        protocol = RustControlProtocol()
+        protocol.set_mcp_callback(mcp)
+        await protocol.start(stdin_w, stdout_r)
+
+        request = {"request_id": "agent_2", "subtype": "mcp_message", "data": {"method": "tools/list"}}
+        reply = await asyncio.to_thread(_send_request, stdin_r, stdout_w, request)
+        await protocol.stop()
+
+        assert reply["request_id"] == "agent_2"
+        assert reply["data"] == {"echo": "tools/list"}
@@ -2,9 +2,13 @@
 
 from __future__ import annotations
 
+import json
+
 import pytest
 
 from conduit_sdk import HookRunner, HookType, hook
+from conduit_sdk._conduit_sdk import RustHookDispatcher
+from conduit_sdk.exceptions import HookError
 from conduit_sdk.types import HookContext
 
 
@@ -75,6 +79,194 @@ class TestStandaloneHookDecorator:
         assert my_hook._hook_priority == 5
 
 
+class TestRustHookOrdering:
+    @pytest.mark.asyncio
+    async def test_equal_priorities_run_in_registration_order(self):
+        dispatcher = RustHookDispatcher()
+        calls = []
+
+        def recorder(name):
+            def hook(ctx):
+                calls.append(name)
+
+            return hook
+
+        await dispatcher.register(HookType.PromptSubmit, recorder("late"), priority=5)
+        for name in ("first", "second", "third"):
+            await dispatcher.register(HookType.PromptSubmit, recorder(name), priority=1)
+        await dispatcher.register(HookType.PostToolUse, recorder("other type"), priority=1)
+
+        await dispatcher.dispatch(HookType.PromptSubmit, "{}")
+        assert calls == ["first", "second", "third", "late"]
+
+
+    @pytest.mark.asyncio
+    async def test_unregister_removes_only_that_hook(self):
+        dispatcher = RustHookDispatcher()
+        calls = []
+
+        def mine(ctx):
+            calls.append("mine")
+
+        def theirs(ctx):
+            calls.append("theirs")
+
+        handle = await dispatcher.register(HookType.PreToolUse, mine)
+        await dispatcher.register(HookType.PreToolUse, theirs)
+
+        assert await dispatcher.unregister(handle) is True
+        assert await dispatcher.unregister(handle) is False
+
+        await dispatcher.dispatch(HookType.PreToolUse, "{}")
+        assert calls == ["theirs"]
+
+class TestRustHookContextPassing:
+    @pytest.mark.asyncio
+    async def test_context_is_decoded_and_encoded_once(self, monkeypatch):
+        dumps_calls = []
+        real_dumps = json.dumps
+
+        def counting_dumps(obj, *args, **kwargs):
+            dumps_calls.append(obj)
+            return real_dumps(obj, *args, **kwargs)
+
+        monkeypatch.setattr(json, "dumps", counting_dumps)
+        dispatcher = RustHookDispatcher()
+
+        def bump(ctx):
+            ctx["count"] += 1
+
+        for _ in range(10):
+            await dispatcher.register(HookType.PromptSubmit, bump)
+
+        result = await dispatcher.dispatch(HookType.PromptSubmit, real_dumps({"count": 0}))
+
+        assert json.loads(result) == {"count": 10}
+        assert len(dumps_calls) == 1
+
+
+class TestRustHookErrors:
+    @staticmethod
+    def _broken(ctx):
+        raise ValueError("hook exploded")
+
+    @pytest.mark.asyncio
+    async def test_pass_through_reports_to_error_sink(self):
+        errors = []
+
+        def sink(hook_type, message):
+            errors.append((hook_type, message))
+
+        dispatcher = RustHookDispatcher(error_sink=sink)
+        calls = []
+
+        await dispatcher.register(HookType.PromptSubmit, self._broken, priority=0)
+        await dispatcher.register(HookType.PromptSubmit, lambda ctx: calls.append(ctx), priority=1)
+
+        result = await dispatcher.dispatch(HookType.PromptSubmit, json.dumps({"text": "hi"}))
+
+        assert json.loads(result) == {"text": "hi"}
+        assert calls == [{"text": "hi"}]
+        [(hook_type, message)] = errors
+        assert hook_type == HookType.PromptSubmit
+        assert "ValueError: hook exploded" in message
+
+    @pytest.mark.asyncio
+    async def test_raising_error_sink_is_logged(self):
+        def sink(hook_type, message):
+            raise RuntimeError("sink exploded")
+
+        dispatcher = RustHookDispatcher(error_sink=sink)
+        logged = []
+        dispatcher.set_log_callback(lambda level, message: logged.append((level, message)))
+        await dispatcher.register(HookType.PromptSubmit, self._broken)
+
+        result = await dispatcher.dispatch(HookType.PromptSubmit, json.dumps({"text": "hi"}))
+
+        assert json.loads(result) == {"text": "hi"}
+        [(level, message)] = logged
+        assert level == "error"
+        assert "hook error sink raised" in message
+        assert "sink exploded" in message
+
+    @pytest.mark.asyncio
+    async def test_failed_hook_edits_are_discarded(self):
+        dispatcher = RustHookDispatcher()
+        seen = []
+
+        def half_done(ctx):
+            ctx["text"] = "rewritten"
+            ctx["nested"]["flag"] = True
+            raise ValueError("hook exploded")
+
+        await dispatcher.register(HookType.PromptSubmit, half_done, priority=0)
+        await dispatcher.register(HookType.PromptSubmit, lambda ctx: seen.append(ctx), priority=1)
+
+        context = {"text": "hi", "nested": {"flag": False}}
+        result = await dispatcher.dispatch(HookType.PromptSubmit, json.dumps(context))
+
+        assert json.loads(result) == context
+        assert seen == [context]
+
+    @pytest.mark.asyncio
+    async def test_fail_fast_raises_with_traceback(self):
+        dispatcher = RustHookDispatcher(fail_fast=True)
+        calls = []
+
+        await dispatcher.register(HookType.PromptSubmit, self._broken, priority=0)
+        await dispatcher.register(HookType.PromptSubmit, lambda ctx: calls.append(ctx), priority=1)
+
+        with pytest.raises(HookError, match="hook exploded") as exc_info:
+            await dispatcher.dispatch(HookType.PromptSubmit, "{}")
+
+        assert "Traceback" in str(exc_info.value)
+        assert calls == []
+
+
+class TestRustHookVeto:
+    @pytest.mark.asyncio
+    async def test_deny_short_circuits_later_hooks(self):
+        dispatcher = RustHookDispatcher()
+        calls = []
+
+        def first(ctx):
+            calls.append("first")
+
+        def deny(ctx):
+            calls.append("deny")
+            return {"__action__": "deny", "reason": "rm -rf is not allowed"}
+
+        def last(ctx):
+            calls.append("last")
+
+        await dispatcher.register(HookType.PreToolUse, first, priority=0)
+        await dispatcher.register(HookType.PreToolUse, deny, priority=1)
+        await dispatcher.register(HookType.PreToolUse, last, priority=2)
+
+        result = await dispatcher.dispatch(HookType.PreToolUse, json.dumps({"tool_name": "bash"}))
+
+        assert json.loads(result) == {"__action__": "deny", "reason": "rm -rf is not allowed"}
+        assert calls == ["first", "deny"]
+
+    @pytest.mark.asyncio
+    async def test_deny_ignored_for_non_veto_hook_types(self):
+        dispatcher = RustHookDispatcher()
+        calls = []
+
+        def deny(ctx):
+            calls.append("deny")
+            return {"__action__": "deny"}
+
+        def last(ctx):
+            calls.append("last")
+
+        await dispatcher.register(HookType.PostToolUse, deny, priority=0)
+        await dispatcher.register(HookType.PostToolUse, last, priority=1)
+
+        await dispatcher.dispatch(HookType.PostToolUse, "{}")
+        assert calls == ["deny", "last"]
+
+
 class TestHookContext:
     def test_get_set(self):
         ctx = HookContext(hook_type="test", data={"key": "value"})
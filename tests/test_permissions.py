@@ -23,6 +23,15 @@ class TestPermissionResultAllow:
         result = PermissionResultAllow()
         assert "Allow" in repr(result)
 
+    def test_allow_with_reason_stays_allow(self):
+        result = PermissionResultAllow(reason="read-only tool")
+        assert result.decision == "allow"
+        assert result.reason == "read-only tool"
+
+    def test_option_id(self):
+        result = PermissionResultAllow(option_id="allow-always")
+        assert result.option_id == "allow-always"
+
 
 class TestPermissionResultDeny:
     def test_is_permission_result(self):
@@ -41,6 +50,22 @@ class TestPermissionResultDeny:
         result = PermissionResultDeny("nope")
         assert "nope" in repr(result)
 
+    def test_deny_with_reason_is_deny(self):
+        result = PermissionResultDeny("writes outside workspace")
+        assert result.decision == "deny"
+        assert result.option_id is None
+
+
+class TestPermissionResult:
+    def test_explicit_decision(self):
+        result = PermissionResult("allow", reason="trusted")
+        assert result.decision == "allow"
+        assert result.reason == "trusted"
+
+    def test_rejects_unknown_decision(self):
+        with pytest.raises(ValueError, match="allow"):
+            PermissionResult("maybe")
+
 
 class TestToolPermissionContext:
     def test_basic(self):
@@ -0,0 +1,236 @@
+"""Tests for conduit_sdk.proxy (ProxyChain builder and built-in proxies)."""
+
+from __future__ import annotations
+
+import asyncio
+import json
+import os
+import shlex
+import signal
+import sys
+
+import pytest
+
+from conduit_sdk import Client, ContextInjector, Proxy, ProxyChain, ResponseFilter
+from conduit_sdk._conduit_sdk import ProxyConfig, RustProxyChain
+from conduit_sdk.exceptions import ProxyError
+
+# End-to-end chain tests need a real agent and a pass-through proxy binary.
+_AGENT = os.environ.get("CONDUIT_TEST_AGENT")
+_PASSTHROUGH = os.environ.get("CONDUIT_TEST_PASSTHROUGH_PROXY")
+
+
+class _PassThrough(Proxy):
+    def __init__(self, name: str, *, restart: bool = False) -> None:
+        self._name = name
+        self._restart = restart
+
+    @property
+    def restart(self) -> bool:
+        return self._restart
+
+    @property
+    def name(self) -> str:
+        return self._name
+
+    @property
+    def command(self) -> list[str]:
+        return shlex.split(_PASSTHROUGH or "")
+
+
+class TestProxyChainBuilder:
+    def test_add_preserves_order(self):
+        chain = ProxyChain()
+        chain.add(ContextInjector(context="Be brief.")).add(ResponseFilter(max_tokens=10))
+        assert [p.name for p in chain.proxies] == ["context-injector", "response-filter"]
+        assert "context-injector -> response-filter" in repr(chain)
+
+    def test_to_config(self):
+        config = ContextInjector(context="x", cmd=["my-proxy", "--flag"]).to_config()
+        assert config.name == "context-injector"
+        assert config.command == ["my-proxy", "--flag"]
+        assert config.restart is False
+
+    def test_restart_policy_in_config(self):
+        config = ResponseFilter(max_tokens=5, restart=True).to_config()
+        assert config.restart is True
+
+    def test_env_in_config(self):
+        config = ContextInjector(context="x", env={"LOG_LEVEL": "debug"}).to_config()
+        assert config.env == {"LOG_LEVEL": "debug"}
+
+
+class TestProxyChainEditing:
+    def _chain(self) -> ProxyChain:
+        chain = ProxyChain()
+        for name in ("a", "b", "c", "d"):
+            chain.add(_PassThrough(name))
+        return chain
+
+    def test_remove_missing_returns_false(self):
+        chain = self._chain()
+        assert chain.remove("zzz") is False
+        assert [p.name for p in chain.proxies] == ["a", "b", "c", "d"]
+
+    def test_remove_by_name(self):
+        chain = self._chain()
+        assert chain.remove("b") is True
+        assert chain.index_of("b") is None
+        assert [p.name for p in chain.proxies] == ["a", "c", "d"]
+
+    def test_move_mid_chain_preserves_others(self):
+        chain = self._chain()
+        chain.move_to("b", 2)
+        assert [p.name for p in chain.proxies] == ["a", "c", "b", "d"]
+        assert chain.index_of("b") == 2
+
+    def test_move_out_of_range_raises(self):
+        chain = self._chain()
+        with pytest.raises(ProxyError, match="out of range"):
+            chain.move_to("a", 4)
+
+    @pytest.mark.asyncio
+    async def test_rust_chain_remove_and_move(self):
+        rust_chain = RustProxyChain()
+        for name in ("a", "b", "c", "d"):
+            await rust_chain.add(ProxyConfig(name, ["true"]))
+
+        assert await rust_chain.remove("zzz") is False
+        await rust_chain.move_to("c", 0)
+        assert [p.name for p in await rust_chain.list()] == ["c", "a", "b", "d"]
+        assert await rust_chain.index_of("b") == 2
+        assert await rust_chain.index_of("zzz") is None
+        with pytest.raises(ProxyError, match="out of range"):
+            await rust_chain.move_to("a", 9)
+
+
+class TestProxyChainValidate:
+    @pytest.mark.asyncio
+    async def test_valid_chain_has_no_problems(self):
+        chain = ProxyChain().add(ContextInjector(context="x", cmd=["sh", "-c", "cat"]))
+        chain.add(ResponseFilter(cmd=[sys.executable]))
+        assert await chain.validate() == []
+
+    @pytest.mark.asyncio
+    async def test_missing_binary_reported(self):
+        chain = ProxyChain()
+        chain.add(ContextInjector(context="x", cmd=["conduit-no-such-proxy-binary"]))
+        chain.add(ResponseFilter(cmd=["/nonexistent/dir/filter"]))
+        problems = await chain.validate()
+        assert len(problems) == 2
+        assert "context-injector" in problems[0]
+        assert "conduit-no-such-proxy-binary" in problems[0]
+        assert "response-filter" in problems[1]
+
+    @pytest.mark.asyncio
+    async def test_proxy_env_path_used_for_lookup(self):
+        chain = ProxyChain().add(
+            ContextInjector(context="x", cmd=["sh"], env={"PATH": "/nonexistent"})
+        )
+        assert len(await chain.validate()) == 1
+
+    @pytest.mark.asyncio
+    async def test_command_variables_expanded_for_lookup(self):
+        chain = ProxyChain().add(
+            ContextInjector(context="x", cmd=["${PROXY_BIN}"], env={"PROXY_BIN": sys.executable})
+        )
+        assert await chain.validate() == []
+
+
+class TestProxyChainLifecycle:
+    @pytest.mark.asyncio
+    async def test_build_empty_chain_raises(self):
+        chain = ProxyChain()
+        with pytest.raises(ProxyError, match="empty"):
+            await chain.build(["agent"])
+        assert not chain.built
+
+    @pytest.mark.asyncio
+    async def test_teardown_unbuilt_is_noop(self):
+        chain = ProxyChain()
+        await chain.teardown()
+        assert not chain.built
+
+    @pytest.mark.asyncio
+    async def test_status_before_build_is_empty(self):
+        chain = ProxyChain().add(ContextInjector(context="x"))
+        assert await chain.status() == []
+        await chain.ensure_healthy()
+
+    @pytest.mark.asyncio
+    async def test_crash_looping_proxy_gives_up(self):
+        rust_chain = RustProxyChain()
+        crashing = [sys.executable, "-c", "pass"]
+        await rust_chain.add(ProxyConfig("crashy", crashing, restart=True))
+        handle = await rust_chain.build([sys.executable, "-c", "import time; time.sleep(60)"])
+        try:
+            # Backoff doubles from 0.1s, so five restarts take about 3s.
+            for _ in range(100):
+                (crashy, _agent) = json.loads(await rust_chain.status())
+                if not crashy["alive"] and crashy["restarts"] == 5:
+                    break
+                await asyncio.sleep(0.1)
+            assert crashy["restarts"] == 5
+            with pytest.raises(ProxyError, match="after 5 restarts"):
+                await rust_chain.ensure_healthy()
+        finally:
+            await handle.teardown()
+
+
+class TestClientProxyChain:
+    def test_proxy_chain_stored(self):
+        chain = ProxyChain().add(ContextInjector(context="x"))
+        client = Client(["claude"], proxy_chain=chain)
+        assert client.proxy_chain is chain
+
+    def test_no_proxy_chain_by_default(self):
+        client = Client(["claude"])
+        assert client.proxy_chain is None
+
+
+@pytest.mark.skipif(
+    not (_AGENT and _PASSTHROUGH),
+    reason="set CONDUIT_TEST_AGENT and CONDUIT_TEST_PASSTHROUGH_PROXY to run",
+)
+class TestProxyChainEndToEnd:
+    @pytest.mark.asyncio
+    async def test_prompt_through_two_passthrough_proxies(self):
+        chain = ProxyChain().add(_PassThrough("first")).add(_PassThrough("second"))
+        async with Client(shlex.split(_AGENT), proxy_chain=chain) as client:
+            assert chain.built
+            names = await chain._handle.names()
+            assert names == ["first", "second", "agent"]
+            messages = await client.prompt_sync("Reply with the single word: pong")
+            assert any("pong" in m.text().lower() for m in messages)
+        assert not chain.built
+
+    @pytest.mark.asyncio
+    async def test_killed_proxy_is_restarted(self):
+        chain = ProxyChain().add(_PassThrough("flaky", restart=True))
+        async with Client(shlex.split(_AGENT), proxy_chain=chain) as client:
+            await client.prompt_sync("Reply with the single word: pong")
+            (flaky, _agent) = await chain.status()
+            os.kill(flaky["pid"], signal.SIGKILL)
+
+            for _ in range(50):
+                (flaky, _agent) = await chain.status()
+                if flaky["restarts"] == 1 and flaky["alive"]:
+                    break
+                await asyncio.sleep(0.1)
+            assert flaky["restarts"] == 1
+            await chain.ensure_healthy()
+
+    @pytest.mark.asyncio
+    async def test_killed_proxy_without_restart_fails_chain(self):
+        chain = ProxyChain().add(_PassThrough("fragile"))
+        async with Client(shlex.split(_AGENT), proxy_chain=chain):
+            (fragile, _agent) = await chain.status()
+            os.kill(fragile["pid"], signal.SIGKILL)
+
+            for _ in range(50):
+                (fragile, _agent) = await chain.status()
+                if not fragile["alive"]:
+                    break
+                await asyncio.sleep(0.1)
+            with pytest.raises(ProxyError, match="restart is disabled"):
+                await chain.ensure_healthy()
@@ -6,7 +6,7 @@ import json
 
 import pytest
 
-from conduit_sdk._conduit_sdk import RustControlProtocol
+from conduit_sdk._conduit_sdk import PermissionOption, RustControlProtocol
 from conduit_sdk.permissions import (
     PermissionResultAllow,
     PermissionResultDeny,
@@ -97,6 +97,71 @@ class TestQueryPermissionRouting:
         assert isinstance(captured.get("context"), ToolPermissionContext)
         assert captured["context"].tool_use_id == "tu_1"
 
+    @pytest.mark.asyncio
+    async def test_permission_context_carries_options(self):
+        captured = {}
+
+        async def capture_policy(name, input_, ctx):
+            captured["options"] = ctx.options
+            return PermissionResultAllow()
+
+        protocol = RustControlProtocol()
+        query = Query(protocol, can_use_tool=capture_policy)
+
+        msg = json.dumps({
+            "type": "control",
+            "request_id": "req_test",
+            "subtype": "can_use_tool",
+            "data": {
+                "tool_name": "Bash",
+                "tool_input": {"command": "ls"},
+                "options": [
+                    {"optionId": "once", "name": "Allow once", "kind": "allow_once"},
+                    {
+                        "optionId": "never",
+                        "name": "Reject always",
+                        "kind": "reject_always",
+                    },
+                    {"name": "missing an id"},
+                ],
+            },
+        })
+        try:
+            await query.handle_control_request(msg)
+        except Exception:
+            pass  # Expected: protocol not started
+
+        assert captured["options"] == [
+            PermissionOption("once", "Allow once", "allow_once"),
+            PermissionOption("never", "Reject always", "reject_always"),
+        ]
+
+
+    @pytest.mark.asyncio
+    async def test_permission_denied_without_callback(self):
+        sent = []
+
+        class Protocol:
+            async def send_control_response(self, request_id, subtype, data):
+                sent.append((request_id, subtype, json.loads(data)))
+
+        query = Query(Protocol())
+        msg = json.dumps({
+            "type": "control",
+            "request_id": "req_test",
+            "subtype": "can_use_tool",
+            "data": {"tool_name": "Bash", "tool_input": {"command": "ls"}},
+        })
+        await query.handle_control_request(msg)
+
+        assert sent == [
+            (
+                "req_test",
+                "can_use_tool",
+                {"decision": "deny", "reason": "no permission callback set"},
+            )
+        ]
+
 
 class TestQueryControlMethods:
     """Test Query's outbound control methods (without live protocol)."""
@@ -4,7 +4,7 @@ from __future__ import annotations
 
 import pytest
 
-from conduit_sdk import Client, Session
+from conduit_sdk import Client, ResumeResult, Session
 from conduit_sdk.exceptions import SessionError
 
 
@@ -21,6 +21,27 @@ class TestSessionInit:
         assert "Session" in repr(session)
 
 
+class TestSessionResume:
+    def test_apply_resume_updates_local_state(self):
+        client = Client(["echo"])
+        session = Session(client)
+        result = ResumeResult(
+            "sess-1", mode="code", model="opus", updated_at="2026-01-02T03:04:05Z"
+        )
+        session._apply_resume(result)
+        assert session.session_id == "sess-1"
+        assert session.mode == "code"
+        assert session.model == "opus"
+        assert session.resume_result is result
+
+    def test_apply_resume_without_mode_keeps_previous(self):
+        client = Client(["echo"])
+        session = Session(client)
+        session._mode = "ask"
+        session._apply_resume(ResumeResult("sess-1"))
+        assert session.mode == "ask"
+
+
 class TestSessionGuards:
     @pytest.mark.asyncio
     async def test_set_mode_without_create_raises(self):
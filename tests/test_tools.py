@@ -2,12 +2,14 @@
 
 from __future__ import annotations
 
+import asyncio
 import json
 
 import pytest
 
 from conduit_sdk import ToolSchema, tool
-from conduit_sdk.exceptions import ToolError
+from conduit_sdk._conduit_sdk import RustToolRegistry, ToolDefinition
+from conduit_sdk.exceptions import TimeoutError, ToolError
 from conduit_sdk.tools import (
     McpSdkServerConfig,
     _infer_schema,
@@ -263,3 +265,296 @@ class TestHandleMcpRequest:
     async def test_unknown_method(self):
         result = await handle_mcp_request({}, {"method": "unknown/method"})
         assert "error" in result
+
+
+class TestInvokeStructured:
+    @staticmethod
+    async def _registry_with(name, callback) -> RustToolRegistry:
+        registry = RustToolRegistry()
+        schema = json.dumps({"type": "object", "properties": {}})
+        await registry.register(ToolDefinition(name, "test tool", schema), callback)
+        return registry
+
+    @pytest.mark.asyncio
+    async def test_throwing_tool_reports_error(self):
+        async def explode() -> str:
+            raise ValueError("disk on fire")
+
+        registry = await self._registry_with("explode", explode)
+        result = json.loads(await registry.invoke_structured("explode", "{}"))
+        assert result["is_error"] is True
+        assert result["content"] == [{"type": "text", "text": "disk on fire"}]
+
+    @pytest.mark.asyncio
+    async def test_dict_returning_tool(self):
+        async def lookup(key: str) -> dict:
+            return {"key": key, "value": 42}
+
+        registry = await self._registry_with("lookup", lookup)
+        result = json.loads(await registry.invoke_structured("lookup", '{"key": "k"}'))
+        assert result["is_error"] is False
+        [block] = result["content"]
+        assert block["type"] == "text"
+        assert json.loads(block["text"]) == {"key": "k", "value": 42}
+
+    @pytest.mark.asyncio
+    async def test_mcp_shaped_result_passes_through(self):
+        async def shaped() -> dict:
+            return {"content": [{"type": "text", "text": "hi"}], "isError": True}
+
+        registry = await self._registry_with("shaped", shaped)
+        result = json.loads(await registry.invoke_structured("shaped", "{}"))
+        assert result == {"content": [{"type": "text", "text": "hi"}], "is_error": True}
+
+    @pytest.mark.asyncio
+    async def test_unknown_tool_still_raises(self):
+        registry = RustToolRegistry()
+        with pytest.raises(ToolError, match="not found"):
+            await registry.invoke_structured("missing", "{}")
+
+
+class TestInputSchemaValidation:
+    _SCHEMA = json.dumps(
+        {
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"],
+        }
+    )
+
+    @staticmethod
+    def _recording_tool(calls: list):
+        async def read_file(path: str = "<missing>") -> str:
+            calls.append(path)
+            return f"read {path}"
+
+        return read_file
+
+    @pytest.mark.asyncio
+    async def test_missing_required_field_skips_callback(self):
+        calls: list = []
+        registry = RustToolRegistry()
+        definition = ToolDefinition("read_file", "Read a file", self._SCHEMA)
+        await registry.register(definition, self._recording_tool(calls), strict_schema=True)
+
+        with pytest.raises(ToolError, match="invalid input.*path"):
+            await registry.invoke("read_file", "{}")
+        result = json.loads(await registry.invoke_structured("read_file", "{}"))
+        assert result["is_error"] is True
+        assert "path" in result["content"][0]["text"]
+        assert calls == []
+
+        assert await registry.invoke("read_file", '{"path": "a.txt"}') == "read a.txt"
+        assert calls == ["a.txt"]
+
+    @pytest.mark.asyncio
+    async def test_input_is_unchecked_by_default(self):
+        calls: list = []
+        registry = RustToolRegistry()
+        definition = ToolDefinition("read_file", "Read a file", self._SCHEMA)
+        await registry.register(definition, self._recording_tool(calls))
+
+        assert await registry.invoke("read_file", "{}") == "read <missing>"
+        assert calls == ["<missing>"]
+
+
+class TestConcurrentInvoke:
+    @pytest.mark.asyncio
+    async def test_same_tool_from_two_tasks(self):
+        both_started = asyncio.Barrier(2)
+
+        async def rendezvous(tag: str) -> str:
+            # Each call waits for the other, so this only finishes if both
+            # invocations are in flight at once.
+            await both_started.wait()
+            return tag
+
+        registry = RustToolRegistry()
+        schema = json.dumps({"type": "object", "properties": {"tag": {"type": "string"}}})
+        await registry.register(ToolDefinition("rendezvous", "", schema), rendezvous)
+
+        results = await asyncio.wait_for(
+            asyncio.gather(
+                registry.invoke("rendezvous", '{"tag": "a"}'),
+                registry.invoke("rendezvous", '{"tag": "b"}'),
+            ),
+            timeout=5,
+        )
+        assert sorted(results) == ["a", "b"]
+
+
+class TestInputMiddleware:
+    @staticmethod
+    async def _echo_registry() -> tuple[RustToolRegistry, list]:
+        seen: list = []
+
+        async def run_command(command: str, cwd: str = ".") -> str:
+            seen.append({"command": command, "cwd": cwd})
+            return "ok"
+
+        registry = RustToolRegistry()
+        schema = json.dumps(
+            {
+                "type": "object",
+                "properties": {"command": {"type": "string"}, "cwd": {"type": "string"}},
+                "required": ["command"],
+            }
+        )
+        await registry.register(ToolDefinition("run_command", "", schema), run_command)
+        return registry, seen
+
+    @pytest.mark.asyncio
+    async def test_middleware_rewrites_input_before_callback(self):
+        registry, seen = await self._echo_registry()
+
+        def redact(tool_name: str, tool_input: dict) -> dict:
+            assert tool_name == "run_command"
+            return {**tool_input, "command": tool_input["command"].replace("hunter2", "***")}
+
+        registry.set_input_middleware(redact)
+        await registry.invoke("run_command", '{"command": "login --password hunter2"}')
+        assert seen == [{"command": "login --password ***", "cwd": "."}]
+
+    @pytest.mark.asyncio
+    async def test_async_middleware_and_none_passthrough(self):
+        registry, seen = await self._echo_registry()
+
+        async def normalize(tool_name: str, tool_input: dict) -> dict | None:
+            if "cwd" not in tool_input:
+                return None
+            return {**tool_input, "cwd": tool_input["cwd"].rstrip("/")}
+
+        registry.set_input_middleware(normalize)
+        await registry.invoke("run_command", '{"command": "ls", "cwd": "/tmp/"}')
+        await registry.invoke("run_command", '{"command": "pwd"}')
+        assert seen == [{"command": "ls", "cwd": "/tmp"}, {"command": "pwd", "cwd": "."}]
+
+        registry.set_input_middleware(None)
+        await registry.invoke("run_command", '{"command": "ls", "cwd": "/tmp/"}')
+        assert seen[-1] == {"command": "ls", "cwd": "/tmp/"}
+
+
+class TestToolLimits:
+    _SCHEMA = json.dumps({"type": "object", "properties": {}})
+
+    @pytest.mark.asyncio
+    async def test_concurrency_cap(self):
+        running = 0
+        peak = 0
+        release = asyncio.Event()
+
+        async def slow() -> str:
+            nonlocal running, peak
+            running += 1
+            peak = max(peak, running)
+            await release.wait()
+            running -= 1
+            return "done"
+
+        registry = RustToolRegistry()
+        await registry.register(
+            ToolDefinition("slow", "", self._SCHEMA), slow, max_concurrency=2
+        )
+        calls = [asyncio.ensure_future(registry.invoke("slow", "{}")) for _ in range(4)]
+        for _ in range(50):
+            if running == 2:
+                break
+            await asyncio.sleep(0.01)
+        await asyncio.sleep(0.05)
+
+        stats = json.loads(await registry.stats())
+        assert stats["slow"]["in_flight"] == 2
+        assert stats["slow"]["max_concurrency"] == 2
+
+        release.set()
+        assert await asyncio.wait_for(asyncio.gather(*calls), timeout=5) == ["done"] * 4
+        assert peak == 2
+        assert json.loads(await registry.stats())["slow"]["in_flight"] == 0
+
+    @pytest.mark.asyncio
+    async def test_timeout_fires_and_releases_permit(self):
+        async def hang() -> str:
+            await asyncio.sleep(10)
+            return "never"
+
+        registry = RustToolRegistry()
+        await registry.register(
+            ToolDefinition("hang", "", self._SCHEMA), hang, max_concurrency=1, timeout_secs=0.05
+        )
+
+        with pytest.raises(TimeoutError, match="timed out"):
+            await registry.invoke("hang", "{}")
+        result = json.loads(await asyncio.wait_for(registry.invoke_structured("hang", "{}"), 5))
+        assert result["is_error"] is True
+        assert "timed out" in result["content"][0]["text"]
+
+        stats = json.loads(await registry.stats())
+        assert stats["hang"] == {"in_flight": 0, "max_concurrency": 1, "timeout_secs": 0.05}
+
+    @pytest.mark.asyncio
+    async def test_timed_out_tool_is_cancelled_before_returning(self):
+        events: list[str] = []
+
+        async def hang() -> str:
+            try:
+                await asyncio.sleep(10)
+            except asyncio.CancelledError:
+                events.append("cancelled")
+                await asyncio.sleep(0.05)
+                events.append("unwound")
+                raise
+            return "never"
+
+        registry = RustToolRegistry()
+        await registry.register(
+            ToolDefinition("hang", "", self._SCHEMA), hang, max_concurrency=1, timeout_secs=0.05
+        )
+
+        with pytest.raises(TimeoutError, match="timed out"):
+            await registry.invoke("hang", "{}")
+        assert events == ["cancelled", "unwound"]
+
+
+class TestManifest:
+    @pytest.mark.asyncio
+    async def test_manifest_shape(self):
+        async def read_file(path: str) -> str:
+            return path
+
+        async def list_dir() -> str:
+            return ""
+
+        read_schema = {
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"],
+        }
+        registry = RustToolRegistry()
+        await registry.register(
+            ToolDefinition("read_file", "Read a file", json.dumps(read_schema)), read_file
+        )
+        await registry.register(
+            ToolDefinition("list_dir", "List a directory", '{"type": "object"}'), list_dir
+        )
+
+        manifest = json.loads(await registry.manifest())
+        assert manifest == [
+            {
+                "name": "list_dir",
+                "description": "List a directory",
+                "inputSchema": {"type": "object"},
+            },
+            {"name": "read_file", "description": "Read a file", "inputSchema": read_schema},
+        ]
+
+    @pytest.mark.asyncio
+    async def test_invalid_stored_schema_raises(self):
+        async def noop() -> str:
+            return ""
+
+        registry = RustToolRegistry()
+        await registry.register(
+            ToolDefinition("noop", "", "{not json"), noop, strict_schema=False
+        )
+        with pytest.raises(ToolError, match="invalid input schema"):
+            await registry.manifest()
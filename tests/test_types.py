@@ -2,6 +2,10 @@
 
 from __future__ import annotations
 
+import json
+import sys
+from decimal import Decimal
+
 import pytest
 
 from conduit_sdk import (
@@ -9,13 +13,21 @@ from conduit_sdk import (
     ClientConfig,
     ContentBlock,
     ContentType,
+    Cost,
+    FileEdit,
+    McpServerConfig,
     Message,
     MessageRole,
+    PlanEntry,
+    ResumeResult,
     SessionUpdate,
+    ToolContent,
     ToolDefinition,
     ToolSchema,
     UpdateKind,
+    UsageInfo,
 )
+from conduit_sdk.exceptions import SessionError
 
 
 class TestCapabilities:
@@ -26,6 +38,7 @@ class TestCapabilities:
         assert caps.proxy is False
         assert caps.modes == []
         assert caps.models == []
+        assert caps.protocol_version == ""
 
     def test_custom_values(self):
         caps = Capabilities(
@@ -42,6 +55,63 @@ class TestCapabilities:
         caps = Capabilities()
         assert "Capabilities" in repr(caps)
 
+    def test_acp_round_trip(self):
+        caps = Capabilities(
+            sessions=True,
+            tools=False,
+            proxy=True,
+            modes=["ask", "code"],
+            models=["claude-4", "claude-4-mini"],
+            mcp_hot_add=True,
+        )
+        wire = json.loads(caps.to_json())
+        assert wire["loadSession"] is True
+        assert wire["_meta"]["mcpHotAdd"] is True
+        assert wire["_meta"]["conduit"] == {
+            "tools": False,
+            "proxy": True,
+            "modes": ["ask", "code"],
+            "models": ["claude-4", "claude-4-mini"],
+        }
+
+        again = Capabilities.from_json(caps.to_json())
+        assert again == caps
+        assert Capabilities.from_json(again.to_json()) == caps
+
+    def test_from_json_defaults(self):
+        caps = Capabilities.from_json("{}")
+        assert caps.sessions is False
+        assert caps.tools is True
+        assert caps.modes == []
+        assert caps.mcp_hot_add is False
+
+    def test_unnamespaced_meta_is_not_read(self):
+        caps = Capabilities.from_json('{"_meta": {"proxy": true, "modes": ["ask"]}}')
+        assert caps.proxy is False
+        assert caps.modes == []
+
+    @pytest.mark.parametrize(
+        "meta",
+        [
+            {"conduit": {"modes": "ask"}},
+            {"conduit": {"proxy": "yes"}},
+            {"conduit": {"tools": "no"}},
+            {"mcpHotAdd": 1},
+        ],
+    )
+    def test_malformed_meta_takes_defaults(self, meta):
+        assert Capabilities.from_json(json.dumps({"_meta": meta})) == Capabilities()
+
+    def test_constructor_defaults_match_the_handshake(self):
+        assert Capabilities() == Capabilities.from_json("{}")
+
+    def test_repr_includes_every_field(self):
+        caps = Capabilities(mcp_hot_add=True, protocol_version="1")
+        assert repr(caps) == (
+            "Capabilities(sessions=false, tools=true, proxy=false, modes=[], "
+            'models=[], mcp_hot_add=true, protocol_version="1")'
+        )
+
 
 class TestMessage:
     def test_text_extraction(self):
@@ -97,6 +167,116 @@ class TestSessionUpdate:
         update = SessionUpdate(UpdateKind.Error, error="something broke")
         assert update.error == "something broke"
 
+    def test_tool_contents_surfaces_images(self):
+        content = [
+            {"type": "content", "content": {"type": "text", "text": "rendered diff:"}},
+            {
+                "type": "content",
+                "content": {"type": "image", "mimeType": "image/png", "data": "iVBORw0KGgo="},
+            },
+            {"type": "terminal", "terminalId": "term-1"},
+        ]
+        update = SessionUpdate(
+            UpdateKind.ToolUseUpdate, tool_use_id="tu_1", tool_content=json.dumps(content)
+        )
+        text, image, terminal = update.tool_contents
+        assert text.kind == "text"
+        assert text.text == "rendered diff:"
+        assert image.kind == "image"
+        assert image.mime_type == "image/png"
+        assert image.data == "iVBORw0KGgo="
+        assert terminal.kind == "terminal"
+        assert json.loads(terminal.raw_json)["terminalId"] == "term-1"
+        # The raw JSON stays available.
+        assert json.loads(update.tool_content) == content
+
+    def test_tool_content_blocks_parse_text_and_diff(self):
+        content = [
+            {"type": "content", "content": {"type": "text", "text": "Updated config"}},
+            {"type": "diff", "path": "/repo/config.toml", "oldText": "a = 1", "newText": "a = 2"},
+            {"type": "terminal", "terminalId": "term-1"},
+        ]
+        update = SessionUpdate(
+            UpdateKind.ToolUseUpdate, tool_use_id="tu_1", tool_content=json.dumps(content)
+        )
+        text, diff = update.tool_content_blocks
+        assert text.content_type == ContentType.Text
+        assert text.text == "Updated config"
+        assert diff.content_type == ContentType.Diff
+        assert diff.path == "/repo/config.toml"
+        assert diff.old_text == "a = 1"
+        assert diff.new_text == "a = 2"
+        assert json.loads(update.tool_content) == content
+
+    def test_tool_contents_empty_without_content(self):
+        update = SessionUpdate(UpdateKind.ToolUseUpdate, tool_content="not json")
+        assert update.tool_contents == []
+        assert update.tool_content_blocks == []
+        assert ToolContent.parse_list("{}") == []
+
+
+class TestResumeResult:
+    def test_from_json_parses_restored_state(self):
+        raw = json.dumps(
+            {
+                "sessionId": "sess-2",
+                "modes": {
+                    "currentModeId": "architect",
+                    "availableModes": [{"id": "architect", "name": "Architect"}],
+                },
+                "models": {"currentModelId": "sonnet"},
+                "_meta": {"updatedAt": "2026-01-02T03:04:05Z"},
+            }
+        )
+        result = ResumeResult.from_json("sess-1", raw)
+        assert result.session_id == "sess-2"
+        assert result.mode == "architect"
+        assert result.model == "sonnet"
+        assert result.updated_at == "2026-01-02T03:04:05Z"
+        assert json.loads(result.raw_json)["modes"]["availableModes"][0]["id"] == "architect"
+
+    def test_from_json_empty_response_keeps_requested_id(self):
+        result = ResumeResult.from_json("sess-1", "{}")
+        assert result.session_id == "sess-1"
+        assert result.mode is None
+        assert result.model is None
+        assert result.updated_at is None
+
+
+class TestUsageInfo:
+    def test_decimal_string_cost_is_exact(self):
+        raw = json.dumps(
+            {"used": 1200, "size": 200000, "cost": {"amount": "0.0021", "currency": "USD"}}
+        )
+        usage = UsageInfo.from_json(raw)
+        assert usage.used == 1200
+        assert usage.size == 200000
+        assert usage.cost_amount == Decimal("0.0021")
+        assert usage.cost_currency == "USD"
+
+    def test_numeric_cost_skips_float_rounding(self):
+        raw = '{"used": 1, "size": 2, "cost": {"amount": 0.10000000000000000555, "currency": "USD"}}'
+        usage = UsageInfo.from_json(raw)
+        assert usage.cost_amount == Decimal("0.10000000000000000555")
+
+    def test_missing_cost(self):
+        usage = UsageInfo.from_json('{"used": 5, "size": 10, "cost": null}')
+        assert usage.cost_amount is None
+        assert usage.cost_currency is None
+
+
+class TestCost:
+    def test_amount_is_kept_as_a_decimal_string(self):
+        cost = Cost(Decimal("0.10000000000000000555"), " usd")
+        assert cost.amount == "0.10000000000000000555"
+        assert cost.currency == "USD"
+        assert Cost(2, "EUR").amount == "2"
+
+    @pytest.mark.parametrize("amount", ["nan", "inf", "1,5", "", True])
+    def test_non_decimal_amount_is_rejected(self, amount):
+        with pytest.raises(ValueError):
+            Cost(amount, "USD")
+
 
 class TestClientConfig:
     def test_minimal(self):
@@ -116,6 +296,115 @@ class TestClientConfig:
         assert config.cwd == "/tmp"
         assert config.env["GOOSE_MODEL"] == "claude-4"
 
+    def test_empty_command_fails_validation(self):
+        with pytest.raises(ValueError, match="must not be empty"):
+            ClientConfig(command=[]).validate()
+        with pytest.raises(ValueError, match="program"):
+            ClientConfig(command=["  ", "--agent"])
+        # Clients that don't spawn an agent need no command.
+        ClientConfig(command=[], endpoint="127.0.0.1:9000").validate()
+
+    def test_nonexistent_cwd_fails_validation(self, tmp_path):
+        ClientConfig(command=["agent"], cwd=str(tmp_path)).validate()
+        with pytest.raises(ValueError, match="not an existing directory"):
+            ClientConfig(command=["agent"], cwd=str(tmp_path / "missing")).validate()
+
+    def test_check_path(self, tmp_path):
+        ClientConfig(command=[sys.executable]).validate(check_path=True)
+        with pytest.raises(ValueError, match="not found"):
+            ClientConfig(command=["no-such-agent-binary"]).validate(check_path=True)
+        with pytest.raises(ValueError, match="not found"):
+            ClientConfig(command=[str(tmp_path / "agent")]).validate(check_path=True)
+        # Not checked by default.
+        ClientConfig(command=["no-such-agent-binary"]).validate()
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="no executable bit on Windows")
+    def test_check_path_needs_an_executable(self, tmp_path):
+        agent = tmp_path / "agent"
+        agent.write_text("#!/bin/sh\n")
+        agent.chmod(0o644)
+        with pytest.raises(ValueError, match="not found"):
+            ClientConfig(command=[str(agent)]).validate(check_path=True)
+        agent.chmod(0o755)
+        ClientConfig(command=[str(agent)]).validate(check_path=True)
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="no executable bit on Windows")
+    def test_check_path_searches_the_config_path(self, tmp_path):
+        agent = tmp_path / "my-agent"
+        agent.write_text("#!/bin/sh\n")
+        agent.chmod(0o755)
+        config = ClientConfig(command=["my-agent"], env={"PATH": str(tmp_path)})
+        config.validate(check_path=True)
+
+
+class TestMcpServerConfig:
+    def test_stdio_server(self):
+        server = McpServerConfig(
+            "echo", command="/usr/bin/env", args=["mcp-echo"], env={"B": "2", "A": "1"}
+        )
+        assert json.loads(server.to_json()) == {
+            "name": "echo",
+            "command": "/usr/bin/env",
+            "args": ["mcp-echo"],
+            "env": [{"name": "A", "value": "1"}, {"name": "B", "value": "2"}],
+        }
+
+    def test_http_server(self):
+        server = McpServerConfig(
+            "remote", url="https://mcp.example.com", headers={"Authorization": "Bearer t"}
+        )
+        assert json.loads(server.to_json()) == {
+            "type": "http",
+            "name": "remote",
+            "url": "https://mcp.example.com",
+            "headers": [{"name": "Authorization", "value": "Bearer t"}],
+        }
+        assert "url=" in repr(server)
+
+    def test_needs_exactly_one_transport(self):
+        with pytest.raises(SessionError, match="needs a command or a url"):
+            McpServerConfig("bare").to_json()
+        both = McpServerConfig("both", command="srv", url="https://mcp.example.com")
+        with pytest.raises(SessionError, match="not both"):
+            both.to_json()
+
+    def test_name_required(self):
+        with pytest.raises(SessionError, match="name is empty"):
+            McpServerConfig("", command="srv").to_json()
+
+
+class TestPlanEntry:
+    def test_parse_list_passes_unknown_values_through(self):
+        entries = [
+            {"content": "Ship it", "priority": "urgent", "status": "blocked"},
+            "not an entry",
+            {"content": "No priority or status"},
+        ]
+        assert PlanEntry.parse_list(json.dumps(entries)) == [
+            PlanEntry("Ship it", "urgent", "blocked"),
+            PlanEntry("No priority or status"),
+        ]
+        assert PlanEntry("x") == PlanEntry("x", "medium", "pending")
+
+    def test_parse_list_of_non_array(self):
+        assert PlanEntry.parse_list('{"content": "x"}') == []
+        assert PlanEntry.parse_list("not json") == []
+
+
+class TestFileEdit:
+    def test_parse_list_handles_creation_and_deletion(self):
+        content = [
+            {"type": "diff", "path": "/a.rs", "oldText": None, "newText": "new"},
+            {"type": "diff", "path": "/b.rs", "oldText": "old"},
+            {"type": "diff", "oldText": "no path", "newText": "x"},
+            {"type": "content", "content": {"type": "text", "text": "done"}},
+        ]
+        assert FileEdit.parse_list(json.dumps(content)) == [
+            FileEdit("/a.rs", new_text="new"),
+            FileEdit("/b.rs", old_text="old"),
+        ]
+        assert FileEdit.parse_list("not json") == []
+
 
 class TestToolDefinition:
     def test_creation(self):